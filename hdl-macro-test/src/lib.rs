@@ -2,11 +2,17 @@
 mod tests {
     use bumpalo::Bump;
     use hdl::create_subchip;
+    use hdl::ids::IdScope;
+    use hdl::netlist::NodeKind;
+    use hdl::ports::Port;
+    use hdl::timing::{Hazard, Timing};
     use hdl::NandInputs;
+    use hdl::Selector;
     use hdl::SizedChip;
     use hdl::StructuredData;
-    use hdl::{ChipInput, ChipOutput, ChipOutputType, Input, Machine, Nand};
-    use hdl_macro::{chip, StructuredData};
+    use hdl::{ArrayInto, Bus, Chip, Clocked, DefaultChip, Error, SharedBus, TriStateDriver};
+    use hdl::{ChipInput, ChipOutputType, Input, Logic, Machine, MachineBuilder, Nand, UserInput};
+    use hdl_macro::{chip, hdl_chip, Selector, StructuredData};
 
     #[derive(StructuredData, PartialEq, Debug)]
     struct TwoBitNumOutput<T> {
@@ -45,6 +51,352 @@ mod tests {
         let transformed_under_test = Test::<bool>::from_flat(under_test.clone().to_flat());
 
         assert_eq!(under_test, transformed_under_test);
+        assert_eq!(
+            Test::<bool>::flat_names(),
+            [
+                "arrayinput1-0",
+                "arrayinput1-1",
+                "nonarrayinput1",
+                "arrayinput2-0",
+                "arrayinput2-1",
+                "arrayinput2-2",
+                "arrayinput2-3",
+                "arrayinput2-4",
+                "nonarrayinput2"
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_struct_has_a_nested_struct_field_derive_trait_flattens_it_in_field_order() {
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Flags<T> {
+            zero: T,
+            carry: [T; 2],
+        }
+
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Test<T> {
+            out: [T; 2],
+            flags: Flags<T>,
+            valid: T,
+        }
+
+        let under_test = Test::<bool> {
+            out: [true, false],
+            flags: Flags {
+                zero: true,
+                carry: [false, true],
+            },
+            valid: false,
+        };
+
+        assert_eq!(Test::<bool>::get_arity(), 6);
+        assert_eq!(
+            under_test.clone().to_flat(),
+            [true, false, true, false, true, false]
+        );
+        assert_eq!(
+            Test::<bool>::from_flat(under_test.clone().to_flat()),
+            under_test
+        );
+        assert_eq!(
+            Test::<bool>::flat_names(),
+            [
+                "out-0",
+                "out-1",
+                "flags.zero",
+                "flags.carry-0",
+                "flags.carry-1",
+                "valid"
+            ]
+        );
+        assert_eq!(
+            Test::<bool>::field_info(),
+            [
+                ("out".to_owned(), 2),
+                ("flags.zero".to_owned(), 0),
+                ("flags.carry".to_owned(), 2),
+                ("valid".to_owned(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn when_struct_fields_are_skipped_or_flattened_derive_trait_lays_out_the_rest() {
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Flags<T> {
+            zero: T,
+            carry: [T; 2],
+        }
+
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Test<T> {
+            out: [T; 2],
+            #[structured(skip)]
+            name: &'static str,
+            #[structured(flatten)]
+            flags: Flags<T>,
+        }
+
+        let under_test = Test::<bool> {
+            out: [true, false],
+            name: "test",
+            flags: Flags {
+                zero: true,
+                carry: [false, true],
+            },
+        };
+
+        assert_eq!(Test::<bool>::get_arity(), 5);
+        assert_eq!(
+            under_test.clone().to_flat(),
+            [true, false, true, false, true]
+        );
+        assert_eq!(
+            Test::<bool>::from_flat(under_test.clone().to_flat()),
+            Test {
+                name: "",
+                ..under_test
+            }
+        );
+        assert_eq!(
+            Test::<bool>::flat_names(),
+            ["out-0", "out-1", "zero", "carry-0", "carry-1"]
+        );
+        assert_eq!(
+            Test::<bool>::field_info(),
+            [
+                ("out".to_owned(), 2),
+                ("zero".to_owned(), 0),
+                ("carry".to_owned(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_chip_output_has_a_nested_struct_field_it_can_be_processed_via_machine() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct Flags<T> {
+            zero: T,
+        }
+
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct NestedOutput<T> {
+            out: T,
+            flags: Flags<T>,
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> NestedOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            let not = Nand::new(alloc, nand.into(), nand.into());
+            NestedOutput {
+                out: ChipOutputType::NandOutput(nand),
+                flags: Flags {
+                    zero: ChipOutputType::NandOutput(not),
+                },
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            NestedOutput {
+                out: false,
+                flags: Flags { zero: true }
+            }
+        );
+        assert!(machine.get_output("flags.zero").unwrap());
+        assert_eq!(machine.outputs[1].output.inner.label, "flags.zero");
+    }
+
+    #[test]
+    fn when_a_tuple_struct_is_defined_derive_trait_names_elements_by_field_index() {
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Pair<T>(T, [T; 2]);
+
+        let under_test = Pair::<bool>(true, [false, true]);
+
+        assert_eq!(under_test.clone().to_flat(), [true, false, true]);
+        assert_eq!(
+            Pair::<bool>::from_flat(under_test.clone().to_flat()),
+            under_test
+        );
+        assert_eq!(Pair::<bool>::flat_names(), ["0", "1-0", "1-1"]);
+        assert_eq!(
+            Pair::<bool>::field_info(),
+            [("0".to_owned(), 0), ("1".to_owned(), 2)]
+        );
+    }
+
+    #[test]
+    fn when_a_chip_output_is_a_newtype_it_can_be_processed_via_machine() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct Word<T>([T; 2]);
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> Word<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            Word([
+                ChipOutputType::NandOutput(nand),
+                ChipOutputType::NandOutput(nand),
+            ])
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        let output = machine
+            .process(TestchipInputs {
+                in1: false,
+                in2: true,
+            })
+            .unwrap();
+        assert_eq!(output, Word::from_u16(3));
+        assert_eq!(output.to_u16(), 3);
+        assert_eq!(machine.outputs[0].output.inner.label, "0-1");
+    }
+
+    #[test]
+    fn when_a_struct_has_an_array_of_arrays_derive_trait_flattens_it_row_by_row() {
+        #[derive(StructuredData, PartialEq, Debug, Clone)]
+        struct Test<T> {
+            words: [[T; 2]; 3],
+            bit: T,
+        }
+
+        let under_test = Test::<bool> {
+            words: [[true, false], [false, false], [false, true]],
+            bit: true,
+        };
+
+        assert_eq!(Test::<bool>::get_arity(), 7);
+        assert_eq!(
+            under_test.clone().to_flat(),
+            [true, false, false, false, false, true, true]
+        );
+        assert_eq!(
+            Test::<bool>::from_flat(under_test.clone().to_flat()),
+            under_test
+        );
+        assert_eq!(
+            Test::<bool>::flat_names(),
+            [
+                "words-0-0",
+                "words-0-1",
+                "words-1-0",
+                "words-1-1",
+                "words-2-0",
+                "words-2-1",
+                "bit"
+            ]
+        );
+        assert_eq!(
+            Test::<bool>::field_info(),
+            [
+                ("words-0".to_owned(), 2),
+                ("words-1".to_owned(), 2),
+                ("words-2".to_owned(), 2),
+                ("bit".to_owned(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_chip_takes_and_returns_arrays_of_arrays_it_can_be_processed_via_machine() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct WordsOutput<T> {
+            out: [[T; 2]; 2],
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            words: [[&'a ChipInput<'a>; 2]; 2],
+        ) -> WordsOutput<ChipOutputType<'a>> {
+            WordsOutput {
+                out: words
+                    .map(|word| word.map(|bit| Nand::new(alloc, bit.into(), bit.into()).into())),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    words: [[true, false], [false, true]]
+                })
+                .unwrap(),
+            WordsOutput {
+                out: [[false, true], [true, false]]
+            }
+        );
+        assert_eq!(machine.outputs[0].output.inner.label, "out-0-1");
+        assert_eq!(
+            machine
+                .describe()
+                .inputs
+                .iter()
+                .map(|port| (port.name.as_str(), port.flat.clone()))
+                .collect::<Vec<_>>(),
+            [("words-0", 0..2), ("words-1", 2..4)]
+        );
+    }
+
+    #[test]
+    fn when_selector_is_derived_variants_are_numbered_on_the_fewest_lines() {
+        #[derive(Clone, Copy, Debug, PartialEq, Selector)]
+        enum Op {
+            Add,
+            Sub,
+            And,
+            Or,
+            Not,
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let [_, sel1, sel2] = Op::Or.inputs(alloc);
+            TwoBitNumOutput {
+                out: [
+                    Nand::new(alloc, in_.into(), sel1).into(),
+                    Nand::new(alloc, in_.into(), sel2).into(),
+                ],
+            }
+        }
+
+        let bits: [bool; 3] = Op::Sub.into();
+        assert_eq!(bits, [false, false, true]);
+        assert_eq!(Op::Not.to_bits(), [true, false, false]);
+        assert_eq!(Op::from_bits([false, true, false]), Some(Op::And));
+        assert_eq!(Op::from_bits([true, true, false]), None);
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine.process(TestchipInputs { in_: true }).unwrap(),
+            TwoBitNumOutput {
+                out: [false, false]
+            }
+        );
     }
 
     #[test]
@@ -55,26 +407,30 @@ mod tests {
             in1: &'a ChipInput<'a>,
             in2: &'a ChipInput<'a>,
         ) -> UnaryChipOutput<ChipOutputType<'a>> {
-            let nand = Nand::new(&alloc, in1.into(), in2.into());
+            let nand = Nand::new(alloc, in1.into(), in2.into());
             UnaryChipOutput {
                 out: ChipOutputType::NandOutput(nand),
             }
         }
 
         let alloc = Bump::new();
-        let mut machine = Machine::new(&alloc, Testchip::from);
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
         assert_eq!(
-            machine.process(TestchipInputs {
-                in1: true,
-                in2: false
-            }),
+            machine
+                .process(TestchipInputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
             UnaryChipOutput { out: true }
         );
         assert_eq!(
-            machine.process(TestchipInputs {
-                in1: true,
-                in2: true
-            }),
+            machine
+                .process(TestchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
             UnaryChipOutput { out: false }
         );
 
@@ -102,23 +458,36 @@ mod tests {
         }
 
         let alloc = Bump::new();
-        let mut machine = Machine::new(&alloc, Testchip::from);
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
         assert_eq!(
-            machine.process(TestchipInputs {
-                num1: [true, true],
-                num2: [true, false],
-                bit: true
-            }),
+            machine
+                .process(TestchipInputs {
+                    num1: [true, true],
+                    num2: [true, false],
+                    bit: true
+                })
+                .unwrap(),
             TwoBitNumOutput { out: [true, false] }
         );
         assert_eq!(
-            machine.process(TestchipInputs {
-                num1: [true, true],
-                num2: [true, false],
-                bit: false
-            }),
+            machine
+                .process(TestchipInputs {
+                    num1: [true, true],
+                    num2: [true, false],
+                    bit: false
+                })
+                .unwrap(),
             TwoBitNumOutput { out: [true, true] }
         );
+        // buses can also be given as numbers
+        let output = machine
+            .process(TestchipInputsWords {
+                num1: 3,
+                num2: 2,
+                bit: true,
+            })
+            .unwrap();
+        assert_eq!(TwoBitNumOutputWords::from(output).out, 2);
 
         assert_eq!(machine.outputs[0].output.inner.label, "out-1");
     }
@@ -131,7 +500,7 @@ mod tests {
             in1: &'a ChipInput<'a>,
             in2: &'a ChipInput<'a>,
         ) -> UnaryChipOutput<ChipOutputType<'a>> {
-            let nand = Nand::new(&alloc, in1.into(), in2.into());
+            let nand = Nand::new(alloc, in1.into(), in2.into());
             UnaryChipOutput {
                 out: ChipOutputType::NandOutput(nand),
             }
@@ -144,7 +513,7 @@ mod tests {
             in2: &'a ChipInput<'a>,
         ) -> BinaryChipOutput<ChipOutputType<'a>> {
             let chip = Testchip::new(alloc, in1.into(), in2.into());
-            let nand = Nand::new(&alloc, in1.into(), chip.get_out(alloc).out.into());
+            let nand = Nand::new(alloc, in1.into(), chip.get_out(alloc).out.into());
             BinaryChipOutput::<_> {
                 out1: ChipOutputType::NandOutput(nand),
                 out2: ChipOutputType::ChipInput(in2),
@@ -152,22 +521,26 @@ mod tests {
         }
 
         let alloc = Bump::new();
-        let mut machine = Machine::new(&alloc, Testchip2::from);
+        let mut machine = Machine::new(&alloc, Testchip2::from).unwrap();
         assert_eq!(
-            machine.process(Testchip2Inputs {
-                in1: true,
-                in2: false
-            }),
+            machine
+                .process(Testchip2Inputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
             BinaryChipOutput {
                 out1: false,
                 out2: false
             }
         );
         assert_eq!(
-            machine.process(Testchip2Inputs {
-                in1: true,
-                in2: true
-            }),
+            machine
+                .process(Testchip2Inputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
             BinaryChipOutput {
                 out1: true,
                 out2: true
@@ -176,7 +549,7 @@ mod tests {
     }
 
     #[test]
-    fn when_dependent_chips_of_two_different_types_are_defined_the_type_checker_passes() {
+    fn when_a_chip_is_built_from_subchips_its_gate_count_includes_theirs() {
         #[chip]
         fn testchip<'a>(
             alloc: &'a Bump,
@@ -190,7 +563,70 @@ mod tests {
         }
 
         #[chip]
-        fn cyclicchip<'a>(
+        fn testchip2<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let chip1 = Testchip::new(alloc, in1.into(), in2.into());
+            let chip2 = Testchip::new(alloc, in2.into(), in1.into());
+            let nand = Nand::new(
+                alloc,
+                chip1.get_out(alloc).out.into(),
+                chip2.get_out(alloc).out.into(),
+            );
+            // drives no output, but is still built
+            Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn testchip3<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let mut out = Testchip2::new(alloc, in_[0].into(), in_[1].into());
+            for _ in 1..=3 {
+                out = Testchip2::new(alloc, out.get_out(alloc).out.into(), in_[1].into());
+            }
+            UnaryChipOutput {
+                out: out.get_out(alloc).out.into(),
+            }
+        }
+
+        #[chip]
+        fn testchip4<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let [out1, out2] = in_.map(|in_| Testchip::new(alloc, in_.into(), in_.into()));
+            BinaryChipOutput {
+                out1: out1.get_out(alloc).out.into(),
+                out2: out2.get_out(alloc).out.into(),
+            }
+        }
+
+        #[chip]
+        fn testchip5<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let mut out = Testchip::new(alloc, in_[0].into(), in_[1].into());
+            for i in 0..4 {
+                if i == 2 {
+                    break;
+                }
+                out = Testchip::new(alloc, out.get_out(alloc).out.into(), in_[1].into());
+            }
+            UnaryChipOutput {
+                out: out.get_out(alloc).out.into(),
+            }
+        }
+
+        #[chip]
+        fn testchip6<'a>(
             alloc: &'a Bump,
             in1: &'a ChipInput<'a>,
             in2: &'a ChipInput<'a>,
@@ -202,24 +638,2306 @@ mod tests {
                     in2: testchip.get_out(alloc).out.into(),
                 },
                 &|(nand,)| TestchipInputs {
-                    in1: in2.into(),
+                    in1: Testchip::new(alloc, in2.into(), in2.into())
+                        .get_out(alloc)
+                        .out
+                        .into(),
                     in2: nand.into(),
                 },
             );
-
             UnaryChipOutput {
                 out: tc.get_out(alloc).out.into(),
             }
         }
 
+        assert_eq!(Testchip::GATE_COUNT, Some(1));
+        assert_eq!(Testchip2::GATE_COUNT, Some(4));
+        assert_eq!(Testchip3::GATE_COUNT, Some(16));
+        // the closure could be run any number of times
+        assert_eq!(Testchip4::GATE_COUNT, None);
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Testchip4::from).unwrap();
+        assert_eq!(machine.gate_count(), 2);
+        // the loop leaves before it's been round as many times as its range
+        assert_eq!(Testchip5::GATE_COUNT, None);
+        let machine = Machine::new(&alloc, Testchip5::from).unwrap();
+        assert_eq!(machine.gate_count(), 3);
+        // create_subchip runs each of its closures once
+        assert_eq!(Testchip6::GATE_COUNT, Some(3));
+        let machine = Machine::new(&alloc, Testchip6::from).unwrap();
+        assert_eq!(machine.gate_count(), 3);
+    }
+
+    #[test]
+    fn when_chips_are_chained_by_a_pipeline_each_takes_the_previous_output() {
+        #[chip]
+        fn notchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_.into(), in_.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn nandchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        // in1 AND in2, from NOT (in1 NAND in2)
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let chip = hdl::pipeline!(alloc, in1 => Nandchip(in2.into()) => Notchip());
+            UnaryChipOutput {
+                out: chip.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        for (in1, in2) in [(false, false), (false, true), (true, false), (true, true)] {
+            assert_eq!(
+                machine.process(TestchipInputs { in1, in2 }).unwrap(),
+                UnaryChipOutput { out: in1 && in2 }
+            );
+        }
+    }
+
+    #[test]
+    fn when_chip_inputs_have_defaults_they_can_be_left_out_of_new_and_the_machine() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+            #[default(true)] enable: &'a ChipInput<'a>,
+            #[default(2)] mask: [&'a ChipInput<'a>; 2],
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_.into(), enable.into());
+            let masked = mask.map(|bit| Nand::new(alloc, nand.into(), bit.into()));
+            TwoBitNumOutput {
+                out: masked.map(ChipOutputType::NandOutput),
+            }
+        }
+
+        #[chip]
+        fn testchip2<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let defaulted = Testchip::new(alloc, in1.into());
+            let given = Testchip::new_with_all(alloc, in1.into(), in2.into(), [in2.into(); 2]);
+            BinaryChipOutput {
+                out1: defaulted.get_out(alloc).out[0].into(),
+                out2: given.get_out(alloc).out[0].into(),
+            }
+        }
+
         let alloc = Bump::new();
-        let mut machine = Machine::new(&alloc, Cyclicchip::from);
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
         assert_eq!(
-            machine.process(CyclicchipInputs {
-                in1: true,
-                in2: true
-            }),
-            UnaryChipOutput { out: false }
+            Machine::<TestchipInputsFamily, 1, 2>::input_names(),
+            ["in_"]
+        );
+        assert_eq!(
+            machine.process(TestchipInputs { in_: true }).unwrap(),
+            TwoBitNumOutput { out: [true, true] }
+        );
+        assert_eq!(
+            machine.process(TestchipInputs { in_: false }).unwrap(),
+            TwoBitNumOutput { out: [false, true] }
+        );
+
+        let mut machine = Machine::new(&alloc, Testchip2::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Testchip2Inputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: true
+            }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_is_built_by_its_builder_inputs_are_connected_by_name() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: [&'a ChipInput<'a>; 2],
+            #[default(false)] in3: &'a ChipInput<'a>,
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let nand1 = Nand::new(alloc, in1.into(), in2[0].into());
+            let nand2 = Nand::new(alloc, in2[1].into(), in3.into());
+            TwoBitNumOutput {
+                out: [nand1.into(), nand2.into()],
+            }
+        }
+
+        #[chip]
+        fn testchip2<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let chip = Testchip::builder(alloc).in2([in1, in2]).in1(in2).build();
+            TwoBitNumOutput {
+                out: chip.get_out(alloc).out.ainto(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip2::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Testchip2Inputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            TwoBitNumOutput { out: [false, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Testchip2Inputs {
+                    in1: false,
+                    in2: true
+                })
+                .unwrap(),
+            TwoBitNumOutput { out: [true, true] }
+        );
+
+        let unconnected = std::panic::catch_unwind(|| {
+            let alloc = Bump::new();
+            let in_ = hdl::UserInput::new(&alloc);
+            Testchip::builder(&alloc).in1(in_).build();
+        });
+        assert_eq!(
+            unconnected.unwrap_err().downcast_ref::<&str>(),
+            Some(&"Testchip: port `in2` isn't connected")
+        );
+    }
+
+    #[test]
+    fn when_a_chip_returns_its_inputs_struct_it_passes_its_inputs_through() {
+        #[chip]
+        fn testchip<'a>(
+            _alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: [&'a ChipInput<'a>; 2],
+            in3: Bus<'a, 2>,
+        ) -> TestchipInputs<ChipOutputType<'a>> {
+            TestchipInputs {
+                in1: in1.into(),
+                in2: in2.ainto(),
+                in3: in3.outputs(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        let inputs = TestchipInputs {
+            in1: true,
+            in2: [false, true],
+            in3: [true, false],
+        };
+        assert_eq!(machine.process(inputs.clone()).unwrap(), inputs);
+    }
+
+    #[test]
+    fn when_a_chip_is_defined_its_inputs_struct_can_be_defaulted_and_compared() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: [&'a ChipInput<'a>; 40],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2[39].into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let inputs = TestchipInputs {
+            in1: true,
+            ..Default::default()
+        };
+        assert_eq!(inputs.in2, [false; 40]);
+        assert_ne!(inputs, TestchipInputs::default());
+        assert!(format!("{:?}", TestchipInputs::<u8>::default())
+            .starts_with("TestchipInputs { in1: 0, in2: [0, 0"));
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine.process(inputs).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn when_hdl_has_the_serde_feature_chip_inputs_can_be_read_from_json() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: [&'a ChipInput<'a>; 40],
+            words: [[&'a ChipInput<'a>; 2]; 2],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in2[39].into(), words[1][0].into());
+            let nand = Nand::new(alloc, in1.into(), nand.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let mut in2 = [false; 40];
+        in2[39] = true;
+        let inputs = TestchipInputs {
+            in1: true,
+            in2,
+            words: [[false, false], [true, false]],
+        };
+        let json = serde_json::to_string(&inputs).unwrap();
+        assert!(json.starts_with(r#"{"in1":true,"in2":[false,false"#));
+        assert!(json.ends_with(r#"true],"words":[[false,false],[true,false]]}"#));
+        let read: TestchipInputs<bool> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, inputs);
+        assert!(serde_json::from_str::<TestchipInputs<bool>>(
+            r#"{"in1":true,"in2":[],"words":[]}"#
+        )
+        .is_err());
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine.process(read).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+    }
+
+    #[test]
+    fn when_dependent_chips_of_two_different_types_are_defined_the_type_checker_passes() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn cyclicchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let (_, tc): (&Nand, &Testchip) = create_subchip(
+                alloc,
+                &|(testchip,)| NandInputs {
+                    in1: in1.into(),
+                    in2: testchip.get_out(alloc).out.into(),
+                },
+                &|(nand,)| TestchipInputs {
+                    in1: in2.into(),
+                    in2: nand.into(),
+                },
+            );
+
+            UnaryChipOutput {
+                out: tc.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Cyclicchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(CyclicchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_output_is_forced_the_machine_outputs_the_forced_value_until_released() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        let out_id = machine.outputs[0].output.inner.id;
+        let out = machine
+            .find_chip_output(out_id)
+            .expect("output should be found in the graph");
+
+        out.force(false);
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+
+        out.release();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert!(machine.find_chip_output(u32::MAX).is_none());
+    }
+
+    #[test]
+    fn when_a_machine_is_processed_more_times_than_the_iteration_counter_holds_it_keeps_evaluating()
+    {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        for i in 0..1000 {
+            let in1 = i % 2 == 0;
+            assert_eq!(
+                machine.process(TestchipInputs { in1, in2: true }).unwrap(),
+                UnaryChipOutput { out: !in1 }
+            );
+        }
+    }
+
+    #[test]
+    fn when_a_netlist_is_built_from_a_machine_it_has_stable_ids_and_chip_hierarchy() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn testchip2<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let chip = Testchip::new(alloc, in1.into(), in2.into());
+            let nand = Nand::new(alloc, in1.into(), chip.get_out(alloc).out.into());
+            BinaryChipOutput::<_> {
+                out1: ChipOutputType::NandOutput(nand),
+                out2: ChipOutputType::ChipInput(in2),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip2::from).unwrap();
+        machine
+            .process(Testchip2Inputs {
+                in1: true,
+                in2: true,
+            })
+            .unwrap();
+        let netlist = machine.netlist();
+
+        assert_eq!(netlist.inputs, [0, 1]);
+        assert_eq!(netlist.outputs.len(), 2);
+        assert_eq!(
+            netlist
+                .chips
+                .iter()
+                .map(|chip| (chip.label, chip.parent))
+                .collect::<Vec<_>>(),
+            [("Testchip2", None), ("Testchip", Some(0))]
+        );
+        let nand_chips = netlist
+            .nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::Nand)
+            .map(|node| node.chip)
+            .collect::<Vec<_>>();
+        assert_eq!(nand_chips, [Some(0), Some(1)]);
+        assert_eq!(
+            netlist
+                .outputs
+                .iter()
+                .map(|&out| netlist.nodes[out].value)
+                .collect::<Vec<_>>(),
+            [Logic::High, Logic::High]
+        );
+        // out2 is driven straight from the chip's second input
+        let out2_driver = netlist
+            .drivers(netlist.outputs[1])
+            .flat_map(|node| netlist.drivers(node))
+            .collect::<Vec<_>>();
+        assert_eq!(out2_driver.len(), 1);
+        assert_eq!(netlist.nodes[out2_driver[0]].kind, NodeKind::ChipInput);
+        assert_eq!(netlist.nodes[out2_driver[0]].label, "in2");
+
+        let other_alloc = Bump::new();
+        let other_netlist = Machine::new(&other_alloc, Testchip2::from)
+            .unwrap()
+            .netlist();
+        assert_eq!(netlist.edges, other_netlist.edges);
+        assert_eq!(
+            netlist
+                .nodes
+                .iter()
+                .map(|node| (node.kind, node.label.clone(), node.chip))
+                .collect::<Vec<_>>(),
+            other_netlist
+                .nodes
+                .iter()
+                .map(|node| (node.kind, node.label.clone(), node.chip))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn when_a_chip_function_has_doc_comments_they_become_its_description() {
+        /// Inverts the AND of its inputs.
+        ///
+        /// Built from a single NAND.
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn testchip2<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let chip = Testchip::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: chip.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Testchip2::from).unwrap();
+        assert_eq!(
+            machine
+                .netlist()
+                .chips
+                .iter()
+                .map(|chip| chip.description)
+                .collect::<Vec<_>>(),
+            [
+                "",
+                "Inverts the AND of its inputs.\n\nBuilt from a single NAND."
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_machine_is_rebuilt_repeatedly_the_builder_memory_stays_bounded() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let mut builder = MachineBuilder::new();
+        // a reset keeps only the largest arena chunk, so the first rebuild may still grow it
+        builder.build(Testchip::from).unwrap();
+        builder.build(Testchip::from).unwrap();
+        let allocated = builder.allocated_bytes();
+
+        for _ in 0..1000 {
+            let mut machine = builder.build(Testchip::from).unwrap();
+            assert_eq!(
+                machine
+                    .process(TestchipInputs {
+                        in1: true,
+                        in2: true
+                    })
+                    .unwrap(),
+                UnaryChipOutput { out: false }
+            );
+        }
+        assert!(builder.allocated_bytes() <= allocated);
+
+        builder.reset();
+        assert!(builder.allocated_bytes() <= allocated);
+    }
+
+    #[test]
+    fn when_a_machine_is_set_unknown_latches_stay_unknown_until_they_are_set() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        // an active low SR latch, where in1 resets and in2 sets
+        #[chip]
+        fn latchchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let (_, tc): (&Nand, &Testchip) = create_subchip(
+                alloc,
+                &|(testchip,)| NandInputs {
+                    in1: in1.into(),
+                    in2: testchip.get_out(alloc).out.into(),
+                },
+                &|(nand,)| TestchipInputs {
+                    in1: in2.into(),
+                    in2: nand.into(),
+                },
+            );
+
+            UnaryChipOutput {
+                out: tc.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Latchchip::from).unwrap();
+        machine.set_unknown();
+        let hold = LatchchipInputs {
+            in1: Logic::High,
+            in2: Logic::High,
+        };
+        assert_eq!(
+            machine.process_logic(hold).unwrap(),
+            UnaryChipOutput {
+                out: Logic::Unknown
+            }
+        );
+        // a low input decides a NAND even if the other input is unknown
+        assert_eq!(
+            machine
+                .process_logic(LatchchipInputs {
+                    in1: Logic::Unknown,
+                    in2: Logic::Low
+                })
+                .unwrap(),
+            UnaryChipOutput { out: Logic::High }
+        );
+        assert_eq!(
+            machine
+                .process_logic(LatchchipInputs {
+                    in1: Logic::High,
+                    in2: Logic::High
+                })
+                .unwrap(),
+            UnaryChipOutput { out: Logic::High }
+        );
+
+        machine.set_unknown();
+        assert_eq!(
+            machine
+                .process(LatchchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false },
+            "unknown values read as false in two-valued processing"
+        );
+    }
+
+    #[test]
+    fn when_a_machine_clock_is_set_tick_and_tock_drive_it_high_and_low() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(machine.get_clock(), None);
+
+        machine.set_clock("in2").unwrap();
+        assert_eq!(machine.get_clock(), Some("in2".to_owned()));
+        let input = || TestchipInputs {
+            in1: true,
+            in2: false,
+        };
+        assert_eq!(
+            machine.tick(input()).unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine.tock(input()).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine.cycle(input()).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_names_its_reset_input_the_machine_uses_it() {
+        #[chip(reset = "in2")]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn resettable<'a>(
+            alloc: &'a Bump,
+            reset: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, reset.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(machine.get_reset(), Some("in2".to_owned()));
+        let machine = Machine::new(&alloc, Resettable::from).unwrap();
+        assert_eq!(machine.get_reset(), Some("reset".to_owned()));
+    }
+
+    #[test]
+    fn when_a_machine_uses_unit_delays_transient_glitches_appear_in_the_waveform() {
+        // a AND NOT a is always false with zero delay, but the inverter lags its input
+        #[chip]
+        fn hazardchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let not = Nand::new(alloc, in1.into(), in1.into());
+            let nand = Nand::new(alloc, in1.into(), not.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Hazardchip::from).unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 10 });
+        assert_eq!(
+            machine.process(HazardchipInputs { in1: false }).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+
+        let timed = machine
+            .process_timed(HazardchipInputs { in1: Logic::High })
+            .expect("circuit should stabilise");
+        assert_eq!(timed.outputs, UnaryChipOutput { out: Logic::High });
+        assert_eq!(timed.steps, 2);
+        assert_eq!(timed.waveform, [[Logic::High], [Logic::Low], [Logic::High]]);
+    }
+
+    #[test]
+    fn when_a_circuit_oscillates_under_unit_delays_it_is_reported() {
+        // a NAND fed back in to itself inverts itself every step while in1 is high
+        #[chip]
+        fn oscillatorchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand: &Nand = DefaultChip::new(alloc);
+            nand.set_inputs(
+                alloc,
+                NandInputs {
+                    in1: in1.into(),
+                    in2: nand.into(),
+                },
+            );
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Oscillatorchip::from).unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 10 });
+        assert!(machine
+            .process_timed(OscillatorchipInputs { in1: Logic::Low })
+            .is_ok());
+        let Err(Error::Oscillation { steps, nodes }) =
+            machine.process_timed(OscillatorchipInputs { in1: Logic::High })
+        else {
+            panic!("expected the NAND to oscillate");
+        };
+        assert_eq!((steps, nodes.len()), (10, 1));
+    }
+
+    #[test]
+    fn when_a_chip_leaves_a_nand_unconnected_building_a_machine_returns_an_error() {
+        #[chip]
+        fn unconnectedchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let _ = in1;
+            let nand: &Nand = DefaultChip::new(alloc);
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn outerchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let inner = Unconnectedchip::new(alloc, in1.into());
+            UnaryChipOutput {
+                out: inner.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let err = match Machine::new(&alloc, Outerchip::from) {
+            Err(Error::Unconnected(unconnected)) => unconnected,
+            _ => panic!("the unconnected NAND should be reported"),
+        };
+        assert_eq!(
+            err.iter()
+                .map(|node| (node.kind, node.label.as_str(), node.path.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (NodeKind::Nand, "in1", vec!["Outerchip", "Unconnectedchip"]),
+                (NodeKind::Nand, "in2", vec!["Outerchip", "Unconnectedchip"])
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_machine_has_no_clock_clock_operations_return_an_error() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .tick(TestchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .err(),
+            Some(Error::NoClock)
+        );
+        assert_eq!(
+            machine.set_clock("clock"),
+            Err(Error::UnknownInput("clock".to_owned()))
+        );
+    }
+
+    #[test]
+    fn when_an_output_glitches_after_an_input_transition_a_hazard_is_reported() {
+        #[chip]
+        fn hazardchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let not = Nand::new(alloc, in1.into(), in1.into());
+            let nand = Nand::new(alloc, in1.into(), not.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Hazardchip::from).unwrap();
+        assert_eq!(
+            machine
+                .find_hazards(
+                    HazardchipInputs { in1: false },
+                    HazardchipInputs { in1: true }
+                )
+                .err(),
+            Some(Error::ZeroDelayTiming)
+        );
+
+        machine.set_timing(Timing::UnitDelay { max_steps: 10 });
+        let hazards = machine
+            .find_hazards(
+                HazardchipInputs { in1: false },
+                HazardchipInputs { in1: true },
+            )
+            .unwrap();
+        assert_eq!(
+            hazards,
+            [Hazard {
+                output: "out".into(),
+                from: vec![false],
+                to: vec![true],
+                waveform: vec![Logic::High, Logic::Low, Logic::High]
+            }]
+        );
+        assert_eq!(
+            machine
+                .find_hazards(
+                    HazardchipInputs { in1: true },
+                    HazardchipInputs { in1: false }
+                )
+                .unwrap(),
+            []
+        );
+        assert_eq!(machine.find_single_input_hazards().unwrap(), hazards);
+    }
+
+    #[test]
+    fn when_a_machine_has_too_many_inputs_to_check_every_state_hazards_are_found_from_a_sample() {
+        #[chip]
+        fn widehazardchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 64],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let not = Nand::new(alloc, in_[0].into(), in_[0].into());
+            let nand = Nand::new(alloc, in_[0].into(), not.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Widehazardchip::from).unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 10 });
+        let hazards = machine.find_single_input_hazards().unwrap();
+        // only the first input rising glitches the output, whatever the others are
+        assert!(!hazards.is_empty());
+        for hazard in hazards {
+            assert_eq!((hazard.from[0], hazard.to[0]), (false, true));
+            assert_eq!(hazard.from[1..], hazard.to[1..]);
+        }
+    }
+
+    #[test]
+    fn when_an_input_drives_gates_through_subchips_its_fan_out_counts_every_nand_input() {
+        #[chip]
+        fn fanoutinner<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let not = Nand::new(alloc, in1.into(), in1.into());
+            let nand = Nand::new(alloc, in1.into(), not.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        #[chip]
+        fn fanoutouter<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let inner = Fanoutinner::new(alloc, in1.into());
+            UnaryChipOutput {
+                out: inner.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Fanoutouter::from).unwrap();
+        let netlist = machine.netlist();
+        let fan_out = netlist.fan_out();
+        let gate_fan_out = netlist.gate_fan_out();
+        let input = netlist.inputs[0];
+        assert_eq!((fan_out[input], gate_fan_out[input]), (1, 3));
+        let inner_input = netlist
+            .nodes
+            .iter()
+            .position(|node| {
+                node.kind == NodeKind::ChipInput
+                    && netlist.chip_path(node.chip) == ["Fanoutouter", "Fanoutinner"]
+            })
+            .unwrap();
+        assert_eq!((fan_out[inner_input], gate_fan_out[inner_input]), (3, 3));
+
+        assert_eq!(machine.check_fan_out(3), Ok(()));
+        let exceeded = match machine.check_fan_out(2) {
+            Err(Error::FanOut { limit: 2, exceeded }) => exceeded,
+            _ => panic!("the machine input should exceed the fan-out limit"),
+        };
+        assert_eq!(
+            exceeded
+                .iter()
+                .map(|node| (node.kind, node.path.clone(), node.fan_out))
+                .collect::<Vec<_>>(),
+            [(NodeKind::UserInput, vec![], 3)]
+        );
+    }
+
+    #[test]
+    fn when_a_machine_state_is_randomized_latches_power_up_in_a_seeded_state() {
+        #[chip]
+        fn srlatch<'a>(
+            alloc: &'a Bump,
+            ns: &'a ChipInput<'a>,
+            nr: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let (q, _): (&Nand, &Nand) = create_subchip(
+                alloc,
+                &|(nq,)| NandInputs {
+                    in1: ns.into(),
+                    in2: nq.into(),
+                },
+                &|(q,)| NandInputs {
+                    in1: nr.into(),
+                    in2: q.into(),
+                },
+            );
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(q),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Srlatch::from).unwrap();
+        let mut power_up = |seed| {
+            machine.randomize_state(seed);
+            machine
+                .process(SrlatchInputs { ns: true, nr: true })
+                .unwrap();
+            // a random power up needn't be a stable state of the latch
+            machine.settle(4).unwrap().out == Logic::High
+        };
+        let states = (0..16).map(&mut power_up).collect::<Vec<_>>();
+        assert!(states.contains(&true) && states.contains(&false));
+        assert_eq!((0..16).map(power_up).collect::<Vec<_>>(), states);
+    }
+
+    // a latch which is transparent while its clock is high
+    #[chip]
+    fn dlatch<'a>(
+        alloc: &'a Bump,
+        data: &'a ChipInput<'a>,
+        clock: &'a ChipInput<'a>,
+    ) -> UnaryChipOutput<ChipOutputType<'a>> {
+        let notdata = Nand::new(alloc, data.into(), data.into());
+        let set = Nand::new(alloc, data.into(), clock.into());
+        let reset = Nand::new(alloc, notdata.into(), clock.into());
+        let (q, _): (&Nand, &Nand) = create_subchip(
+            alloc,
+            &|(nq,)| NandInputs {
+                in1: set.into(),
+                in2: nq.into(),
+            },
+            &|(q,)| NandInputs {
+                in1: reset.into(),
+                in2: q.into(),
+            },
+        );
+        UnaryChipOutput {
+            out: ChipOutputType::NandOutput(q),
+        }
+    }
+
+    #[test]
+    fn when_a_machine_has_several_clock_domains_each_can_be_ticked_on_its_own() {
+        #[chip]
+        fn twodomains<'a>(
+            alloc: &'a Bump,
+            data: &'a ChipInput<'a>,
+            clock: &'a ChipInput<'a>,
+            clock_slow: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let fast = Dlatch::new(alloc, data.into(), clock.into());
+            let slow = Dlatch::new(alloc, data.into(), clock_slow.into());
+            BinaryChipOutput {
+                out1: fast.get_out(alloc).out.into(),
+                out2: slow.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Twodomains::from).unwrap();
+        assert_eq!(machine.clock_domains(), ["clock", "clock_slow"]);
+        let chips = |domain| {
+            machine
+                .clock_domain_chips(domain)
+                .unwrap()
+                .into_iter()
+                .map(|chip| (chip.label, chip.id))
+                .collect::<Vec<_>>()
+        };
+        // the outer chip takes both clocks, while each latch belongs to a single domain
+        let (fast, slow) = (chips("clock"), chips("clock_slow"));
+        assert_eq!(fast[0], slow[0]);
+        assert_eq!(fast[0].0, "Twodomains");
+        assert_eq!((fast[1].0, slow[1].0), ("Dlatch", "Dlatch"));
+        assert_ne!(fast[1], slow[1]);
+        assert_eq!((fast.len(), slow.len()), (2, 2));
+
+        let input = |data| TwodomainsInputs {
+            data,
+            clock: true,
+            clock_slow: true,
+        };
+        // the clock fields of the input are ignored, so the fast latch holds its state
+        let res = machine.cycle_domain("clock_slow", input(true)).unwrap();
+        assert_eq!((res.out1, res.out2), (false, true));
+        let res = machine.cycle_domain("clock", input(true)).unwrap();
+        assert_eq!((res.out1, res.out2), (true, true));
+        let res = machine.tick_domain("clock", input(false)).unwrap();
+        assert_eq!((res.out1, res.out2), (false, true));
+
+        assert_eq!(
+            machine.tick_domain("clock_fast", input(false)).err(),
+            Some(Error::UnknownClockDomain("clock_fast".to_owned()))
+        );
+        machine.add_clock_domain("data").unwrap();
+        assert_eq!(machine.clock_domains(), ["clock", "clock_slow", "data"]);
+    }
+
+    #[test]
+    fn when_a_clock_domain_is_active_low_ticking_drives_its_wire_low() {
+        #[chip]
+        fn lowdomain<'a>(
+            alloc: &'a Bump,
+            data: &'a ChipInput<'a>,
+            clock: &'a ChipInput<'a>,
+            #[structured(active_low)] clock_n: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let high = Dlatch::new(alloc, data.into(), clock.into());
+            let low = Dlatch::new(alloc, data.into(), clock_n.into());
+            BinaryChipOutput {
+                out1: high.get_out(alloc).out.into(),
+                out2: low.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Lowdomain::from).unwrap();
+        assert_eq!(machine.clock_domains(), ["clock", "clock_n"]);
+        let input = |data| LowdomainInputs {
+            data,
+            clock: false,
+            clock_n: false,
+        };
+        // the active low latch is closed by the tick and captures the data at the tock
+        let res = machine.tick_domain("clock_n", input(true)).unwrap();
+        assert_eq!((res.out1, res.out2), (false, false));
+        let res = machine.tock_domain("clock_n", input(true)).unwrap();
+        assert_eq!((res.out1, res.out2), (false, true));
+        assert_eq!(machine.inputs()[2], ("clock_n".to_owned(), Logic::High));
+
+        // ticking the other domain holds the active low clock's wire high, leaving its
+        // latch open
+        let res = machine.tick_domain("clock", input(false)).unwrap();
+        assert_eq!((res.out1, res.out2), (false, false));
+        assert_eq!(machine.inputs()[2], ("clock_n".to_owned(), Logic::High));
+        let res = machine.tick_domain("clock_n", input(true)).unwrap();
+        assert_eq!((res.out1, res.out2), (true, false));
+        assert_eq!(machine.inputs()[2], ("clock_n".to_owned(), Logic::Low));
+    }
+
+    #[test]
+    fn when_a_struct_has_bus_fields_numeric_helpers_convert_them_msb_first() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct Numbers<T> {
+            wide: [T; 16],
+            narrow: [T; 4],
+            flag: T,
+        }
+
+        let numbers = Numbers {
+            wide: hdl::bits::from_i16(-2, hdl::bits::BitOrder::MsbFirst),
+            narrow: [true, false, true, true],
+            flag: true,
+        };
+        assert_eq!(numbers.wide_to_i16(), -2);
+        assert_eq!(numbers.wide_to_u16(), 0xfffe);
+        assert_eq!(numbers.narrow_to_u16(), 11);
+        assert_eq!(numbers.narrow_to_i16(), -5);
+
+        assert_eq!(TwoBitNumOutput::from_u16(2).out, [true, false]);
+        assert_eq!(TwoBitNumOutput { out: [false, true] }.to_i16(), 1);
+        assert_eq!(
+            hdl::bits::from_u16::<4>(1, hdl::bits::BitOrder::LsbFirst),
+            [true, false, false, false]
+        );
+        assert_eq!(
+            hdl::bits::to_u16([false, false, true], hdl::bits::BitOrder::LsbFirst),
+            4
+        );
+        let wide = hdl::bits::from_u64::<33>(1 << 32 | 5, hdl::bits::BitOrder::MsbFirst);
+        assert!(wide[0] && wide[30] && wide[32]);
+        assert_eq!(wide.iter().filter(|&&bit| bit).count(), 3);
+        assert_eq!(
+            hdl::bits::to_u64(wide, hdl::bits::BitOrder::MsbFirst),
+            1 << 32 | 5
+        );
+    }
+
+    #[test]
+    fn when_a_struct_has_bus_fields_it_converts_to_and_from_words() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct Numbers<T> {
+            wide: [T; 16],
+            narrow: [T; 4],
+            flag: T,
+        }
+
+        let words = NumbersWords {
+            wide: 0x8001,
+            narrow: 9u8,
+            flag: true,
+        };
+        let numbers = Numbers::from(words);
+        assert_eq!(numbers.wide_to_u16(), 0x8001);
+        assert_eq!(numbers.narrow, [true, false, false, true]);
+        assert!(numbers.flag);
+        assert_eq!(NumbersWords::from(numbers), words);
+    }
+
+    #[test]
+    fn when_a_chip_takes_a_bus_it_can_be_split_and_joined() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct FourBitOutput<T> {
+            out: [T; 4],
+        }
+
+        // swaps the two halves of the bus, inverting the upper half
+        #[chip]
+        fn swapchip<'a>(alloc: &'a Bump, in_: Bus<'a, 4>) -> FourBitOutput<ChipOutputType<'a>> {
+            let (upper, lower) = in_.split::<2, 2>();
+            let inverted = upper.map(|in_| Nand::new(alloc, in_, in_));
+            FourBitOutput {
+                out: lower.concat::<2, 4>(inverted).into(),
+            }
+        }
+
+        #[chip]
+        fn outerchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 4],
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let swapped = Swapchip::new(alloc, in_.into());
+            let out = Bus::from(swapped.get_out(alloc).out).slice::<1, 2>();
+            TwoBitNumOutput { out: out.into() }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Swapchip::from).unwrap();
+        let res = machine
+            .process(SwapchipInputs {
+                in_: [true, false, true, true],
+            })
+            .unwrap();
+        assert_eq!(res.out, [true, true, false, true]);
+        assert_eq!(
+            Machine::<SwapchipInputsFamily, 4, 4>::input_names(),
+            ["in_-0", "in_-1", "in_-2", "in_-3"]
+        );
+
+        let mut machine = Machine::new(&alloc, Outerchip::from).unwrap();
+        let res = machine
+            .process(OuterchipInputs {
+                in_: [false, false, true, false],
+            })
+            .unwrap();
+        assert_eq!(res.out, [false, true]);
+    }
+
+    #[test]
+    fn when_several_drivers_share_a_bus_the_enabled_one_drives_it_and_conflicts_are_reported() {
+        #[chip]
+        fn sharedchip<'a>(
+            alloc: &'a Bump,
+            a: &'a ChipInput<'a>,
+            enable_a: &'a ChipInput<'a>,
+            b: &'a ChipInput<'a>,
+            enable_b: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let bus = SharedBus::new(
+                alloc,
+                &[
+                    TriStateDriver {
+                        data: a.into(),
+                        enable: enable_a.into(),
+                    },
+                    TriStateDriver {
+                        data: b.into(),
+                        enable: enable_b.into(),
+                    },
+                ],
+            );
+            UnaryChipOutput { out: bus.into() }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Sharedchip::from).unwrap();
+        let mut process = |a, enable_a, b, enable_b| {
+            machine
+                .process_logic(SharedchipInputs {
+                    a,
+                    enable_a,
+                    b,
+                    enable_b,
+                })
+                .map(|res| res.out)
+        };
+        let (high, low) = (Logic::High, Logic::Low);
+        assert_eq!(process(high, low, high, low), Ok(Logic::HighZ));
+        assert_eq!(process(high, high, low, low), Ok(Logic::High));
+        assert_eq!(process(high, low, low, high), Ok(Logic::Low));
+        assert_eq!(process(high, high, high, high), Ok(Logic::High));
+        assert_eq!(process(high, Logic::Unknown, low, high), Ok(Logic::Unknown));
+        assert!(matches!(
+            process(high, high, low, high),
+            Err(Error::BusContention(buses)) if buses.len() == 1
+        ));
+        assert_eq!(process(high, low, low, high), Ok(Logic::Low));
+    }
+
+    #[test]
+    fn when_a_feedback_loop_has_an_odd_number_of_inversions_settling_reports_oscillation() {
+        #[chip]
+        fn buffer<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let not = Nand::new(alloc, in_.into(), in_.into());
+            let out = Nand::new(alloc, not.into(), not.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(out),
+            }
+        }
+
+        // inverts its own output while enabled
+        #[chip]
+        fn ring<'a>(
+            alloc: &'a Bump,
+            enable: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let (nand, _): (&Nand, &Buffer) = create_subchip(
+                alloc,
+                &|(buffer,)| NandInputs {
+                    in1: enable.into(),
+                    in2: buffer.get_out(alloc).out.into(),
+                },
+                &|(nand,)| BufferInputs { in_: nand.into() },
+            );
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ring::from).unwrap();
+        machine.process(RingInputs { enable: false }).unwrap();
+        assert_eq!(machine.settle(8), Ok(UnaryChipOutput { out: Logic::High }));
+
+        machine.process(RingInputs { enable: true }).unwrap();
+        let Err(Error::Oscillation { steps, nodes }) = machine.settle(8) else {
+            panic!("expected the ring to oscillate");
+        };
+        assert_eq!((steps, nodes.len()), (8, 3));
+
+        machine.set_timing(Timing::UnitDelay { max_steps: 8 });
+        let Err(Error::Oscillation { steps, nodes }) = machine.settle(8) else {
+            panic!("expected the ring to oscillate");
+        };
+        assert_eq!(steps, 8);
+        assert!(!nodes.is_empty());
+    }
+
+    #[test]
+    fn when_io_is_accessed_by_name_it_uses_the_flattened_names() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_[0].into(), in_[1].into());
+            TwoBitNumOutput {
+                out: [
+                    ChipOutputType::NandOutput(nand),
+                    ChipOutputType::ChipInput(in_[1]),
+                ],
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        machine.set_input("in_-0", true).unwrap();
+        machine.set_input("in_-1", true).unwrap();
+        assert_eq!(
+            machine.update().unwrap(),
+            TwoBitNumOutput {
+                out: [Logic::Low, Logic::High]
+            }
+        );
+        assert!(!machine.get_output("out-0").unwrap());
+        assert!(machine.get_output("out-1").unwrap());
+        assert_eq!(
+            machine.inputs(),
+            [
+                ("in_-0".to_owned(), Logic::High),
+                ("in_-1".to_owned(), Logic::High)
+            ]
+        );
+        assert_eq!(
+            machine.outputs(),
+            [
+                ("out-0".to_owned(), Logic::Low),
+                ("out-1".to_owned(), Logic::High)
+            ]
+        );
+
+        assert_eq!(
+            machine.set_input("in_-2", true),
+            Err(Error::UnknownInput("in_-2".to_owned()))
+        );
+        assert_eq!(
+            machine.get_output("out"),
+            Err(Error::UnknownOutput("out".to_owned()))
+        );
+    }
+
+    #[test]
+    fn when_a_machine_is_described_each_field_is_a_port_of_flat_indices() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 3],
+            sel: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_[2].into(), sel.into());
+            BinaryChipOutput {
+                out1: ChipOutputType::NandOutput(nand),
+                out2: ChipOutputType::ChipInput(in_[0]),
+            }
+        }
+
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Testchip::from).unwrap();
+        let description = machine.describe();
+        assert_eq!(
+            description.inputs,
+            [
+                Port {
+                    name: "in_".to_owned(),
+                    width: 3,
+                    is_array: true,
+                    flat: 0..3,
+                    active_low: false
+                },
+                Port {
+                    name: "sel".to_owned(),
+                    width: 1,
+                    is_array: false,
+                    flat: 3..4,
+                    active_low: false
+                }
+            ]
+        );
+        assert_eq!(
+            description
+                .outputs
+                .iter()
+                .map(|port| (port.name.as_str(), port.flat.clone()))
+                .collect::<Vec<_>>(),
+            [("out1", 0..1), ("out2", 1..2)]
+        );
+    }
+
+    #[test]
+    fn when_ports_are_active_low_the_machine_inverts_them_at_the_boundary() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct ActiveLowOutput<T> {
+            #[structured(active_low)]
+            out_n: T,
+        }
+
+        // out_n is asserted when in_ is high and the chip is enabled
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+            #[structured(active_low)] enable_n: &'a ChipInput<'a>,
+        ) -> ActiveLowOutput<ChipOutputType<'a>> {
+            let not_enable = Nand::new(alloc, enable_n.into(), enable_n.into());
+            let nand = Nand::new(alloc, in_.into(), not_enable.into());
+            ActiveLowOutput {
+                out_n: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in_: true,
+                    enable_n: true
+                })
+                .unwrap(),
+            ActiveLowOutput { out_n: true }
+        );
+        assert_eq!(
+            machine.inputs(),
+            [
+                ("in_".to_owned(), Logic::High),
+                ("enable_n".to_owned(), Logic::Low)
+            ]
+        );
+        assert_eq!(machine.outputs(), [("out_n".to_owned(), Logic::Low)]);
+
+        machine.set_input("enable_n", false).unwrap();
+        machine.update().unwrap();
+        assert!(!machine.get_output("out_n").unwrap());
+
+        let description = machine.describe();
+        assert_eq!(
+            description
+                .inputs
+                .iter()
+                .map(|port| port.active_low)
+                .collect::<Vec<_>>(),
+            [false, true]
+        );
+        assert!(description.outputs[0].active_low);
+    }
+
+    #[test]
+    fn when_a_chip_is_built_it_describes_its_ports_and_children() {
+        #[chip]
+        fn inner<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+            #[structured(active_low)] sel: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_[0].into(), sel.into());
+            UnaryChipOutput {
+                out: Nand::new(alloc, nand.into(), in_[1].into()).into(),
+            }
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> TwoBitNumOutput<ChipOutputType<'a>> {
+            let inner1 = Inner::new(alloc, in_.ainto(), in_[0].into());
+            let inner2 = Inner::new(alloc, in_.ainto(), in_[1].into());
+            let nand = Nand::new(
+                alloc,
+                inner1.get_out(alloc).out.into(),
+                inner2.get_out(alloc).out.into(),
+            );
+            TwoBitNumOutput {
+                out: [nand.into(), inner2.get_out(alloc).out.into()],
+            }
+        }
+
+        let alloc = Bump::new();
+        let user_inputs = [UserInput::new(&alloc), UserInput::new(&alloc)];
+        let chip = Testchip::new(&alloc, user_inputs.map(Input::UserInput));
+        let children = chip.get_children();
+        assert_eq!(
+            children
+                .iter()
+                .map(|child| child.get_label())
+                .collect::<Vec<_>>(),
+            ["Inner", "Inner"]
+        );
+        assert!(children[0].get_children().is_empty());
+
+        let ports = children[0].get_ports();
+        assert_eq!(
+            ports
+                .inputs
+                .iter()
+                .map(|port| (port.name.as_str(), port.width, port.active_low))
+                .collect::<Vec<_>>(),
+            [("in_", 2, false), ("sel", 1, true)]
+        );
+        assert_eq!(ports.outputs[0].name, "out");
+        assert_eq!(chip.get_ports().outputs[0].flat, 0..2);
+    }
+
+    #[test]
+    fn when_chip_outputs_are_listed_they_are_the_ones_in_the_netlist() {
+        #[chip]
+        fn inner<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            UnaryChipOutput {
+                out: Nand::new(alloc, in_.into(), in_.into()).into(),
+            }
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let first = Inner::new(alloc, in_.into());
+            let second = Inner::new(alloc, first.get_out(alloc).out.into());
+            BinaryChipOutput {
+                out1: first.get_out(alloc).out.into(),
+                out2: second.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        machine.process(TestchipInputs { in_: false }).unwrap();
+        let mut listed = machine
+            .chip_outputs()
+            .iter()
+            .map(|out| (out.id, out.value()))
+            .collect::<Vec<_>>();
+        listed.sort_by_key(|&(id, _)| id);
+        let mut netlist = machine
+            .netlist()
+            .nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::ChipOutput)
+            .map(|node| (node.source_id, node.value))
+            .collect::<Vec<_>>();
+        netlist.sort_by_key(|&(id, _)| id);
+        assert_eq!(listed.len(), 4);
+        assert_eq!(listed, netlist);
+    }
+
+    #[test]
+    fn when_a_chip_instance_is_named_paths_use_its_name() {
+        #[chip]
+        fn inner<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            UnaryChipOutput {
+                out: Nand::new(alloc, in_.into(), in_.into()).into(),
+            }
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let named = Inner::new_named(alloc, "R0", in_.into());
+            let unnamed = Inner::new(alloc, in_.into());
+            BinaryChipOutput {
+                out1: named.get_out(alloc).out.into(),
+                out2: unnamed.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        machine.process(TestchipInputs { in_: false }).unwrap();
+        assert_eq!(
+            machine.netlist().chip_names(),
+            ["Testchip0", "R0", "Inner0"]
+        );
+        assert_eq!(machine.probe("Testchip0/R0/out"), Ok(true));
+        assert_eq!(machine.probe("Testchip0/Inner0/out"), Ok(true));
+    }
+
+    #[test]
+    fn when_outputs_are_found_by_name_only_chips_the_path_tells_apart_are_found() {
+        #[chip]
+        fn inner<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            UnaryChipOutput {
+                out: Nand::new(alloc, in_.into(), in_.into()).into(),
+            }
+        }
+
+        #[chip]
+        fn adder4<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            UnaryChipOutput {
+                out: Nand::new(alloc, in_.into(), in_.into()).into(),
+            }
+        }
+
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct ThreeOutputs<T> {
+            out: [T; 3],
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+        ) -> ThreeOutputs<ChipOutputType<'a>> {
+            // the named chip only feeds the others, so is found through them
+            let named = Inner::new_named(alloc, "R0", in_.into());
+            let inverted = named.get_out(alloc).out;
+            let first = Inner::new(alloc, inverted.into());
+            let second = Inner::new(alloc, in_.into());
+            let adder = Adder4::new(alloc, inverted.into());
+            ThreeOutputs {
+                out: [
+                    first.get_out(alloc).out.into(),
+                    second.get_out(alloc).out.into(),
+                    adder.get_out(alloc).out.into(),
+                ],
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        machine.process(TestchipInputs { in_: false }).unwrap();
+        let outputs = machine
+            .find_named_outputs(&[
+                "Testchip0/Adder4_0/out",
+                "Testchip0/R0/out",
+                "Testchip0/out-2",
+            ])
+            .unwrap();
+        assert_eq!(
+            outputs
+                .iter()
+                .map(|output| output.value())
+                .collect::<Vec<_>>(),
+            [Logic::Low, Logic::High, Logic::Low]
+        );
+        // the two unnamed Inners are numbered in the netlist's order, which isn't walked
+        assert_eq!(
+            machine
+                .find_named_outputs(&["Testchip0/R0/out", "Testchip0/Inner0/out"])
+                .err(),
+            Some(Error::UnknownNode("Testchip0/Inner0/out".to_owned()))
+        );
+    }
+
+    #[test]
+    fn when_machines_are_built_in_fresh_id_scopes_their_ids_match() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let first = Machine::new_in(&alloc, &IdScope::new(), Testchip::from).unwrap();
+        // building outside a scope moves the global counters on
+        Machine::new(&alloc, Testchip::from).unwrap();
+        let second = Machine::new_in(&alloc, &IdScope::new(), Testchip::from).unwrap();
+
+        let (first, second) = (first.netlist(), second.netlist());
+        assert_eq!(first, second);
+        assert_eq!(first.chips[0].id, "Testchip0");
+        assert_eq!(
+            first
+                .nodes
+                .iter()
+                .filter(|node| node.kind == NodeKind::UserInput)
+                .map(|node| node.source_id)
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn when_a_chip_is_given_a_label_and_id_prefix_they_replace_its_struct_name() {
+        #[chip(label = "Test chip (NAND)", id_prefix = "test")]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in1.into(), in2.into());
+            UnaryChipOutput {
+                out: ChipOutputType::NandOutput(nand),
+            }
+        }
+
+        let alloc = Bump::new();
+        let machine = Machine::new_in(&alloc, &IdScope::new(), Testchip::from).unwrap();
+        let netlist = machine.netlist();
+        assert_eq!(netlist.chips[0].label, "Test chip (NAND)");
+        assert_eq!(netlist.chips[0].id, "test0");
+    }
+
+    #[test]
+    fn when_a_clocked_chip_builds_clocked_subchips_they_are_wired_to_its_clock() {
+        #[chip(clocked)]
+        fn gate<'a>(
+            alloc: &'a Clocked<'a>,
+            in_: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nand::new(alloc, in_.into(), alloc.clock().into());
+            UnaryChipOutput { out: nand.into() }
+        }
+
+        #[chip(clocked)]
+        fn twogates<'a>(
+            alloc: &'a Clocked<'a>,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            BinaryChipOutput {
+                out1: Gate::new(alloc, in1.into()).get_out(alloc).out.into(),
+                out2: Gate::new(alloc, in2.into()).get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Twogates::from).unwrap();
+        assert_eq!(machine.clock_domains(), ["clock"]);
+        assert_eq!(
+            machine
+                .process(TwogatesInputs {
+                    in1: true,
+                    in2: false,
+                    clock: true
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: true
+            }
+        );
+        assert_eq!(
+            machine
+                .process(TwogatesInputs {
+                    in1: true,
+                    in2: false,
+                    clock: false
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: true,
+                out2: true
+            }
+        );
+        assert_eq!(machine.clock_domain_chips("clock").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn when_a_crate_path_is_given_the_generated_code_uses_it() {
+        mod wrapper {
+            pub use hdl as renamed;
+        }
+        use wrapper::renamed;
+
+        #[derive(StructuredData, PartialEq, Debug)]
+        #[structured(crate = "wrapper::renamed")]
+        struct Output<T> {
+            out: T,
+        }
+
+        #[chip(crate = "wrapper::renamed")]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a renamed::ChipInput<'a>,
+            in2: &'a renamed::ChipInput<'a>,
+        ) -> Output<renamed::ChipOutputType<'a>> {
+            let nand = renamed::Nand::new(alloc, in1.into(), in2.into());
+            Output { out: nand.into() }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = renamed::Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            Output { out: false }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_takes_a_slice_it_can_be_built_with_any_width() {
+        #[chip]
+        fn andn<'a>(
+            alloc: &'a Bump,
+            in_: &[&'a ChipInput<'a>],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = in_.iter().skip(1).fold(
+                Nand::new(alloc, in_[0].into(), in_[0].into()),
+                |acc, in_| {
+                    let and = Nand::new(alloc, acc.into(), acc.into());
+                    Nand::new(alloc, and.into(), (*in_).into())
+                },
+            );
+            UnaryChipOutput {
+                out: Nand::new(alloc, nand.into(), nand.into()).into(),
+            }
+        }
+
+        #[chip]
+        fn and3<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 3],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let in_ = in_.map(Input::from);
+            UnaryChipOutput {
+                out: Andn::new(alloc, &in_).get_out(alloc).out.into(),
+            }
+        }
+
+        #[chip]
+        fn and5<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 5],
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let in_ = in_.map(Input::from);
+            UnaryChipOutput {
+                out: Andn::new(alloc, &in_).get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut and3 = Machine::new(&alloc, And3::from).unwrap();
+        assert_eq!(
+            and3.process(And3Inputs { in_: [true; 3] }).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            and3.process(And3Inputs {
+                in_: [true, false, true]
+            })
+            .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        let mut and5 = Machine::new(&alloc, And5::from).unwrap();
+        assert_eq!(
+            and5.process(And5Inputs { in_: [true; 5] }).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert!(and5
+            .netlist()
+            .nodes
+            .iter()
+            .any(|node| node.kind == NodeKind::ChipInput && node.label == "in_-4"));
+    }
+
+    #[test]
+    fn when_a_chip_is_generic_over_a_type_each_instance_can_be_built_in_to_a_machine() {
+        trait Gate {
+            fn gate<'a>(
+                alloc: &'a Bump,
+                in1: &'a ChipInput<'a>,
+                in2: &'a ChipInput<'a>,
+            ) -> ChipOutputType<'a>;
+        }
+
+        struct NandGate;
+        impl Gate for NandGate {
+            fn gate<'a>(
+                alloc: &'a Bump,
+                in1: &'a ChipInput<'a>,
+                in2: &'a ChipInput<'a>,
+            ) -> ChipOutputType<'a> {
+                Nand::new(alloc, in1.into(), in2.into()).into()
+            }
+        }
+
+        struct AndGate;
+        impl Gate for AndGate {
+            fn gate<'a>(
+                alloc: &'a Bump,
+                in1: &'a ChipInput<'a>,
+                in2: &'a ChipInput<'a>,
+            ) -> ChipOutputType<'a> {
+                let nand = Nand::new(alloc, in1.into(), in2.into());
+                Nand::new(alloc, nand.into(), nand.into()).into()
+            }
+        }
+
+        // the function's lifetimes needn't be named 'a, and it can have others
+        #[chip(instance(NandChip(NandGate, 2), AndChip(AndGate, 2)))]
+        fn gatechip<'x, 'y, G, const N: usize>(
+            alloc: &'x Bump,
+            in1: [&'x ChipInput<'x>; N],
+            in2: [&'x ChipInput<'x>; N],
+        ) -> TwoBitNumOutput<ChipOutputType<'x>>
+        where
+            'x: 'y,
+            G: Gate,
+        {
+            TwoBitNumOutput {
+                out: core::array::from_fn(|i| G::gate(alloc, in1[i], in2[i])),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, NandChip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(NandChipInputs {
+                    in1: [true, false],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            TwoBitNumOutput { out: [false, true] }
+        );
+        let mut machine = Machine::new(&alloc, AndChip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(AndChipInputs {
+                    in1: [true, false],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            TwoBitNumOutput { out: [true, false] }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_is_generic_over_its_width_each_instance_can_be_built_in_to_a_machine() {
+        #[derive(StructuredData, PartialEq, Debug)]
+        struct Bits<T, const N: usize> {
+            out: [T; N],
+        }
+
+        #[chip(instance(Nand2(2), Nand3(3)))]
+        fn nandn<'a, const N: usize>(
+            alloc: &'a Bump,
+            in1: [&'a ChipInput<'a>; N],
+            in2: Bus<'a, N>,
+        ) -> Bits<ChipOutputType<'a>, N> {
+            Bits {
+                out: core::array::from_fn(|i| {
+                    ChipOutputType::NandOutput(Nand::new(alloc, in1[i].into(), in2[i]))
+                }),
+            }
+        }
+
+        // generic chips can be built at any width inside other chips
+        #[chip]
+        fn and1<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let nand = Nandn::<1>::new(alloc, [in1.into()], [in2].into());
+            let out = nand.get_out(alloc).out[0];
+            let not = Nandn::<1>::new(alloc, [out.into()], [out].into());
+            UnaryChipOutput {
+                out: not.get_out(alloc).out[0].into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Nand2::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Nand2Inputs {
+                    in1: [true, false],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            Bits { out: [false, true] }
+        );
+        assert_eq!(
+            Machine::<Nand3InputsFamily, 6, 3>::input_names(),
+            ["in1-0", "in1-1", "in1-2", "in2-0", "in2-1", "in2-2"]
+        );
+        // instances are labelled with their aliases
+        assert_eq!(
+            Machine::new(&alloc, Nand3::from)
+                .unwrap()
+                .netlist()
+                .chip_names(),
+            ["Nand3_0"]
+        );
+
+        let mut machine = Machine::new(&alloc, And1::from).unwrap();
+        assert_eq!(
+            machine
+                .process(And1Inputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(And1Inputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        // a width without an instance has no alias to be labelled with
+        assert_eq!(
+            machine.netlist().chip_names(),
+            ["And1_0", "Nandn0", "Nandn1"]
+        );
+    }
+
+    #[test]
+    fn when_a_chip_takes_a_parameter_each_instance_is_built_with_its_own_value() {
+        #[chip]
+        fn maybenot<'a>(
+            alloc: &'a Bump,
+            in_: &'a ChipInput<'a>,
+            #[param] invert: bool,
+        ) -> UnaryChipOutput<ChipOutputType<'a>> {
+            let out = if invert {
+                ChipOutputType::NandOutput(Nand::new(alloc, in_.into(), in_.into()))
+            } else {
+                ChipOutputType::ChipInput(in_)
+            };
+            UnaryChipOutput { out }
+        }
+
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in1: &'a ChipInput<'a>,
+            in2: &'a ChipInput<'a>,
+        ) -> BinaryChipOutput<ChipOutputType<'a>> {
+            let inverted = Maybenot::new(alloc, in1.into(), true);
+            let passed = Maybenot::builder(alloc).in_(in2).invert(false).build();
+            BinaryChipOutput {
+                out1: inverted.get_out(alloc).out.into(),
+                out2: passed.get_out(alloc).out.into(),
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: true
+            }
+        );
+        assert_eq!(machine.gate_count(), 1);
+    }
+
+    #[test]
+    fn when_a_chip_returns_an_array_of_structs_each_is_a_group_of_outputs() {
+        #[chip]
+        fn testchip<'a>(
+            alloc: &'a Bump,
+            in_: [&'a ChipInput<'a>; 2],
+        ) -> [BinaryChipOutput<ChipOutputType<'a>>; 2] {
+            in_.map(|in_| BinaryChipOutput {
+                out1: in_.into(),
+                out2: Nand::new(alloc, in_.into(), in_.into()).into(),
+            })
+        }
+
+        assert_eq!(
+            TestchipOutputs::<bool>::flat_names(),
+            ["out-0.out1", "out-0.out2", "out-1.out1", "out-1.out2"]
+        );
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Testchip::from).unwrap();
+        assert_eq!(
+            machine
+                .process(TestchipInputs { in_: [true, false] })
+                .unwrap(),
+            TestchipOutputs {
+                out: [
+                    BinaryChipOutput {
+                        out1: true,
+                        out2: false
+                    },
+                    BinaryChipOutput {
+                        out1: false,
+                        out2: true
+                    }
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn when_a_chip_is_written_as_wiring_it_expands_to_a_chip() {
+        hdl_chip! {
+            /// Exclusive or
+            #[chip(label = "XOR")]
+            xor {
+                inputs { a, b }
+                wires { n = nand(a, b); }
+                outputs { out = nand(nand(a, n), nand(b, n)) }
+            }
+        }
+        hdl_chip! {
+            swap {
+                inputs { in_[2], sel }
+                outputs { out[2] = [in_[1], in_[0]], sel_n = nand(sel, sel) }
+            }
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Xor::from).unwrap();
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            assert_eq!(
+                machine.process(XorInputs { a, b }).unwrap(),
+                XorOutputs { out: a != b }
+            );
+        }
+        assert_eq!(Xor::GATE_COUNT, Some(4));
+        let netlist = machine.netlist();
+        assert_eq!(netlist.chips[0].label, "XOR");
+        assert_eq!(netlist.chips[0].description, "Exclusive or");
+
+        let mut machine = Machine::new(&alloc, Swap::from).unwrap();
+        assert_eq!(
+            machine
+                .process(SwapInputs {
+                    in_: [true, false],
+                    sel: true
+                })
+                .unwrap(),
+            SwapOutputs {
+                out: [false, true],
+                sel_n: false
+            }
+        );
+    }
+
+    // the test checking a chip against its model can't be nested in another test, so
+    // the chip is declared here
+    #[chip(model = "nand_model")]
+    fn modelled<'a>(
+        alloc: &'a Bump,
+        in1: &'a ChipInput<'a>,
+        in2: &'a ChipInput<'a>,
+    ) -> UnaryChipOutput<ChipOutputType<'a>> {
+        let nand = Nand::new(alloc, in1.into(), in2.into());
+        UnaryChipOutput {
+            out: ChipOutputType::NandOutput(nand),
+        }
+    }
+
+    fn nand_model(inputs: ModelledInputs<bool>) -> UnaryChipOutput<bool> {
+        UnaryChipOutput {
+            out: !(inputs.in1 && inputs.in2),
+        }
+    }
+
+    #[test]
+    fn when_a_chip_disagrees_with_a_model_the_first_differing_inputs_are_reported() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Modelled::from).unwrap();
+        assert_eq!(
+            machine.check_model(|inputs| UnaryChipOutput {
+                out: inputs.in1 != inputs.in2
+            }),
+            Err(Error::ModelMismatch {
+                inputs: vec![false, false],
+                expected: vec![false],
+                found: vec![true]
+            })
         );
+        assert_eq!(machine.check_model(nand_model), Ok(()));
     }
 }
@@ -0,0 +1,6 @@
+// chips which the #[chip] macro should reject, or whose use should fail to type check,
+// along with the errors they give
+#[test]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
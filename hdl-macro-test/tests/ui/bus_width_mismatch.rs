@@ -0,0 +1,21 @@
+use bumpalo::Bump;
+use hdl::{ChipInput, ChipOutputType, Input, Nand, UserInput};
+use hdl_macro::{chip, StructuredData};
+
+#[derive(StructuredData)]
+struct WideOutput<T> {
+    out: T,
+}
+
+#[chip]
+fn wide<'a>(alloc: &'a Bump, a: [&'a ChipInput<'a>; 16]) -> WideOutput<ChipOutputType<'a>> {
+    WideOutput {
+        out: ChipOutputType::NandOutput(Nand::new(alloc, a[0].into(), a[15].into())),
+    }
+}
+
+fn main() {
+    let alloc = Bump::new();
+    let narrow: [_; 12] = core::array::from_fn(|_| Input::UserInput(UserInput::new(&alloc)));
+    Wide::new(&alloc, narrow);
+}
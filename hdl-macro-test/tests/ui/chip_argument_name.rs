@@ -0,0 +1,24 @@
+// the macro gives only the error, so nothing else is compiled to use the imports
+#![allow(unused_imports)]
+
+use bumpalo::Bump;
+use hdl::{ChipInput, ChipOutputType, Nand};
+use hdl_macro::{chip, StructuredData};
+
+#[derive(StructuredData)]
+struct NandOutput<T> {
+    out: T,
+}
+
+// each input is named for its port, so it can't be destructured
+#[chip]
+fn nand<'a>(
+    alloc: &'a Bump,
+    [in1, in2]: [&'a ChipInput<'a>; 2],
+) -> NandOutput<ChipOutputType<'a>> {
+    NandOutput {
+        out: ChipOutputType::NandOutput(Nand::new(alloc, in1.into(), in2.into())),
+    }
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+// the macro gives only the error, so nothing else is compiled to use the imports
+#![allow(unused_imports)]
+
+use bumpalo::Bump;
+use hdl::{ChipOutputType, Nand, UserInput};
+use hdl_macro::{chip, StructuredData};
+
+#[derive(StructuredData)]
+struct NotOutput<T> {
+    out: T,
+}
+
+// a chip's inputs are wires, not values
+#[chip]
+fn not<'a>(alloc: &'a Bump, in_: bool) -> NotOutput<ChipOutputType<'a>> {
+    let in_ = UserInput::from(alloc, in_);
+    NotOutput {
+        out: ChipOutputType::NandOutput(Nand::new(alloc, in_.into(), in_.into())),
+    }
+}
+
+fn main() {}
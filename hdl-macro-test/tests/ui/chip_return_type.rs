@@ -0,0 +1,14 @@
+// the macro gives only the error, so nothing else is compiled to use the imports
+#![allow(unused_imports)]
+
+use bumpalo::Bump;
+use hdl::{ChipInput, Nand};
+use hdl_macro::chip;
+
+// a chip's outputs are wires, so it can't return the NAND itself
+#[chip]
+fn nand<'a>(alloc: &'a Bump, in1: &'a ChipInput<'a>, in2: &'a ChipInput<'a>) -> &'a Nand<'a> {
+    Nand::new(alloc, in1.into(), in2.into())
+}
+
+fn main() {}
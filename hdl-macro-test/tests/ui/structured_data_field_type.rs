@@ -0,0 +1,9 @@
+use hdl_macro::StructuredData;
+
+// each port is a wire or a fixed width bus of them
+#[derive(StructuredData)]
+struct Ports<T> {
+    out: (T, T),
+}
+
+fn main() {}
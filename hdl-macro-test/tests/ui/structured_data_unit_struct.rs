@@ -0,0 +1,7 @@
+use hdl_macro::StructuredData;
+
+// a unit struct has no fields to hold the ports
+#[derive(StructuredData)]
+struct NoPorts;
+
+fn main() {}
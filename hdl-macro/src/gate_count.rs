@@ -0,0 +1,341 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use quote::quote;
+use syn::{
+    parse::Parser,
+    punctuated::Punctuated,
+    visit::{self, Visit},
+    Expr, Ident, Pat, RangeLimits, Token, Type,
+};
+
+// hdl types which are built with the allocator but hold no gates
+const GATELESS_TYPES: &[&str] = &[
+    "UserInput",
+    "ChipInput",
+    "ChipOutput",
+    "ChipOutputWrapper",
+    "Clocked",
+    "LookupTable",
+    "Lookup",
+    "SharedBus",
+];
+
+// A chip's GATE_COUNT, the NANDs it builds plus the GATE_COUNTs of the chips it builds,
+// worked out from its body as a sum the compiler can evaluate. Chips built in a loop over
+// a range are counted once for each time round it, so the range's ends must be constants,
+// and a loop which can `break` or `continue` isn't counted. The closures passed to
+// `create_subchip` are run once each. A chip built anywhere else the count can't be seen
+// from the body, e.g. in any other closure, a branch, a `while` loop, a loop over an
+// iterator or a function taking the allocator, leaves the GATE_COUNT as None, as does a
+// chip built from one whose GATE_COUNT is None. The body is read as it's written, so a
+// chip built by a macro other than `pipeline!` isn't seen either. A generic chip is
+// counted at the widths it's built with, so they must be given where it's built, e.g.
+// `Addern::<N>::new(alloc, ...)`
+pub(crate) fn gate_count(body: &syn::Block, alloc: &Ident, krate: &syn::Path) -> TokenStream {
+    let mut counter = Counter {
+        alloc,
+        multipliers: vec![],
+        unknown: 0,
+        countable: true,
+        terms: vec![],
+    };
+    counter.visit_block(body);
+    if counter.countable {
+        let terms = counter.terms;
+        quote! { #krate::sum_gate_counts(&[#(#terms),*]) }
+    } else {
+        quote! { Option::None }
+    }
+}
+
+struct Counter<'i> {
+    alloc: &'i Ident,
+    // the lengths of the ranges the chips being visited are built in a loop over
+    multipliers: Vec<TokenStream>,
+    // how deep the chips being visited are in code run an unknown number of times
+    unknown: usize,
+    countable: bool,
+    terms: Vec<TokenStream>,
+}
+
+impl Counter<'_> {
+    fn count(&mut self, ty: TokenStream) {
+        if self.unknown > 0 {
+            self.countable = false;
+        } else {
+            let multipliers = &self.multipliers;
+            self.terms
+                .push(quote! { (1 #(* #multipliers)*, <#ty>::GATE_COUNT) });
+        }
+    }
+
+    fn is_alloc(&self, expr: &Expr) -> bool {
+        matches!(expr, Expr::Path(path) if path.path.is_ident(self.alloc))
+    }
+
+    fn mentions_alloc(&self, tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ident == *self.alloc,
+            TokenTree::Group(group) => self.mentions_alloc(group.stream()),
+            _ => false,
+        })
+    }
+
+    fn visit_unknown(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.unknown += 1;
+        visit(self);
+        self.unknown -= 1;
+    }
+
+    // pipeline!(alloc, input => Chip(args) => Next(args)) builds each chip once, along
+    // with any chips its input and arguments build
+    fn count_pipeline(&mut self, tokens: TokenStream) {
+        let mut stages = vec![vec![]];
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match (&token, tokens.peek()) {
+                (TokenTree::Punct(eq), Some(TokenTree::Punct(gt)))
+                    if eq.as_char() == '='
+                        && eq.spacing() == Spacing::Joint
+                        && gt.as_char() == '>' =>
+                {
+                    tokens.next();
+                    stages.push(vec![]);
+                }
+                _ => stages.last_mut().unwrap().push(token),
+            }
+        }
+        let mut stages = stages.into_iter();
+        let input = stages.next().unwrap().into_iter().collect();
+        self.visit_args(input);
+        for stage in stages {
+            match &stage[..] {
+                [TokenTree::Ident(chip), TokenTree::Group(args)]
+                    if args.delimiter() == Delimiter::Parenthesis =>
+                {
+                    self.count(quote! { #chip });
+                    self.visit_args(args.stream());
+                }
+                _ => self.countable = false,
+            }
+        }
+    }
+
+    // the comma separated expressions of a macro's arguments
+    fn visit_args(&mut self, tokens: TokenStream) {
+        match Punctuated::<Expr, Token![,]>::parse_terminated.parse2(tokens) {
+            Ok(args) => {
+                for arg in &args {
+                    self.visit_expr(arg);
+                }
+            }
+            Err(_) => self.countable = false,
+        }
+    }
+}
+
+// the number of times a loop over a range goes round, forwards or reversed
+fn range_len(expr: &Expr) -> Option<TokenStream> {
+    match expr {
+        Expr::Range(syn::ExprRange {
+            from: Some(from),
+            to: Some(to),
+            limits,
+            ..
+        }) => Some(match limits {
+            RangeLimits::HalfOpen(_) => quote! { ((#to) - (#from)) },
+            RangeLimits::Closed(_) => quote! { ((#to) - (#from) + 1) },
+        }),
+        Expr::Paren(paren) => range_len(&paren.expr),
+        Expr::MethodCall(call) if call.method == "rev" && call.args.is_empty() => {
+            range_len(&call.receiver)
+        }
+        _ => None,
+    }
+}
+
+// whether a loop's body can leave it early, or skip chips it builds. A `break` in a loop
+// nested in the body might only leave the nested loop, but that loop isn't counted either
+#[derive(Default)]
+struct Exits(bool);
+
+impl<'ast> Visit<'ast> for Exits {
+    fn visit_expr_break(&mut self, _: &'ast syn::ExprBreak) {
+        self.0 = true;
+    }
+
+    fn visit_expr_continue(&mut self, _: &'ast syn::ExprContinue) {
+        self.0 = true;
+    }
+
+    // a closure can't break out of the loop it's in
+    fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
+
+fn exits_early(body: &syn::Block) -> bool {
+    let mut exits = Exits::default();
+    exits.visit_block(body);
+    exits.0
+}
+
+impl<'ast> Visit<'ast> for Counter<'_> {
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if call.args.iter().any(|arg| self.is_alloc(arg)) {
+            let path = match &*call.func {
+                Expr::Path(path) if path.qself.is_none() && path.path.segments.len() > 1 => {
+                    Some(&path.path)
+                }
+                _ => None,
+            };
+            let constructor = path.and_then(|path| {
+                let segments = path.segments.iter().collect::<Vec<_>>();
+                let (function, ty) = segments.split_last()?;
+                let ty_name = &ty.last()?.ident;
+                if GATELESS_TYPES.iter().any(|gateless| ty_name == gateless) {
+                    return Some(None);
+                }
+                let builds = ["new", "new_named", "builder"]
+                    .iter()
+                    .any(|build| function.ident == build);
+                let leading = &path.leading_colon;
+                (builds && ty_name != "DefaultChip" && self.is_alloc(&call.args[0]))
+                    .then(|| Some(quote! { #leading #(#ty)::* }))
+            });
+            match constructor {
+                Some(Some(ty)) => self.count(ty),
+                Some(None) => {}
+                None => self.countable = false,
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let builds_nothing = ["get_out", "set_inputs"]
+            .iter()
+            .any(|method| call.method == method);
+        if !builds_nothing && call.args.iter().any(|arg| self.is_alloc(arg)) {
+            self.countable = false;
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    // `let chip: &Chip = DefaultChip::new(alloc)` and
+    // `let (a, b): (&A, &B) = create_subchip(alloc, ...)` name the chips they build
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        let call = match &local.init {
+            Some((_, init)) => match &**init {
+                Expr::Call(call) => Some(call),
+                _ => None,
+            },
+            None => None,
+        };
+        let function = call.and_then(|call| match &*call.func {
+            Expr::Path(path) => path.path.segments.last().map(|segment| &segment.ident),
+            _ => None,
+        });
+        let built = match (&local.pat, call, function) {
+            (Pat::Type(pat), Some(call), Some(function))
+                if call.args.first().is_some_and(|arg| self.is_alloc(arg))
+                    && (function == "new" || function == "create_subchip") =>
+            {
+                match &*pat.ty {
+                    Type::Reference(chip) if function == "new" => Some(vec![&chip.elem]),
+                    Type::Tuple(chips) if function == "create_subchip" => chips
+                        .elems
+                        .iter()
+                        .map(|chip| match chip {
+                            Type::Reference(chip) => Some(&chip.elem),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match (built, call) {
+            (Some(chips), Some(call)) => {
+                for chip in chips {
+                    self.count(quote! { #chip });
+                }
+                for arg in call.args.iter().skip(1) {
+                    // create_subchip runs each of its closures once
+                    let arg = match arg {
+                        Expr::Reference(reference)
+                            if function.is_some_and(|function| function == "create_subchip") =>
+                        {
+                            match &*reference.expr {
+                                Expr::Closure(closure) => &closure.body,
+                                _ => arg,
+                            }
+                        }
+                        _ => arg,
+                    };
+                    self.visit_expr(arg);
+                }
+            }
+            _ => visit::visit_local(self, local),
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, for_loop: &'ast syn::ExprForLoop) {
+        self.visit_expr(&for_loop.expr);
+        match range_len(&for_loop.expr).filter(|_| !exits_early(&for_loop.body)) {
+            Some(len) => {
+                self.multipliers.push(len);
+                self.visit_block(&for_loop.body);
+                self.multipliers.pop();
+            }
+            None => self.visit_unknown(|counter| counter.visit_block(&for_loop.body)),
+        }
+    }
+
+    fn visit_expr_while(&mut self, while_loop: &'ast syn::ExprWhile) {
+        self.visit_unknown(|counter| visit::visit_expr_while(counter, while_loop));
+    }
+
+    fn visit_expr_loop(&mut self, expr_loop: &'ast syn::ExprLoop) {
+        self.visit_unknown(|counter| visit::visit_expr_loop(counter, expr_loop));
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        self.visit_unknown(|counter| visit::visit_expr_closure(counter, closure));
+    }
+
+    fn visit_expr_if(&mut self, expr_if: &'ast syn::ExprIf) {
+        self.visit_expr(&expr_if.cond);
+        self.visit_unknown(|counter| {
+            counter.visit_block(&expr_if.then_branch);
+            if let Some((_, else_branch)) = &expr_if.else_branch {
+                counter.visit_expr(else_branch);
+            }
+        });
+    }
+
+    fn visit_expr_match(&mut self, expr_match: &'ast syn::ExprMatch) {
+        self.visit_expr(&expr_match.expr);
+        self.visit_unknown(|counter| {
+            for arm in &expr_match.arms {
+                counter.visit_arm(arm);
+            }
+        });
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "pipeline")
+        {
+            self.count_pipeline(mac.tokens.clone());
+        } else if self.mentions_alloc(mac.tokens.clone()) {
+            self.countable = false;
+        }
+    }
+
+    // functions and the like declared in the body aren't run by it
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
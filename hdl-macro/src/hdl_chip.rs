@@ -0,0 +1,290 @@
+use quote::quote;
+use syn::{
+    braced, bracketed, parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token::Comma,
+    Ident, LitInt, Token,
+};
+
+use crate::{default_crate_path, expand_chip, expand_structured_data, parse_chip_attrs};
+
+const HDL_CHIP_BLOCK_ERR: &str =
+    "hdl_chip! takes a chip name followed by braces holding inputs { ... }, an optional wires { ... } and outputs { ... }";
+const HDL_CHIP_SIGNAL_ERR: &str = "unknown input or wire";
+const HDL_CHIP_INDEX_ERR: &str =
+    "array inputs must be indexed with a literal less than their width, and other signals can't be indexed";
+const HDL_CHIP_DUPLICATE_ERR: &str = "inputs, wires and outputs must have different names";
+const HDL_CHIP_OUTPUT_ERR: &str = "an array output must be given one signal for each element";
+const HDL_CHIP_ATTR_ERR: &str = "hdl_chip! only takes doc comments and #[chip(...)] attributes";
+
+// A chip written as wiring rather than a function body, e.g.
+//
+// hdl_chip! {
+//     xor {
+//         inputs { a, b }
+//         wires { n = nand(a, b); }
+//         outputs { out = nand(nand(a, n), nand(b, n)) }
+//     }
+// }
+//
+// which expands to a #[chip] function returning a `XorOutputs` struct
+pub struct HdlChip {
+    attrs: Vec<syn::Attribute>,
+    name: Ident,
+    inputs: Vec<Port>,
+    wires: Vec<(Ident, Signal)>,
+    outputs: Vec<(Port, Vec<Signal>)>,
+}
+
+// an input or output, and its width if it's an array
+struct Port {
+    name: Ident,
+    width: Option<LitInt>,
+}
+
+enum Signal {
+    Name(Ident),
+    Index(Ident, LitInt),
+    Nand(Box<Signal>, Box<Signal>),
+}
+
+impl Parse for Port {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let width = if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            Some(content.parse()?)
+        } else {
+            None
+        };
+        Ok(Port { name, width })
+    }
+}
+
+impl Parse for Signal {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        if name == "nand" && input.peek(syn::token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            let a = content.parse()?;
+            content.parse::<Token![,]>()?;
+            let b = content.parse()?;
+            return Ok(Signal::Nand(Box::new(a), Box::new(b)));
+        }
+        if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            return Ok(Signal::Index(name, content.parse()?));
+        }
+        Ok(Signal::Name(name))
+    }
+}
+
+// parses the contents of a block introduced by `keyword`
+fn block<'b>(input: ParseStream<'b>, keyword: &str) -> syn::Result<syn::parse::ParseBuffer<'b>> {
+    let ident: Ident = input.parse()?;
+    if ident != keyword {
+        return Err(syn::Error::new_spanned(ident, HDL_CHIP_BLOCK_ERR));
+    }
+    let content;
+    braced!(content in input);
+    Ok(content)
+}
+
+impl Parse for HdlChip {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let name = input.parse()?;
+        let content;
+        braced!(content in input);
+
+        let inputs = block(&content, "inputs")?;
+        let inputs = Punctuated::<Port, Comma>::parse_terminated(&inputs)?;
+        // a chip without wires can leave out the block
+        let mut wires = vec![];
+        if content
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "wires")
+        {
+            let block = block(&content, "wires")?;
+            while !block.is_empty() {
+                let wire = block.parse()?;
+                block.parse::<Token![=]>()?;
+                wires.push((wire, block.parse()?));
+                block.parse::<Token![;]>()?;
+            }
+        }
+        let outputs = block(&content, "outputs")?;
+        let outputs =
+            Punctuated::<(Port, Vec<Signal>), Comma>::parse_terminated_with(&outputs, |input| {
+                let port: Port = input.parse()?;
+                input.parse::<Token![=]>()?;
+                let signals = if port.width.is_some() {
+                    let content;
+                    bracketed!(content in input);
+                    Punctuated::<Signal, Comma>::parse_terminated(&content)?
+                        .into_iter()
+                        .collect()
+                } else {
+                    vec![input.parse()?]
+                };
+                Ok((port, signals))
+            })?;
+
+        Ok(HdlChip {
+            attrs,
+            name,
+            inputs: inputs.into_iter().collect(),
+            wires,
+            outputs: outputs.into_iter().collect(),
+        })
+    }
+}
+
+// the width of each signal declared so far, where `None` is a single bit
+type Scope = Vec<(Ident, Option<usize>)>;
+
+fn declare(scope: &mut Scope, name: &Ident, width: Option<usize>) -> syn::Result<()> {
+    if scope.iter().any(|(declared, _)| declared == name) {
+        return Err(syn::Error::new_spanned(name, HDL_CHIP_DUPLICATE_ERR));
+    }
+    scope.push((name.clone(), width));
+    Ok(())
+}
+
+// a signal as an expression taking `Into<Input>`, checking the names it uses
+fn signal_tokens(
+    signal: &Signal,
+    scope: &Scope,
+    krate: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let width = |name: &Ident| {
+        scope
+            .iter()
+            .find(|(declared, _)| declared == name)
+            .map(|(_, width)| *width)
+            .ok_or_else(|| syn::Error::new_spanned(name, HDL_CHIP_SIGNAL_ERR))
+    };
+    match signal {
+        Signal::Name(name) => match width(name)? {
+            None => Ok(quote! {#name}),
+            Some(_) => Err(syn::Error::new_spanned(name, HDL_CHIP_INDEX_ERR)),
+        },
+        Signal::Index(name, index) => match width(name)? {
+            Some(width) if index.base10_parse::<usize>()? < width => Ok(quote! {#name[#index]}),
+            _ => Err(syn::Error::new_spanned(index, HDL_CHIP_INDEX_ERR)),
+        },
+        Signal::Nand(a, b) => {
+            let a = signal_tokens(a, scope, krate)?;
+            let b = signal_tokens(b, scope, krate)?;
+            Ok(quote! { #krate::Nand::new(alloc, #a.into(), #b.into()) })
+        }
+    }
+}
+
+fn port_width(port: &Port) -> syn::Result<Option<usize>> {
+    port.width.as_ref().map(LitInt::base10_parse).transpose()
+}
+
+pub fn expand_hdl_chip(chip: HdlChip) -> syn::Result<proc_macro2::TokenStream> {
+    // #[chip(...)] arguments are given to the chip, and doc comments to its function
+    let mut chip_args = vec![];
+    let mut docs = vec![];
+    for attr in chip.attrs {
+        if attr.path.is_ident("doc") {
+            docs.push(attr);
+        } else if attr.path.is_ident("chip") {
+            match attr.parse_meta()? {
+                syn::Meta::List(list) => chip_args.extend(list.nested),
+                _ => return Err(syn::Error::new_spanned(attr, HDL_CHIP_ATTR_ERR)),
+            }
+        } else {
+            return Err(syn::Error::new_spanned(attr, HDL_CHIP_ATTR_ERR));
+        }
+    }
+    let chip_attrs = parse_chip_attrs(chip_args)?;
+    let krate = chip_attrs.krate.clone().unwrap_or_else(default_crate_path);
+
+    let mut scope = Scope::new();
+    let mut args = vec![];
+    for port in &chip.inputs {
+        let name = &port.name;
+        let width = port_width(port)?;
+        declare(&mut scope, name, width)?;
+        args.push(match &port.width {
+            Some(width) => quote! { #name: [&'a #krate::ChipInput<'a>; #width] },
+            None => quote! { #name: &'a #krate::ChipInput<'a> },
+        });
+    }
+    let mut wires = vec![];
+    for (name, signal) in &chip.wires {
+        let signal = signal_tokens(signal, &scope, &krate)?;
+        declare(&mut scope, name, None)?;
+        wires.push(quote! { let #name = #signal; });
+    }
+
+    let outputs_name = Ident::new(
+        &format!("{}Outputs", crate::struct_name(&chip.name)),
+        chip.name.span(),
+    );
+    let mut output_fields = vec![];
+    let mut output_values = vec![];
+    for (port, signals) in &chip.outputs {
+        let name = &port.name;
+        declare(&mut scope, name, None)?;
+        let signals = signals
+            .iter()
+            .map(|signal| {
+                let signal = signal_tokens(signal, &scope, &krate)?;
+                Ok(quote! { #krate::ChipOutputType::from(#signal) })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        match &port.width {
+            Some(width) => {
+                if port_width(port)? != Some(signals.len()) {
+                    return Err(syn::Error::new_spanned(width, HDL_CHIP_OUTPUT_ERR));
+                }
+                output_fields.push(quote! { #name: [T; #width] });
+                output_values.push(quote! { #name: [#(#signals),*] });
+            }
+            None => {
+                output_fields.push(quote! { #name: T });
+                output_values.push(quote! { #name: #(#signals)* });
+            }
+        }
+    }
+    let outputs_struct: syn::DeriveInput = syn::parse_quote! {
+        #[derive(Debug, PartialEq)]
+        struct #outputs_name<T> {
+            #(#output_fields),*
+        }
+    };
+    let outputs_impls = expand_structured_data(&outputs_struct, &krate)?;
+
+    let name = &chip.name;
+    let chip_fn: syn::ItemFn = syn::parse_quote! {
+        #(#docs)*
+        fn #name<'a>(
+            alloc: &'a #krate::bumpalo::Bump,
+            #(#args),*
+        ) -> #outputs_name<#krate::ChipOutputType<'a>> {
+            #(#wires)*
+            #outputs_name {
+                #(#output_values),*
+            }
+        }
+    };
+    let chip_impls = expand_chip(chip_attrs, chip_fn)?;
+
+    Ok(quote! {
+        #outputs_struct
+
+        #outputs_impls
+
+        #chip_impls
+    })
+}
@@ -1,282 +1,1652 @@
+mod gate_count;
+mod hdl_chip;
+
 use proc_macro::TokenStream;
 use proc_macro2::Span;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::{
-    punctuated::Punctuated,
-    spanned::Spanned,
-    token::{Colon2, Comma, Semi},
-    GenericParam, Ident, ItemFn, Lifetime, LifetimeDef, LitInt, LitStr, PathArguments,
+    parse_macro_input, punctuated::Punctuated, spanned::Spanned, token::Comma, AttributeArgs,
+    GenericArgument, GenericParam, Ident, ItemFn, Lifetime, LifetimeDef, Lit, LitInt, LitStr, Meta,
+    NestedMeta, PathArguments,
 };
 
 const CHIP_FN_TYPE_ERR: &str =
-    "chip function must return type [ChipOutputInner;n] where n is a literal greater than 0";
-const CHIP_ARG_TYPE_ERR: &str = "chip function must take arguments of &Bump,{Input<'_>|[Input<'_>; N]}* where _n_ is a literal greater than 0";
+    "chip function must return a StructuredData struct of ChipOutputType, e.g. ArrayLen16<ChipOutputType<'a>>, or a non-generic chip an array of them with a literal width";
+const CHIP_ARG_TYPE_ERR: &str = "chip function must take arguments of &Bump,{Input<'_>|[Input<'_>; N]|[[Input<'_>; N]; M]|&[Input<'_>]|Bus<'_, N>|#[param] Type}* where each width is a literal greater than 0 or a const parameter";
+const CHIP_ARG_NAME_ERR: &str = "chip function arguments must be plain identifiers";
+const CHIP_CLOCK_ERR: &str =
+    "a clocked chip is given its clock port by the attribute, and reads it from alloc.clock()";
+const CHIP_DYNAMIC_INSTANCE_ERR: &str =
+    "a chip taking slices or #[param] arguments can't have instances, use it in a chip of fixed width and parameters instead";
+const CHIP_DEFAULT_ERR: &str = "a chip input's default must be #[default(true|false)], or a number which fits an array or bus input, e.g. #[default(1)]";
+const CHIP_STRUCTURED_ERR: &str =
+    "a chip input's structured attribute must be #[structured(active_low)]";
+const CHIP_WIDTH_ERR: &str =
+    "width must be a literal or one of the chip function's const parameters";
+
+const CHIP_ATTR_ERR: &str =
+    "chip attribute must be of the form #[chip(clocked, crate = \"path\", reset = \"input\", label = \"Label\", id_prefix = \"prefix\", model = \"path\", instance(Name(width|Type, ...)))]";
+const CHIP_MODEL_ERR: &str =
+    "only chips which aren't generic or clocked can be checked against a model";
+const CHIP_INSTANCE_ERR: &str =
+    "chip instances must give a literal for each of the chip function's const parameters and a type for each of its type parameters, in order";
+
+// options given as #[chip(...)] arguments
+#[derive(Default)]
+struct ChipAttrs {
+    reset: Option<LitStr>,
+    // shown in place of the chip's struct name, and the start of its ids
+    label: Option<LitStr>,
+    id_prefix: Option<LitStr>,
+    // whether the chip gets a clock port which its clocked subchips are wired to
+    clocked: bool,
+    // the path of the hdl crate in the generated code
+    krate: Option<syn::Path>,
+    // names and generic arguments of the concrete chips built from a generic chip
+    instances: Vec<(Ident, Vec<InstanceArg>)>,
+    // a function from the chip's inputs to its outputs, which a generated test checks
+    // the chip against
+    model: Option<syn::Path>,
+}
+
+// an instance's value for one of the chip's const or type parameters
+enum InstanceArg {
+    Width(LitInt),
+    Type(syn::Path),
+}
+
+impl quote::ToTokens for InstanceArg {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            InstanceArg::Width(width) => width.to_tokens(tokens),
+            InstanceArg::Type(ty) => ty.to_tokens(tokens),
+        }
+    }
+}
+
+fn parse_chip_attrs(args: AttributeArgs) -> syn::Result<ChipAttrs> {
+    let mut attrs = ChipAttrs::default();
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("reset") => match nv.lit {
+                Lit::Str(reset) => attrs.reset = Some(reset),
+                lit => return Err(syn::Error::new_spanned(lit, CHIP_ATTR_ERR)),
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("label") => match nv.lit {
+                Lit::Str(label) => attrs.label = Some(label),
+                lit => return Err(syn::Error::new_spanned(lit, CHIP_ATTR_ERR)),
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("id_prefix") => {
+                match nv.lit {
+                    Lit::Str(id_prefix) => attrs.id_prefix = Some(id_prefix),
+                    lit => return Err(syn::Error::new_spanned(lit, CHIP_ATTR_ERR)),
+                }
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("clocked") => attrs.clocked = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => {
+                attrs.krate = Some(crate_path(&nv.lit, CHIP_ATTR_ERR)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("model") => {
+                attrs.model = Some(crate_path(&nv.lit, CHIP_ATTR_ERR)?)
+            }
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("instance") => {
+                for instance in list.nested {
+                    let NestedMeta::Meta(Meta::List(instance)) = instance else {
+                        return Err(syn::Error::new_spanned(instance, CHIP_ATTR_ERR));
+                    };
+                    let Some(name) = instance.path.get_ident().cloned() else {
+                        return Err(syn::Error::new_spanned(instance.path, CHIP_ATTR_ERR));
+                    };
+                    let values = instance
+                        .nested
+                        .into_iter()
+                        .map(|value| match value {
+                            NestedMeta::Lit(Lit::Int(value)) => Ok(InstanceArg::Width(value)),
+                            NestedMeta::Meta(Meta::Path(ty)) => Ok(InstanceArg::Type(ty)),
+                            value => Err(syn::Error::new_spanned(value, CHIP_ATTR_ERR)),
+                        })
+                        .collect::<syn::Result<_>>()?;
+                    attrs.instances.push((name, values));
+                }
+            }
+            arg => return Err(syn::Error::new_spanned(arg, CHIP_ATTR_ERR)),
+        }
+    }
+    Ok(attrs)
+}
+
+// The generated code names the hdl crate, and the crates it re-exports, by this path. It
+// can be set for crates which depend on hdl under another name or through a wrapper
+fn crate_path(lit: &Lit, err: &str) -> syn::Result<syn::Path> {
+    match lit {
+        Lit::Str(path) => path.parse(),
+        lit => Err(syn::Error::new_spanned(lit, err)),
+    }
+}
+
+fn default_crate_path() -> syn::Path {
+    syn::parse_quote!(::hdl)
+}
+
+enum ArgType {
+    Input,
+    // the width of arrays and buses is either a literal or one of the chip's const
+    // parameters
+    InputArray(syn::Expr),
+    Bus(syn::Expr),
+    // an array of arrays, with the outermost width first
+    InputGrid(Vec<syn::Expr>),
+    // a slice, whose width is only known once the chip is built
+    InputSlice,
+    // a value rather than wires, given by #[param] on the argument, e.g. a ROM's
+    // contents. It's passed to the chip function as is
+    Param(Box<syn::Type>),
+}
+
+// the widths of an array of arrays, outermost first
+fn array_dims(ty: &syn::TypeArray) -> Vec<syn::Expr> {
+    let mut dims = vec![ty.len.clone()];
+    let mut elem = &*ty.elem;
+    while let syn::Type::Array(inner) = elem {
+        dims.push(inner.len.clone());
+        elem = &*inner.elem;
+    }
+    dims
+}
+
+// an array of arrays of `elem` with the given widths, outermost first
+fn array_type(
+    elem: proc_macro2::TokenStream,
+    dims: impl DoubleEndedIterator<Item = proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
+    dims.rev().fold(elem, |elem, dim| quote! {[#elem; #dim]})
+}
+
+// the width of a `Bus<'a, N>` argument
+fn bus_width(path: &syn::TypePath) -> syn::Result<syn::Expr> {
+    let error = || syn::Error::new_spanned(path, CHIP_ARG_TYPE_ERR);
+    let segment = path.path.segments.last().ok_or_else(error)?;
+    if segment.ident != "Bus" {
+        return Err(error());
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return Err(error());
+    };
+    args.args
+        .iter()
+        .find_map(|arg| match arg {
+            GenericArgument::Const(width @ syn::Expr::Lit(_)) => Some(width.clone()),
+            // a bare const parameter is indistinguishable from a type when parsing
+            GenericArgument::Type(syn::Type::Path(param)) if param.path.get_ident().is_some() => {
+                Some(syn::parse_quote!(#param))
+            }
+            _ => None,
+        })
+        .ok_or_else(error)
+}
+
+// A chip's const and type parameters, and the values an instance gives them. Empty
+// values leave the parameters in place, for code inside the generic impls
+struct GenericArgs<'c> {
+    params: &'c [Ident],
+    // the const and type parameters in the order they're declared in
+    order: &'c [Ident],
+    values: &'c [InstanceArg],
+}
+
+impl GenericArgs<'_> {
+    fn value(&self, ident: &Ident) -> Option<&LitInt> {
+        let position = self.order.iter().position(|param| param == ident)?;
+        match self.values.get(position)? {
+            InstanceArg::Width(value) => Some(value),
+            InstanceArg::Type(_) => None,
+        }
+    }
+
+    fn is_param(&self, ident: &Ident) -> bool {
+        self.params.contains(ident)
+    }
+
+    // the generic arguments of the chip's struct and function
+    fn args(&self) -> Vec<proc_macro2::TokenStream> {
+        self.order
+            .iter()
+            .enumerate()
+            .map(|(i, param)| match self.values.get(i) {
+                Some(value) => quote! {#value},
+                None => quote! {#param},
+            })
+            .collect()
+    }
+
+    fn width(&self, width: &syn::Expr) -> syn::Expr {
+        match width {
+            syn::Expr::Path(path) => {
+                match path.path.get_ident().and_then(|ident| self.value(ident)) {
+                    Some(value) => syn::parse_quote!(#value),
+                    None => width.clone(),
+                }
+            }
+            _ => width.clone(),
+        }
+    }
+
+    fn width_value(&self, width: &syn::Expr) -> syn::Result<usize> {
+        match self.width(width) {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: Lit::Int(width),
+                ..
+            }) => width.base10_parse(),
+            _ => Err(syn::Error::new_spanned(width, CHIP_WIDTH_ERR)),
+        }
+    }
+
+    // the turbofish for calling the chip function
+    fn turbofish(&self) -> proc_macro2::TokenStream {
+        if self.order.is_empty() {
+            return quote! {};
+        }
+        let args = self.args();
+        quote! {::<#(#args),*>}
+    }
+}
+
+// the chip's output struct with its element type replaced by `elem` and its const
+// parameters by the instance's values
+fn output_type(
+    path: &syn::TypePath,
+    elem: proc_macro2::TokenStream,
+    consts: &GenericArgs,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let error = syn::Error::new_spanned(path, CHIP_FN_TYPE_ERR);
+    let mut path = path.clone();
+    let segment = path.path.segments.last_mut().ok_or(error.clone())?;
+    let mut args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().cloned().collect(),
+        _ => vec![],
+    };
+    args.retain(|arg| !matches!(arg, GenericArgument::Lifetime(_)));
+    let mut replaced = false;
+    let args = args.into_iter().map(|arg| match arg {
+        GenericArgument::Type(syn::Type::Path(param))
+            if param
+                .path
+                .get_ident()
+                .is_some_and(|ident| consts.is_param(ident)) =>
+        {
+            let width = consts.width(&syn::parse_quote!(#param));
+            quote! {#width}
+        }
+        GenericArgument::Const(width) => {
+            let width = consts.width(&width);
+            quote! {{#width}}
+        }
+        GenericArgument::Type(_) if !replaced => {
+            replaced = true;
+            elem.clone()
+        }
+        arg => quote! {#arg},
+    });
+    let args = args.collect::<Vec<_>>();
+    segment.arguments = PathArguments::None;
+    if !replaced {
+        return Err(error);
+    }
+    Ok(quote! {#path<#(#args),*>})
+}
+
+// what the macro needs to know about a chip function
+struct ChipFn {
+    ident: Ident,
+    struct_name: Ident,
+    const_params: Vec<Ident>,
+    // type parameters, with the bounds they're declared with, which the chip's struct
+    // holds through a PhantomData
+    type_params: Vec<syn::TypeParam>,
+    // the const and type parameters in the order they're declared in. The function's
+    // lifetimes are left to be inferred
+    generic_order: Vec<Ident>,
+    // the bounds on type parameters from the function's where clause, which are also
+    // put on the chip's impls
+    where_clause: Option<syn::WhereClause>,
+    args: Vec<(Ident, ArgType)>,
+    output: syn::TypePath,
+    // the struct generated to hold the array returned by a chip function returning an
+    // array of structs, whose `out` field the array is put in
+    output_wrapper: Option<Ident>,
+    reset: Option<LitStr>,
+    // a clocked chip's clock is its last argument, which the chip function doesn't take
+    clocked: bool,
+    // the constants wired to inputs which `new()` and machines leave out, given by
+    // #[default(...)] on the argument
+    defaults: Vec<(Ident, Lit)>,
+    // the #[structured(...)] attributes given to arguments, which are put on the fields
+    // of the machine's inputs struct, e.g. to mark an input active low
+    structured_attrs: Vec<(Ident, syn::Attribute)>,
+    krate: syn::Path,
+}
+
+impl ChipFn {
+    // the chip's struct's generic parameters, which are its lifetime followed by the
+    // function's const and type parameters
+    fn struct_generics(&self) -> proc_macro2::TokenStream {
+        let params = self.generic_order.iter().map(|param| {
+            match self.type_params.iter().find(|ty| &ty.ident == param) {
+                Some(ty) => quote! {#ty},
+                None => quote! {const #param: usize},
+            }
+        });
+        quote! {<'a, #(#params),*>}
+    }
+
+    // marks the struct as using its type parameters
+    fn phantom_ty(&self) -> proc_macro2::TokenStream {
+        let type_params = self.type_params.iter().map(|ty| &ty.ident);
+        quote! { core::marker::PhantomData<fn() -> (#(#type_params,)*)> }
+    }
+
+    fn generic_args<'c>(&'c self, values: &'c [InstanceArg]) -> GenericArgs<'c> {
+        GenericArgs {
+            params: &self.const_params,
+            order: &self.generic_order,
+            values,
+        }
+    }
+
+    fn default(&self, arg_name: &Ident) -> Option<&Lit> {
+        self.defaults
+            .iter()
+            .find(|(name, _)| name == arg_name)
+            .map(|(_, value)| value)
+    }
+}
+
+// the constant user inputs wired to an input with a default, built in `alloc`. A number
+// is given most significant bit first, like the chip's numeric helpers
+fn default_input(
+    krate: &syn::Path,
+    arg_type: &ArgType,
+    value: &Lit,
+    width: impl Fn(&syn::Expr) -> syn::Expr,
+) -> proc_macro2::TokenStream {
+    let bits = |len: &syn::Expr| {
+        let len = width(len);
+        match value {
+            Lit::Int(value) => {
+                quote! { #krate::bits::from_u16::<#len>(#value, #krate::bits::BitOrder::MsbFirst) }
+            }
+            value => quote! { [#value; #len] },
+        }
+    };
+    match arg_type {
+        ArgType::Input => quote! { #krate::Input::from(#krate::UserInput::from(alloc, #value)) },
+        ArgType::InputArray(len) => {
+            let bits = bits(len);
+            quote! { #bits.map(|bit| #krate::Input::from(#krate::UserInput::from(alloc, bit))) }
+        }
+        ArgType::Bus(len) => {
+            let bits = bits(len);
+            quote! { #krate::Bus::from(#bits.map(|bit| #krate::UserInput::from(alloc, bit))) }
+        }
+        ArgType::InputGrid(_) | ArgType::InputSlice | ArgType::Param(_) => {
+            unreachable!("grids, slices and parameters can't have defaults")
+        }
+    }
+}
+
+// checks a #[default(...)] attribute's value suits the input it's on
+fn check_default(attr: &syn::Attribute, arg_type: &ArgType) -> syn::Result<Lit> {
+    let error = || syn::Error::new_spanned(attr, CHIP_DEFAULT_ERR);
+    let value = attr.parse_args::<Lit>().map_err(|_| error())?;
+    let width = |len: &syn::Expr| match len {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: Lit::Int(len), ..
+        }) => len.base10_parse::<u32>().ok(),
+        _ => None,
+    };
+    let fits = match (&value, arg_type) {
+        (Lit::Bool(_), ArgType::Input | ArgType::InputArray(_) | ArgType::Bus(_)) => true,
+        (Lit::Int(int), ArgType::InputArray(len) | ArgType::Bus(len)) => {
+            match (int.base10_parse::<u16>(), width(len)) {
+                (Ok(int), Some(width)) => width >= 16 || u32::from(int) < 1 << width,
+                // a width given by a const parameter is only known for each instance
+                (Ok(_), None) => true,
+                (Err(_), _) => false,
+            }
+        }
+        _ => false,
+    };
+    if !fits {
+        return Err(error());
+    }
+    Ok(value)
+}
+
+// the chip function's arguments, wrapping each of the chip's inputs in a ChipInput
+// labelled after the argument
+fn chip_fn_params(
+    chip: &ChipFn,
+    arg_value: impl Fn(&Ident, &ArgType) -> proc_macro2::TokenStream,
+) -> Punctuated<proc_macro2::TokenStream, Comma> {
+    let krate = &chip.krate;
+    chip.args
+        .iter()
+        .map(|(arg_name, ty)| {
+            let name_lit = LitStr::new(&arg_name.to_string(), Span::call_site());
+            let value = arg_value(arg_name, ty);
+            match ty {
+                ArgType::Input => quote! {#krate::ChipInput::new(&alloc, #value, #name_lit.into()) },
+                ArgType::InputArray(_) => {
+                    quote! {{
+                        let mut i = 0;
+                        #value.map(|x| {
+                            let ret = #krate::ChipInput::new(&alloc, x, #name_lit.to_owned()+"-"+&i.to_string());
+                            i += 1;
+                            ret
+                        })
+                    }}
+                }
+                ArgType::Bus(_) => {
+                    quote! {{
+                        let mut i = 0;
+                        #krate::Bus::from(#value.map(|x| {
+                            let ret = #krate::ChipInput::new(&alloc, x, #name_lit.to_owned()+"-"+&i.to_string());
+                            i += 1;
+                            ret
+                        }))
+                    }}
+                }
+                ArgType::InputSlice => {
+                    quote! {
+                        alloc.alloc_slice_fill_iter(#value.iter().enumerate().map(|(i, x)| {
+                            #krate::ChipInput::new(&alloc, *x, format!("{}-{}", #name_lit, i))
+                        }))
+                    }
+                }
+                ArgType::Param(_) => value,
+                // labelled with each of the element's indices, e.g. "words-2-15"
+                ArgType::InputGrid(dims) => {
+                    let indices = (0..dims.len())
+                        .map(|i| Ident::new(&format!("i{i}"), Span::call_site()))
+                        .collect::<Vec<_>>();
+                    let format = LitStr::new(
+                        &format!("{}{}", arg_name, "-{}".repeat(dims.len())),
+                        Span::call_site(),
+                    );
+                    let input = quote! {
+                        #krate::ChipInput::new(&alloc, #value #([#indices])*, format!(#format, #(#indices),*))
+                    };
+                    indices.iter().rev().fold(input, |elem, index| {
+                        quote! { core::array::from_fn(|#index| #elem) }
+                    })
+                }
+            }
+        })
+        .collect()
+}
+
+// the allocator `new()` builds the chip in, which gives a clocked chip its clock
+fn short_new_alloc_ty(chip: &ChipFn) -> proc_macro2::TokenStream {
+    let krate = &chip.krate;
+    if chip.clocked {
+        quote! { #krate::Clocked<'a> }
+    } else {
+        quote! { #krate::bumpalo::Bump }
+    }
+}
+
+// A builder setting the chip's inputs by name, as `new()` takes them in order, e.g.
+// `Mux16::builder(alloc).in1(a).in2(b).sel(s).build()`. Inputs left unset are wired to
+// their defaults, and building a chip with any other input unset panics naming it
+fn chip_builder(
+    chip: &ChipFn,
+    struct_name_str: &str,
+    alloc_ty: &proc_macro2::TokenStream,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let krate = &chip.krate;
+    let struct_name = &chip.struct_name;
+    let builder_name = Ident::new(&format!("{struct_name_str}Builder"), struct_name.span());
+    let generic_params = &chip.generic_order;
+    let builder_ty = quote! {#builder_name<'a, #(#generic_params),*>};
+    let generics = chip.struct_generics();
+    let where_clause = &chip.where_clause;
+    let phantom_ty = chip.phantom_ty();
+    let args = chip
+        .args
+        .iter()
+        .filter(|(arg_name, _)| !(chip.clocked && arg_name == "clock"))
+        .collect::<Vec<_>>();
+    let field_ty = |arg_type: &ArgType| match arg_type {
+        ArgType::Input => quote! { #krate::Input<'a> },
+        ArgType::InputArray(len) => quote! { [#krate::Input<'a>; #len] },
+        ArgType::Bus(len) => quote! { #krate::Bus<'a, #len> },
+        ArgType::InputGrid(dims) => array_type(
+            quote! {#krate::Input<'a>},
+            dims.iter().map(|dim| quote! {#dim}),
+        ),
+        ArgType::InputSlice => quote! { Vec<#krate::Input<'a>> },
+        ArgType::Param(ty) => quote! { #ty },
+    };
+    let fields = args.iter().map(|(arg_name, arg_type)| {
+        let ty = field_ty(arg_type);
+        quote! { #arg_name: Option<#ty> }
+    });
+    let setters = args.iter().map(|(arg_name, arg_type)| {
+        let ty = field_ty(arg_type);
+        let (param_ty, value) = match arg_type {
+            ArgType::Input | ArgType::Bus(_) => {
+                (quote! { impl Into<#ty> }, quote! { #arg_name.into() })
+            }
+            ArgType::InputArray(_) => (
+                quote! { impl #krate::ArrayInto<#ty> },
+                quote! { #krate::ArrayInto::ainto(#arg_name) },
+            ),
+            ArgType::InputGrid(_) | ArgType::Param(_) => (ty, quote! { #arg_name }),
+            ArgType::InputSlice => (
+                quote! { &[#krate::Input<'a>] },
+                quote! { #arg_name.to_vec() },
+            ),
+        };
+        quote! {
+            fn #arg_name(mut self, #arg_name: #param_ty) -> Self {
+                self.#arg_name = Some(#value);
+                self
+            }
+        }
+    });
+    let arg_values = chip.args.iter().map(|(arg_name, arg_type)| {
+        let message = match arg_type {
+            ArgType::Param(_) => format!("{struct_name_str}: parameter `{arg_name}` isn't set"),
+            _ => format!("{struct_name_str}: port `{arg_name}` isn't connected"),
+        };
+        let message = LitStr::new(&message, Span::call_site());
+        let value = match chip.default(arg_name) {
+            _ if chip.clocked && arg_name == "clock" => return quote! { alloc.clock().into() },
+            Some(value) => {
+                let default = default_input(krate, arg_type, value, Clone::clone);
+                quote! { self.#arg_name.unwrap_or_else(|| #default) }
+            }
+            None => quote! { self.#arg_name.unwrap_or_else(|| panic!(#message)) },
+        };
+        match arg_type {
+            ArgType::InputSlice => quote! { &#value },
+            _ => value,
+        }
+    });
+    let arg_names = args.iter().map(|(arg_name, _)| arg_name);
+    let new_ident = constructor_ident(chip);
+
+    let builder = quote! {
+        // not every chip is built through its builder
+        #[allow(dead_code)]
+        struct #builder_name #generics #where_clause {
+            alloc: &'a #alloc_ty,
+            types: #phantom_ty,
+            #(#fields),*
+        }
+
+        #[allow(dead_code)]
+        impl #generics #builder_ty #where_clause {
+            #(#setters)*
+
+            fn build(self) -> &'a #struct_name<'a, #(#generic_params),*> {
+                let alloc = self.alloc;
+                #struct_name::#new_ident(alloc, #(#arg_values),*)
+            }
+        }
+    };
+    let builder_fn = quote! {
+        #[allow(dead_code)]
+        fn builder(alloc: &'a #alloc_ty) -> #builder_ty {
+            #builder_name {
+                alloc,
+                types: core::marker::PhantomData,
+                #(#arg_names: None),*
+            }
+        }
+    };
+    (builder, builder_fn)
+}
+
+// the call to the chip function, given the `alloc` and the values of its arguments. A
+// clocked chip's function is given its clock through a `Clocked` allocator instead
+fn chip_fn_call(
+    chip: &ChipFn,
+    turbofish: &proc_macro2::TokenStream,
+    arg_value: impl Fn(&Ident, &ArgType) -> proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let ident = &chip.ident;
+    let krate = &chip.krate;
+    let mut params = chip_fn_params(chip, arg_value);
+    let call = if !chip.clocked {
+        quote! { #ident #turbofish(alloc, #params) }
+    } else {
+        let clock = params.pop().map(|clock| clock.into_value());
+        quote! {{
+            let clock = #clock;
+            #ident #turbofish(#krate::Clocked::new(alloc, clock), #params)
+        }}
+    };
+    match &chip.output_wrapper {
+        Some(wrapper) => quote! { #wrapper { out: #call } },
+        None => call,
+    }
+}
+
+// the constructor taking each of the chip's inputs
+fn constructor_ident(chip: &ChipFn) -> Ident {
+    let name = if chip.clocked {
+        "new_with_clock"
+    } else if !chip.defaults.is_empty() {
+        "new_with_all"
+    } else {
+        "new"
+    };
+    Ident::new(name, Span::call_site())
+}
 
 #[proc_macro_attribute]
-pub fn chip(_: TokenStream, item: TokenStream) -> TokenStream {
-    let ast: ItemFn = syn::parse(item).unwrap();
-    let ident = &ast.sig.ident;
+pub fn chip(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let ast = parse_macro_input!(item as ItemFn);
+    parse_chip_attrs(args)
+        .and_then(|attrs| expand_chip(attrs, ast))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// a chip's struct is named after its function, with the first letter in upper case
+fn struct_name(ident: &Ident) -> String {
     let name = ident.to_string();
-    let struct_name_str = &(name
-        .chars()
+    name.chars()
         .take(1)
         .next()
         .unwrap()
         .to_uppercase()
         .to_string()
-        + &name[1..]);
-    let struct_name = Ident::new(struct_name_str, ast.sig.ident.span());
+        + &name[1..]
+}
+
+#[proc_macro]
+pub fn hdl_chip(item: TokenStream) -> TokenStream {
+    let chip = parse_macro_input!(item as hdl_chip::HdlChip);
+    hdl_chip::expand_hdl_chip(chip)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    assert!(ast.sig.inputs.len() > 1, "{}", CHIP_ARG_TYPE_ERR);
-    let struct_inputs_name_str = format!("{}Inputs", struct_name_str);
-    let struct_inputs_name = Ident::new(&struct_inputs_name_str, ast.sig.ident.span());
-    let struct_inputs_name_family =
-        Ident::new(&format!("{}Family", struct_inputs_name_str), ast.span());
+fn expand_chip(attrs: ChipAttrs, mut ast: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &ast.sig.ident;
+    let struct_name_str = &struct_name(ident);
+    let struct_name = Ident::new(struct_name_str, ast.sig.ident.span());
 
-    enum ArgType {
-        Input,
-        InputArray(LitInt),
+    if ast.sig.inputs.len() < 2 {
+        return Err(syn::Error::new_spanned(&ast.sig.inputs, CHIP_ARG_TYPE_ERR));
     }
+    let const_params = ast
+        .sig
+        .generics
+        .const_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    // the struct holds its type parameters for its own lifetime, so they must outlive it
+    let type_params = ast
+        .sig
+        .generics
+        .type_params()
+        .map(|param| {
+            let mut param = param.clone();
+            param.bounds.push(syn::parse_quote!('a));
+            param
+        })
+        .collect::<Vec<_>>();
+    let generic_order = ast
+        .sig
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(param.ident.clone()),
+            GenericParam::Const(param) => Some(param.ident.clone()),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    // bounds between the function's lifetimes only concern the function
+    let where_clause = ast.sig.generics.where_clause.as_ref().map(|where_clause| {
+        let mut where_clause = where_clause.clone();
+        where_clause.predicates = where_clause
+            .predicates
+            .into_iter()
+            .filter(|predicate| matches!(predicate, syn::WherePredicate::Type(_)))
+            .collect();
+        where_clause
+    });
+    let check_width = |width: syn::Expr| match &width {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(_),
+            ..
+        }) => Ok(width),
+        syn::Expr::Path(path)
+            if path
+                .path
+                .get_ident()
+                .is_some_and(|ident| const_params.contains(ident)) =>
+        {
+            Ok(width)
+        }
+        _ => Err(syn::Error::new_spanned(width, CHIP_WIDTH_ERR)),
+    };
 
-    let input_name_to_type = ast
+    let mut args = ast
         .sig
         .inputs
         .iter()
         .skip(1)
         .map(|farg| match farg {
-            syn::FnArg::Receiver(_) => panic!("{}", CHIP_ARG_TYPE_ERR),
+            syn::FnArg::Receiver(receiver) => {
+                Err(syn::Error::new_spanned(receiver, CHIP_ARG_TYPE_ERR))
+            }
             syn::FnArg::Typed(pat) => {
-                let arg_name = pat.pat.clone();
-                let arg_type = match *(pat.ty.clone()) {
-                    syn::Type::Array(tya) => {
-                        match tya.len {
-                            syn::Expr::Lit(x) => match x.lit {
-                                // unwrap should be safe because we already know it's a literal
-                                syn::Lit::Int(i) => ArgType::InputArray(i),
-                                _ => panic!("{}", CHIP_ARG_TYPE_ERR),
-                            },
-                            _ => panic!("{}", CHIP_ARG_TYPE_ERR),
-                        }
+                let arg_name = match &*pat.pat {
+                    syn::Pat::Ident(ident) => ident.ident.clone(),
+                    arg_name => return Err(syn::Error::new_spanned(arg_name, CHIP_ARG_NAME_ERR)),
+                };
+                let arg_type = match &*pat.ty {
+                    ty if pat.attrs.iter().any(|attr| attr.path.is_ident("param")) => {
+                        ArgType::Param(Box::new(ty.clone()))
+                    }
+                    syn::Type::Array(tya @ syn::TypeArray { elem, .. })
+                        if matches!(**elem, syn::Type::Array(_)) =>
+                    {
+                        ArgType::InputGrid(
+                            array_dims(tya)
+                                .into_iter()
+                                .map(check_width)
+                                .collect::<syn::Result<_>>()?,
+                        )
+                    }
+                    syn::Type::Array(tya) => ArgType::InputArray(check_width(tya.len.clone())?),
+                    syn::Type::Reference(syn::TypeReference { elem, .. })
+                        if matches!(**elem, syn::Type::Slice(_)) =>
+                    {
+                        ArgType::InputSlice
                     }
                     syn::Type::Reference(_) => ArgType::Input,
-                    _ => panic!("{}", CHIP_ARG_TYPE_ERR),
+                    syn::Type::Path(path) => ArgType::Bus(check_width(bus_width(path)?)?),
+                    ty => return Err(syn::Error::new_spanned(ty, CHIP_ARG_TYPE_ERR)),
                 };
-                (arg_name, arg_type)
+                Ok((arg_name, arg_type))
             }
         })
-        .collect::<Vec<_>>();
-
-    let mapped_chip_inputs = input_name_to_type
-        .iter()
-        .map(|(arg_name, _)| quote!(inputs.#arg_name))
-        .collect::<Punctuated<_, Comma>>();
-    let mapped_struct_inputs = input_name_to_type
-        .iter()
-        .map(|(arg_name, ty)| {
-            let name_lit = match *(arg_name.clone()) {
-                syn::Pat::Ident(ident) => LitStr::new(&ident.ident.to_string(), Span::call_site()),
-                _ => panic!("{}", CHIP_ARG_TYPE_ERR),
-            };
-            match ty {
-                ArgType::Input => quote! {ChipInput::new(&alloc, inputs.#arg_name, #name_lit.into()) },
-                ArgType::InputArray(_) => {
-                    quote! {{
-                        let mut i = 0;
-                        inputs.#arg_name.map(|x| {
-                            let ret = ChipInput::new(&alloc, x, #name_lit.to_owned()+"-"+&i.to_string());
-                            i += 1;
-                            ret
-                        })
-                    }}
-                }
+        .collect::<syn::Result<Vec<_>>>()?;
+    // the #[default(...)], #[structured(...)] and #[param] attributes are read here, and
+    // aren't left on the function
+    let mut defaults = vec![];
+    let mut structured_attrs = vec![];
+    for (farg, (arg_name, arg_type)) in ast.sig.inputs.iter_mut().skip(1).zip(&args) {
+        let syn::FnArg::Typed(pat) = farg else {
+            continue;
+        };
+        for attr in pat
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("default"))
+        {
+            defaults.push((arg_name.clone(), check_default(attr, arg_type)?));
+        }
+        for attr in pat
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("structured"))
+        {
+            // skipping or flattening an input would change the machine's arity
+            match attr.parse_meta()? {
+                Meta::List(list)
+                    if matches!(
+                        list.nested.iter().collect::<Vec<_>>()[..],
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("active_low")
+                    ) => {}
+                _ => return Err(syn::Error::new_spanned(attr, CHIP_STRUCTURED_ERR)),
             }
-        })
-        .collect::<Punctuated<_, Comma>>();
-    let inputs = input_name_to_type
+            structured_attrs.push((arg_name.clone(), attr.clone()));
+        }
+        pat.attrs.retain(|attr| {
+            !["default", "structured", "param"]
+                .iter()
+                .any(|name| attr.path.is_ident(name))
+        });
+    }
+    if attrs.clocked {
+        if let Some((clock, _)) = args.iter().find(|(arg_name, _)| arg_name == "clock") {
+            return Err(syn::Error::new_spanned(clock, CHIP_CLOCK_ERR));
+        }
+        args.push((Ident::new("clock", Span::call_site()), ArgType::Input));
+    }
+    let mut output_wrapper = None;
+    let mut output_wrapper_struct = quote! {};
+    let output = match &ast.sig.output {
+        syn::ReturnType::Default => {
+            return Err(syn::Error::new_spanned(&ast.sig.ident, CHIP_FN_TYPE_ERR))
+        }
+        syn::ReturnType::Type(_, ty) => match &**ty {
+            syn::Type::Path(p) => p.clone(),
+            // an array of structs is put in a struct of its own, as StructuredData can't
+            // be implemented for arrays outside of the hdl crate
+            syn::Type::Array(syn::TypeArray { elem, len, .. })
+                if generic_order.is_empty() && matches!(len, syn::Expr::Lit(_)) =>
+            {
+                let syn::Type::Path(elem) = &**elem else {
+                    return Err(syn::Error::new_spanned(elem, CHIP_FN_TYPE_ERR));
+                };
+                let krate = attrs.krate.clone().unwrap_or_else(default_crate_path);
+                let wrapper = Ident::new(&format!("{struct_name}Outputs"), ident.span());
+                let no_generics = GenericArgs {
+                    params: &[],
+                    order: &[],
+                    values: &[],
+                };
+                let elem = output_type(elem, quote! {T}, &no_generics)?;
+                let wrapper_struct: syn::DeriveInput = syn::parse_quote! {
+                    #[derive(Debug, PartialEq)]
+                    struct #wrapper<T> {
+                        out: [#elem; #len],
+                    }
+                };
+                let wrapper_impls = expand_structured_data(&wrapper_struct, &krate)?;
+                output_wrapper_struct = quote! {
+                    #wrapper_struct
+                    #wrapper_impls
+                };
+                let output = syn::parse_quote! { #wrapper<#krate::ChipOutputType<'a>> };
+                output_wrapper = Some(wrapper);
+                output
+            }
+            ty => return Err(syn::Error::new_spanned(ty, CHIP_FN_TYPE_ERR)),
+        },
+    };
+    let chip = ChipFn {
+        ident: ident.clone(),
+        struct_name: struct_name.clone(),
+        const_params,
+        type_params,
+        generic_order,
+        where_clause,
+        args,
+        output,
+        output_wrapper,
+        reset: attrs.reset,
+        clocked: attrs.clocked,
+        defaults,
+        structured_attrs,
+        krate: attrs.krate.unwrap_or_else(default_crate_path),
+    };
+    let krate = &chip.krate;
+    let consts = chip.generic_args(&[]);
+    let generic_params = &chip.generic_order;
+    let struct_ty = quote! {#struct_name<'a, #(#generic_params),*>};
+    let struct_generics = chip.struct_generics();
+    let where_clause = &chip.where_clause;
+    let phantom_ty = chip.phantom_ty();
+    let out_bool = output_type(&chip.output, quote! {bool}, &consts)?;
+
+    // Array and bus arguments to `new()` keep the chip's declared widths, so connecting a
+    // bus of the wrong width is a type error under `cargo check`. They're spanned on the
+    // chip function's own arguments, so the error's note names the chip and port rather
+    // than pointing at the #[chip] attribute
+    let function_args = chip
+        .args
         .iter()
         .map(|(arg_name, arg_type)| match arg_type {
-            ArgType::Input => quote! { #arg_name: T },
+            ArgType::Input => quote! { #arg_name: #krate::Input<'a> },
             ArgType::InputArray(len) => {
-                quote! { #arg_name: [T;#len] }
+                quote_spanned! {arg_name.span()=> #arg_name: [#krate::Input<'a>;#len] }
+            }
+            ArgType::Bus(len) => {
+                quote_spanned! {arg_name.span()=> #arg_name: #krate::Bus<'a, #len> }
+            }
+            ArgType::InputSlice => quote! { #arg_name: &[#krate::Input<'a>] },
+            ArgType::Param(ty) => quote! { #arg_name: #ty },
+            ArgType::InputGrid(dims) => {
+                let ty = array_type(
+                    quote! {#krate::Input<'a>},
+                    dims.iter().map(|dim| quote! {#dim}),
+                );
+                quote! { #arg_name: #ty }
             }
         })
         .collect::<Punctuated<_, Comma>>();
-    let function_params = input_name_to_type
+    let turbofish = consts.turbofish();
+    let chip_call = chip_fn_call(&chip, &turbofish, |arg_name, arg_type| match arg_type {
+        ArgType::Bus(_) => quote! {#arg_name.inputs()},
+        _ => quote! {#arg_name},
+    });
+    // A clocked chip's `new()` takes its clock from the `Clocked` allocator of the chip
+    // it's built in. `new_with_clock()` takes it like any other input, for building the
+    // chip at the top of a machine. `new()` also leaves out inputs with defaults, which
+    // `new_with_clock()` or `new_with_all()` take
+    let new_ident = constructor_ident(&chip);
+    let is_left_out = |arg_name: &Ident| {
+        chip.default(arg_name).is_some() || (chip.clocked && arg_name == "clock")
+    };
+    let short_args = function_args
         .iter()
-        .map(|(arg_name, ty)| {
-            let name_lit = match *(arg_name.clone()) {
-                syn::Pat::Ident(ident) => LitStr::new(&ident.ident.to_string(), Span::call_site()),
-                _ => panic!("{}", CHIP_ARG_TYPE_ERR),
-            };
-            match ty {
-                ArgType::Input => quote! {ChipInput::new(&alloc, #arg_name, #name_lit.into()) },
-                ArgType::InputArray(_) => {
-                    quote! {{
-                        let mut i = 0;
-                        #arg_name.map(|x| {
-                            let ret = ChipInput::new(&alloc, x, #name_lit.to_owned()+"-"+&i.to_string());
-                            i += 1;
-                            ret
-                        })
-                    }}
+        .zip(&chip.args)
+        .filter(|(_, (arg_name, _))| !is_left_out(arg_name))
+        .map(|(function_arg, _)| function_arg)
+        .collect::<Punctuated<_, Comma>>();
+    let short_new = if chip.clocked || !chip.defaults.is_empty() {
+        let arg_values =
+            chip.args
+                .iter()
+                .map(|(arg_name, arg_type)| match chip.default(arg_name) {
+                    Some(value) => default_input(krate, arg_type, value, Clone::clone),
+                    None if chip.clocked && arg_name == "clock" => quote! { alloc.clock().into() },
+                    None => quote! { #arg_name },
+                });
+        let alloc_ty = short_new_alloc_ty(&chip);
+        quote! {
+            #[allow(clippy::too_many_arguments)]
+            fn new(alloc: &'a #alloc_ty, #short_args) -> &'a #struct_ty {
+                Self::#new_ident(alloc, #(#arg_values),*)
+            }
+        }
+    } else {
+        quote! {}
+    };
+    // `new()` with a name for the instance, which netlists, probes and the UI show in
+    // place of the chip's label, e.g. "R0" rather than "Register16"
+    let short_arg_names = chip
+        .args
+        .iter()
+        .map(|(arg_name, _)| arg_name)
+        .filter(|arg_name| !is_left_out(arg_name));
+    let alloc_ty = short_new_alloc_ty(&chip);
+    let new_named = quote! {
+        #[allow(dead_code, clippy::too_many_arguments)]
+        fn new_named(alloc: &'a #alloc_ty, instance_name: &'static str, #short_args) -> &'a #struct_ty {
+            let chip = Self::new(alloc, #(#short_arg_names),*);
+            chip.name.set(Some(instance_name));
+            chip
+        }
+    };
+
+    let (builder, builder_fn) = chip_builder(&chip, struct_name_str, &short_new_alloc_ty(&chip));
+
+    let lit_name = LitStr::new(struct_name_str, Span::call_site());
+    // the chip's inputs as StructuredData fields, like a machine's inputs struct. A
+    // slice's width isn't kept once the chip is built, so it's given as a single wire
+    let input_ports = chip.args.iter().map(|(arg_name, arg_type)| {
+        let name = LitStr::new(&arg_name.to_string(), Span::call_site());
+        let is_active_low = chip
+            .structured_attrs
+            .iter()
+            .any(|(attr_arg, _)| attr_arg == arg_name);
+        match arg_type {
+            ArgType::Param(_) => quote! {},
+            ArgType::Input | ArgType::InputSlice => quote! {
+                inputs.push((#name.to_owned(), 0));
+                active_low.push(#is_active_low);
+            },
+            ArgType::InputArray(len) | ArgType::Bus(len) => quote! {
+                inputs.push((#name.to_owned(), #len));
+                active_low.extend(core::iter::repeat(#is_active_low).take(#len));
+            },
+            ArgType::InputGrid(dims) => {
+                let (inner, outer) = dims.split_last().unwrap();
+                quote! {
+                    let mut rows = vec![#name.to_owned()];
+                    #(
+                        rows = rows
+                            .into_iter()
+                            .flat_map(|row| (0..#outer).map(move |i| format!("{}-{}", row, i)))
+                            .collect();
+                    )*
+                    for row in rows {
+                        inputs.push((row, #inner));
+                        active_low.extend(core::iter::repeat(#is_active_low).take(#inner));
+                    }
+                }
+            }
+        }
+    });
+    let lit_description = LitStr::new(&doc_comment(&ast.attrs), Span::call_site());
+    let gate_count = match ast.sig.inputs.first() {
+        Some(syn::FnArg::Typed(syn::PatType { pat, .. })) => match &**pat {
+            syn::Pat::Ident(alloc) => gate_count::gate_count(&ast.block, &alloc.ident, &chip.krate),
+            _ => return Err(syn::Error::new_spanned(pat, CHIP_ARG_NAME_ERR)),
+        },
+        _ => return Err(syn::Error::new_spanned(&ast.sig.inputs, CHIP_ARG_TYPE_ERR)),
+    };
+    // an instance of a generic chip is labelled with its alias, so e.g. a `Not16` in a
+    // netlist path reads as one. Instances can only be told apart by their widths
+    let instance_labels =
+        if attrs.label.is_none() && chip.const_params.len() == chip.generic_order.len() {
+            attrs
+                .instances
+                .iter()
+                .map(|(alias, values)| {
+                    let lit_alias = LitStr::new(&alias.to_string(), Span::call_site());
+                    let params = &chip.generic_order;
+                    quote! {
+                        if #(#params == #values)&&* {
+                            return #lit_alias;
+                        }
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+    let lit_label = attrs.label.unwrap_or_else(|| lit_name.clone());
+    let id_prefix = attrs
+        .id_prefix
+        .map_or_else(|| struct_name_str.to_owned(), |id_prefix| id_prefix.value());
+    let lit_id = LitStr::new(
+        &format!("{}{{}}", id_prefix.replace('{', "{{").replace('}', "}}")),
+        Span::call_site(),
+    );
+
+    // a generic chip can't implement the traits needed to build a machine from it, as
+    // their arities would be computed from its const parameters. It gets `get_out()`
+    // of its own, and the traits are implemented for each of its instances. A chip
+    // taking slices has no arity until it's built, so it only gets `get_out()`, and is
+    // built in to a machine by a chip which gives its slices a width. Likewise a chip
+    // taking parameters is built in to a machine by a chip which gives them values
+    let is_dynamic = chip
+        .args
+        .iter()
+        .any(|(_, arg_type)| matches!(arg_type, ArgType::InputSlice | ArgType::Param(_)));
+    if is_dynamic {
+        if let Some((alias, _)) = attrs.instances.first() {
+            return Err(syn::Error::new_spanned(alias, CHIP_DYNAMIC_INSTANCE_ERR));
+        }
+    }
+    if let (Some(model), false) = (&attrs.model, chip.generic_order.is_empty() && !is_dynamic) {
+        return Err(syn::Error::new_spanned(model, CHIP_MODEL_ERR));
+    }
+    let (machine_impls, generic_get_out) = if chip.generic_order.is_empty() && !is_dynamic {
+        if let Some((alias, _)) = attrs.instances.first() {
+            return Err(syn::Error::new_spanned(alias, CHIP_INSTANCE_ERR));
+        }
+        let machine_impls = machine_impls(&chip, struct_ty.clone(), struct_name_str, &[])?;
+        let model_test = match &attrs.model {
+            Some(model) if chip.clocked => {
+                return Err(syn::Error::new_spanned(model, CHIP_MODEL_ERR))
+            }
+            Some(model) => {
+                let test_name = Ident::new(&format!("{ident}_matches_model"), ident.span());
+                quote! {
+                    #[cfg(test)]
+                    #[test]
+                    fn #test_name() {
+                        let alloc = #krate::bumpalo::Bump::new();
+                        let mut machine = #krate::Machine::new(&alloc, #struct_name::from).unwrap();
+                        if let Err(err) = machine.check_model(#model) {
+                            panic!("{}: {}", #lit_name, err);
+                        }
+                    }
+                }
+            }
+            None => quote! {},
+        };
+        (quote! { #machine_impls #model_test }, quote! {})
+    } else {
+        let out_wrapper = output_type(
+            &chip.output,
+            quote! {&'a #krate::ChipOutputWrapper<'a>},
+            &consts,
+        )?;
+        let instances = attrs
+            .instances
+            .iter()
+            .map(|(alias, values)| {
+                let kinds_match = values.len() == chip.generic_order.len()
+                    && chip.generic_order.iter().zip(values).all(|(param, value)| {
+                        chip.const_params.contains(param) == matches!(value, InstanceArg::Width(_))
+                    });
+                if !kinds_match {
+                    return Err(syn::Error::new_spanned(alias, CHIP_INSTANCE_ERR));
+                }
+                let instance_ty = quote! {#struct_name<'a, #(#values),*>};
+                let impls = machine_impls(&chip, instance_ty.clone(), &alias.to_string(), values)?;
+                Ok(quote! {
+                    type #alias<'a> = #instance_ty;
+                    #impls
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let generic_get_out = quote! {
+            fn get_out(&'a self, alloc: &'a #krate::bumpalo::Bump) -> #out_wrapper {
+                #krate::StructuredData::from_flat(core::array::from_fn(|i| {
+                    #krate::ChipOutputWrapper::new(alloc, self.out[i], self)
+                }))
+            }
+        };
+        (quote! {#(#instances)*}, generic_get_out)
+    };
+
+    let gen = quote! {
+        struct #struct_name #struct_generics #where_clause {
+            out: &'a [&'a #krate::ChipOutput<'a>],
+            identifier: u32,
+            name: core::cell::Cell<Option<&'static str>>,
+            types: #phantom_ty
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        #ast
+        impl #struct_generics #struct_ty #where_clause {
+            // array outputs are labelled from their last element down
+            fn get_output_names() -> Vec<String> {
+                <#out_bool>::get_field_info()
+                    .into_iter()
+                    .flat_map(|(field_name, arr_len)| {
+                        if arr_len == 0 {
+                            vec![field_name]
+                        } else {
+                            (0..arr_len)
+                                .rev()
+                                .map(|i| format!("{}-{}", field_name, i))
+                                .collect()
+                        }
+                    })
+                    .collect()
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            fn #new_ident(alloc: &'a #krate::bumpalo::Bump, #function_args) -> &'a #struct_ty {
+                let inner = #chip_call;
+                let chipout = #krate::StructuredData::to_flat(inner)
+                    .into_iter()
+                    .zip(Self::get_output_names())
+                    .map(|(in_, name)| #krate::ChipOutput::new(alloc, name, in_))
+                    .collect::<Vec<_>>();
+                Self::from_output(alloc, &chipout)
+            }
+
+            fn from_output(alloc: &'a #krate::bumpalo::Bump, out: &[&'a #krate::ChipOutput<'a>]) -> &'a mut Self {
+                static COUNTER: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+                alloc.alloc(#struct_name{
+                    out: alloc.alloc_slice_copy(out),
+                    identifier: #krate::ids::next_id(#lit_name, &COUNTER),
+                    name: core::cell::Cell::new(None),
+                    types: core::marker::PhantomData
+                })
+            }
+
+            // the NANDs the chip builds, including those of the chips it's built from, or
+            // None if they can't be counted from its body, e.g. when it builds chips in a
+            // closure, a branch or a loop which isn't over a range. See gate_count.rs
+            pub const GATE_COUNT: Option<usize> = #gate_count;
+
+            #short_new
+
+            #new_named
+
+            #builder_fn
+
+            #generic_get_out
+        }
+
+        impl #struct_generics #krate::Chip<'a> for #struct_ty #where_clause {
+            fn get_id(&self) -> String {
+                format!(#lit_id, self.identifier)
+            }
+
+            fn get_label(&self) -> &'static str {
+                #(#instance_labels)*
+                #lit_label
+            }
+
+            fn get_description(&self) -> &'static str {
+                #lit_description
+            }
+
+            fn get_name(&self) -> Option<&'static str> {
+                self.name.get()
+            }
+
+            fn get_ports(&self) -> #krate::ports::Description {
+                let mut inputs = vec![];
+                let mut active_low = vec![];
+                #(#input_ports)*
+                #krate::ports::Description {
+                    inputs: #krate::ports::ports(inputs, &active_low),
+                    outputs: #krate::ports::ports(<#out_bool>::get_field_info(), &<#out_bool>::get_active_low()),
                 }
             }
+
+            fn get_children(&self) -> Vec<&'a dyn #krate::Chip<'a>> {
+                #krate::child_chips(self.out)
+            }
+
+            fn get_outputs(&self) -> &'a [&'a #krate::ChipOutput<'a>] {
+                self.out
+            }
+        }
+
+        #builder
+
+        #machine_impls
+
+        #output_wrapper_struct
+    };
+    Ok(gen)
+}
+
+// the text of the function's doc comments, with the space after each `///` removed
+fn doc_comment(attrs: &[syn::Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(syn::MetaNameValue {
+                lit: Lit::Str(line),
+                ..
+            })) => Some(line.value()),
+            _ => None,
         })
+        .map(|line| line.strip_prefix(' ').map(str::to_owned).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+// The inputs struct and the trait impls a machine needs to be built from a chip. Their
+// arities must be literals, so for a generic chip they're generated for each instance,
+// named after the instance
+fn machine_impls(
+    chip: &ChipFn,
+    self_ty: proc_macro2::TokenStream,
+    name_str: &str,
+    values: &[InstanceArg],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let consts = chip.generic_args(values);
+    let krate = &chip.krate;
+    let struct_inputs_name_str = format!("{}Inputs", name_str);
+    let struct_inputs_name = Ident::new(&struct_inputs_name_str, chip.struct_name.span());
+    let struct_inputs_name_family = Ident::new(
+        &format!("{}Family", struct_inputs_name_str),
+        chip.struct_name.span(),
+    );
+
+    // inputs with defaults aren't inputs of the machine, and are wired to their defaults
+    let mapped_chip_inputs = chip
+        .args
+        .iter()
+        .map(
+            |(arg_name, arg_type)| match (chip.default(arg_name), arg_type) {
+                (Some(value), _) => default_input(krate, arg_type, value, |len| consts.width(len)),
+                (None, ArgType::Bus(_)) => quote!(#krate::Bus::from(inputs.#arg_name)),
+                (None, _) => quote!(inputs.#arg_name),
+            },
+        )
         .collect::<Punctuated<_, Comma>>();
-    let function_args = input_name_to_type
+    let turbofish = consts.turbofish();
+    let chip_call = chip_fn_call(chip, &turbofish, |arg_name, arg_type| {
+        match (chip.default(arg_name), arg_type) {
+            // the chip function is given a bus's inputs as an array
+            (Some(value), ArgType::Bus(len)) => {
+                default_input(krate, &ArgType::InputArray(len.clone()), value, |len| {
+                    consts.width(len)
+                })
+            }
+            (Some(value), _) => default_input(krate, arg_type, value, |len| consts.width(len)),
+            (None, _) => quote! {inputs.#arg_name},
+        }
+    });
+    let new_ident = constructor_ident(chip);
+    let machine_args = chip
+        .args
         .iter()
-        .map(|(arg_name, arg_type)| match arg_type {
-            ArgType::Input => quote! { #arg_name: Input<'a> },
-            ArgType::InputArray(len) => {
-                quote! { #arg_name: [Input<'a>;#len] }
+        .filter(|(arg_name, _)| chip.default(arg_name).is_none())
+        .collect::<Vec<_>>();
+    let inputs = machine_args
+        .iter()
+        .map(|(arg_name, arg_type)| {
+            let attrs = chip
+                .structured_attrs
+                .iter()
+                .filter(|(name, _)| name == arg_name)
+                .map(|(_, attr)| attr);
+            (quote! {#(#attrs)*}, arg_name, arg_type)
+        })
+        .map(|(attrs, arg_name, arg_type)| match arg_type {
+            ArgType::Input => quote! { #attrs #arg_name: T },
+            ArgType::InputArray(len) | ArgType::Bus(len) => {
+                let len = consts.width(len);
+                quote! { #attrs #arg_name: [T;#len] }
+            }
+            ArgType::InputGrid(dims) => {
+                let ty = array_type(
+                    quote! {T},
+                    dims.iter().map(|dim| {
+                        let dim = consts.width(dim);
+                        quote! {#dim}
+                    }),
+                );
+                quote! { #attrs #arg_name: #ty }
+            }
+            ArgType::InputSlice | ArgType::Param(_) => {
+                unreachable!("chips taking slices or parameters have no machine impls")
             }
         })
         .collect::<Punctuated<_, Comma>>();
-
-    let arity_num = input_name_to_type
+    let arity_num = machine_args
         .iter()
         .map(|(_, arg_type)| match arg_type {
-            ArgType::Input => 1,
-            ArgType::InputArray(litint) => litint.to_string().parse().unwrap(),
+            ArgType::Input => Ok(1),
+            ArgType::InputArray(len) | ArgType::Bus(len) => consts.width_value(len),
+            ArgType::InputGrid(dims) => dims
+                .iter()
+                .map(|dim| consts.width_value(dim))
+                .product::<syn::Result<usize>>(),
+            ArgType::InputSlice | ArgType::Param(_) => {
+                unreachable!("chips taking slices or parameters have no machine impls")
+            }
         })
-        .sum::<usize>();
-    let arity = LitInt::new(&arity_num.to_string(), ast.span());
-    let lit_name = LitStr::new(struct_name_str, Span::call_site());
-    let lit_id = LitStr::new(&format!("{}{{}}", struct_name_str), Span::call_site());
-
-    let struct_outputs_type = match ast.sig.output {
-        syn::ReturnType::Default => panic!("{}", CHIP_FN_TYPE_ERR),
-        syn::ReturnType::Type(_, ref ty) => match *ty.clone() {
-            syn::Type::Path(p) => p
-                .path
-                .segments
-                .into_iter()
-                .map(|mut seg| {
-                    seg.arguments = PathArguments::None;
-                    seg
-                })
-                .collect::<Punctuated<_, Colon2>>(),
-            _ => panic!("{}", CHIP_ARG_TYPE_ERR),
-        },
+        .sum::<syn::Result<usize>>()?;
+    let arity = LitInt::new(&arity_num.to_string(), Span::call_site());
+    let reset_input = match &chip.reset {
+        Some(reset) => quote! { const RESET_INPUT: Option<&'static str> = Some(#reset); },
+        None => quote! {},
+    };
+    let out_bool = output_type(&chip.output, quote! {bool}, &consts)?;
+    let out_t = output_type(&chip.output, quote! {T}, &consts)?;
+    let out_wrapper = output_type(
+        &chip.output,
+        quote! {&'a #krate::ChipOutputWrapper<'a>},
+        &consts,
+    )?;
+    let out_arity = quote! {{<#out_bool>::get_arity()}};
+    // expanded here rather than derived, so the derive needn't be imported where the
+    // chip is defined
+    let inputs_struct: syn::DeriveInput = syn::parse_quote! {
+        struct #struct_inputs_name<T> {
+            #inputs
+        }
     };
+    let inputs_impls = expand_structured_data(&inputs_struct, krate)?;
+    // the field attributes are only read by the expansion
+    let mut inputs_struct = inputs_struct;
+    if let syn::Data::Struct(data) = &mut inputs_struct.data {
+        for field in data.fields.iter_mut() {
+            field.attrs.clear();
+        }
+    }
+    // hdl's serde feature derives serde's traits on the struct, through hdl's own serde,
+    // and reads and writes the fields with `serde_array` as serde can't for wide arrays.
+    // Output structs are declared outside of the macro, and take the derives directly
+    let krate_str = quote!(#krate).to_string().replace(' ', "");
+    let lit = |value: String| LitStr::new(&value, Span::call_site());
+    let serde_path = lit(format!("{krate_str}::serde"));
+    let serde_bound = lit(format!("T: {krate_str}::serde_array::Field"));
+    let serde_with = lit(format!("{krate_str}::serde_array"));
+    if let syn::Data::Struct(data) = &mut inputs_struct.data {
+        for field in data.fields.iter_mut() {
+            field
+                .attrs
+                .push(syn::parse_quote!(#[serde(with = #serde_with)]));
+        }
+    }
 
-    let gen = quote! {
-        // note that we don't define a const for the output arity because we'd get
-        // const name clashes with multiple uses of this macro
-        struct #struct_name<'a> {
-            out: [&'a hdl::ChipOutput<'a>; {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}],
-            identifier: u32
+    Ok(quote! {
+        #krate::inputs_struct! {
+            #serde_path, #serde_bound,
+            // a chip which only passes its inputs through can return its inputs struct
+            #[derive(Clone, Debug, PartialEq)]
+            #inputs_struct
         }
 
-        #[derive(StructuredData, Clone)]
-        struct #struct_inputs_name<T> {
-            #inputs
+        #inputs_impls
+
+        // derived Default is only implemented for arrays of up to 32 elements
+        impl<T: Default> Default for #struct_inputs_name<T> {
+            fn default() -> Self {
+                #krate::StructuredData::from_flat(core::array::from_fn(|_| T::default()))
+            }
         }
 
         struct #struct_inputs_name_family;
-        impl hdl::StructuredDataFamily<#arity, {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}> for #struct_inputs_name_family {
+        impl #krate::StructuredDataFamily<#arity, #out_arity> for #struct_inputs_name_family {
             type StructuredInput<T> = #struct_inputs_name<T>;
-            type StructuredOutput<T> = #struct_outputs_type<T>;
+            type StructuredOutput<T> = #out_t;
+            #reset_input
         }
 
-        #ast
-        impl<'a> #struct_name<'a> {
-            fn from(alloc: &'a bumpalo::Bump, inputs: #struct_inputs_name<Input<'a>>) -> &'a #struct_name<'a> {
-                #struct_name::<'a>::new(alloc,#mapped_chip_inputs)
-            }
-
-            fn get_output_names() -> [String; {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}] {
-                let field_names = #struct_outputs_type::<bool>::get_field_info();
-                let mut field_i = 0;
-                let mut array_i = field_names[0].1;
-                core::array::from_fn(|_| {
-                    let (field_name,arr_len) = field_names[field_i];
-                    if arr_len==0 {
-                        field_i += 1;
-                        field_name.to_owned()
-                    } else {
-                        array_i -= 1;
-                        let ret = format!("{}-{}", field_name, array_i);
-                        if array_i == 0 {
-                            field_i += 1;
-                            if field_i<field_names.len() {
-                                (_,array_i) = field_names[field_i];
-                            }
-                        };
-                        ret
-                    }
-                })
-            }
-
-            fn new(alloc: &'a bumpalo::Bump, #function_args) -> &'a #struct_name<'a> {
-                let inner = #ident(alloc,#function_params);
-                let output_names = #struct_name::get_output_names();
-                let mut i = 0;
-                let chipout = hdl::StructuredData::to_flat(inner).map(|in_| {
-                    let ret = ChipOutput::new(
-                        alloc,
-                        output_names[i].clone(),
-                        in_
-                    );
-                    i += 1;
-                    ret
-                });
-                #struct_name::<'a>::from_output(alloc, chipout)
+        impl<'a> #self_ty {
+            fn from(alloc: &'a #krate::bumpalo::Bump, inputs: #struct_inputs_name<#krate::Input<'a>>) -> &'a #self_ty {
+                <#self_ty>::#new_ident(alloc,#mapped_chip_inputs)
             }
 
-            fn from_output(alloc: &'a Bump, out: [&'a hdl::ChipOutput<'a>; {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}]) -> &'a mut Self {
-                static COUNTER: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
-                alloc.alloc(#struct_name{
-                    out,
-                    identifier: COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed)
-                })
-            }
         }
 
-        impl<'a> hdl::SizedChip<'a, #struct_inputs_name_family, {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}, #arity> for #struct_name<'a> {
-            // TODO: probably don't need to allocate this in the arena
-            // can instead just return the struct rather than a pointer
-            fn get_out(&'a self, alloc: &'a Bump) -> #struct_outputs_type<&'a hdl::ChipOutputWrapper> {
-                let flat_out = self.out.map(|out| hdl::ChipOutputWrapper::new(alloc, out, self));
-                hdl::StructuredData::from_flat(flat_out)
+        impl<'a> #krate::SizedChip<'a, #struct_inputs_name_family, #out_arity, #arity> for #self_ty {
+            fn get_out(&'a self, alloc: &'a #krate::bumpalo::Bump) -> #out_wrapper {
+                #krate::StructuredData::from_flat(core::array::from_fn(|i| {
+                    #krate::ChipOutputWrapper::new(alloc, self.out[i], self)
+                }))
             }
         }
 
-        impl<'a> hdl::DefaultChip<'a,#struct_inputs_name_family, #arity, {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}> for #struct_name<'a> {
-            fn new(alloc: &'a Bump) -> &mut Self {
-                let output_names = #struct_name::get_output_names();
-                #struct_name::<'a>::from_output(alloc, core::array::from_fn(|i| ChipOutput::new_from_option(alloc, output_names[i].clone(), Option::None)))
+        impl<'a> #krate::DefaultChip<'a,#struct_inputs_name_family, #arity, #out_arity> for #self_ty {
+            fn new(alloc: &'a #krate::bumpalo::Bump) -> &mut Self {
+                let chipout = Self::get_output_names()
+                    .into_iter()
+                    .map(|name| #krate::ChipOutput::new_from_option(alloc, name, Option::None))
+                    .collect::<Vec<_>>();
+                Self::from_output(alloc, &chipout)
             }
 
-            fn set_inputs(&'a self, alloc: &'a Bump, inputs: <#struct_inputs_name_family as hdl::StructuredDataFamily<#arity, {#struct_outputs_type::<bool/* type doesn't matter */>::get_arity()}>>::StructuredInput<Input<'a>>) {
-                let inner = #ident(alloc,#mapped_struct_inputs);
-                let outputs = hdl::StructuredData::to_flat(inner);
+            fn set_inputs(&'a self, alloc: &'a #krate::bumpalo::Bump, inputs: <#struct_inputs_name_family as #krate::StructuredDataFamily<#arity, #out_arity>>::StructuredInput<#krate::Input<'a>>) {
+                let inner = #chip_call;
+                let outputs = #krate::StructuredData::to_flat(inner);
 
                 for (i,output) in outputs.into_iter().enumerate() {
                     self.out[i].set_out(output);
                 }
             }
         }
+    })
+}
 
-        impl<'a> hdl::Chip<'a> for #struct_name<'a> {
-            fn get_id(&self) -> String {
-                format!(#lit_id, self.identifier)
-            }
+const STRUCT_DERIVE_ERROR_MSG: &str = "can only derive StructuredData on a struct";
+const STRUCT_ATTR_ERROR_MSG: &str =
+    "a struct's structured attribute must be of the form #[structured(crate = \"path\")]";
+const SELECTOR_DERIVE_ERROR_MSG: &str =
+    "can only derive Selector on an enum of between 1 and 65536 variants without fields";
+const SELECTOR_ATTR_ERROR_MSG: &str =
+    "selector attribute must be of the form #[selector(crate = \"path\")]";
+const STRUCT_FIELD_ATTR_ERROR_MSG: &str =
+    "field attribute must be #[structured(skip)], #[structured(active_low)], or #[structured(flatten)] on a field whose type derives StructuredData";
+const STRUCT_UNIT_ERROR_MSG: &str = "can't derive StructuredData on a unit struct";
+const STRUCT_FIELD_TYPE_ERROR_MSG: &str = "StructuredData fields must be of type T or [T; N]";
+const STRUCT_FIELD_LEN_ERROR_MSG: &str =
+    "array length must be a literal, or a const parameter of a struct generic over its width";
+const GENERIC_STRUCT_DERIVE_ERROR_MSG: &str =
+    "a struct generic over its width must have a single field of type [T; N], where N is a const parameter";
 
-            fn get_label(&self) -> &'static str {
-                #lit_name
+#[proc_macro_derive(StructuredData, attributes(structured))]
+pub fn chip_output_collection_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+    derive_crate(&ast, "structured", STRUCT_ATTR_ERROR_MSG)
+        .and_then(|krate| expand_structured_data(&ast, &krate))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// the crate path given by a derive's attribute, e.g. #[structured(crate = "path")]
+fn derive_crate(ast: &syn::DeriveInput, attr_name: &str, err: &str) -> syn::Result<syn::Path> {
+    let mut krate = default_crate_path();
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(attr_name))
+    {
+        let Meta::List(list) = attr.parse_meta()? else {
+            return Err(syn::Error::new_spanned(attr, err));
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => {
+                    krate = crate_path(&nv.lit, err)?
+                }
+                nested => return Err(syn::Error::new_spanned(nested, err)),
             }
         }
+    }
+    Ok(krate)
+}
+
+#[proc_macro_derive(Selector, attributes(selector))]
+pub fn selector_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as syn::DeriveInput);
+    derive_crate(&ast, "selector", SELECTOR_ATTR_ERROR_MSG)
+        .and_then(|krate| expand_selector(&ast, &krate))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
+// a select line for each bit of the largest variant's number
+fn expand_selector(
+    ast: &syn::DeriveInput,
+    krate: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let error = || syn::Error::new_spanned(name, SELECTOR_DERIVE_ERROR_MSG);
+    let syn::Data::Enum(data) = &ast.data else {
+        return Err(error());
     };
-    gen.into()
+    if data.variants.is_empty() || data.variants.len() > 1 << 16 {
+        return Err(error());
+    }
+    if let Some(variant) = data
+        .variants
+        .iter()
+        .find(|variant| !matches!(variant.fields, syn::Fields::Unit))
+    {
+        return Err(syn::Error::new_spanned(variant, SELECTOR_DERIVE_ERROR_MSG));
+    }
+    let width = (usize::BITS - (data.variants.len() - 1).leading_zeros()).max(1);
+    let width = LitInt::new(&width.to_string(), Span::call_site());
+    let variants = data
+        .variants
+        .iter()
+        .map(|variant| &variant.ident)
+        .collect::<Vec<_>>();
+    let numbers = (0..variants.len())
+        .map(|i| LitInt::new(&format!("{i}u16"), Span::call_site()))
+        .collect::<Vec<_>>();
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::Selector<#width> for #name #ty_generics #where_clause {
+            fn to_bits(self) -> [bool; #width] {
+                let number = match self {
+                    #(#name::#variants => #numbers),*
+                };
+                #krate::bits::from_u16(number, #krate::bits::BitOrder::MsbFirst)
+            }
+
+            fn from_bits(bits: [bool; #width]) -> Option<Self> {
+                match #krate::bits::to_u16(bits, #krate::bits::BitOrder::MsbFirst) {
+                    #(#numbers => Some(#name::#variants),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl #impl_generics From<#name #ty_generics> for [bool; #width] #where_clause {
+            fn from(value: #name #ty_generics) -> Self {
+                #krate::Selector::to_bits(value)
+            }
+        }
+    })
 }
 
-const STRUCT_DERIVE_ERROR_MSG: &str = "can only derive StructuredData on a struct";
+// the shape of a field of a StructuredData struct
+enum FieldKind {
+    Scalar,
+    // the widths of an array, or an array of arrays, outermost first
+    Array(Vec<usize>),
+    // a field whose type derives StructuredData itself, with the struct's type
+    // parameters replaced by `bool` so its arity can be named outside of the impl, and
+    // whether its names are given without the field's name in front
+    Nested(proc_macro2::TokenStream, bool),
+    // an array of nested structs, with its width
+    NestedArray(proc_macro2::TokenStream, usize),
+    // a field which holds no wires, and is set to its default by `from_flat()`
+    Skipped,
+}
 
-#[proc_macro_derive(StructuredData)]
-pub fn chip_output_collection_derive(input: TokenStream) -> TokenStream {
-    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+// the options given by a field's #[structured(...)] attributes
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    flatten: bool,
+    active_low: bool,
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("structured"))
+    {
+        let Meta::List(list) = attr.parse_meta()? else {
+            return Err(syn::Error::new_spanned(attr, STRUCT_FIELD_ATTR_ERROR_MSG));
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    field_attrs.skip = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("flatten") => {
+                    field_attrs.flatten = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("active_low") => {
+                    field_attrs.active_low = true
+                }
+                nested => return Err(syn::Error::new_spanned(nested, STRUCT_FIELD_ATTR_ERROR_MSG)),
+            }
+        }
+    }
+    if field_attrs.skip && (field_attrs.flatten || field_attrs.active_low) {
+        return Err(syn::Error::new_spanned(field, STRUCT_FIELD_ATTR_ERROR_MSG));
+    }
+    Ok(field_attrs)
+}
+
+// replaces each of `params` in a type with `bool`
+fn with_bool_params(
+    tokens: proc_macro2::TokenStream,
+    params: &[&Ident],
+) -> proc_macro2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) if params.contains(&&ident) => {
+                proc_macro2::TokenTree::Ident(Ident::new("bool", ident.span()))
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    with_bool_params(group.stream(), params),
+                );
+                replaced.set_span(group.span());
+                proc_macro2::TokenTree::Group(replaced)
+            }
+            token => token,
+        })
+        .collect()
+}
+
+fn expand_structured_data(
+    ast: &syn::DeriveInput,
+    krate: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
     let generics = &ast.generics;
 
@@ -287,123 +1657,307 @@ pub fn chip_output_collection_derive(input: TokenStream) -> TokenStream {
             Lifetime::new("'a", ast.span()),
         ))]);
 
-    let fields = match ast.data {
-        syn::Data::Struct(ref s) => match &s.fields {
+    let fields = match &ast.data {
+        syn::Data::Struct(s) => match &s.fields {
             syn::Fields::Named(fields) => &fields.named,
-            _ => panic!("{}", STRUCT_DERIVE_ERROR_MSG),
+            syn::Fields::Unnamed(fields) => &fields.unnamed,
+            syn::Fields::Unit => return Err(syn::Error::new_spanned(name, STRUCT_UNIT_ERROR_MSG)),
         },
-        _ => panic!("{}", STRUCT_DERIVE_ERROR_MSG),
+        syn::Data::Enum(e) => {
+            return Err(syn::Error::new_spanned(
+                e.enum_token,
+                STRUCT_DERIVE_ERROR_MSG,
+            ))
+        }
+        syn::Data::Union(u) => {
+            return Err(syn::Error::new_spanned(
+                u.union_token,
+                STRUCT_DERIVE_ERROR_MSG,
+            ))
+        }
     };
-    let field_names_and_array_lens = fields.iter().map(|f| {
-        let fieldname = f
-            .ident
-            .clone()
-            .expect("field must have a name for a non-tuple struct");
-        let arraylen = match &f.ty {
-            syn::Type::Array(ty) => {
-                let arraylen: usize = match &ty.len {
-                    syn::Expr::Lit(lit) => match &lit.lit {
-                        syn::Lit::Int(int) => int.to_string().parse().unwrap(),
-                        _ => panic!("shouldn't get here"),
-                    },
-                    _ => panic!("{}", STRUCT_DERIVE_ERROR_MSG),
-                };
-                arraylen
-            }
-            syn::Type::Path(_) => 0,
-            _ => panic!("{}", STRUCT_DERIVE_ERROR_MSG),
-        };
-        (fieldname, arraylen)
-    });
-    let (from_flat_mapping, _) = field_names_and_array_lens.clone().fold(
-        (vec![], 0),
-        |(mut fieldlist, i), (fieldname, arraylen)| {
-            let new_i = if arraylen > 0 {
-                let i_subset = (i..arraylen + i)
-                    .map(|x| Ident::new(&format!("in{x}"), Span::call_site()))
-                    .collect::<Punctuated<_, Comma>>();
-                fieldlist.push(quote! {
-                    #fieldname: [#i_subset]
-                });
-                i + arraylen
-            } else {
-                let curr_ident = Ident::new(&format!("in{i}"), Span::call_site());
-                fieldlist.push(quote! {
-                    #fieldname: #curr_ident
-                });
-                i + 1
-            };
-            (fieldlist, new_i)
-        },
-    );
-    let inputs_from_flat_mapping = from_flat_mapping.iter().collect::<Punctuated<_, Comma>>();
-    let (destructured_inputs, _) =
-        field_names_and_array_lens
-            .clone()
-            .fold((vec![], 0), |(mut acc, i), (_, arraylen)| {
-                let new_i = if arraylen > 0 {
-                    for new_i in i..i + arraylen {
-                        acc.push(Ident::new(&format!("in{}", new_i), Span::call_site()));
+    if generics.const_params().next().is_some() {
+        return generic_structured_data(ast, fields, krate);
+    }
+    let type_params = generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let fieldname = field_member(i, f);
+            let field_attrs = parse_field_attrs(f)?;
+            let kind = match &f.ty {
+                _ if field_attrs.skip => FieldKind::Skipped,
+                ty if field_attrs.flatten && !matches!(ty, syn::Type::Path(_)) => {
+                    return Err(syn::Error::new_spanned(ty, STRUCT_FIELD_ATTR_ERROR_MSG))
+                }
+                syn::Type::Array(syn::TypeArray { elem, len, .. })
+                    if matches!(&**elem, syn::Type::Path(path) if !path
+                        .path
+                        .get_ident()
+                        .is_some_and(|ident| type_params.contains(&ident))) =>
+                {
+                    let len = match len {
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Int(int),
+                            ..
+                        }) => int.base10_parse()?,
+                        len => {
+                            return Err(syn::Error::new_spanned(len, STRUCT_FIELD_LEN_ERROR_MSG))
+                        }
+                    };
+                    FieldKind::NestedArray(with_bool_params(quote! {#elem}, &type_params), len)
+                }
+                syn::Type::Array(ty) => FieldKind::Array(
+                    array_dims(ty)
+                        .iter()
+                        .map(|len| match len {
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Int(int),
+                                ..
+                            }) => int.base10_parse(),
+                            len => Err(syn::Error::new_spanned(len, STRUCT_FIELD_LEN_ERROR_MSG)),
+                        })
+                        .collect::<syn::Result<_>>()?,
+                ),
+                syn::Type::Path(path)
+                    if path
+                        .path
+                        .get_ident()
+                        .is_some_and(|ident| type_params.contains(&ident)) =>
+                {
+                    if field_attrs.flatten {
+                        return Err(syn::Error::new_spanned(path, STRUCT_FIELD_ATTR_ERROR_MSG));
                     }
-                    i + arraylen
-                } else {
-                    acc.push(Ident::new(&format!("in{i}"), Span::call_site()));
-                    i + 1
-                };
-                (acc, new_i)
-            });
-    let destructured_inputs = destructured_inputs.iter().collect::<Punctuated<_, Comma>>();
-    let (destructing_var_names, numvars) = field_names_and_array_lens.clone().fold(
-        (vec![], 0),
-        |(mut acc, i), (fieldname, arraylen)| {
-            let new_i = if arraylen > 0 {
-                let destructured_var_names = (i..i + arraylen)
-                    .map(|elem| Ident::new(&format!("o{}", elem), Span::call_site()))
-                    .collect::<Punctuated<_, Comma>>();
-                acc.push(quote! {
-                    let [#destructured_var_names] = self.#fieldname
-                });
-                i + arraylen
-            } else {
-                let destructured_var_name = Ident::new(&format!("o{}", i), Span::call_site());
-                acc.push(quote! {
-                    let #destructured_var_name = self.#fieldname
-                });
-                i + 1
+                    FieldKind::Scalar
+                }
+                syn::Type::Path(path) => FieldKind::Nested(
+                    with_bool_params(quote! {#path}, &type_params),
+                    field_attrs.flatten,
+                ),
+                ty => return Err(syn::Error::new_spanned(ty, STRUCT_FIELD_TYPE_ERROR_MSG)),
             };
-            (acc, new_i)
+            Ok(((fieldname, kind), field_attrs.active_low))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let (fields, active_low): (Vec<(syn::Member, FieldKind)>, Vec<bool>) =
+        fields.into_iter().unzip();
+
+    // the elements are taken from and given to the flattened array in field order,
+    // recursing into nested structs
+    let from_flat_fields = fields.iter().map(|(fieldname, kind)| match kind {
+        FieldKind::Scalar => quote! { #fieldname: input.next().unwrap() },
+        FieldKind::Array(dims) => {
+            let elems = dims.iter().fold(quote! { input.next().unwrap() }, |elem, _| {
+                quote! { core::array::from_fn(|_| #elem) }
+            });
+            quote! { #fieldname: #elems }
+        }
+        FieldKind::Nested(..) => quote! {
+            #fieldname: #krate::StructuredData::from_flat(core::array::from_fn(|_| input.next().unwrap()))
         },
-    );
-    let destructing_var_names = destructing_var_names
+        FieldKind::NestedArray(..) => quote! {
+            #fieldname: core::array::from_fn(|_| {
+                #krate::StructuredData::from_flat(core::array::from_fn(|_| input.next().unwrap()))
+            })
+        },
+        FieldKind::Skipped => quote! { #fieldname: Default::default() },
+    });
+    let to_flat_fields = fields.iter().map(|(fieldname, kind)| match kind {
+        FieldKind::Scalar => quote! { core::iter::once(self.#fieldname) },
+        FieldKind::Array(dims) => {
+            let flatten = (1..dims.len()).map(|_| quote! {.flatten()});
+            quote! { self.#fieldname.into_iter()#(#flatten)* }
+        }
+        FieldKind::Nested(..) => quote! { #krate::StructuredData::to_flat(self.#fieldname) },
+        FieldKind::NestedArray(..) => quote! {
+            self.#fieldname.into_iter().flat_map(#krate::StructuredData::to_flat)
+        },
+        FieldKind::Skipped => quote! { core::iter::empty() },
+    });
+    // a nested struct's trait impl, named through its `bool` instance
+    let nested_impl = |ty: &proc_macro2::TokenStream| {
+        quote! { <#ty as #krate::StructuredData<bool, {<#ty>::get_arity()}>> }
+    };
+    // an array of arrays is made up of rows of its innermost arrays, each named after
+    // the indices leading to it, e.g. "out-2" for the third row of `out: [[T; 16]; 8]`
+    let rows = |fieldname: &str, dims: &[usize]| {
+        let (inner, outer) = dims.split_last().unwrap();
+        let rows = outer.iter().fold(vec![fieldname.to_owned()], |rows, dim| {
+            rows.iter()
+                .flat_map(|row| (0..*dim).map(move |i| format!("{row}-{i}")))
+                .collect()
+        });
+        let rows = rows.iter().map(|row| LitStr::new(row, Span::call_site()));
+        (quote! {[#(#rows),*]}, *inner)
+    };
+    // the format of the names of a nested struct's elements
+    let nested_format = |fieldname: &str, flatten: bool| {
+        if flatten {
+            LitStr::new("{}", Span::call_site())
+        } else {
+            LitStr::new(&format!("{fieldname}.{{}}"), Span::call_site())
+        }
+    };
+    // array elements are numbered from 0, matching the labels given to chip inputs, and
+    // the names of a nested struct's elements are prefixed with the field's name unless
+    // it's flattened
+    let flat_names = fields.iter().map(|(fieldname, kind)| {
+        let fieldname = member_name(fieldname);
+        match kind {
+            FieldKind::Scalar => {
+                let name = LitStr::new(&fieldname, Span::call_site());
+                quote! { core::iter::once(#name.to_owned()) }
+            }
+            FieldKind::Array(dims) => {
+                let (rows, arraylen) = rows(&fieldname, dims);
+                quote! {
+                    #rows
+                        .into_iter()
+                        .flat_map(|row| (0..#arraylen).map(move |i| format!("{}-{}", row, i)))
+                }
+            }
+            FieldKind::Nested(ty, flatten) => {
+                let format = nested_format(&fieldname, *flatten);
+                let nested_impl = nested_impl(ty);
+                quote! { #nested_impl::flat_names().into_iter().map(|name| format!(#format, name)) }
+            }
+            // each element's names are prefixed with its index, e.g. "out-2.sum"
+            FieldKind::NestedArray(ty, len) => {
+                let nested_impl = nested_impl(ty);
+                let format = LitStr::new(&format!("{fieldname}-{{}}.{{}}"), Span::call_site());
+                quote! {
+                    (0..#len).flat_map(|i| {
+                        #nested_impl::flat_names().into_iter().map(move |name| format!(#format, i, name))
+                    })
+                }
+            }
+            FieldKind::Skipped => quote! { core::iter::empty() },
+        }
+    });
+    let field_info = fields.iter().map(|(fieldname, kind)| {
+        let fieldname = member_name(fieldname);
+        let name = LitStr::new(&fieldname, Span::call_site());
+        match kind {
+            FieldKind::Scalar => quote! { core::iter::once((#name.to_owned(), 0)) },
+            FieldKind::Array(dims) => {
+                let (rows, arraylen) = rows(&fieldname, dims);
+                quote! { #rows.into_iter().map(|row| (row.to_owned(), #arraylen)) }
+            }
+            FieldKind::Nested(ty, flatten) => {
+                let format = nested_format(&fieldname, *flatten);
+                quote! {
+                    <#ty>::get_field_info()
+                        .into_iter()
+                        .map(|(name, arraylen)| (format!(#format, name), arraylen))
+                }
+            }
+            FieldKind::NestedArray(ty, len) => {
+                let format = LitStr::new(&format!("{fieldname}-{{}}.{{}}"), Span::call_site());
+                quote! {
+                    (0..#len).flat_map(|i| {
+                        <#ty>::get_field_info()
+                            .into_iter()
+                            .map(move |(name, arraylen)| (format!(#format, i, name), arraylen))
+                    })
+                }
+            }
+            FieldKind::Skipped => quote! { core::iter::empty() },
+        }
+    });
+    // a nested struct's own active low elements are flipped back if the whole struct
+    // is marked active low
+    let active_low = fields
         .iter()
-        .collect::<Punctuated<_, Semi>>();
-    let destructured_fields = (0..numvars)
-        .map(|fi| Ident::new(&format!("o{}", fi), Span::call_site()))
-        .collect::<Punctuated<_, Comma>>();
-    let arity = LitInt::new(&numvars.to_string(), ast.span());
-    let num_fields = LitInt::new(&fields.len().to_string(), Span::call_site());
-
-    let field_info = field_names_and_array_lens
-        .clone()
-        .map(|(fieldname, arraylen)| {
-            let arraylen = LitInt::new(&arraylen.to_string(), Span::call_site());
-            let fieldname = LitStr::new(&fieldname.to_string(), Span::call_site());
-            quote! {(#fieldname, #arraylen)}
+        .zip(active_low)
+        .map(|((_, kind), active_low)| match kind {
+            FieldKind::Scalar => quote! { core::iter::once(#active_low) },
+            FieldKind::Array(dims) => {
+                let len = dims.iter().product::<usize>();
+                quote! { core::iter::repeat(#active_low).take(#len) }
+            }
+            FieldKind::Nested(ty, _) => {
+                let nested_impl = nested_impl(ty);
+                quote! { #nested_impl::active_low().into_iter().map(|low| low != #active_low) }
+            }
+            FieldKind::NestedArray(ty, len) => {
+                let nested_impl = nested_impl(ty);
+                quote! {
+                    (0..#len).flat_map(|_| #nested_impl::active_low()).map(|low| low != #active_low)
+                }
+            }
+            FieldKind::Skipped => quote! { core::iter::empty() },
         });
-    let field_info = field_info.collect::<Punctuated<_, Comma>>();
+    // nested arities are only known once their own impls are, so the arity is a
+    // const expression whenever the struct has a nested field
+    let flat_len = fields
+        .iter()
+        .map(|(_, kind)| match kind {
+            FieldKind::Scalar => 1,
+            FieldKind::Array(dims) => dims.iter().product(),
+            FieldKind::Nested(..) | FieldKind::NestedArray(..) | FieldKind::Skipped => 0,
+        })
+        .sum::<usize>();
+    let flat_len = LitInt::new(&flat_len.to_string(), ast.span());
+    let nested_arities = fields
+        .iter()
+        .filter_map(|(_, kind)| match kind {
+            FieldKind::Nested(ty, _) => Some(quote! {<#ty>::get_arity()}),
+            FieldKind::NestedArray(ty, len) => Some(quote! {#len * <#ty>::get_arity()}),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let arity = if nested_arities.is_empty() {
+        quote! {#flat_len}
+    } else {
+        quote! {{#flat_len #(+ #nested_arities)*}}
+    };
 
-    quote! {
-        impl #structured_data_generics hdl::StructuredData<T, #arity> for #name #generics {
-            fn from_flat(input: [T; #arity]) -> Self { // TODO: don't make this dependent on generic name
-            let [#destructured_inputs] = input;
+    let numeric_fields = fields
+        .iter()
+        .filter_map(|(fieldname, kind)| match kind {
+            FieldKind::Array(dims) if dims.len() == 1 => Some((fieldname.clone(), dims[0])),
+            FieldKind::Skipped => None,
+            _ => Some((fieldname.clone(), 0)),
+        })
+        .collect();
+    let skipped_fields = fields
+        .iter()
+        .filter(|(_, kind)| matches!(kind, FieldKind::Skipped))
+        .map(|(fieldname, _)| fieldname.clone())
+        .collect::<Vec<_>>();
+    let numeric_helpers = numeric_helpers(name, numeric_fields, &skipped_fields, krate);
+    let words = words_struct(&ast.vis, name, &fields, krate);
+
+    Ok(quote! {
+        impl #structured_data_generics #krate::StructuredData<T, #arity> for #name #generics {
+            fn from_flat(input: [T; #arity]) -> Self {
+                let mut input = input.into_iter();
                 #name {
-                    #inputs_from_flat_mapping
+                    #(#from_flat_fields),*
                 }
             }
 
             fn to_flat(self) -> [T; #arity] {
-                #destructing_var_names;
-                [#destructured_fields]
+                let mut flat = core::iter::empty()#(.chain(#to_flat_fields))*;
+                core::array::from_fn(|_| flat.next().unwrap())
+            }
+
+            fn flat_names() -> [String; #arity] {
+                let mut names = core::iter::empty()#(.chain(#flat_names))*;
+                core::array::from_fn(|_| names.next().unwrap())
+            }
+
+            fn field_info() -> Vec<(String, usize)> {
+                Self::get_field_info()
+            }
+
+            fn active_low() -> [bool; #arity] {
+                let active_low = Self::get_active_low();
+                core::array::from_fn(|i| active_low[i])
             }
         }
 
@@ -412,11 +1966,253 @@ pub fn chip_output_collection_derive(input: TokenStream) -> TokenStream {
                 #arity
             }
 
-            // returns an array of tuple (fieldname,arraylen)
-            const fn get_field_info() -> [(&'static str,usize);#num_fields] {
-                [#field_info]
+            // returns a vec of tuple (fieldname,arraylen), with the fields of nested
+            // structs in place of the nested struct
+            fn get_field_info() -> Vec<(String, usize)> {
+                core::iter::empty()#(.chain(#field_info))*.collect()
+            }
+
+            // the flattened elements' active low flags, which chips describe their
+            // outputs with
+            fn get_active_low() -> Vec<bool> {
+                core::iter::empty()#(.chain(#active_low))*.collect()
+            }
+        }
+
+        #numeric_helpers
+
+        #words
+    })
+}
+
+// the way a field is accessed: its name, or its index in a tuple struct
+fn field_member(index: usize, field: &syn::Field) -> syn::Member {
+    match &field.ident {
+        Some(ident) => syn::Member::Named(ident.clone()),
+        None => syn::Member::Unnamed(index.into()),
+    }
+}
+
+// the name a field's elements are labelled with, which is its index in a tuple struct
+fn member_name(member: &syn::Member) -> String {
+    match member {
+        syn::Member::Named(ident) => ident.to_string(),
+        syn::Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+// Arities can't be computed from const parameters on stable Rust, so a struct generic
+// over its width must hold a single array whose width is one of its const parameters,
+// e.g. the outputs of a generic chip
+fn generic_structured_data(
+    ast: &syn::DeriveInput,
+    fields: &Punctuated<syn::Field, Comma>,
+    krate: &syn::Path,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let [field] = fields.iter().collect::<Vec<_>>()[..] else {
+        return Err(syn::Error::new_spanned(
+            fields,
+            GENERIC_STRUCT_DERIVE_ERROR_MSG,
+        ));
+    };
+    let fieldname = field_member(0, field);
+    let width = match &field.ty {
+        syn::Type::Array(syn::TypeArray {
+            len: syn::Expr::Path(width),
+            ..
+        }) => width,
+        ty => return Err(syn::Error::new_spanned(ty, GENERIC_STRUCT_DERIVE_ERROR_MSG)),
+    };
+    let fieldname_lit = LitStr::new(&member_name(&fieldname), Span::call_site());
+    let active_low = parse_field_attrs(field)?.active_low;
+
+    Ok(quote! {
+        impl #impl_generics #krate::StructuredData<T, #width> for #name #ty_generics #where_clause {
+            fn from_flat(input: [T; #width]) -> Self {
+                #name { #fieldname: input }
+            }
+
+            fn to_flat(self) -> [T; #width] {
+                self.#fieldname
+            }
+
+            fn flat_names() -> [String; #width] {
+                core::array::from_fn(|i| format!("{}-{}", #fieldname_lit, i))
+            }
+
+            fn active_low() -> [bool; #width] {
+                let active_low = Self::get_active_low();
+                core::array::from_fn(|i| active_low[i])
+            }
+
+            fn field_info() -> Vec<(String, usize)> {
+                Self::get_field_info()
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            const fn get_arity() -> usize {
+                #width
+            }
+
+            fn get_field_info() -> Vec<(String, usize)> {
+                vec![(#fieldname_lit.to_owned(), #width)]
+            }
+
+            fn get_active_low() -> Vec<bool> {
+                vec![#active_low; #width]
+            }
+        }
+    })
+}
+
+// A struct of named bits and buses of up to 16 bits gets a `{Name}Words` twin holding
+// each bus as a number, most significant bit first, which converts to and from the
+// struct of bools. Machines take anything which converts into their inputs, so e.g.
+// `Adder16Words { num1: 452, num2: 671 }` can be processed directly
+fn words_struct(
+    vis: &syn::Visibility,
+    name: &Ident,
+    fields: &[(syn::Member, FieldKind)],
+    krate: &syn::Path,
+) -> proc_macro2::TokenStream {
+    let mut word_fields = vec![];
+    let mut skipped = vec![];
+    for (fieldname, kind) in fields {
+        let syn::Member::Named(fieldname) = fieldname else {
+            return quote! {};
+        };
+        match kind {
+            FieldKind::Scalar => word_fields.push((fieldname, None)),
+            FieldKind::Array(dims) if dims.len() == 1 && (1..=16).contains(&dims[0]) => {
+                word_fields.push((fieldname, Some(dims[0])))
+            }
+            FieldKind::Skipped => skipped.push(fieldname),
+            _ => return quote! {},
+        }
+    }
+    if word_fields.is_empty() {
+        return quote! {};
+    }
+
+    let words_name = Ident::new(&format!("{name}Words"), name.span());
+    let order = quote! { #krate::bits::BitOrder::MsbFirst };
+    let mut declarations = vec![];
+    let mut to_bits = vec![];
+    let mut from_bits = vec![];
+    for (fieldname, width) in word_fields {
+        match width {
+            None => {
+                declarations.push(quote! { pub #fieldname: bool });
+                to_bits.push(quote! { #fieldname: words.#fieldname });
+                from_bits.push(quote! { #fieldname: bits.#fieldname });
+            }
+            Some(width) => {
+                let ty = if width <= 8 {
+                    quote! {u8}
+                } else {
+                    quote! {u16}
+                };
+                declarations.push(quote! { pub #fieldname: #ty });
+                to_bits.push(quote! {
+                    #fieldname: #krate::bits::from_u16(words.#fieldname.into(), #order)
+                });
+                from_bits.push(quote! {
+                    #fieldname: #krate::bits::to_u16(bits.#fieldname, #order) as #ty
+                });
+            }
+        }
+    }
+    quote! {
+        #[allow(dead_code)]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+        #vis struct #words_name {
+            #(#declarations),*
+        }
+
+        impl From<#words_name> for #name<bool> {
+            fn from(words: #words_name) -> Self {
+                Self {
+                    #(#to_bits,)*
+                    #(#skipped: Default::default(),)*
+                }
+            }
+        }
+
+        impl From<#name<bool>> for #words_name {
+            fn from(bits: #name<bool>) -> Self {
+                Self {
+                    #(#from_bits),*
+                }
+            }
+        }
+    }
+}
+
+// numeric accessors for buses of up to 16 bits, most significant bit first. A struct
+// holding a single bus, like a chip output, can also be built from a number, with its
+// skipped fields set to their defaults
+fn numeric_helpers(
+    name: &Ident,
+    fields: Vec<(syn::Member, usize)>,
+    skipped: &[syn::Member],
+    krate: &syn::Path,
+) -> proc_macro2::TokenStream {
+    let is_numeric = |arraylen: &usize| (1..=16).contains(arraylen);
+    // a tuple struct's fields have no names to give their accessors
+    let accessors = fields
+        .iter()
+        .filter_map(|(fieldname, arraylen)| match fieldname {
+            syn::Member::Named(fieldname) if is_numeric(arraylen) => Some(fieldname),
+            _ => None,
+        })
+        .map(|fieldname| {
+            let to_u16 = Ident::new(&format!("{fieldname}_to_u16"), Span::call_site());
+            let to_i16 = Ident::new(&format!("{fieldname}_to_i16"), Span::call_site());
+            quote! {
+                pub fn #to_u16(&self) -> u16 {
+                    #krate::bits::to_u16(self.#fieldname, #krate::bits::BitOrder::MsbFirst)
+                }
+
+                pub fn #to_i16(&self) -> i16 {
+                    #krate::bits::to_i16(self.#fieldname, #krate::bits::BitOrder::MsbFirst)
+                }
+            }
+        });
+    let constructors = match fields.as_slice() {
+        [(fieldname, arraylen)] if is_numeric(arraylen) => quote! {
+            pub fn from_u16(value: u16) -> Self {
+                Self {
+                    #fieldname: #krate::bits::from_u16(value, #krate::bits::BitOrder::MsbFirst),
+                    #(#skipped: Default::default(),)*
+                }
+            }
+
+            pub fn from_i16(value: i16) -> Self {
+                Self {
+                    #fieldname: #krate::bits::from_i16(value, #krate::bits::BitOrder::MsbFirst),
+                    #(#skipped: Default::default(),)*
+                }
+            }
+
+            pub fn to_u16(&self) -> u16 {
+                #krate::bits::to_u16(self.#fieldname, #krate::bits::BitOrder::MsbFirst)
+            }
+
+            pub fn to_i16(&self) -> i16 {
+                #krate::bits::to_i16(self.#fieldname, #krate::bits::BitOrder::MsbFirst)
             }
+        },
+        _ => quote! {},
+    };
+    quote! {
+        // not every chip's tests need every helper
+        #[allow(dead_code)]
+        impl #name<bool> {
+            #(#accessors)*
+            #constructors
         }
     }
-    .into()
 }
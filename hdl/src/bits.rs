@@ -0,0 +1,70 @@
+// Conversions between numbers and bus values. Chips in this repo put the most
+// significant bit first, so bus element 0 is the sign bit of a 16 bit number, but
+// the order is explicit here so a bus wired the other way round can still be read
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+impl BitOrder {
+    // the position in a bus of width `width` holding bit `bit`, counting from the LSB
+    fn index(self, bit: usize, width: usize) -> usize {
+        match self {
+            BitOrder::MsbFirst => width - 1 - bit,
+            BitOrder::LsbFirst => bit,
+        }
+    }
+}
+
+// buses wider than 16 bits are zero extended
+pub fn from_u16<const N: usize>(value: u16, order: BitOrder) -> [bool; N] {
+    from_i32(value.into(), order)
+}
+
+// buses wider than 16 bits are sign extended
+pub fn from_i16<const N: usize>(value: i16, order: BitOrder) -> [bool; N] {
+    from_i32(value.into(), order)
+}
+
+// only the lowest 16 bits of wider buses are read
+pub fn to_u16<const N: usize>(bits: [bool; N], order: BitOrder) -> u16 {
+    (0..N.min(16))
+        .filter(|&bit| bits[order.index(bit, N)])
+        .fold(0, |acc, bit| acc | 1 << bit)
+}
+
+// buses narrower than 16 bits are sign extended from their most significant bit
+pub fn to_i16<const N: usize>(bits: [bool; N], order: BitOrder) -> i16 {
+    let value = to_u16(bits, order);
+    if N > 0 && N < 16 && bits[order.index(N - 1, N)] {
+        (value | u16::MAX << N) as i16
+    } else {
+        value as i16
+    }
+}
+
+// for buses too wide for a 16 bit number, e.g. a 32 bit adder's. Buses wider than 64
+// bits are zero extended
+pub fn from_u64<const N: usize>(value: u64, order: BitOrder) -> [bool; N] {
+    let mut bits = [false; N];
+    for bit in 0..N.min(64) {
+        bits[order.index(bit, N)] = value >> bit & 1 == 1;
+    }
+    bits
+}
+
+// only the lowest 64 bits of wider buses are read
+pub fn to_u64<const N: usize>(bits: [bool; N], order: BitOrder) -> u64 {
+    (0..N.min(64))
+        .filter(|&bit| bits[order.index(bit, N)])
+        .fold(0, |acc, bit| acc | 1 << bit)
+}
+
+fn from_i32<const N: usize>(value: i32, order: BitOrder) -> [bool; N] {
+    let mut bits = [false; N];
+    for bit in 0..N {
+        bits[order.index(bit, N)] = value >> bit.min(31) & 1 == 1;
+    }
+    bits
+}
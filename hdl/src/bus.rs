@@ -0,0 +1,99 @@
+use std::{array, ops::Index};
+
+use crate::{ChipOutputType, Input};
+
+// A group of wires handled as one value, e.g. a 16 bit number. Chips can take a bus
+// as an argument in place of an array of chip inputs, and buses can be split,
+// sliced and joined to route them between chips without building arrays by hand.
+// Element 0 is the most significant bit, following the rest of the repo
+#[derive(Clone, Copy)]
+pub struct Bus<'a, const N: usize>([Input<'a>; N]);
+
+impl<'a, const N: usize> Bus<'a, N> {
+    pub fn new(inputs: [Input<'a>; N]) -> Self {
+        Bus(inputs)
+    }
+
+    pub fn inputs(self) -> [Input<'a>; N] {
+        self.0
+    }
+
+    pub fn map<T: Into<Input<'a>>>(self, mut f: impl FnMut(Input<'a>) -> T) -> Bus<'a, N> {
+        Bus(self.0.map(|in_| f(in_).into()))
+    }
+
+    // applies `f` to each pair of wires, e.g. to build a 16 bit gate from 16 gates
+    pub fn zip_map<T: Into<Input<'a>>>(
+        self,
+        other: Bus<'a, N>,
+        mut f: impl FnMut(Input<'a>, Input<'a>) -> T,
+    ) -> Bus<'a, N> {
+        Bus(array::from_fn(|i| f(self.0[i], other.0[i]).into()))
+    }
+
+    // the LEN wires starting at START
+    pub fn slice<const START: usize, const LEN: usize>(self) -> Bus<'a, LEN> {
+        assert!(START + LEN <= N, "slice out of range of bus");
+        Bus(array::from_fn(|i| self.0[START + i]))
+    }
+
+    pub fn split<const N1: usize, const N2: usize>(self) -> (Bus<'a, N1>, Bus<'a, N2>) {
+        assert_eq!(N1 + N2, N, "split sizes must add up to the bus width");
+        (self.slice::<0, N1>(), self.slice::<N1, N2>())
+    }
+
+    // `self` becomes the most significant part of the result
+    pub fn concat<const M: usize, const OUT: usize>(self, other: Bus<'a, M>) -> Bus<'a, OUT> {
+        assert_eq!(
+            N + M,
+            OUT,
+            "concatenated bus width must be the sum of its parts"
+        );
+        Bus(array::from_fn(|i| {
+            if i < N {
+                self.0[i]
+            } else {
+                other.0[i - N]
+            }
+        }))
+    }
+
+    // chip outputs can't be driven by machine inputs, so this panics if the bus
+    // contains any
+    pub fn outputs(self) -> [ChipOutputType<'a>; N] {
+        self.0.map(|in_| match in_ {
+            Input::ChipOutput(out) => ChipOutputType::ChipOutput(out),
+            Input::ChipInput(in_) => ChipOutputType::ChipInput(in_),
+            Input::NandInput(nand) => ChipOutputType::NandOutput(nand),
+            Input::SharedBus(bus) => ChipOutputType::SharedBus(bus),
+            Input::Lookup(lookup) => ChipOutputType::Lookup(lookup),
+            Input::UserInput(_) => panic!("a user input can't drive a chip output"),
+        })
+    }
+}
+
+impl<'a, const N: usize> Index<usize> for Bus<'a, N> {
+    type Output = Input<'a>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<'a, T: Into<Input<'a>>, const N: usize> From<[T; N]> for Bus<'a, N> {
+    fn from(inputs: [T; N]) -> Self {
+        Bus(inputs.map(Into::into))
+    }
+}
+
+impl<'a, const N: usize> From<Bus<'a, N>> for [Input<'a>; N] {
+    fn from(bus: Bus<'a, N>) -> Self {
+        bus.0
+    }
+}
+
+impl<'a, const N: usize> From<Bus<'a, N>> for [ChipOutputType<'a>; N] {
+    fn from(bus: Bus<'a, N>) -> Self {
+        bus.outputs()
+    }
+}
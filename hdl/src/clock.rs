@@ -0,0 +1,158 @@
+use std::{collections::HashSet, ops::Deref};
+
+use bumpalo::Bump;
+
+use crate::{
+    invert,
+    netlist::{NetlistChip, NodeKind},
+    ChipInput, Error, Logic, Machine, StructuredData, StructuredDataFamily,
+};
+
+// The allocator given to a `#[chip(clocked)]` function. It carries the chip's clock,
+// so the clocked chips built inside it are wired to that clock without it being passed
+// down by hand. It derefs to the `Bump` for building everything else
+pub struct Clocked<'a> {
+    alloc: &'a Bump,
+    clock: &'a ChipInput<'a>,
+}
+
+impl<'a> Clocked<'a> {
+    pub fn new(alloc: &'a Bump, clock: &'a ChipInput<'a>) -> &'a Self {
+        alloc.alloc(Clocked { alloc, clock })
+    }
+
+    pub fn clock(&self) -> &'a ChipInput<'a> {
+        self.clock
+    }
+}
+
+impl Deref for Clocked<'_> {
+    type Target = Bump;
+
+    fn deref(&self) -> &Bump {
+        self.alloc
+    }
+}
+
+// A machine can run several clocks independently, e.g. a peripheral clocked at half
+// the rate of the CPU. Each clock domain is named after the input which drives it.
+// Inputs named "clock" or starting with "clock_" are declared as domains when the
+// machine is built, and `add_clock_domain()` declares any others
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    pub(crate) fn default_clock_domains() -> Vec<usize> {
+        Self::input_names()
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| *name == "clock" || name.starts_with("clock_"))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn add_clock_domain(&mut self, input: &str) -> Result<(), Error> {
+        let position = Self::input_names()
+            .iter()
+            .position(|name| name == input)
+            .ok_or_else(|| Error::UnknownInput(input.to_owned()))?;
+        if !self.clock_domains.contains(&position) {
+            self.clock_domains.push(position);
+        }
+        Ok(())
+    }
+
+    pub fn clock_domains(&self) -> Vec<String> {
+        let names = Self::input_names();
+        self.clock_domains
+            .iter()
+            .map(|&i| names[i].clone())
+            .collect()
+    }
+
+    // drives the domain's clock high, or its wire low when the clock is active low.
+    // Clocks of the other domains hold their current level, whatever `input` sets them to
+    pub fn tick_domain(
+        &mut self,
+        domain: &str,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        self.process_with_domain_clock(domain, input, true)
+    }
+
+    pub fn tock_domain(
+        &mut self,
+        domain: &str,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        self.process_with_domain_clock(domain, input, false)
+    }
+
+    // a full cycle of the domain's clock, returning the outputs after the tock
+    pub fn cycle_domain(
+        &mut self,
+        domain: &str,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        let flat_input = input.to_flat();
+        self.tick_domain(domain, TFam::StructuredInput::from_flat(flat_input))?;
+        self.tock_domain(domain, TFam::StructuredInput::from_flat(flat_input))
+    }
+
+    // the chips with an input wired to the domain's clock. A chip which crosses
+    // domains belongs to each of them
+    pub fn clock_domain_chips(&self, domain: &str) -> Result<Vec<NetlistChip>, Error> {
+        let clock = self.find_clock_domain(domain)?;
+        let netlist = self.netlist();
+        let mut chips = vec![];
+        let mut seen = HashSet::new();
+        let mut stack = vec![netlist.inputs[clock]];
+        while let Some(node) = stack.pop() {
+            for consumer in netlist.consumers(node) {
+                // the clock passes through chip inputs untouched, so keep following it
+                // until it reaches a gate
+                if netlist.nodes[consumer].kind != NodeKind::ChipInput {
+                    continue;
+                }
+                stack.push(consumer);
+                if let Some(chip) = netlist.nodes[consumer].chip {
+                    if seen.insert(chip) {
+                        chips.push(netlist.chips[chip].clone());
+                    }
+                }
+            }
+        }
+        Ok(chips)
+    }
+
+    fn find_clock_domain(&self, domain: &str) -> Result<usize, Error> {
+        let names = Self::input_names();
+        self.clock_domains
+            .iter()
+            .copied()
+            .find(|&i| names[i] == domain)
+            .ok_or_else(|| Error::UnknownClockDomain(domain.to_owned()))
+    }
+
+    fn process_with_domain_clock(
+        &mut self,
+        domain: &str,
+        input: TFam::StructuredInput<bool>,
+        level: bool,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let clock = self.find_clock_domain(domain)?;
+        let mut flat_input = input.to_flat();
+        flat_input[clock] = level;
+        // the other clocks keep the levels on their wires, which are past the inversion
+        // of active low inputs, so only the given inputs are inverted
+        let mut levels = invert(flat_input, Self::input_active_low());
+        for &other in &self.clock_domains {
+            if other != clock {
+                levels[other] = self.inputs[other].value.get() == Logic::High;
+            }
+        }
+        self.process_levels(levels)
+    }
+}
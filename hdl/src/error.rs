@@ -0,0 +1,139 @@
+use std::fmt;
+
+use crate::{
+    netlist::{FanOut, Unconnected},
+    Logic,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    // NANDs or chip outputs which never had their drivers set, see Machine::validate()
+    Unconnected(Vec<Unconnected>),
+    // nodes driving more NAND inputs than allowed, see Machine::check_fan_out()
+    FanOut {
+        limit: usize,
+        exceeded: Vec<FanOut>,
+    },
+    // the circuit didn't stabilise within `steps` unit delay steps or Machine::settle()
+    // passes, with the identifiers of the NANDs still changing at the last of them
+    Oscillation {
+        steps: u32,
+        nodes: Vec<u32>,
+    },
+    // unit delay evaluation was requested of a machine in the zero delay timing mode
+    ZeroDelayTiming,
+    NoClock,
+    // identifiers of shared buses with several enabled drivers disagreeing
+    BusContention(Vec<u32>),
+    UnknownClockDomain(String),
+    UnknownInput(String),
+    UnknownOutput(String),
+    // a path passed to Machine::probe() which doesn't name a chip output
+    UnknownNode(String),
+    // a saved state doesn't fit the machine it's being loaded in to, counted in nodes
+    StateMismatch {
+        expected: usize,
+        found: usize,
+    },
+    // bytes which aren't a saved machine state
+    InvalidState,
+    // a replayed trace produced different outputs, see Machine::replay()
+    TraceMismatch {
+        step: usize,
+        expected: Vec<Logic>,
+        found: Vec<Logic>,
+    },
+    InvalidTrace(String),
+    // Machine::load_program() needs exactly one lookup table to load in to
+    ProgramMemory {
+        tables: usize,
+    },
+    // a machine disagreed with its behavioural model, see Machine::check_model(). The
+    // values are flattened in the order of the machine's input and output names
+    ModelMismatch {
+        inputs: Vec<bool>,
+        expected: Vec<bool>,
+        found: Vec<bool>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unconnected(unconnected) => {
+                write!(f, "circuit has unconnected nodes:")?;
+                for node in unconnected {
+                    write!(
+                        f,
+                        "\n  {:?} {} ({}) in {}",
+                        node.kind,
+                        node.label,
+                        node.source_id,
+                        node.path.join("/")
+                    )?;
+                }
+                Ok(())
+            }
+            Error::FanOut { limit, exceeded } => {
+                write!(f, "nodes exceed the fan-out limit of {limit}:")?;
+                for node in exceeded {
+                    write!(
+                        f,
+                        "\n  {:?} {} ({}) in {} drives {}",
+                        node.kind,
+                        node.label,
+                        node.source_id,
+                        node.path.join("/"),
+                        node.fan_out
+                    )?;
+                }
+                Ok(())
+            }
+            Error::Oscillation { steps, nodes } => write!(
+                f,
+                "circuit did not stabilise within {steps} steps, NANDs still changing: {nodes:?}"
+            ),
+            Error::ZeroDelayTiming => {
+                write!(f, "machine is not in the unit delay timing mode")
+            }
+            Error::NoClock => write!(f, "machine has no clock input"),
+            Error::BusContention(buses) => {
+                write!(f, "shared buses driven to conflicting values: {buses:?}")
+            }
+            Error::UnknownClockDomain(name) => {
+                write!(f, "machine has no clock domain named {name}")
+            }
+            Error::UnknownInput(name) => write!(f, "machine has no input named {name}"),
+            Error::UnknownOutput(name) => write!(f, "machine has no output named {name}"),
+            Error::UnknownNode(path) => write!(f, "machine has no chip output at {path}"),
+            Error::StateMismatch { expected, found } => write!(
+                f,
+                "saved state has {found} nodes but the machine has {expected}"
+            ),
+            Error::InvalidState => write!(f, "not a saved machine state"),
+            Error::TraceMismatch {
+                step,
+                expected,
+                found,
+            } => write!(
+                f,
+                "trace step {step} expected outputs {expected:?} but found {found:?}"
+            ),
+            Error::InvalidTrace(reason) => write!(f, "invalid trace: {reason}"),
+            Error::ProgramMemory { tables } => write!(
+                f,
+                "programs are loaded in to a machine's only lookup table, but it has {tables}"
+            ),
+            Error::ModelMismatch {
+                inputs,
+                expected,
+                found,
+            } => write!(
+                f,
+                "model expected outputs {expected:?} for inputs {inputs:?} but found {found:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
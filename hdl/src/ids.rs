@@ -0,0 +1,54 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+// Graph objects are numbered by a counter for each kind of object. The counters are
+// global by default, so ids depend on everything built before, including by other
+// tests, and can wrap around in long running processes. Objects built while a scope is
+// entered are numbered from that scope's counters instead, so two builds of the same
+// chip in fresh scopes get the same ids
+#[derive(Clone, Default)]
+pub struct IdScope {
+    counters: Rc<RefCell<HashMap<&'static str, u32>>>,
+}
+
+thread_local! {
+    static SCOPES: RefCell<Vec<IdScope>> = const { RefCell::new(vec![]) };
+}
+
+impl IdScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // numbers every object `f` builds on this thread from this scope
+    pub fn enter<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Exit;
+        impl Drop for Exit {
+            fn drop(&mut self) {
+                SCOPES.with(|scopes| scopes.borrow_mut().pop());
+            }
+        }
+
+        SCOPES.with(|scopes| scopes.borrow_mut().push(self.clone()));
+        let _exit = Exit;
+        f()
+    }
+}
+
+// the next id for a kind of object, from the innermost entered scope or else `global`
+pub fn next_id(kind: &'static str, global: &AtomicU32) -> u32 {
+    SCOPES.with(|scopes| match scopes.borrow().last() {
+        Some(scope) => {
+            let mut counters = scope.counters.borrow_mut();
+            let counter = counters.entry(kind).or_insert(0);
+            let id = *counter;
+            *counter = counter.wrapping_add(1);
+            id
+        }
+        None => global.fetch_add(1, Ordering::Relaxed),
+    })
+}
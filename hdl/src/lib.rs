@@ -1,11 +1,58 @@
 use std::{
     cell::Cell,
+    collections::{HashMap, HashSet},
+    hash::{BuildHasherDefault, Hasher},
     marker::PhantomData,
-    sync::atomic::{AtomicU32, Ordering},
+    sync::atomic::AtomicU32,
 };
 
 use bumpalo::Bump;
 
+// re-exported for the code generated by hdl-macro
+pub use bumpalo;
+#[cfg(feature = "serde")]
+pub use serde;
+
+pub mod bits;
+mod bus;
+mod clock;
+mod error;
+pub mod ids;
+mod logic;
+mod lookup;
+pub mod model;
+pub mod netlist;
+pub mod ports;
+mod selector;
+#[cfg(feature = "serde")]
+pub mod serde_array;
+pub mod state;
+pub mod timing;
+pub mod trace;
+mod tristate;
+pub mod vcd;
+mod watch;
+
+pub use bus::Bus;
+pub use clock::Clocked;
+pub use error::Error;
+use ids::IdScope;
+pub use logic::Logic;
+pub use lookup::{Lookup, LookupTable};
+pub use selector::Selector;
+use timing::Timing;
+use trace::Trace;
+use tristate::CONTENTION;
+pub use tristate::{SharedBus, TriStateDriver};
+use vcd::VcdRecording;
+use watch::Watch;
+
+thread_local! {
+    // the number of NANDs and chip outputs created without their connections, e.g. by
+    // DefaultChip::new(), which haven't been connected since
+    static UNCONNECTED: Cell<i64> = const { Cell::new(0) };
+}
+
 // FIXME: work out how to mark struct as non-threadsafe
 // maybe it's already ok - it's not Send, Clone or Copy
 pub struct Machine<
@@ -16,18 +63,55 @@ pub struct Machine<
 > {
     inputs: [&'a UserInput; NINPUT],
     pub outputs: [Output<'a>; NOUT],
+    // index of the input driven by `tick()` and `tock()`
+    clock: Option<usize>,
+    // indices of the inputs driving each clock domain, see the clock module
+    clock_domains: Vec<usize>,
+    reset: Option<usize>,
+    timing: Timing,
+    // only collected in the unit delay timing mode, or once the machine has settled
+    nands: Vec<&'a Nand<'a>>,
     iteration: u8,
+    // set while recording, see the trace module
+    trace: Option<Trace>,
+    // set while recording, see the vcd module
+    vcd: Option<VcdRecording<'a>>,
+    watches: Vec<Watch<'a>>,
+    // the number of processes so far, reported to watches
+    cycle: u64,
     phantom_data: PhantomData<TFam>,
 }
 
 pub trait StructuredData<T, const NINPUT: usize> {
     fn from_flat(input: [T; NINPUT]) -> Self;
     fn to_flat(self) -> [T; NINPUT];
+    // the name of each flattened element, e.g. "in_-0" for the first element of `in_`
+    fn flat_names() -> [String; NINPUT];
+    // the name and width of each field, where a width of 0 is a single element rather
+    // than an array. By default every element is its own field
+    fn field_info() -> Vec<(String, usize)> {
+        Self::flat_names()
+            .into_iter()
+            .map(|name| (name, 0))
+            .collect()
+    }
+    // whether each flattened element is active low, which the machine inverts between
+    // the values it's given or gives back and the levels on the wires
+    fn active_low() -> [bool; NINPUT] {
+        [false; NINPUT]
+    }
 }
 
 pub trait StructuredDataFamily<const NINPUT: usize, const NOUT: usize> {
     type StructuredInput<T>: StructuredData<T, NINPUT>;
     type StructuredOutput<T>: StructuredData<T, NOUT>;
+    // the input driven by `Machine::reset()`. Falls back to an input named "reset"
+    const RESET_INPUT: Option<&'static str> = None;
+}
+
+// flips the values of the active low elements
+fn invert<const N: usize>(values: [bool; N], active_low: [bool; N]) -> [bool; N] {
+    core::array::from_fn(|i| values[i] != active_low[i])
 }
 
 impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
@@ -36,32 +120,645 @@ impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NO
     pub fn new<TChip: SizedChip<'a, TFam, NOUT, NINPUT>>(
         alloc: &'a Bump,
         new_fn: fn(&'a Bump, TFam::StructuredInput<Input<'a>>) -> &'a TChip,
-    ) -> Self {
-        let inputs = [0; NINPUT].map(|_| UserInput::new(&alloc));
-        let input_struct =
-            TFam::StructuredInput::from_flat(inputs.map(|in_| Input::UserInput(in_)));
-        let chip = new_fn(&alloc, input_struct);
-        let outputs = chip.get_out(alloc).to_flat().map(|out| Output::new(out));
+    ) -> Result<Self, Error> {
+        let unconnected = UNCONNECTED.with(Cell::get);
+        let inputs = [0; NINPUT].map(|_| UserInput::new(alloc));
+        let input_struct = TFam::StructuredInput::from_flat(inputs.map(Input::UserInput));
+        let chip = new_fn(alloc, input_struct);
+        let outputs = chip.get_out(alloc).to_flat().map(Output::new);
+
+        // an input named "clock" is the clock by convention
+        let clock = Self::input_names().iter().position(|name| name == "clock");
+        let reset_name = TFam::RESET_INPUT.unwrap_or("reset");
+        let reset = Self::input_names()
+            .iter()
+            .position(|name| name == reset_name);
+
         let machine = Machine {
             inputs,
             outputs,
+            clock,
+            clock_domains: Self::default_clock_domains(),
+            reset,
+            timing: Timing::ZeroDelay,
+            nands: vec![],
             iteration: 0,
+            trace: None,
+            vcd: None,
+            watches: vec![],
+            cycle: 0,
             phantom_data: PhantomData,
         };
-        machine
+        // evaluation assumes every node is connected, so check that up front rather
+        // than panicking part way through a process. Walking large graphs is slow, so
+        // only go looking for the culprit if building the chip left anything unconnected
+        if UNCONNECTED.with(Cell::get) != unconnected {
+            machine.validate()?;
+        }
+        Ok(machine)
+    }
+
+    // builds the machine with ids numbered from `ids` rather than the global counters
+    pub fn new_in<TChip: SizedChip<'a, TFam, NOUT, NINPUT>>(
+        alloc: &'a Bump,
+        ids: &IdScope,
+        new_fn: fn(&'a Bump, TFam::StructuredInput<Input<'a>>) -> &'a TChip,
+    ) -> Result<Self, Error> {
+        ids.enter(|| Self::new(alloc, new_fn))
+    }
+
+    // unknown values can only come out of a two-valued machine after `set_unknown()`,
+    // and read as false
+    pub fn process(
+        &mut self,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        self.process_levels(invert(input.to_flat(), Self::input_active_low()))
+    }
+
+    // processes the levels on the input wires, which active low inputs have already
+    // been inverted in to
+    pub(crate) fn process_levels(
+        &mut self,
+        levels: [bool; NINPUT],
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = TFam::StructuredInput::from_flat(levels.map(Logic::from));
+        let res = self.process_logic(input)?.to_flat();
+        Ok(TFam::StructuredOutput::from_flat(invert(
+            res.map(|value| value == Logic::High),
+            Self::output_active_low(),
+        )))
+    }
+
+    fn input_active_low() -> [bool; NINPUT] {
+        TFam::StructuredInput::<bool>::active_low()
+    }
+
+    fn output_active_low() -> [bool; NOUT] {
+        TFam::StructuredOutput::<bool>::active_low()
+    }
+
+    pub fn input_names() -> [String; NINPUT] {
+        TFam::StructuredInput::<bool>::flat_names()
+    }
+
+    pub fn output_names() -> [String; NOUT] {
+        TFam::StructuredOutput::<bool>::flat_names()
+    }
+
+    // Name based access for interactive tools, using the flattened names above. Inputs
+    // set this way take effect at the next `update()`, and outputs read the values
+    // from the last process
+    pub fn set_input(&mut self, name: &str, value: bool) -> Result<(), Error> {
+        let position = Self::input_names().iter().position(|input| input == name);
+        let position = position.ok_or_else(|| Error::UnknownInput(name.to_owned()))?;
+        self.inputs[position].set(value != Self::input_active_low()[position]);
+        Ok(())
+    }
+
+    // the ids of the inputs, in the order of their names, which tells them apart from
+    // the constants chips are built with
+    pub fn input_ids(&self) -> [u32; NINPUT] {
+        self.inputs.map(|in_| in_.id)
+    }
+
+    pub fn get_output(&self, name: &str) -> Result<bool, Error> {
+        let position = Self::output_names()
+            .iter()
+            .position(|output| output == name);
+        let position = position.ok_or_else(|| Error::UnknownOutput(name.to_owned()))?;
+        let level = self.outputs[position].output.inner.value.get() == Logic::High;
+        Ok(level != Self::output_active_low()[position])
+    }
+
+    // the levels on the wires, which active low ports don't invert
+    pub fn inputs(&self) -> Vec<(String, Logic)> {
+        Self::input_names()
+            .into_iter()
+            .zip(self.inputs.map(|in_| in_.value.get()))
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<(String, Logic)> {
+        Self::output_names()
+            .into_iter()
+            .zip(self.outputs.iter().map(|out| out.output.inner.value.get()))
+            .collect()
+    }
+
+    // processes the inputs as they currently are
+    pub fn update(&mut self) -> Result<TFam::StructuredOutput<Logic>, Error> {
+        let inputs = self.inputs.map(|in_| in_.value.get());
+        self.process_logic(TFam::StructuredInput::from_flat(inputs))
+    }
+
+    pub fn set_clock(&mut self, name: &str) -> Result<(), Error> {
+        let position = Self::input_names().iter().position(|input| input == name);
+        self.clock = Some(position.ok_or_else(|| Error::UnknownInput(name.to_owned()))?);
+        Ok(())
+    }
+
+    pub fn get_clock(&self) -> Option<String> {
+        self.clock.map(|i| Self::input_names()[i].clone())
+    }
+
+    // drives the clock high, ignoring whatever the clock field of `input` is set to
+    pub fn tick(
+        &mut self,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        self.process_with_clock(input, true)
+    }
+
+    // drives the clock low. Flip-flops built from master-slave latches update here
+    pub fn tock(
+        &mut self,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        self.process_with_clock(input, false)
+    }
+
+    // a full clock cycle, returning the outputs after the tock
+    pub fn cycle(
+        &mut self,
+        input: impl Into<TFam::StructuredInput<bool>>,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let input = input.into();
+        let flat_input = input.to_flat();
+        self.tick(TFam::StructuredInput::from_flat(flat_input))?;
+        self.tock(TFam::StructuredInput::from_flat(flat_input))
+    }
+
+    // A full clock cycle, settling after each edge and returning the settled outputs
+    // after the tock. A single zero delay pass reads feedback from before the pass, so a
+    // chip whose state feeds back in to the logic working out its next state, like a
+    // counter or a CPU, needs settling before an edge has taken effect. Fails like
+    // `settle()` if either edge takes more than `max_iters` passes to settle
+    pub fn cycle_settled(
+        &mut self,
+        input: impl Into<TFam::StructuredInput<bool>>,
+        max_iters: u32,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let flat_input = input.into().to_flat();
+        self.tick(TFam::StructuredInput::from_flat(flat_input))?;
+        self.settle(max_iters)?;
+        self.tock(TFam::StructuredInput::from_flat(flat_input))?;
+        self.settle(max_iters)?;
+        self.process_with_clock(TFam::StructuredInput::from_flat(flat_input), false)
+    }
+
+    fn process_with_clock(
+        &mut self,
+        input: TFam::StructuredInput<bool>,
+        level: bool,
+    ) -> Result<TFam::StructuredOutput<bool>, Error> {
+        let clock = self.clock.ok_or(Error::NoClock)?;
+        let mut flat_input = input.to_flat();
+        flat_input[clock] = level;
+        self.process(TFam::StructuredInput::from_flat(flat_input))
+    }
+
+    pub fn get_reset(&self) -> Option<String> {
+        self.reset.map(|i| Self::input_names()[i].clone())
+    }
+
+    // holds the reset input high for a clock cycle with every other input low. Chips
+    // without a reset input have their state cleared directly, back to the state they
+    // were built in
+    pub fn reset(&mut self) -> Result<(), Error> {
+        let Some(reset) = self.reset else {
+            self.set_state(|| Logic::Low);
+            return Ok(());
+        };
+        let mut flat_input = [false; NINPUT];
+        flat_input[reset] = true;
+        let input = TFam::StructuredInput::from_flat(flat_input);
+        match self.clock {
+            Some(_) => self.cycle(input)?,
+            None => self.process(input)?,
+        };
+        Ok(())
     }
 
-    pub fn process(&mut self, input: TFam::StructuredInput<bool>) -> TFam::StructuredOutput<bool> {
+    pub fn process_logic(
+        &mut self,
+        input: TFam::StructuredInput<Logic>,
+    ) -> Result<TFam::StructuredOutput<Logic>, Error> {
         let flat_input = input.to_flat();
+        let flat_output = match self.timing {
+            Timing::UnitDelay { .. } => self
+                .process_timed(TFam::StructuredInput::from_flat(flat_input))?
+                .outputs
+                .to_flat(),
+            Timing::ZeroDelay => self.process_zero_delay(flat_input)?,
+        };
+        self.record_step(flat_input, flat_output);
+        self.record_vcd(flat_input, flat_output);
+        self.check_watches();
+        Ok(TFam::StructuredOutput::from_flat(flat_output))
+    }
+
+    fn process_zero_delay(&mut self, flat_input: [Logic; NINPUT]) -> Result<[Logic; NOUT], Error> {
         for (in_, val) in self.inputs.iter().zip(flat_input) {
-            in_.set(val);
+            in_.set_logic(val);
         }
-        self.iteration += 1;
-        let mut res = [true; NOUT];
-        for (i, out) in (&self.outputs).iter().enumerate() {
+        self.evaluate()
+    }
+
+    fn evaluate(&mut self) -> Result<[Logic; NOUT], Error> {
+        // nodes start at iteration 0, so skip it on wraparound to avoid reading stale values
+        self.iteration = self.iteration.checked_add(1).unwrap_or(1);
+        let contention = CONTENTION.with(Cell::get);
+        let mut res = [Logic::Unknown; NOUT];
+        for (i, out) in self.outputs.iter().enumerate() {
             res[i] = out.output.process(self.iteration);
         }
-        TFam::StructuredOutput::from_flat(res)
+        if CONTENTION.with(Cell::get) != contention {
+            return Err(Error::BusContention(self.find_contended_buses()));
+        }
+        Ok(res)
+    }
+
+    // A single zero delay pass evaluates each NAND once, breaking feedback loops by
+    // reading the value a NAND had before the pass. Feedback circuits may need several
+    // passes before every NAND agrees with its inputs, so this re-evaluates the current
+    // inputs until no NAND changes. Fails with the NANDs still changing if that takes
+    // more than `max_iters` passes. Each pass processes the inputs as they are, so it's
+    // traced, recorded and watched like any other step. In the unit delay timing mode,
+    // settling is already part of processing
+    pub fn settle(&mut self, max_iters: u32) -> Result<TFam::StructuredOutput<Logic>, Error> {
+        if let Timing::UnitDelay { .. } = self.timing {
+            return self.update();
+        }
+
+        self.collect_nands();
+        let mut values = self.nand_values();
+        for _ in 0..max_iters {
+            let outputs = self.update()?;
+            let next = self.nand_values();
+            if next == values {
+                return Ok(outputs);
+            }
+            values = next;
+        }
+
+        // one more pass to find the NANDs which haven't settled
+        self.update()?;
+        let nodes = self
+            .nands
+            .iter()
+            .zip(values)
+            .filter(|(nand, value)| nand.value.get() != *value)
+            .map(|(nand, _)| nand.identifier)
+            .collect();
+        Err(Error::Oscillation {
+            steps: max_iters,
+            nodes,
+        })
+    }
+
+    // the set of NANDs is fixed once the machine is built, so collect them once rather
+    // than walking the graph on every step
+    pub(crate) fn collect_nands(&mut self) {
+        if !self.nands.is_empty() {
+            return;
+        }
+        let mut nands = vec![];
+        self.for_each_node(|node| {
+            if let GraphNode::Nand(nand) = node {
+                nands.push(nand);
+            }
+        });
+        self.nands = nands;
+    }
+
+    fn nand_values(&self) -> Vec<Logic> {
+        self.nands.iter().map(|nand| nand.value.get()).collect()
+    }
+
+    // puts every node in to the unknown state, as if the circuit had just been powered
+    // on. Latches keep reading unknown until they are set, which catches designs that
+    // depend on the initial state
+    pub fn set_unknown(&mut self) {
+        for in_ in self.inputs {
+            in_.set_logic(Logic::Unknown);
+        }
+        self.set_state(|| Logic::Unknown);
+    }
+
+    // gives every node a pseudo-random initial value derived from `seed`, so latches
+    // power up in an arbitrary but reproducible state. Running a design with a few
+    // different seeds shows whether it relies on everything starting low
+    pub fn randomize_state(&mut self, seed: u64) {
+        let mut rng = SplitMix64(seed);
+        self.set_state(|| Logic::from(rng.next() & 1 == 1));
+    }
+
+    // user inputs inside chips are constants, so they keep their values
+    fn set_state(&mut self, mut value: impl FnMut() -> Logic) {
+        self.for_each_node(|node| match node {
+            GraphNode::UserInput(_) | GraphNode::ChipInput(_) => {}
+            GraphNode::ChipOutput(out) => {
+                out.iteration.set(0);
+                out.value.set(value());
+            }
+            GraphNode::Nand(nand) => {
+                nand.iteration.set(0);
+                nand.value.set(value());
+            }
+            // a bus has no state of its own, it floats until it's next evaluated
+            GraphNode::SharedBus(bus) => {
+                bus.iteration.set(0);
+                bus.value.set(Logic::HighZ);
+            }
+            // nor does a lookup, it reads the table again when it's next evaluated
+            GraphNode::Lookup(lookup) => lookup.iteration.set(0),
+        });
+    }
+
+    fn find_contended_buses(&self) -> Vec<u32> {
+        let mut contended = vec![];
+        self.for_each_node(|node| match node {
+            GraphNode::SharedBus(bus) if bus.contended.get() => contended.push(bus.identifier),
+            _ => {}
+        });
+        contended.sort();
+        contended
+    }
+
+    // Loads the words of the machine's only lookup table, e.g. the program in a
+    // computer's ROM. Lookups read the new words from the next process
+    pub fn load_program(&self, words: &[u16]) -> Result<(), Error> {
+        let mut tables = AddressSet::default();
+        let mut table = None;
+        self.for_each_node(|node| {
+            if let GraphNode::Lookup(lookup) = node {
+                let found = lookup.get_table();
+                if tables.insert(found as *const LookupTable as usize) {
+                    table = Some(found);
+                }
+            }
+        });
+        match table {
+            Some(table) if tables.len() == 1 => {
+                table.load(words);
+                Ok(())
+            }
+            _ => Err(Error::ProgramMemory {
+                tables: tables.len(),
+            }),
+        }
+    }
+
+    pub fn find_chip_output(&self, id: u32) -> Option<&'a ChipOutput<'a>> {
+        let mut found = None;
+        self.for_each_node(|node| match node {
+            GraphNode::ChipOutput(out) if out.id == id => found = Some(out),
+            _ => {}
+        });
+        found
+    }
+
+    // every chip output reachable from the machine outputs, for reading many of them as
+    // the machine runs without walking the graph each time
+    pub fn chip_outputs(&self) -> Vec<&'a ChipOutput<'a>> {
+        let mut outputs = vec![];
+        self.for_each_node(|node| {
+            if let GraphNode::ChipOutput(out) = node {
+                outputs.push(out);
+            }
+        });
+        outputs
+    }
+
+    // The chip outputs at paths in the form `Netlist::node_path()` gives, found by walking
+    // down the chips on each path rather than building the netlist, so it works on
+    // machines too large to build one for. Siblings are only numbered in the netlist's
+    // order, so each chip on a path must be named or the only one with its label among
+    // its siblings
+    pub fn find_named_outputs(&self, paths: &[&str]) -> Result<Vec<&'a ChipOutput<'a>>, Error> {
+        let mut top = AddressSet::default();
+        let top = self
+            .outputs
+            .iter()
+            .map(|out| out.output.parent)
+            .filter(|&chip| top.insert(chip_address(chip)))
+            .collect::<Vec<_>>();
+        // the children of each chip walked through, as several paths share most of theirs
+        let mut children = HashMap::<usize, Vec<&'a dyn Chip<'a>>>::new();
+        paths
+            .iter()
+            .map(|&path| {
+                let unknown = || Error::UnknownNode(path.to_owned());
+                let (chips, label) = path.rsplit_once('/').ok_or_else(unknown)?;
+                let mut siblings = &top;
+                let mut chip = None;
+                for name in chips.split('/') {
+                    let found = find_child(siblings, name).ok_or_else(unknown)?;
+                    siblings = children
+                        .entry(chip_address(found))
+                        .or_insert_with(|| found.get_children());
+                    chip = Some(found);
+                }
+                chip.and_then(|chip| chip.get_outputs().iter().find(|out| out.label == label))
+                    .copied()
+                    .ok_or_else(unknown)
+            })
+            .collect()
+    }
+
+    // visits every node reachable from the machine outputs once. The graph may contain
+    // cycles and shared nodes, so we keep track of visited nodes by address
+    fn for_each_node(&self, mut f: impl FnMut(GraphNode<'a>)) {
+        let mut visited = AddressSet::default();
+        let mut stack = self
+            .outputs
+            .iter()
+            .map(|out| GraphNode::ChipOutput(out.output.inner))
+            .collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node.address()) {
+                continue;
+            }
+            f(node);
+            match node {
+                GraphNode::UserInput(_) => {}
+                GraphNode::ChipInput(in_) => stack.push(in_.in_.into()),
+                GraphNode::ChipOutput(out) => stack.extend(out.out.get().map(GraphNode::from)),
+                GraphNode::Nand(nand) => stack.extend(
+                    [nand.in1.get(), nand.in2.get()]
+                        .into_iter()
+                        .flatten()
+                        .map(GraphNode::from),
+                ),
+                GraphNode::SharedBus(bus) => stack.extend(
+                    bus.get_drivers()
+                        .iter()
+                        .flat_map(|driver| [driver.data, driver.enable])
+                        .map(GraphNode::from),
+                ),
+                GraphNode::Lookup(lookup) => {
+                    stack.extend(lookup.get_address().iter().map(|&in_| GraphNode::from(in_)))
+                }
+            }
+        }
+    }
+}
+
+fn chip_address<'a>(chip: &'a dyn Chip<'a>) -> usize {
+    chip as *const dyn Chip<'a> as *const () as usize
+}
+
+// the sibling going by the name, which is either its instance name or, when no sibling
+// shares its label, the label numbered the way `Netlist::chip_names()` numbers the first
+fn find_child<'a>(siblings: &[&'a dyn Chip<'a>], name: &str) -> Option<&'a dyn Chip<'a>> {
+    if let Some(&named) = siblings.iter().find(|chip| chip.get_name() == Some(name)) {
+        return Some(named);
+    }
+    let first_name = |label: &str| {
+        if label.ends_with(|c: char| c.is_ascii_digit()) {
+            format!("{label}_0")
+        } else {
+            format!("{label}0")
+        }
+    };
+    let unnamed = siblings.iter().filter(|chip| chip.get_name().is_none());
+    let &found = unnamed
+        .clone()
+        .find(|chip| first_name(chip.get_label()) == name)?;
+    let shared = unnamed
+        .filter(|chip| chip.get_label() == found.get_label())
+        .count();
+    (shared == 1).then_some(found)
+}
+
+// Addresses are already unique, so a cheap multiplicative hash is plenty. The default
+// SipHash dominates the time taken to walk graphs the size of Ram16k
+#[derive(Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u8(*byte);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_usize(i.into());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        let hash = (self.0 ^ i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        // fold the well mixed high bits in to the low bits used for bucket selection
+        self.0 = hash ^ (hash >> 32);
+    }
+}
+
+type AddressSet = HashSet<usize, BuildHasherDefault<AddressHasher>>;
+
+// a tiny PRNG, good enough for picking initial states. See
+// https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum GraphNode<'a> {
+    UserInput(&'a UserInput),
+    ChipInput(&'a ChipInput<'a>),
+    ChipOutput(&'a ChipOutput<'a>),
+    Nand(&'a Nand<'a>),
+    SharedBus(&'a SharedBus<'a>),
+    Lookup(&'a Lookup<'a>),
+}
+
+impl GraphNode<'_> {
+    fn address(&self) -> usize {
+        match self {
+            GraphNode::UserInput(in_) => *in_ as *const _ as usize,
+            GraphNode::ChipInput(in_) => *in_ as *const _ as usize,
+            GraphNode::ChipOutput(out) => *out as *const _ as usize,
+            GraphNode::Nand(nand) => *nand as *const _ as usize,
+            GraphNode::SharedBus(bus) => *bus as *const _ as usize,
+            GraphNode::Lookup(lookup) => *lookup as *const _ as usize,
+        }
+    }
+}
+
+impl<'a> From<Input<'a>> for GraphNode<'a> {
+    fn from(val: Input<'a>) -> Self {
+        match val {
+            Input::UserInput(in_) => GraphNode::UserInput(in_),
+            Input::ChipOutput(out) => GraphNode::ChipOutput(out.inner),
+            Input::ChipInput(in_) => GraphNode::ChipInput(in_),
+            Input::NandInput(nand) => GraphNode::Nand(nand),
+            Input::SharedBus(bus) => GraphNode::SharedBus(bus),
+            Input::Lookup(lookup) => GraphNode::Lookup(lookup),
+        }
+    }
+}
+
+impl<'a> From<ChipOutputType<'a>> for GraphNode<'a> {
+    fn from(val: ChipOutputType<'a>) -> Self {
+        match val {
+            ChipOutputType::ChipOutput(out) => GraphNode::ChipOutput(out.inner),
+            ChipOutputType::NandOutput(nand) => GraphNode::Nand(nand),
+            ChipOutputType::ChipInput(in_) => GraphNode::ChipInput(in_),
+            ChipOutputType::SharedBus(bus) => GraphNode::SharedBus(bus),
+            ChipOutputType::Lookup(lookup) => GraphNode::Lookup(lookup),
+        }
+    }
+}
+
+// Owns the arena that machines are built in. Building a new machine resets the arena,
+// so the same chip can be rebuilt in a loop (e.g. in property tests) without memory
+// growing. The borrow checker ensures the previous machine is gone before a rebuild.
+#[derive(Default)]
+pub struct MachineBuilder {
+    alloc: Bump,
+}
+
+impl MachineBuilder {
+    pub fn new() -> Self {
+        MachineBuilder { alloc: Bump::new() }
+    }
+
+    pub fn build<
+        'a,
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        TChip: SizedChip<'a, TFam, NOUT, NINPUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &'a mut self,
+        new_fn: fn(&'a Bump, TFam::StructuredInput<Input<'a>>) -> &'a TChip,
+    ) -> Result<Machine<'a, TFam, NINPUT, NOUT>, Error> {
+        self.alloc.reset();
+        Machine::new(&self.alloc, new_fn)
+    }
+
+    pub fn reset(&mut self) {
+        self.alloc.reset();
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.alloc.allocated_bytes()
     }
 }
 
@@ -75,13 +772,13 @@ impl<'a> Output<'a> {
         static COUNTER: AtomicU32 = AtomicU32::new(0);
         Output {
             output,
-            identifier: COUNTER.fetch_add(1, Ordering::Relaxed),
-        } // FIXME: don't wraparound
+            identifier: ids::next_id("Output", &COUNTER),
+        }
     }
 }
 
 pub struct UserInput {
-    value: Cell<bool>,
+    value: Cell<Logic>,
     pub id: u32,
 }
 
@@ -93,19 +790,23 @@ impl UserInput {
     pub fn from(alloc: &Bump, val: bool) -> &Self {
         static COUNTER: AtomicU32 = AtomicU32::new(0);
         alloc.alloc(UserInput {
-            value: Cell::new(val),
-            id: COUNTER.fetch_add(1, Ordering::Relaxed),
+            value: Cell::new(val.into()),
+            id: ids::next_id("UserInput", &COUNTER),
         })
     }
 
     pub fn set(&self, value: bool) {
+        self.value.set(value.into());
+    }
+
+    pub fn set_logic(&self, value: Logic) {
         self.value.set(value);
     }
 }
 
-impl<'a> Into<Input<'a>> for &'a UserInput {
-    fn into(self) -> Input<'a> {
-        Input::UserInput(self)
+impl<'a> From<&'a UserInput> for Input<'a> {
+    fn from(val: &'a UserInput) -> Self {
+        Input::UserInput(val)
     }
 }
 
@@ -115,15 +816,31 @@ pub enum Input<'a> {
     ChipOutput(&'a ChipOutputWrapper<'a>),
     ChipInput(&'a ChipInput<'a>),
     NandInput(&'a Nand<'a>),
+    SharedBus(&'a SharedBus<'a>),
+    Lookup(&'a Lookup<'a>),
 }
 
 impl Input<'_> {
-    fn process(&self, iteration: u8) -> bool {
+    fn process(&self, iteration: u8) -> Logic {
         match self {
             Input::UserInput(in_) => in_.value.get(),
             Input::ChipOutput(out) => out.inner.process(iteration),
             Input::ChipInput(in_) => in_.process(iteration),
             Input::NandInput(nand) => nand.process(iteration),
+            Input::SharedBus(bus) => bus.process(iteration),
+            Input::Lookup(lookup) => lookup.process(iteration),
+        }
+    }
+
+    // the value from the last time this input was processed, without re-evaluating it
+    pub fn value(&self) -> Logic {
+        match self {
+            Input::UserInput(in_) => in_.value.get(),
+            Input::ChipOutput(out) => out.inner.value.get(),
+            Input::ChipInput(in_) => in_.in_.value(),
+            Input::NandInput(nand) => nand.value.get(),
+            Input::SharedBus(bus) => bus.value.get(),
+            Input::Lookup(lookup) => lookup.value.get(),
         }
     }
 }
@@ -131,8 +848,9 @@ impl Input<'_> {
 pub struct ChipInput<'a> {
     pub in_: Input<'a>,
     pub id: u32,
-    pub label: String, // note that this could instead be a &'static str
-                       // it would make the macros slightly more complex
+    // kept in the arena, as the arena never drops what's in it and a String's buffer
+    // would leak
+    pub label: &'a str,
 }
 
 impl<'a> ChipInput<'a> {
@@ -140,19 +858,19 @@ impl<'a> ChipInput<'a> {
         static COUNTER: AtomicU32 = AtomicU32::new(0);
         alloc.alloc(ChipInput {
             in_,
-            id: COUNTER.fetch_add(1, Ordering::Relaxed),
-            label,
+            id: ids::next_id("ChipInput", &COUNTER),
+            label: alloc.alloc_str(&label),
         })
     }
 
-    fn process(&self, iteration: u8) -> bool {
+    fn process(&self, iteration: u8) -> Logic {
         self.in_.process(iteration)
     }
 }
 
-impl<'a> Into<Input<'a>> for &'a ChipInput<'a> {
-    fn into(self) -> Input<'a> {
-        Input::ChipInput(self)
+impl<'a> From<&'a ChipInput<'a>> for Input<'a> {
+    fn from(val: &'a ChipInput<'a>) -> Self {
+        Input::ChipInput(val)
     }
 }
 
@@ -161,14 +879,31 @@ pub enum ChipOutputType<'a> {
     ChipOutput(&'a ChipOutputWrapper<'a>),
     NandOutput(&'a Nand<'a>),
     ChipInput(&'a ChipInput<'a>),
+    SharedBus(&'a SharedBus<'a>),
+    Lookup(&'a Lookup<'a>),
+}
+
+impl ChipOutputType<'_> {
+    // the value from the last time this output was processed, without re-evaluating it
+    pub fn value(&self) -> Logic {
+        match self {
+            ChipOutputType::ChipOutput(out) => out.inner.value(),
+            ChipOutputType::NandOutput(nand) => nand.value.get(),
+            ChipOutputType::ChipInput(in_) => in_.in_.value(),
+            ChipOutputType::SharedBus(bus) => bus.value.get(),
+            ChipOutputType::Lookup(lookup) => lookup.value.get(),
+        }
+    }
 }
 
 pub struct ChipOutput<'a> {
     out: Cell<Option<ChipOutputType<'a>>>,
-    value: Cell<bool>,
+    value: Cell<Logic>,
+    forced: Cell<Option<bool>>,
     iteration: Cell<u8>,
     pub id: u32,
-    pub label: String,
+    // kept in the arena, like a chip input's
+    pub label: &'a str,
 }
 
 pub struct ChipOutputWrapper<'a> {
@@ -176,21 +911,117 @@ pub struct ChipOutputWrapper<'a> {
     pub parent: &'a dyn Chip<'a>,
 }
 
-impl<'a> Into<Input<'a>> for &'a ChipOutputWrapper<'a> {
-    fn into(self) -> Input<'a> {
-        Input::ChipOutput(self)
+impl<'a> From<&'a ChipOutputWrapper<'a>> for Input<'a> {
+    fn from(val: &'a ChipOutputWrapper<'a>) -> Self {
+        Input::ChipOutput(val)
+    }
+}
+
+impl<'a> From<&'a ChipOutputWrapper<'a>> for ChipOutputType<'a> {
+    fn from(val: &'a ChipOutputWrapper<'a>) -> Self {
+        ChipOutputType::ChipOutput(val)
     }
 }
 
-impl<'a> Into<ChipOutputType<'a>> for &'a ChipOutputWrapper<'a> {
-    fn into(self) -> ChipOutputType<'a> {
-        ChipOutputType::ChipOutput(self)
+// passes a chip's input straight through to one of its outputs
+impl<'a> From<&'a ChipInput<'a>> for ChipOutputType<'a> {
+    fn from(val: &'a ChipInput<'a>) -> Self {
+        ChipOutputType::ChipInput(val)
     }
 }
 
 pub trait Chip<'a> {
     fn get_id(&self) -> String;
     fn get_label(&self) -> &'static str;
+    // what the chip does, taken from the chip function's doc comments
+    fn get_description(&self) -> &'static str {
+        ""
+    }
+    // the name given to this instance of the chip, e.g. by the `new_named()` generated
+    // by #[chip]
+    fn get_name(&self) -> Option<&'static str> {
+        None
+    }
+    // the names and widths of the chip's inputs and outputs, for front ends labelling
+    // the wires in to and out of it
+    fn get_ports(&self) -> ports::Description {
+        ports::Description::default()
+    }
+    // the subchips the chip was built from, see `child_chips()`
+    fn get_children(&self) -> Vec<&'a dyn Chip<'a>> {
+        vec![]
+    }
+    // the chip's outputs, in the order its output struct flattens them
+    fn get_outputs(&self) -> &'a [&'a ChipOutput<'a>] {
+        &[]
+    }
+}
+
+// The chips built directly inside the chip with these outputs, in the order they're
+// found. Paths back from the outputs are walked through the subchips, so chips which only
+// feed other subchips are found, and end at the chip's own inputs. NANDs are walked
+// through rather than counted as chips
+pub fn child_chips<'a>(outputs: &[&'a ChipOutput<'a>]) -> Vec<&'a dyn Chip<'a>> {
+    let driver = |out: ChipOutputType<'a>| match out {
+        ChipOutputType::ChipOutput(out) => Input::ChipOutput(out),
+        ChipOutputType::NandOutput(nand) => Input::NandInput(nand),
+        ChipOutputType::ChipInput(in_) => Input::ChipInput(in_),
+        ChipOutputType::SharedBus(bus) => Input::SharedBus(bus),
+        ChipOutputType::Lookup(lookup) => Input::Lookup(lookup),
+    };
+    let mut children: Vec<&'a dyn Chip<'a>> = vec![];
+    let mut visited = AddressSet::default();
+    // each input with how many chips below this one it's inside
+    let mut stack = outputs
+        .iter()
+        .filter_map(|out| out.out.get())
+        .map(|out| (driver(out), 0))
+        .collect::<Vec<_>>();
+    while let Some((input, depth)) = stack.pop() {
+        match input {
+            Input::UserInput(_) => {}
+            Input::ChipInput(in_) => {
+                if depth > 0 && visited.insert(in_ as *const ChipInput as usize) {
+                    stack.push((in_.in_, depth - 1));
+                }
+            }
+            Input::ChipOutput(out) => {
+                if depth == 0 && visited.insert(chip_address(out.parent)) {
+                    children.push(out.parent);
+                }
+                if visited.insert(out.inner as *const ChipOutput as usize) {
+                    stack.extend(out.inner.out.get().map(|out| (driver(out), depth + 1)));
+                }
+            }
+            Input::NandInput(nand) => {
+                if visited.insert(nand as *const Nand as usize) {
+                    stack.extend(
+                        [nand.in2.get(), nand.in1.get()]
+                            .into_iter()
+                            .flatten()
+                            .map(|in_| (in_, depth)),
+                    );
+                }
+            }
+            Input::SharedBus(bus) => {
+                if visited.insert(bus as *const SharedBus as usize) {
+                    stack.extend(
+                        bus.get_drivers()
+                            .iter()
+                            .rev()
+                            .flat_map(|driver| [driver.enable, driver.data])
+                            .map(|in_| (in_, depth)),
+                    );
+                }
+            }
+            Input::Lookup(lookup) => {
+                if visited.insert(lookup as *const Lookup as usize) {
+                    stack.extend(lookup.get_address().iter().rev().map(|&in_| (in_, depth)));
+                }
+            }
+        }
+    }
+    children
 }
 
 pub trait DefaultChip<
@@ -200,7 +1031,8 @@ pub trait DefaultChip<
     const NOUT: usize,
 >: Chip<'a>
 {
-    fn new(alloc: &'a Bump) -> &mut Self;
+    #[allow(clippy::mut_from_ref)] // the returned reference is a fresh arena allocation
+    fn new(alloc: &'a Bump) -> &'a mut Self;
     fn set_inputs(&'a self, alloc: &'a Bump, input: TDataFam::StructuredInput<Input<'a>>);
 }
 
@@ -216,7 +1048,7 @@ pub trait SizedChip<
     const NINPUT: usize,
 >: Chip<'a>
 {
-    fn get_out(&self, alloc: &'a Bump) -> TDataFam::StructuredOutput<&'a ChipOutputWrapper>;
+    fn get_out(&self, alloc: &'a Bump) -> TDataFam::StructuredOutput<&'a ChipOutputWrapper<'_>>;
 }
 
 impl<'a> ChipOutput<'a> {
@@ -230,16 +1062,23 @@ impl<'a> ChipOutput<'a> {
         out: Option<ChipOutputType<'a>>,
     ) -> &'a Self {
         static COUNTER: AtomicU32 = AtomicU32::new(0);
+        if out.is_none() {
+            UNCONNECTED.with(|count| count.set(count.get() + 1));
+        }
         alloc.alloc(ChipOutput {
             out: Cell::new(out),
             iteration: Cell::new(0),
-            value: Cell::new(false),
-            label,
-            id: COUNTER.fetch_add(1, Ordering::Relaxed),
+            value: Cell::new(Logic::Low),
+            forced: Cell::new(None),
+            label: alloc.alloc_str(&label),
+            id: ids::next_id("ChipOutput", &COUNTER),
         })
     }
 
     pub fn set_out(&self, out: ChipOutputType<'a>) {
+        if self.out.get().is_none() {
+            UNCONNECTED.with(|count| count.set(count.get() - 1));
+        }
         self.out.set(Some(out));
     }
 
@@ -250,14 +1089,41 @@ impl<'a> ChipOutput<'a> {
         self.out.get().unwrap()
     }
 
-    fn process(&self, iteration: u8) -> bool {
+    // forces the output to a value regardless of what drives it, until `release()` is called.
+    // Useful for "what if this wire were high?" experiments
+    pub fn force(&self, value: bool) {
+        self.forced.set(Some(value));
+    }
+
+    pub fn release(&self) {
+        self.forced.set(None);
+    }
+
+    pub fn get_forced(&self) -> Option<bool> {
+        self.forced.get()
+    }
+
+    // the value from the last process, which is the forced value while one is set
+    pub fn value(&self) -> Logic {
+        self.value.get()
+    }
+
+    fn process(&self, iteration: u8) -> Logic {
         if self.iteration.get() == iteration {
             return self.value.get();
         };
 
+        if let Some(forced) = self.forced.get() {
+            self.iteration.set(iteration);
+            self.value.set(forced.into());
+            return forced.into();
+        }
+
         let res = match self.get_out() {
             ChipOutputType::ChipOutput(out) => out.inner.process(iteration),
             ChipOutputType::NandOutput(nand) => nand.process(iteration),
+            ChipOutputType::SharedBus(bus) => bus.process(iteration),
+            ChipOutputType::Lookup(lookup) => lookup.process(iteration),
             ChipOutputType::ChipInput(in_) => in_.process(iteration),
         };
         self.iteration.set(iteration);
@@ -271,7 +1137,7 @@ impl<'a> ChipOutputWrapper<'a> {
         alloc.alloc(ChipOutputWrapper { inner, parent })
     }
 
-    fn process(&self, iteration: u8) -> bool {
+    fn process(&self, iteration: u8) -> Logic {
         self.inner.process(iteration)
     }
 }
@@ -280,7 +1146,7 @@ pub struct Nand<'a> {
     in1: Cell<Option<Input<'a>>>,
     in2: Cell<Option<Input<'a>>>,
     iteration: Cell<u8>,
-    value: Cell<bool>,
+    value: Cell<Logic>,
     pub identifier: u32,
 }
 
@@ -298,6 +1164,10 @@ impl<T> StructuredData<T, 2> for NandInputs<T> {
     fn to_flat(self) -> [T; 2] {
         [self.in1, self.in2]
     }
+
+    fn flat_names() -> [String; 2] {
+        ["in1".to_owned(), "in2".to_owned()]
+    }
 }
 
 pub struct NandOutputs<T> {
@@ -313,13 +1183,18 @@ impl<T> StructuredData<T, 1> for NandOutputs<T> {
     fn to_flat(self) -> [T; 1] {
         [self.out]
     }
+
+    fn flat_names() -> [String; 1] {
+        ["out".to_owned()]
+    }
 }
 
 impl<'a> Nand<'a> {
+    pub const GATE_COUNT: Option<usize> = Some(1);
+
     pub fn new(alloc: &'a Bump, in1: Input<'a>, in2: Input<'a>) -> &'a Self {
-        let nand: &mut Nand<'a> = DefaultChip::new(alloc);
-        nand.in1.set(Some(in1));
-        nand.in2.set(Some(in2));
+        let nand: &'a Nand<'a> = DefaultChip::new(alloc);
+        nand.set_inputs(alloc, NandInputs { in1, in2 });
         nand
     }
 
@@ -333,7 +1208,7 @@ impl<'a> Nand<'a> {
         [self.in1.get().unwrap(), self.in2.get().unwrap()]
     }
 
-    fn process(&self, iteration: u8) -> bool {
+    fn process(&self, iteration: u8) -> Logic {
         let in1 = match self.in1.get() {
             Some(x) => x,
             // should never get here
@@ -341,7 +1216,7 @@ impl<'a> Nand<'a> {
         };
         let in2 = match self.in2.get() {
             Some(x) => x,
-            // should never get here
+            // should never get here, Machine::new() checks every NAND is connected
             None => panic!("NAND must have two inputs before processing"),
         };
         if iteration == self.iteration.get() {
@@ -354,7 +1229,7 @@ impl<'a> Nand<'a> {
         self.iteration.set(iteration);
         let in1 = in1.process(iteration);
         let in2 = in2.process(iteration);
-        let res = !(in1 && in2);
+        let res = in1.nand(in2);
         self.value.set(res);
         res
     }
@@ -367,9 +1242,9 @@ impl StructuredDataFamily<2, 1> for NandInputsFamily {
     type StructuredOutput<T> = NandOutputs<T>;
 }
 
-impl<'a> Into<Input<'a>> for &'a Nand<'a> {
-    fn into(self) -> Input<'a> {
-        Input::NandInput(self)
+impl<'a> From<&'a Nand<'a>> for Input<'a> {
+    fn from(val: &'a Nand<'a>) -> Self {
+        Input::NandInput(val)
     }
 }
 
@@ -381,17 +1256,22 @@ impl<'a> Chip<'a> for Nand<'a> {
     fn get_label(&self) -> &'static str {
         "NAND"
     }
+
+    fn get_description(&self) -> &'static str {
+        "Outputs false only when both inputs are true. Every other chip is built from these"
+    }
 }
 
 impl<'a> DefaultChip<'a, NandInputsFamily, 2, 1> for Nand<'a> {
     fn new(alloc: &Bump) -> &mut Self {
         static COUNTER: AtomicU32 = AtomicU32::new(0);
+        UNCONNECTED.with(|count| count.set(count.get() + 1));
         alloc.alloc(Nand {
             in1: Cell::new(None),
             in2: Cell::new(None),
             iteration: Cell::new(0),
-            value: Cell::new(false),
-            identifier: COUNTER.fetch_add(1, Ordering::Relaxed),
+            value: Cell::new(Logic::Low),
+            identifier: ids::next_id("Nand", &COUNTER),
         })
     }
 
@@ -400,14 +1280,17 @@ impl<'a> DefaultChip<'a, NandInputsFamily, 2, 1> for Nand<'a> {
         _: &Bump,
         input: <NandInputsFamily as StructuredDataFamily<2, 1>>::StructuredInput<Input<'a>>,
     ) {
+        if self.in1.get().is_none() {
+            UNCONNECTED.with(|count| count.set(count.get() - 1));
+        }
         self.in1.set(Some(input.in1));
         self.in2.set(Some(input.in2));
     }
 }
 
-impl<'a> Into<ChipOutputType<'a>> for &'a Nand<'a> {
-    fn into(self) -> ChipOutputType<'a> {
-        ChipOutputType::NandOutput(self)
+impl<'a> From<&'a Nand<'a>> for ChipOutputType<'a> {
+    fn from(val: &'a Nand<'a>) -> Self {
+        ChipOutputType::NandOutput(val)
     }
 }
 
@@ -415,12 +1298,76 @@ pub trait ArrayInto<T> {
     fn ainto(self) -> T;
 }
 
-impl<'a, TIn: Into<TOut>, TOut, const N: usize> ArrayInto<[TOut; N]> for [TIn; N] {
+impl<TIn: Into<TOut>, TOut, const N: usize> ArrayInto<[TOut; N]> for [TIn; N] {
     fn ainto(self) -> [TOut; N] {
         self.map(|e| e.into())
     }
 }
 
+// Chains chips which each take the previous chip's `out` as their first input, e.g.
+// `pipeline!(alloc, x => Zeronum(zx.into()) => Negatenum(nx.into()))` zeroes then
+// negates `x`. The arguments after a chip's name are its remaining inputs, and the
+// last chip of the chain is returned
+#[macro_export]
+macro_rules! pipeline {
+    ($alloc:expr, $input:expr => $chip:ident($($arg:expr),* $(,)?) $(=> $next:ident($($next_arg:expr),* $(,)?))*) => {{
+        let chip = $chip::new($alloc, $input.into(), $($arg),*);
+        $(let chip = $next::new($alloc, chip.get_out($alloc).out.into(), $($next_arg),*);)*
+        chip
+    }};
+}
+
+// Wraps the inputs struct #[chip] generates, deriving serde's Serialize and Deserialize
+// on it when the serde feature is on, so test vectors can be read in to a machine's
+// inputs. The feature is hdl's rather than hdl-macro's so the derives are only generated
+// along with the serde they name. The literals are the path serde is found at and the
+// bound on the struct's parameter, and the fields' #[serde(...)] attributes are dropped
+// when the feature is off
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! inputs_struct {
+    (
+        $serde:literal, $bound:literal,
+        $(#[$attr:meta])*
+        struct $name:ident<T> { $($(#[$field_attr:meta])* $field:ident: $ty:ty),* $(,)? }
+    ) => {
+        $(#[$attr])*
+        #[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
+        #[serde(crate = $serde, bound = $bound)]
+        struct $name<T> { $($(#[$field_attr])* $field: $ty),* }
+    };
+}
+
+#[cfg(not(feature = "serde"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! inputs_struct {
+    (
+        $serde:literal, $bound:literal,
+        $(#[$attr:meta])*
+        struct $name:ident<T> { $($(#[$field_attr:meta])* $field:ident: $ty:ty),* $(,)? }
+    ) => {
+        $(#[$attr])*
+        struct $name<T> { $($field: $ty),* }
+    };
+}
+
+// The GATE_COUNT of a chip from those of the chips it builds, each paired with the
+// number of times it's built. The count is unknown if any of theirs is
+pub const fn sum_gate_counts(counts: &[(usize, Option<usize>)]) -> Option<usize> {
+    let mut total = 0;
+    let mut i = 0;
+    while i < counts.len() {
+        match counts[i] {
+            (times, Some(count)) => total += times * count,
+            (_, None) => return None,
+        }
+        i += 1;
+    }
+    Some(total)
+}
+
 pub fn create_subchip<
     'a,
     const NINPUT1: usize,
@@ -0,0 +1,59 @@
+use std::fmt::{self, Write};
+
+// A three-valued signal, plus the high impedance state of a bus nothing is driving.
+// `Unknown` models wires that haven't been driven yet, such as the initial state of a
+// latch, and propagates through gates unless another input decides the result on its
+// own. Gates read a floating input as unknown
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Logic {
+    Low,
+    High,
+    Unknown,
+    HighZ,
+}
+
+impl Logic {
+    pub fn nand(self, other: Logic) -> Logic {
+        match (self, other) {
+            // a low input forces a NAND high whatever the other input is
+            (Logic::Low, _) | (_, Logic::Low) => Logic::High,
+            (Logic::High, Logic::High) => Logic::Low,
+            _ => Logic::Unknown,
+        }
+    }
+
+    pub fn to_bool(self) -> Option<bool> {
+        match self {
+            Logic::Low => Some(false),
+            Logic::High => Some(true),
+            Logic::Unknown | Logic::HighZ => None,
+        }
+    }
+
+    // the character traces and the UI show the value as, see the Display impl
+    pub fn to_char(self) -> char {
+        match self {
+            Logic::Low => '0',
+            Logic::High => '1',
+            Logic::Unknown => 'X',
+            Logic::HighZ => 'Z',
+        }
+    }
+}
+
+// 0, 1, X for unknown or Z for high impedance
+impl fmt::Display for Logic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char(self.to_char())
+    }
+}
+
+impl From<bool> for Logic {
+    fn from(value: bool) -> Self {
+        if value {
+            Logic::High
+        } else {
+            Logic::Low
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{cell::Cell, sync::atomic::AtomicU32};
+
+use bumpalo::Bump;
+
+use crate::{ids, ChipOutputType, Input, Logic};
+
+// Words of constant storage, e.g. a ROM's. Built from gates, a ROM would need a mux
+// tree over every bit of every word, so instead the words are kept as data which
+// `Lookup`s read. The words can be replaced between processes, see
+// `Machine::load_program()`. They're kept in the arena, which never runs destructors,
+// so the table holds nothing which needs dropping
+pub struct LookupTable<'a> {
+    alloc: &'a Bump,
+    words: Cell<&'a [u16]>,
+    pub identifier: u32,
+}
+
+impl<'a> LookupTable<'a> {
+    pub fn new(alloc: &'a Bump, words: &[u16]) -> &'a Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        alloc.alloc(LookupTable {
+            alloc,
+            words: Cell::new(alloc.alloc_slice_copy(words)),
+            identifier: ids::next_id("LookupTable", &COUNTER),
+        })
+    }
+
+    pub fn load(&self, words: &[u16]) {
+        self.words.set(self.alloc.alloc_slice_copy(words));
+    }
+
+    pub fn words(&self) -> &'a [u16] {
+        self.words.get()
+    }
+}
+
+// One bit of the word in a table at the address on `address` (MSB first). Addresses
+// past the end of the table read low. Like a shared bus the lookup has no delay
+pub struct Lookup<'a> {
+    address: &'a [Input<'a>],
+    table: &'a LookupTable<'a>,
+    // the bit of each word this reads, with 0 as the MSB
+    bit: u32,
+    pub(crate) value: Cell<Logic>,
+    pub(crate) iteration: Cell<u8>,
+    pub identifier: u32,
+}
+
+impl<'a> Lookup<'a> {
+    pub fn new(
+        alloc: &'a Bump,
+        address: &[Input<'a>],
+        table: &'a LookupTable<'a>,
+        bit: u32,
+    ) -> &'a Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        assert!(bit < 16, "words only have 16 bits");
+        alloc.alloc(Lookup {
+            address: alloc.alloc_slice_copy(address),
+            table,
+            bit,
+            value: Cell::new(Logic::Low),
+            iteration: Cell::new(0),
+            identifier: ids::next_id("Lookup", &COUNTER),
+        })
+    }
+
+    // every bit of the word at the address
+    pub fn word(
+        alloc: &'a Bump,
+        address: &[Input<'a>],
+        table: &'a LookupTable<'a>,
+    ) -> [&'a Self; 16] {
+        std::array::from_fn(|bit| Lookup::new(alloc, address, table, bit as u32))
+    }
+
+    pub fn get_address(&self) -> &'a [Input<'a>] {
+        self.address
+    }
+
+    pub fn get_table(&self) -> &'a LookupTable<'a> {
+        self.table
+    }
+
+    pub(crate) fn process(&self, iteration: u8) -> Logic {
+        if iteration == self.iteration.get() {
+            return self.value.get();
+        }
+        self.iteration.set(iteration);
+        let res = self.resolve(|in_| in_.process(iteration));
+        self.value.set(res);
+        res
+    }
+
+    // the bit given a way of reading the address. Every address bit is read, so they're
+    // all up to date after a pass even once the value is known to be undetermined
+    pub(crate) fn resolve(&self, mut read: impl FnMut(Input<'a>) -> Logic) -> Logic {
+        let mut index = Some(0usize);
+        for in_ in self.address {
+            index = match (index, read(*in_)) {
+                (Some(index), Logic::Low) => index.checked_mul(2),
+                (Some(index), Logic::High) => index.checked_mul(2).map(|index| index + 1),
+                _ => None,
+            };
+        }
+        match index {
+            Some(index) => {
+                let word = self.table.words().get(index).copied().unwrap_or(0);
+                Logic::from(word & (0x8000 >> self.bit) != 0)
+            }
+            None => Logic::Unknown,
+        }
+    }
+}
+
+impl<'a> From<&'a Lookup<'a>> for Input<'a> {
+    fn from(val: &'a Lookup<'a>) -> Self {
+        Input::Lookup(val)
+    }
+}
+
+impl<'a> From<&'a Lookup<'a>> for ChipOutputType<'a> {
+    fn from(val: &'a Lookup<'a>) -> Self {
+        ChipOutputType::Lookup(val)
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{Error, Machine, SplitMix64, StructuredData, StructuredDataFamily};
+
+// machines with up to this many inputs are checked against every combination of them
+pub const EXHAUSTIVE_INPUTS: usize = 16;
+// the number of input combinations checked for wider machines
+pub const RANDOM_SAMPLES: usize = 1 << EXHAUSTIVE_INPUTS;
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    // Compares a combinational machine against a behavioural model of it, stopping at
+    // the first inputs they disagree on. Wide machines are checked on inputs drawn from
+    // a fixed seed, so failures are reproducible
+    pub fn check_model(
+        &mut self,
+        model: impl Fn(TFam::StructuredInput<bool>) -> TFam::StructuredOutput<bool>,
+    ) -> Result<(), Error> {
+        let mut rng = SplitMix64(0);
+        let count = if NINPUT <= EXHAUSTIVE_INPUTS {
+            1 << NINPUT
+        } else {
+            RANDOM_SAMPLES
+        };
+        for i in 0..count {
+            let inputs: [bool; NINPUT] = if NINPUT <= EXHAUSTIVE_INPUTS {
+                core::array::from_fn(|bit| i >> bit & 1 == 1)
+            } else {
+                core::array::from_fn(|_| rng.next() & 1 == 1)
+            };
+            let expected = model(TFam::StructuredInput::from_flat(inputs)).to_flat();
+            let found = self
+                .process(TFam::StructuredInput::from_flat(inputs))?
+                .to_flat();
+            if found != expected {
+                return Err(Error::ModelMismatch {
+                    inputs: inputs.to_vec(),
+                    expected: expected.to_vec(),
+                    found: found.to_vec(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+
+use crate::{
+    ChipInput, ChipOutput, ChipOutputType, ChipOutputWrapper, Error, Input, Logic, Lookup, Machine,
+    Nand, Output, SharedBus, StructuredDataFamily, UserInput,
+};
+
+// A flattened, reference-free copy of a machine's graph. Node ids are indices in to
+// `nodes` and are assigned in traversal order starting from the machine inputs, so
+// they're stable between two builds of the same chip, unlike the global counters
+// stored on the graph objects themselves.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Netlist {
+    pub nodes: Vec<NetlistNode>,
+    // edges point in the direction of data flow, from driver to consumer
+    pub edges: Vec<NetlistEdge>,
+    pub chips: Vec<NetlistChip>,
+    pub inputs: Vec<usize>,
+    pub outputs: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NodeKind {
+    UserInput,
+    ChipInput,
+    ChipOutput,
+    Nand,
+    SharedBus,
+    Lookup,
+    MachineOutput,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetlistNode {
+    pub kind: NodeKind,
+    pub label: String,
+    // the identifier stored on the graph object. Only unique within a node kind
+    pub source_id: u32,
+    // index in to `Netlist::chips` of the innermost chip containing this node
+    pub chip: Option<usize>,
+    pub value: Logic,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NetlistEdge {
+    pub from: usize,
+    pub to: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NetlistChip {
+    pub id: String,
+    pub label: &'static str,
+    pub description: &'static str,
+    // the name given to the instance, which stands in for the label in paths
+    pub name: Option<&'static str>,
+    pub parent: Option<usize>,
+}
+
+// a NAND input or chip output which was never connected to its driver
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Unconnected {
+    pub kind: NodeKind,
+    pub source_id: u32,
+    // the chip output's label, or which of the NAND's inputs is missing
+    pub label: String,
+    // labels of the chips containing the node, outermost first
+    pub path: Vec<&'static str>,
+}
+
+// a node driving more NAND inputs than the configured limit
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FanOut {
+    pub kind: NodeKind,
+    pub source_id: u32,
+    pub label: String,
+    pub path: Vec<&'static str>,
+    pub fan_out: usize,
+}
+
+impl Netlist {
+    pub fn from_outputs(inputs: &[&UserInput], outputs: &[Output]) -> Netlist {
+        NetlistBuilder::build(inputs, outputs).netlist
+    }
+
+    pub fn drivers(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.to == node)
+            .map(|edge| edge.from)
+    }
+
+    pub fn consumers(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.from == node)
+            .map(|edge| edge.to)
+    }
+
+    // the number of nodes each node is directly connected to, e.g. the sel input of Mux16
+    // drives the sel input of 16 Mux chips
+    pub fn fan_out(&self) -> Vec<usize> {
+        let mut fan_out = vec![0; self.nodes.len()];
+        for edge in &self.edges {
+            fan_out[edge.from] += 1;
+        }
+        fan_out
+    }
+
+    // the number of NAND inputs each node drives once wires are followed through chip
+    // boundaries, which is what matters electrically. A NOT gate counts twice as its
+    // NAND has both inputs tied together
+    pub fn gate_fan_out(&self) -> Vec<usize> {
+        let mut consumers = vec![vec![]; self.nodes.len()];
+        for edge in &self.edges {
+            consumers[edge.from].push(edge.to);
+        }
+        let mut gate_fan_out = vec![None; self.nodes.len()];
+        (0..self.nodes.len())
+            .map(|node| self.count_gate_inputs(node, &consumers, &mut gate_fan_out))
+            .collect()
+    }
+
+    fn count_gate_inputs(
+        &self,
+        node: usize,
+        consumers: &[Vec<usize>],
+        memo: &mut [Option<usize>],
+    ) -> usize {
+        if let Some(count) = memo[node] {
+            return count;
+        }
+        let count = consumers[node]
+            .iter()
+            .map(|&consumer| match self.nodes[consumer].kind {
+                // each tri-state buffer driving a bus is a gate input
+                NodeKind::Nand | NodeKind::SharedBus | NodeKind::Lookup => 1,
+                NodeKind::ChipInput | NodeKind::ChipOutput => {
+                    self.count_gate_inputs(consumer, consumers, memo)
+                }
+                NodeKind::UserInput | NodeKind::MachineOutput => 0,
+            })
+            .sum();
+        memo[node] = Some(count);
+        count
+    }
+
+    // labels, or instance names, of `chip` and the chips containing it, outermost first
+    pub fn chip_path(&self, chip: Option<usize>) -> Vec<&'static str> {
+        let mut path = vec![];
+        let mut chip = chip;
+        while let Some(index) = chip {
+            path.push(self.chips[index].name.unwrap_or(self.chips[index].label));
+            chip = self.chips[index].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    // a name for each chip which is unique among its siblings, made of its label and its
+    // position among siblings with the same label, e.g. "Mux2" or "Adder16_0". Unlike
+    // chip ids, these don't depend on how many chips were built before. Chips given an
+    // instance name go by it instead
+    pub fn chip_names(&self) -> Vec<String> {
+        let mut counts = HashMap::new();
+        self.chips
+            .iter()
+            .map(|chip| {
+                if let Some(name) = chip.name {
+                    return name.to_owned();
+                }
+                let count = counts.entry((chip.parent, chip.label)).or_insert(0);
+                let name = if chip.label.ends_with(|c: char| c.is_ascii_digit()) {
+                    format!("{}_{count}", chip.label)
+                } else {
+                    format!("{}{count}", chip.label)
+                };
+                *count += 1;
+                name
+            })
+            .collect()
+    }
+
+    // the chip names from the outermost chip down, followed by the node's label, e.g.
+    // "Alu0/Andorplus0/Adder16_0/out-3"
+    pub fn node_path(&self, node: usize) -> String {
+        let names = self.chip_names();
+        let mut path = vec![self.nodes[node].label.as_str()];
+        let mut chip = self.nodes[node].chip;
+        while let Some(index) = chip {
+            path.push(&names[index]);
+            chip = self.chips[index].parent;
+        }
+        path.reverse();
+        path.join("/")
+    }
+
+    // the chip output at a path in the form returned by `node_path()`
+    pub fn find_output(&self, path: &str) -> Option<usize> {
+        let (chips, label) = path.rsplit_once('/')?;
+        let names = self.chip_names();
+        let mut chip = None;
+        for name in chips.split('/') {
+            chip = Some(
+                (0..self.chips.len())
+                    .find(|&index| self.chips[index].parent == chip && names[index] == name)?,
+            );
+        }
+        self.nodes.iter().position(|node| {
+            node.kind == NodeKind::ChipOutput && node.chip == chip && node.label == label
+        })
+    }
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    pub fn netlist(&self) -> Netlist {
+        Netlist::from_outputs(&self.inputs, &self.outputs)
+    }
+
+    // the number of NANDs the outputs are built from. NANDs that drive no output aren't
+    // reachable from the machine and aren't counted
+    pub fn gate_count(&self) -> usize {
+        self.netlist()
+            .nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::Nand)
+            .count()
+    }
+
+    // reads the value an internal chip output had at the last process, so a chip can be
+    // debugged without adding outputs for it. See `Netlist::node_path()` for the format of
+    // the path
+    pub fn probe(&self, path: &str) -> Result<bool, Error> {
+        Ok(self.probe_logic(path)? == Logic::High)
+    }
+
+    pub fn probe_logic(&self, path: &str) -> Result<Logic, Error> {
+        Ok(self.find_output_at(path)?.value.get())
+    }
+
+    pub(crate) fn find_output_at(&self, path: &str) -> Result<&'a ChipOutput<'a>, Error> {
+        let netlist = self.netlist();
+        netlist
+            .find_output(path)
+            .and_then(|node| self.find_chip_output(netlist.nodes[node].source_id))
+            .ok_or_else(|| Error::UnknownNode(path.to_owned()))
+    }
+
+    // reports every NAND input and chip output reachable from the machine outputs which
+    // was left unconnected, e.g. by DefaultChip::new() without a call to set_inputs()
+    pub fn validate(&self) -> Result<(), Error> {
+        let builder = NetlistBuilder::build(&self.inputs, &self.outputs);
+        if builder.unconnected.is_empty() {
+            return Ok(());
+        }
+        let netlist = &builder.netlist;
+        let unconnected = builder
+            .unconnected
+            .iter()
+            .map(|(node, label)| {
+                let node = &netlist.nodes[*node];
+                Unconnected {
+                    kind: node.kind,
+                    source_id: node.source_id,
+                    label: label.clone(),
+                    path: netlist.chip_path(node.chip),
+                }
+            })
+            .collect();
+        Err(Error::Unconnected(unconnected))
+    }
+
+    // reports every NAND, shared bus and user input which drives more than `limit` NAND inputs,
+    // emulating the electrical limits of real gates
+    pub fn check_fan_out(&self, limit: usize) -> Result<(), Error> {
+        let netlist = self.netlist();
+        let exceeded = netlist
+            .gate_fan_out()
+            .into_iter()
+            .enumerate()
+            .filter(|&(node, fan_out)| {
+                matches!(
+                    netlist.nodes[node].kind,
+                    NodeKind::Nand | NodeKind::UserInput | NodeKind::SharedBus | NodeKind::Lookup
+                ) && fan_out > limit
+            })
+            .map(|(node, fan_out)| {
+                let node = &netlist.nodes[node];
+                FanOut {
+                    kind: node.kind,
+                    source_id: node.source_id,
+                    label: node.label.clone(),
+                    path: netlist.chip_path(node.chip),
+                    fan_out,
+                }
+            })
+            .collect::<Vec<_>>();
+        if exceeded.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::FanOut { limit, exceeded })
+        }
+    }
+}
+
+#[derive(Default)]
+struct NetlistBuilder {
+    netlist: Netlist,
+    // graph objects are keyed by address as ids are only unique per type
+    visited: HashMap<usize, usize>,
+    chips: HashMap<String, usize>,
+    // node index and label of everything found without a driver
+    unconnected: Vec<(usize, String)>,
+}
+
+impl NetlistBuilder {
+    fn build(inputs: &[&UserInput], outputs: &[Output]) -> NetlistBuilder {
+        let mut builder = NetlistBuilder::default();
+        for in_ in inputs {
+            let node = builder.visit_user_input(in_, None);
+            builder.netlist.inputs.push(node);
+        }
+        for out in outputs {
+            let from = builder.visit_output_wrapper(out.output, None);
+            let (node, _) = builder.add_node(
+                out as *const _ as usize,
+                NetlistNode {
+                    kind: NodeKind::MachineOutput,
+                    label: out.output.inner.label.to_owned(),
+                    source_id: out.identifier,
+                    chip: None,
+                    value: out.output.inner.value.get(),
+                },
+            );
+            builder.netlist.edges.push(NetlistEdge { from, to: node });
+            builder.netlist.outputs.push(node);
+        }
+        builder
+    }
+
+    // returns the node index and whether the node was newly added
+    fn add_node(&mut self, address: usize, node: NetlistNode) -> (usize, bool) {
+        if let Some(&existing) = self.visited.get(&address) {
+            return (existing, false);
+        }
+        let index = self.netlist.nodes.len();
+        self.netlist.nodes.push(node);
+        self.visited.insert(address, index);
+        (index, true)
+    }
+
+    fn add_chip(&mut self, chip: &ChipOutputWrapper<'_>, parent: Option<usize>) -> usize {
+        let id = chip.parent.get_id();
+        if let Some(&existing) = self.chips.get(&id) {
+            return existing;
+        }
+        let index = self.netlist.chips.len();
+        self.netlist.chips.push(NetlistChip {
+            id: id.clone(),
+            label: chip.parent.get_label(),
+            description: chip.parent.get_description(),
+            name: chip.parent.get_name(),
+            parent,
+        });
+        self.chips.insert(id, index);
+        index
+    }
+
+    fn visit_user_input(&mut self, in_: &UserInput, chip: Option<usize>) -> usize {
+        let (node, _) = self.add_node(
+            in_ as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::UserInput,
+                label: "INPUT".into(),
+                source_id: in_.id,
+                chip,
+                value: in_.value.get(),
+            },
+        );
+        node
+    }
+
+    fn visit_input(&mut self, in_: Input<'_>, chip: Option<usize>) -> usize {
+        match in_ {
+            Input::UserInput(in_) => self.visit_user_input(in_, chip),
+            Input::ChipOutput(out) => self.visit_output_wrapper(out, chip),
+            Input::ChipInput(in_) => self.visit_chip_input(in_, chip),
+            Input::NandInput(nand) => self.visit_nand(nand, chip),
+            Input::SharedBus(bus) => self.visit_shared_bus(bus, chip),
+            Input::Lookup(lookup) => self.visit_lookup(lookup, chip),
+        }
+    }
+
+    // `chip` is the chip which owns this input, the input's driver lives in its parent
+    fn visit_chip_input(&mut self, in_: &ChipInput<'_>, chip: Option<usize>) -> usize {
+        let (node, is_new) = self.add_node(
+            in_ as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::ChipInput,
+                label: in_.label.to_owned(),
+                source_id: in_.id,
+                chip,
+                value: in_.in_.value(),
+            },
+        );
+        if is_new {
+            let parent = chip.and_then(|chip| self.netlist.chips[chip].parent);
+            let from = self.visit_input(in_.in_, parent);
+            self.netlist.edges.push(NetlistEdge { from, to: node });
+        }
+        node
+    }
+
+    // `parent` is the chip containing the chip which owns this output
+    fn visit_output_wrapper(
+        &mut self,
+        out: &ChipOutputWrapper<'_>,
+        parent: Option<usize>,
+    ) -> usize {
+        let chip = self.add_chip(out, parent);
+        let (node, is_new) = self.add_node(
+            out.inner as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::ChipOutput,
+                label: out.inner.label.to_owned(),
+                source_id: out.inner.id,
+                chip: Some(chip),
+                value: out.inner.value.get(),
+            },
+        );
+        if !is_new {
+            return node;
+        }
+        let Some(driver) = out.inner.out.get() else {
+            self.unconnected.push((node, out.inner.label.to_owned()));
+            return node;
+        };
+        let from = match driver {
+            ChipOutputType::ChipOutput(out) => self.visit_output_wrapper(out, Some(chip)),
+            ChipOutputType::NandOutput(nand) => self.visit_nand(nand, Some(chip)),
+            ChipOutputType::ChipInput(in_) => self.visit_chip_input(in_, Some(chip)),
+            ChipOutputType::SharedBus(bus) => self.visit_shared_bus(bus, Some(chip)),
+            ChipOutputType::Lookup(lookup) => self.visit_lookup(lookup, Some(chip)),
+        };
+        self.netlist.edges.push(NetlistEdge { from, to: node });
+        node
+    }
+
+    fn visit_nand(&mut self, nand: &Nand<'_>, chip: Option<usize>) -> usize {
+        let (node, is_new) = self.add_node(
+            nand as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::Nand,
+                label: "NAND".into(),
+                source_id: nand.identifier,
+                chip,
+                value: nand.value.get(),
+            },
+        );
+        if is_new {
+            for (in_, label) in [(nand.in1.get(), "in1"), (nand.in2.get(), "in2")] {
+                match in_ {
+                    Some(in_) => {
+                        let from = self.visit_input(in_, chip);
+                        self.netlist.edges.push(NetlistEdge { from, to: node });
+                    }
+                    None => self.unconnected.push((node, label.to_owned())),
+                }
+            }
+        }
+        node
+    }
+
+    fn visit_shared_bus(&mut self, bus: &SharedBus<'_>, chip: Option<usize>) -> usize {
+        let (node, is_new) = self.add_node(
+            bus as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::SharedBus,
+                label: "BUS".into(),
+                source_id: bus.identifier,
+                chip,
+                value: bus.value.get(),
+            },
+        );
+        if is_new {
+            for driver in bus.get_drivers() {
+                for in_ in [driver.data, driver.enable] {
+                    let from = self.visit_input(in_, chip);
+                    self.netlist.edges.push(NetlistEdge { from, to: node });
+                }
+            }
+        }
+        node
+    }
+
+    fn visit_lookup(&mut self, lookup: &Lookup<'_>, chip: Option<usize>) -> usize {
+        let (node, is_new) = self.add_node(
+            lookup as *const _ as usize,
+            NetlistNode {
+                kind: NodeKind::Lookup,
+                label: "LOOKUP".into(),
+                source_id: lookup.identifier,
+                chip,
+                value: lookup.value.get(),
+            },
+        );
+        if is_new {
+            for &in_ in lookup.get_address() {
+                let from = self.visit_input(in_, chip);
+                self.netlist.edges.push(NetlistEdge { from, to: node });
+            }
+        }
+        node
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::Range;
+
+use crate::{Machine, StructuredData, StructuredDataFamily};
+
+// The shape of a machine's inputs and outputs, so front ends can build forms and
+// waveforms for any chip without knowing its structs
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Description {
+    pub inputs: Vec<Port>,
+    pub outputs: Vec<Port>,
+}
+
+// a field of the input or output struct
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Port {
+    pub name: String,
+    pub width: usize,
+    // whether the field is an array, which may have a single element
+    pub is_array: bool,
+    // indices of the field's elements in the flattened inputs or outputs
+    pub flat: Range<usize>,
+    // drawn with an inversion bubble. The machine's bool values are the asserted state
+    // rather than the level on the wire
+    pub active_low: bool,
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    pub fn describe(&self) -> Description {
+        Description {
+            inputs: ports(
+                TFam::StructuredInput::<bool>::field_info(),
+                &TFam::StructuredInput::<bool>::active_low(),
+            ),
+            outputs: ports(
+                TFam::StructuredOutput::<bool>::field_info(),
+                &TFam::StructuredOutput::<bool>::active_low(),
+            ),
+        }
+    }
+}
+
+// the ports of the fields given by StructuredData::field_info(), with the active low
+// flag of each flattened element. Active low is set per field, so a field's first
+// element speaks for it
+pub fn ports(field_info: Vec<(String, usize)>, active_low: &[bool]) -> Vec<Port> {
+    let mut start = 0;
+    field_info
+        .into_iter()
+        .map(|(name, arraylen)| {
+            let width = arraylen.max(1);
+            let port = Port {
+                name,
+                width,
+                is_array: arraylen > 0,
+                flat: start..start + width,
+                active_low: active_low.get(start).copied().unwrap_or(false),
+            };
+            start += width;
+            port
+        })
+        .collect()
+}
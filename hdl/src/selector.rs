@@ -0,0 +1,20 @@
+use bumpalo::Bump;
+
+use crate::{Input, UserInput};
+
+// A value for a group of select lines, like the address of a register in a RAM chip, so
+// the lines can be set by name rather than as an array of bools. Variants are numbered
+// in the order they're declared, and the number is put on the lines most significant
+// bit first like the rest of the buses in this repo. Derived by hdl_macro::Selector,
+// which also converts the type into its bits for a machine's inputs
+pub trait Selector<const N: usize>: Copy {
+    fn to_bits(self) -> [bool; N];
+    // None when the lines select a number with no variant
+    fn from_bits(bits: [bool; N]) -> Option<Self>;
+
+    // constant inputs selecting this variant, for a chip which always selects the same
+    // line
+    fn inputs(self, alloc: &Bump) -> [Input<'_>; N] {
+        self.to_bits().map(|bit| UserInput::from(alloc, bit).into())
+    }
+}
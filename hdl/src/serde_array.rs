@@ -0,0 +1,60 @@
+// Serde for the fields of the inputs structs #[chip] generates. Serde only implements its
+// traits for arrays of up to 32 elements, and chips take wider inputs, so each field is
+// read and written through `Field` instead, as a bool or nested sequences of them
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+pub trait Field: Sized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+impl Field for bool {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Serialize::serialize(self, serializer)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <bool as Deserialize>::deserialize(deserializer)
+    }
+}
+
+impl<F: Field, const N: usize> Field for [F; N] {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(Ser))
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elems = Vec::<De<F>>::deserialize(deserializer)?;
+        let len = elems.len();
+        let elems = elems.into_iter().map(|De(elem)| elem).collect::<Vec<_>>();
+        elems
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &format!("{N} elements").as_str()))
+    }
+}
+
+// the element of an array field, as serde sees it
+struct Ser<'f, F>(&'f F);
+
+impl<F: Field> Serialize for Ser<'_, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Field::serialize(self.0, serializer)
+    }
+}
+
+struct De<F>(F);
+
+impl<'de, F: Field> Deserialize<'de> for De<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        F::deserialize(deserializer).map(De)
+    }
+}
+
+// for #[serde(with = "...")]
+pub fn serialize<F: Field, S: Serializer>(field: &F, serializer: S) -> Result<S::Ok, S::Error> {
+    field.serialize(serializer)
+}
+
+pub fn deserialize<'de, F: Field, D: Deserializer<'de>>(deserializer: D) -> Result<F, D::Error> {
+    F::deserialize(deserializer)
+}
@@ -0,0 +1,117 @@
+use crate::{Error, GraphNode, Logic, Machine, StructuredDataFamily};
+
+// A snapshot of a machine's inputs and every NAND's value. Latches and flip-flops keep
+// their state in the values of NANDs on feedback loops; storing every NAND is simpler
+// than finding those loops and only costs two bits a gate. Nodes are recorded in the
+// order the graph is walked, so a snapshot can only be restored in to a machine built
+// from the same chip
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MachineState {
+    inputs: Vec<Logic>,
+    nands: Vec<Logic>,
+}
+
+const MAGIC: &[u8; 4] = b"N2OS";
+
+impl MachineState {
+    // the magic number, the input and NAND counts as little endian u32s, then the
+    // values packed four to a byte
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((self.inputs.len() as u32).to_le_bytes());
+        bytes.extend((self.nands.len() as u32).to_le_bytes());
+        let values = self.inputs.iter().chain(&self.nands).collect::<Vec<_>>();
+        bytes.extend(values.chunks(4).map(|chunk| {
+            chunk.iter().enumerate().fold(0, |byte, (i, value)| {
+                byte | logic_to_bits(**value) << (i * 2)
+            })
+        }));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MachineState, Error> {
+        let header = bytes.get(..12).ok_or(Error::InvalidState)?;
+        if &header[..4] != MAGIC {
+            return Err(Error::InvalidState);
+        }
+        let read_count = |range: std::ops::Range<usize>| {
+            u32::from_le_bytes(header[range].try_into().unwrap()) as usize
+        };
+        let (ninputs, nnands) = (read_count(4..8), read_count(8..12));
+        let packed = &bytes[12..];
+        let total = ninputs + nnands;
+        if packed.len() != total.div_ceil(4) {
+            return Err(Error::InvalidState);
+        }
+        let mut values = (0..total).map(|i| bits_to_logic(packed[i / 4] >> (i % 4 * 2)));
+        Ok(MachineState {
+            inputs: values.by_ref().take(ninputs).collect(),
+            nands: values.collect(),
+        })
+    }
+}
+
+fn logic_to_bits(value: Logic) -> u8 {
+    match value {
+        Logic::Low => 0,
+        Logic::High => 1,
+        Logic::Unknown => 2,
+        Logic::HighZ => 3,
+    }
+}
+
+fn bits_to_logic(bits: u8) -> Logic {
+    match bits & 0b11 {
+        0 => Logic::Low,
+        1 => Logic::High,
+        2 => Logic::Unknown,
+        _ => Logic::HighZ,
+    }
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    pub fn save_state(&self) -> MachineState {
+        let mut nands = vec![];
+        self.for_each_node(|node| {
+            if let GraphNode::Nand(nand) = node {
+                nands.push(nand.value.get());
+            }
+        });
+        MachineState {
+            inputs: self.inputs.iter().map(|in_| in_.value.get()).collect(),
+            nands,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &MachineState) -> Result<(), Error> {
+        let mut nands = vec![];
+        self.for_each_node(|node| {
+            if let GraphNode::Nand(nand) = node {
+                nands.push(nand);
+            }
+        });
+        if state.inputs.len() != NINPUT || state.nands.len() != nands.len() {
+            return Err(Error::StateMismatch {
+                expected: NINPUT + nands.len(),
+                found: state.inputs.len() + state.nands.len(),
+            });
+        }
+        for (in_, value) in self.inputs.iter().zip(&state.inputs) {
+            in_.set_logic(*value);
+        }
+        for (nand, value) in nands.into_iter().zip(&state.nands) {
+            nand.value.set(*value);
+        }
+        // cached values are stale, so everything is re-evaluated on the next process
+        self.for_each_node(|node| match node {
+            GraphNode::Nand(nand) => nand.iteration.set(0),
+            GraphNode::ChipOutput(out) => out.iteration.set(0),
+            GraphNode::SharedBus(bus) => bus.iteration.set(0),
+            GraphNode::Lookup(lookup) => lookup.iteration.set(0),
+            GraphNode::UserInput(_) | GraphNode::ChipInput(_) => {}
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    ChipOutput, ChipOutputType, Error, GraphNode, Input, Logic, Machine, SplitMix64,
+    StructuredData, StructuredDataFamily,
+};
+
+// machines with up to this many inputs are checked for hazards from every state
+pub const EXHAUSTIVE_HAZARD_INPUTS: usize = 16;
+// the number of states wider machines are checked from, each transition from a state
+// costing a unit delay evaluation
+pub const HAZARD_SAMPLES: usize = 1 << 8;
+
+// The default evaluator computes every output in a single zero-delay pass, which
+// hides the glitches a real circuit produces while its signals propagate. In the
+// unit delay model every NAND takes one time step to respond to its inputs, and
+// the circuit is stepped until no NAND changes value.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Timing {
+    #[default]
+    ZeroDelay,
+    UnitDelay {
+        max_steps: u32,
+    },
+}
+
+// the result of a unit delay evaluation
+pub struct Timed<T, const NOUT: usize> {
+    pub outputs: T,
+    // time steps taken for the circuit to become stable
+    pub steps: u32,
+    // the flattened outputs at every time step, starting with the state before any
+    // NAND had responded to the new inputs
+    pub waveform: Vec<[Logic; NOUT]>,
+}
+
+// an output which changed value more than once while settling after an input transition
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hazard {
+    pub output: String,
+    pub from: Vec<bool>,
+    pub to: Vec<bool>,
+    // the output's value at each time step
+    pub waveform: Vec<Logic>,
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        if let Timing::UnitDelay { .. } = timing {
+            self.collect_nands();
+        }
+    }
+
+    pub fn get_timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn process_timed(
+        &mut self,
+        input: TFam::StructuredInput<Logic>,
+    ) -> Result<Timed<TFam::StructuredOutput<Logic>, NOUT>, Error> {
+        let max_steps = match self.timing {
+            Timing::UnitDelay { max_steps } => max_steps,
+            Timing::ZeroDelay => return Err(Error::ZeroDelayTiming),
+        };
+        for (in_, val) in self.inputs.iter().zip(input.to_flat()) {
+            in_.set_logic(val);
+        }
+
+        let mut waveform = vec![self.output_wire_values()];
+        let mut changing = vec![];
+        for step in 0..max_steps {
+            // every NAND responds to the values its inputs had at the previous step
+            let next = self
+                .nands
+                .iter()
+                .map(|nand| {
+                    let [in1, in2] = nand.get_inputs();
+                    wire_value(in1).nand(wire_value(in2))
+                })
+                .collect::<Vec<_>>();
+            changing.clear();
+            for (nand, value) in self.nands.iter().zip(next) {
+                if nand.value.get() != value {
+                    changing.push(nand.identifier);
+                }
+                nand.value.set(value);
+            }
+            if changing.is_empty() {
+                self.update_output_caches();
+                let outputs = *waveform.last().unwrap();
+                return Ok(Timed {
+                    outputs: TFam::StructuredOutput::from_flat(outputs),
+                    steps: step,
+                    waveform,
+                });
+            }
+            waveform.push(self.output_wire_values());
+        }
+        Err(Error::Oscillation {
+            steps: max_steps,
+            nodes: changing,
+        })
+    }
+
+    // settles the machine at `from`, then reports every output which glitches while
+    // settling at `to`
+    pub fn find_hazards(
+        &mut self,
+        from: TFam::StructuredInput<bool>,
+        to: TFam::StructuredInput<bool>,
+    ) -> Result<Vec<Hazard>, Error> {
+        let from = from.to_flat();
+        let to = to.to_flat();
+        self.process_timed(TFam::StructuredInput::from_flat(from.map(Logic::from)))?;
+        let timed = self.process_timed(TFam::StructuredInput::from_flat(to.map(Logic::from)))?;
+
+        let hazards = Self::output_names()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, output)| {
+                let waveform = timed
+                    .waveform
+                    .iter()
+                    .map(|values| values[i])
+                    .collect::<Vec<_>>();
+                let changes = waveform
+                    .windows(2)
+                    .filter(|pair| pair[0] != pair[1])
+                    .count();
+                (changes > 1).then(|| Hazard {
+                    output,
+                    from: from.to_vec(),
+                    to: to.to_vec(),
+                    waveform,
+                })
+            })
+            .collect();
+        Ok(hazards)
+    }
+
+    // checks every transition where a single input changes. That's NINPUT * 2^NINPUT
+    // transitions, so wider machines are only checked from a sample of states drawn from
+    // a fixed seed, as Machine::check_model() does
+    pub fn find_single_input_hazards(&mut self) -> Result<Vec<Hazard>, Error> {
+        let mut rng = SplitMix64(0);
+        let count = if NINPUT <= EXHAUSTIVE_HAZARD_INPUTS {
+            1 << NINPUT
+        } else {
+            HAZARD_SAMPLES
+        };
+        let mut hazards = vec![];
+        for state in 0..count {
+            let from: [bool; NINPUT] = if NINPUT <= EXHAUSTIVE_HAZARD_INPUTS {
+                std::array::from_fn(|i| state >> i & 1 == 1)
+            } else {
+                std::array::from_fn(|_| rng.next() & 1 == 1)
+            };
+            for flipped in 0..NINPUT {
+                let mut to = from;
+                to[flipped] = !to[flipped];
+                hazards.extend(self.find_hazards(
+                    TFam::StructuredInput::from_flat(from),
+                    TFam::StructuredInput::from_flat(to),
+                )?);
+            }
+        }
+        Ok(hazards)
+    }
+
+    fn output_wire_values(&self) -> [Logic; NOUT] {
+        self.outputs
+            .each_ref()
+            .map(|out| output_wire_value(out.output.inner))
+    }
+
+    // keeps chip output values in step with the NANDs, for anything that inspects them
+    fn update_output_caches(&self) {
+        self.for_each_node(|node| {
+            if let GraphNode::ChipOutput(out) = node {
+                out.value.set(output_wire_value(out));
+            }
+        });
+    }
+}
+
+// chip inputs and outputs are plain wires, so their value is whatever drives them
+fn wire_value(in_: Input) -> Logic {
+    match in_ {
+        Input::UserInput(in_) => in_.value.get(),
+        Input::ChipOutput(out) => output_wire_value(out.inner),
+        Input::ChipInput(in_) => wire_value(in_.in_),
+        Input::NandInput(nand) => nand.value.get(),
+        // contention is expected while a circuit settles, so it isn't reported here
+        Input::SharedBus(bus) => bus.resolve(wire_value).0,
+        Input::Lookup(lookup) => lookup.resolve(wire_value),
+    }
+}
+
+fn output_wire_value(out: &ChipOutput) -> Logic {
+    if let Some(forced) = out.forced.get() {
+        return forced.into();
+    }
+    match out.get_out() {
+        ChipOutputType::ChipOutput(out) => output_wire_value(out.inner),
+        ChipOutputType::NandOutput(nand) => nand.value.get(),
+        ChipOutputType::ChipInput(in_) => wire_value(in_.in_),
+        ChipOutputType::SharedBus(bus) => bus.resolve(wire_value).0,
+        ChipOutputType::Lookup(lookup) => lookup.resolve(wire_value),
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Error, Logic, Machine, StructuredData, StructuredDataFamily};
+
+// A recording of every input a machine processed and the outputs it produced. Clock
+// steps from `tick()`, `tock()` and `cycle()` go through `process()`, so they're
+// recorded with the clock level they drove. Replaying a trace against a freshly built
+// machine checks a refactored chip still behaves the same, cycle for cycle
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Trace {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceStep {
+    pub inputs: Vec<Logic>,
+    pub outputs: Vec<Logic>,
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    // records every following process in to a new trace, replacing any in progress
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace {
+            inputs: Self::input_names().to_vec(),
+            outputs: Self::output_names().to_vec(),
+            steps: vec![],
+        });
+    }
+
+    pub fn stop_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub(crate) fn record_step(&mut self, inputs: [Logic; NINPUT], outputs: [Logic; NOUT]) {
+        if let Some(trace) = &mut self.trace {
+            trace.steps.push(TraceStep {
+                inputs: inputs.to_vec(),
+                outputs: outputs.to_vec(),
+            });
+        }
+    }
+
+    // processes each step of the trace, stopping at the first whose outputs differ
+    pub fn replay(&mut self, trace: &Trace) -> Result<(), Error> {
+        if trace.inputs != Self::input_names() || trace.outputs != Self::output_names() {
+            return Err(Error::InvalidTrace(
+                "trace was recorded from a machine with different inputs or outputs".into(),
+            ));
+        }
+        for (i, step) in trace.steps.iter().enumerate() {
+            let inputs = step.inputs.as_slice().try_into().map_err(|_| {
+                Error::InvalidTrace(format!("step {i} has the wrong number of inputs"))
+            })?;
+            let outputs = self
+                .process_logic(TFam::StructuredInput::from_flat(inputs))?
+                .to_flat();
+            if outputs != step.outputs.as_slice() {
+                return Err(Error::TraceMismatch {
+                    step: i,
+                    expected: step.outputs.clone(),
+                    found: outputs.to_vec(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// One line naming the inputs, one naming the outputs, then a line per step with the
+// input and output values as strings of 0, 1, X (unknown) and Z (high impedance)
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "inputs {}", self.inputs.join(" "))?;
+        writeln!(f, "outputs {}", self.outputs.join(" "))?;
+        for step in &self.steps {
+            let inputs = step.inputs.iter().map(|value| value.to_char());
+            let outputs = step.outputs.iter().map(|value| value.to_char());
+            writeln!(
+                f,
+                "{} {}",
+                inputs.collect::<String>(),
+                outputs.collect::<String>()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        let mut names = |prefix: &str| -> Result<Vec<String>, Error> {
+            let line = lines.next().unwrap_or_default();
+            let names = line
+                .strip_prefix(prefix)
+                .ok_or_else(|| Error::InvalidTrace(format!("expected a line of {prefix}")))?;
+            Ok(names.split_whitespace().map(str::to_owned).collect())
+        };
+        let inputs = names("inputs")?;
+        let outputs = names("outputs")?;
+        let steps = lines
+            .enumerate()
+            .map(|(i, line)| {
+                let invalid = || Error::InvalidTrace(format!("step {i} is malformed"));
+                let (step_inputs, step_outputs) = line.split_once(' ').ok_or_else(invalid)?;
+                let parse = |values: &str, len| {
+                    let values = values.chars().map(char_logic).collect::<Option<Vec<_>>>();
+                    values
+                        .filter(|values| values.len() == len)
+                        .ok_or_else(invalid)
+                };
+                Ok(TraceStep {
+                    inputs: parse(step_inputs, inputs.len())?,
+                    outputs: parse(step_outputs, outputs.len())?,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Trace {
+            inputs,
+            outputs,
+            steps,
+        })
+    }
+}
+
+fn char_logic(c: char) -> Option<Logic> {
+    match c {
+        '0' => Some(Logic::Low),
+        '1' => Some(Logic::High),
+        'X' => Some(Logic::Unknown),
+        'Z' => Some(Logic::HighZ),
+        _ => None,
+    }
+}
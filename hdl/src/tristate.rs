@@ -0,0 +1,107 @@
+use std::{cell::Cell, sync::atomic::AtomicU32};
+
+use bumpalo::Bump;
+
+use crate::{ids, ChipOutputType, Input, Logic};
+
+thread_local! {
+    // bumped whenever a bus is evaluated with conflicting drivers, so machines only need
+    // to go looking for the culprit when the count changes
+    pub(crate) static CONTENTION: Cell<u64> = const { Cell::new(0) };
+}
+
+// a tri-state buffer driving a shared bus. The bus only sees `data` while `enable` is high
+#[derive(Copy, Clone)]
+pub struct TriStateDriver<'a> {
+    pub data: Input<'a>,
+    pub enable: Input<'a>,
+}
+
+// A wire which several chips can drive through tri-state buffers, e.g. the data bus
+// of a memory-mapped I/O system. With no driver enabled the bus floats at HighZ, and
+// enabling drivers which disagree is reported as contention by `Machine::process()`.
+// Like chip inputs and outputs the bus itself has no delay
+pub struct SharedBus<'a> {
+    drivers: &'a [TriStateDriver<'a>],
+    pub(crate) value: Cell<Logic>,
+    pub(crate) iteration: Cell<u8>,
+    // whether the drivers disagreed the last time the bus was evaluated
+    pub(crate) contended: Cell<bool>,
+    pub identifier: u32,
+}
+
+impl<'a> SharedBus<'a> {
+    pub fn new(alloc: &'a Bump, drivers: &[TriStateDriver<'a>]) -> &'a Self {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        alloc.alloc(SharedBus {
+            drivers: alloc.alloc_slice_copy(drivers),
+            value: Cell::new(Logic::HighZ),
+            iteration: Cell::new(0),
+            contended: Cell::new(false),
+            identifier: ids::next_id("SharedBus", &COUNTER),
+        })
+    }
+
+    // a single tri-state buffer, whose output is high impedance while disabled
+    pub fn tri_state(alloc: &'a Bump, data: Input<'a>, enable: Input<'a>) -> &'a Self {
+        SharedBus::new(alloc, &[TriStateDriver { data, enable }])
+    }
+
+    pub fn get_drivers(&self) -> &'a [TriStateDriver<'a>] {
+        self.drivers
+    }
+
+    pub fn get_contended(&self) -> bool {
+        self.contended.get()
+    }
+
+    pub(crate) fn process(&self, iteration: u8) -> Logic {
+        if iteration == self.iteration.get() {
+            return self.value.get();
+        }
+        self.iteration.set(iteration);
+        let (res, contended) = self.resolve(|in_| in_.process(iteration));
+        if contended {
+            CONTENTION.with(|count| count.set(count.get() + 1));
+        }
+        self.contended.set(contended);
+        self.value.set(res);
+        res
+    }
+
+    // the bus value given a way of reading its drivers, and whether they're in contention
+    pub(crate) fn resolve(&self, mut read: impl FnMut(Input<'a>) -> Logic) -> (Logic, bool) {
+        let mut res = Logic::HighZ;
+        for driver in self.drivers {
+            let value = match read(driver.enable) {
+                Logic::Low => continue,
+                Logic::High => read(driver.data),
+                // a driver which might be enabled leaves the bus undetermined
+                Logic::Unknown | Logic::HighZ => Logic::Unknown,
+            };
+            // an enabled driver passing on another floating bus doesn't drive this one
+            if value == Logic::HighZ {
+                continue;
+            }
+            res = match (res, value) {
+                (Logic::HighZ, value) => value,
+                (Logic::Unknown, _) | (_, Logic::Unknown) => Logic::Unknown,
+                (current, value) if current == value => current,
+                _ => return (Logic::Unknown, true),
+            };
+        }
+        (res, false)
+    }
+}
+
+impl<'a> From<&'a SharedBus<'a>> for Input<'a> {
+    fn from(val: &'a SharedBus<'a>) -> Self {
+        Input::SharedBus(val)
+    }
+}
+
+impl<'a> From<&'a SharedBus<'a>> for ChipOutputType<'a> {
+    fn from(val: &'a SharedBus<'a>) -> Self {
+        ChipOutputType::SharedBus(val)
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use crate::{ChipOutput, Error, Logic, Machine, StructuredDataFamily};
+
+// A recording of a machine's inputs and outputs, and of chosen chip outputs, after
+// every process, written out as a Value Change Dump for waveform viewers like GTKWave.
+// Each process is one unit of time, so a clock cycle takes two. Signals are scoped by
+// the names `Netlist::chip_names()` gives the chips containing them, under a top
+// scope holding the machine's ports
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Vcd {
+    pub signals: Vec<VcdSignal>,
+    // the value of every signal after each process
+    pub steps: Vec<Vec<Logic>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VcdSignal {
+    // outermost first, starting with TOP_SCOPE
+    pub scope: Vec<String>,
+    pub name: String,
+}
+
+pub const TOP_SCOPE: &str = "top";
+
+pub(crate) struct VcdRecording<'a> {
+    outputs: Vec<&'a ChipOutput<'a>>,
+    vcd: Vcd,
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    // records the machine's ports and the chip outputs at `paths` over every following
+    // process, replacing any recording in progress. See `Netlist::node_path()` for the
+    // format of the paths
+    pub fn start_vcd(&mut self, paths: &[&str]) -> Result<(), Error> {
+        let port = |name: &String| VcdSignal {
+            scope: vec![TOP_SCOPE.into()],
+            name: name.clone(),
+        };
+        let mut signals = Self::input_names()
+            .iter()
+            .chain(&Self::output_names())
+            .map(port)
+            .collect::<Vec<_>>();
+        let mut outputs = vec![];
+        for path in paths {
+            outputs.push(self.find_output_at(path)?);
+            let mut scope = path.split('/').map(str::to_owned).collect::<Vec<_>>();
+            let name = scope.pop().unwrap_or_default();
+            scope.insert(0, TOP_SCOPE.into());
+            signals.push(VcdSignal { scope, name });
+        }
+        self.vcd = Some(VcdRecording {
+            outputs,
+            vcd: Vcd {
+                signals,
+                steps: vec![],
+            },
+        });
+        Ok(())
+    }
+
+    pub fn stop_vcd(&mut self) -> Option<Vcd> {
+        self.vcd.take().map(|recording| recording.vcd)
+    }
+
+    pub(crate) fn record_vcd(&mut self, inputs: [Logic; NINPUT], outputs: [Logic; NOUT]) {
+        if let Some(recording) = &mut self.vcd {
+            let mut step = inputs.to_vec();
+            step.extend(outputs);
+            step.extend(recording.outputs.iter().map(|output| output.value.get()));
+            recording.vcd.steps.push(step);
+        }
+    }
+}
+
+impl fmt::Display for Vcd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "$timescale 1ns $end")?;
+        // sorting the signals by scope keeps each scope's signals together
+        let mut order = (0..self.signals.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| self.signals[a].scope.cmp(&self.signals[b].scope));
+        let mut open: &[String] = &[];
+        for index in order {
+            let signal = &self.signals[index];
+            let shared = open
+                .iter()
+                .zip(&signal.scope)
+                .take_while(|(open, scope)| open == scope)
+                .count();
+            for _ in shared..open.len() {
+                writeln!(f, "$upscope $end")?;
+            }
+            for scope in &signal.scope[shared..] {
+                writeln!(f, "$scope module {scope} $end")?;
+            }
+            open = &signal.scope;
+            writeln!(f, "$var wire 1 {} {} $end", code(index), signal.name)?;
+        }
+        for _ in open {
+            writeln!(f, "$upscope $end")?;
+        }
+        writeln!(f, "$enddefinitions $end")?;
+
+        // only the signals which changed are written after the first step
+        let mut last: Option<&Vec<Logic>> = None;
+        for (time, step) in self.steps.iter().enumerate() {
+            writeln!(f, "#{time}")?;
+            for (index, &value) in step.iter().enumerate() {
+                if last.is_none_or(|last| last[index] != value) {
+                    // viewers take x and z in either case, but VCD files conventionally
+                    // write them in lower case
+                    let value = value.to_char().to_ascii_lowercase();
+                    writeln!(f, "{value}{}", code(index))?;
+                }
+            }
+            last = Some(step);
+        }
+        Ok(())
+    }
+}
+
+// the short identifier a signal's changes are written with, from the printable
+// characters '!' to '~'
+fn code(mut index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+    let mut code = String::new();
+    loop {
+        code.push((FIRST + (index % COUNT) as u8) as char);
+        index /= COUNT;
+        if index == 0 {
+            return code;
+        }
+        index -= 1;
+    }
+}
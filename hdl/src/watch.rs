@@ -0,0 +1,45 @@
+use crate::{ChipOutput, Error, Logic, Machine, StructuredDataFamily};
+
+// A chip output being watched for changes. The callback receives the old and new
+// values and the cycle the change happened in, which counts every process, so the tick
+// and tock of a clock cycle are two cycles
+pub(crate) struct Watch<'a> {
+    output: &'a ChipOutput<'a>,
+    last: Logic,
+    callback: Box<dyn FnMut(Logic, Logic, u64) + 'a>,
+}
+
+impl<'a, TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>
+    Machine<'a, TFam, NINPUT, NOUT>
+{
+    // calls `callback` after every process which changed the chip output at `path`. See
+    // `Netlist::node_path()` for the format of the path
+    pub fn watch(
+        &mut self,
+        path: &str,
+        callback: impl FnMut(Logic, Logic, u64) + 'a,
+    ) -> Result<(), Error> {
+        let output = self.find_output_at(path)?;
+        self.watches.push(Watch {
+            output,
+            last: output.value.get(),
+            callback: Box::new(callback),
+        });
+        Ok(())
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    pub(crate) fn check_watches(&mut self) {
+        for watch in &mut self.watches {
+            let value = watch.output.value.get();
+            if value != watch.last {
+                (watch.callback)(watch.last, value, self.cycle);
+                watch.last = value;
+            }
+        }
+        self.cycle += 1;
+    }
+}
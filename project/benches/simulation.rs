@@ -0,0 +1,18 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn simulation(c: &mut Criterion) {
+    for workload in project::bench::WORKLOADS {
+        workload(&mut |name, run| {
+            c.bench_function(name, |b| b.iter(&mut *run));
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    // the RAM workloads take a good fraction of a second each, so fewer samples keep
+    // the suite to a few minutes
+    config = Criterion::default().sample_size(10);
+    targets = simulation
+}
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+// Simulation workloads for the hdl evaluator, timed by the criterion benchmarks in
+// benches/simulation.rs. Run them with:
+//
+//     cargo bench -p project
+//
+// Each workload builds its machine once and then hands the timer a closure running the
+// workload against it, so the numbers reflect evaluation speed rather than graph
+// construction.
+
+use std::hint::black_box;
+
+use bumpalo::Bump;
+use hdl::{
+    bits::{self, BitOrder},
+    Machine,
+};
+
+use crate::{Alu, AluInputs, Ram512, Ram512Inputs, Register16, Register16Inputs};
+
+// times a workload under the given name, running it as many times as it likes
+pub type Timer<'t> = &'t mut dyn FnMut(&'static str, &mut dyn FnMut());
+
+pub const WORKLOADS: [fn(Timer); 4] = [
+    alu_truth_table,
+    register16_clock_cycles,
+    ram512_sequential_write_read,
+    ram512_strided_access,
+];
+
+pub fn alu_truth_table(time: Timer) {
+    let alloc = Bump::new();
+    let mut machine = Machine::new(&alloc, Alu::from).unwrap();
+    time("alu truth table (64 control combinations)", &mut || {
+        for control in 0..64u16 {
+            let [zx, nx, zy, ny, f, no] = bits::from_u16(control, BitOrder::MsbFirst);
+            black_box(
+                machine
+                    .process(AluInputs {
+                        x: bits::from_u16(12345, BitOrder::MsbFirst),
+                        y: bits::from_u16(54321, BitOrder::MsbFirst),
+                        zx,
+                        nx,
+                        zy,
+                        ny,
+                        f,
+                        no,
+                    })
+                    .unwrap(),
+            );
+        }
+    })
+}
+
+pub fn register16_clock_cycles(time: Timer) {
+    let alloc = Bump::new();
+    let mut machine = Machine::new(&alloc, Register16::from).unwrap();
+    time("register16 1k clock cycles", &mut || {
+        for cycle in 0..1000u16 {
+            for clock in [true, false] {
+                black_box(
+                    machine
+                        .process(Register16Inputs {
+                            in_: bits::from_u16(cycle, BitOrder::MsbFirst),
+                            load: cycle % 2 == 0,
+                            clock,
+                        })
+                        .unwrap(),
+                );
+            }
+        }
+    })
+}
+
+pub fn ram512_sequential_write_read(time: Timer) {
+    let alloc = Bump::new();
+    let mut machine = Machine::new(&alloc, Ram512::from).unwrap();
+    // every process call evaluates all 512 registers, so these workloads are kept small
+    time("ram512 sequential write then read (8 words)", &mut || {
+        for load in [true, false] {
+            for address in 0..8u16 {
+                for clock in [true, false] {
+                    black_box(
+                        machine
+                            .process(Ram512Inputs {
+                                in_: bits::from_u16(address, BitOrder::MsbFirst),
+                                address: bits::from_u16(address, BitOrder::MsbFirst),
+                                load,
+                                clock,
+                            })
+                            .unwrap(),
+                    );
+                }
+            }
+        }
+    })
+}
+
+pub fn ram512_strided_access(time: Timer) {
+    let alloc = Bump::new();
+    let mut machine = Machine::new(&alloc, Ram512::from).unwrap();
+    time("ram512 strided read/write (8 words)", &mut || {
+        // a stride coprime with 512 hops between banks on every access
+        for i in 0..8u16 {
+            let address = (i * 73) % 512;
+            for clock in [true, false] {
+                black_box(
+                    machine
+                        .process(Ram512Inputs {
+                            in_: bits::from_u16(i, BitOrder::MsbFirst),
+                            address: bits::from_u16(address, BitOrder::MsbFirst),
+                            load: i % 2 == 0,
+                            clock,
+                        })
+                        .unwrap(),
+                );
+            }
+        }
+    })
+}
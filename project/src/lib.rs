@@ -0,0 +1,5104 @@
+pub mod bench;
+pub mod registry;
+
+use std::{array::from_fn, collections::VecDeque};
+
+use bumpalo::Bump;
+use hdl::{
+    create_subchip, pipeline, ArrayInto, Bus, ChipInput, ChipOutputType, Clocked, DefaultChip,
+    Input, Lookup, LookupTable, Nand, NandInputs, SizedChip, UserInput,
+};
+use hdl_macro::{chip, Selector, StructuredData};
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct UnaryChipOutput<T> {
+    out: T,
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct BinaryChipOutput<T> {
+    out1: T,
+    out2: T,
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct ArrayLen2<T> {
+    out: [T; 2],
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct ArrayLen16<T> {
+    out: [T; 16],
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct ArrayLenN<T, const N: usize> {
+    out: [T; N],
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct BinaryArrayLen16<T> {
+    out1: [T; 16],
+    out2: [T; 16],
+}
+
+#[chip]
+fn not<'a>(alloc: &'a Bump, in_: &'a ChipInput<'a>) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let nand = Nand::new(alloc, in_.into(), in_.into());
+    UnaryChipOutput { out: nand.into() }
+}
+
+#[chip]
+fn and<'a>(
+    alloc: &'a Bump,
+    in1: &'a ChipInput<'a>,
+    in2: &'a ChipInput<'a>,
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let nand = Nand::new(alloc, in1.into(), in2.into());
+    let not = Not::new(alloc, nand.into());
+    UnaryChipOutput {
+        out: not.get_out(alloc).out.into(),
+    }
+}
+
+#[chip]
+fn or<'a>(
+    alloc: &'a Bump,
+    in1: &'a ChipInput<'a>,
+    in2: &'a ChipInput<'a>,
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let not1 = Not::new(alloc, in1.into());
+    let not2 = Not::new(alloc, in2.into());
+    let nand = Nand::new(
+        alloc,
+        not1.get_out(alloc).out.into(),
+        not2.get_out(alloc).out.into(),
+    );
+    UnaryChipOutput { out: nand.into() }
+}
+
+#[chip(model = "tests::xor_model")]
+fn xor<'a>(
+    alloc: &'a Bump,
+    in1: &'a ChipInput<'a>,
+    in2: &'a ChipInput<'a>,
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let and = And::new(alloc, in1.into(), in2.into());
+    let not = Not::new(alloc, and.get_out(alloc).out.into());
+    let or = Or::new(alloc, in1.into(), in2.into());
+    let and2 = And::new(
+        alloc,
+        not.get_out(alloc).out.into(),
+        or.get_out(alloc).out.into(),
+    );
+    UnaryChipOutput {
+        out: and2.get_out(alloc).out.into(),
+    }
+}
+
+#[chip(model = "tests::mux_model")]
+fn mux<'a>(
+    alloc: &'a Bump,
+    in1: &'a ChipInput<'a>,
+    in2: &'a ChipInput<'a>,
+    sel: &'a ChipInput<'a>,
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let and1 = And::new(alloc, in2.into(), sel.into());
+    let not = Not::new(alloc, sel.into());
+    let and2 = And::new(alloc, in1.into(), not.get_out(alloc).out.into());
+    let or = Or::new(
+        alloc,
+        and1.get_out(alloc).out.into(),
+        and2.get_out(alloc).out.into(),
+    );
+    UnaryChipOutput {
+        out: or.get_out(alloc).out.into(),
+    }
+}
+
+#[chip(model = "tests::demux_model")]
+fn demux<'a>(
+    alloc: &'a Bump,
+    in_: &'a ChipInput<'a>,
+    sel: &'a ChipInput<'a>,
+) -> BinaryChipOutput<ChipOutputType<'a>> {
+    let and1 = And::new(alloc, in_.into(), sel.into());
+    let not = Not::new(alloc, sel.into());
+    let and2 = And::new(alloc, in_.into(), not.get_out(alloc).out.into());
+    BinaryChipOutput {
+        out1: and2.get_out(alloc).out.into(),
+        out2: and1.get_out(alloc).out.into(),
+    }
+}
+
+#[chip(instance(Not16(16)))]
+fn notn<'a, const N: usize>(
+    alloc: &'a Bump,
+    input: Bus<'a, N>,
+) -> ArrayLenN<ChipOutputType<'a>, N> {
+    // the gates are built in loops over ranges rather than by mapping the bus, here and in
+    // the wider chips below, so GATE_COUNT can count them
+    let mut out = vec![];
+    for i in 0..N {
+        out.push(Not::new(alloc, input[i]).get_out(alloc).out);
+    }
+    ArrayLenN {
+        out: from_fn(|i| out[i].into()),
+    }
+}
+
+#[chip]
+fn and16<'a>(
+    alloc: &'a Bump,
+    in1: Bus<'a, 16>,
+    in2: Bus<'a, 16>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let mut out = vec![];
+    for i in 0..16 {
+        out.push(And::new(alloc, in1[i], in2[i]).get_out(alloc).out);
+    }
+    ArrayLen16 {
+        out: from_fn(|i| out[i].into()),
+    }
+}
+
+#[chip]
+fn or2<'a>(
+    alloc: &'a Bump,
+    in1: [&'a ChipInput<'a>; 2],
+    in2: [&'a ChipInput<'a>; 2],
+) -> ArrayLen2<ChipOutputType<'a>> {
+    let mut out = vec![];
+    for i in 0..2 {
+        out.push(
+            Or::new(alloc, in1[i].into(), in2[i].into())
+                .get_out(alloc)
+                .out,
+        );
+    }
+    ArrayLen2 {
+        out: from_fn(|i| out[i].into()),
+    }
+}
+
+#[chip]
+fn mux16<'a>(
+    alloc: &'a Bump,
+    in1: Bus<'a, 16>,
+    in2: Bus<'a, 16>,
+    sel: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let mut out = vec![];
+    for i in 0..16 {
+        out.push(
+            Mux::new(alloc, in1[i], in2[i], sel.into())
+                .get_out(alloc)
+                .out,
+        );
+    }
+    ArrayLen16 {
+        out: from_fn(|i| out[i].into()),
+    }
+}
+
+#[chip]
+fn demux16<'a>(
+    alloc: &'a Bump,
+    in_: Bus<'a, 16>,
+    sel: &'a ChipInput<'a>,
+) -> BinaryArrayLen16<ChipOutputType<'a>> {
+    let mut out = vec![];
+    for i in 0..16 {
+        out.push(Demux::new(alloc, in_[i], sel.into()).get_out(alloc));
+    }
+    let out1 = from_fn(|i| out[i].out1.into());
+    let out2 = from_fn(|i| out[i].out2.into());
+    BinaryArrayLen16 { out1, out2 }
+}
+
+/// selects one of `WAYS` words of `W` bits, with `sel` (MSB first) giving the index of
+/// the word. Each select bit halves the words, starting from the LSB. Mux16x4 and
+/// Mux16x8 took their words as separate ports, `in1` onwards, before they were built
+/// from this, and now take them as the one nested `in_`
+#[chip(instance(Mux16x4(16, 4, 2), Mux16x8(16, 8, 3)))]
+fn muxn<'a, const W: usize, const WAYS: usize, const SEL: usize>(
+    alloc: &'a Bump,
+    in_: [[&'a ChipInput<'a>; W]; WAYS],
+    sel: [&'a ChipInput<'a>; SEL],
+) -> ArrayLenN<ChipOutputType<'a>, W> {
+    const {
+        assert!(
+            WAYS == 1 << SEL,
+            "a mux needs a word for each value of its select"
+        )
+    };
+    // the words still to be muxed, with how many select bits have muxed them. Each pair
+    // taken from the front is muxed in to a word at the back, so there's a loop over a
+    // range for GATE_COUNT to count rather than one for each select bit
+    let mut words = in_
+        .map(|word| (Bus::from(word), 0))
+        .into_iter()
+        .collect::<VecDeque<_>>();
+    for _ in 0..WAYS - 1 {
+        let (in1, muxed) = words.pop_front().unwrap();
+        let (in2, _) = words.pop_front().unwrap();
+        let sel = sel[SEL - 1 - muxed];
+        let mut out = vec![];
+        for i in 0..W {
+            out.push(
+                Mux::new(alloc, in1[i], in2[i], sel.into())
+                    .get_out(alloc)
+                    .out,
+            );
+        }
+        words.push_back((Bus::from(from_fn(|i| out[i])), muxed + 1));
+    }
+    ArrayLenN {
+        out: words[0].0.outputs(),
+    }
+}
+
+/// routes a word of `W` bits to the one of the `1 << SEL` outputs that `sel` (MSB first)
+/// gives the index of, with the other outputs low. The outputs are the words one after
+/// another, so `N` is `W << SEL`. It can't be worked out in the output's type, so a
+/// chip built with any other `N` fails to compile. Each select bit doubles the words,
+/// starting from the MSB. Demux1x4, Demux1x8 and Demux16x8 had an output for each word,
+/// `out1` onwards, before they were built from this, and now have the one flat `out`,
+/// with even the 1 bit demuxes taking their input as an array
+#[chip(instance(Demux1x4(1, 2, 4), Demux1x8(1, 3, 8), Demux16x8(16, 3, 128)))]
+fn demuxn<'a, const W: usize, const SEL: usize, const N: usize>(
+    alloc: &'a Bump,
+    in_: [&'a ChipInput<'a>; W],
+    sel: [&'a ChipInput<'a>; SEL],
+) -> ArrayLenN<ChipOutputType<'a>, N> {
+    const {
+        assert!(
+            N == W << SEL,
+            "a demux outputs every bit of each of its words"
+        )
+    };
+    // the words still to be demuxed, with how many select bits have demuxed them. Each
+    // word taken from the front is demuxed in to two at the back, like the muxes of muxn
+    let mut words = VecDeque::from([(Bus::from(in_), 0)]);
+    for _ in 0..(1 << SEL) - 1 {
+        let (word, demuxed) = words.pop_front().unwrap();
+        let sel = sel[demuxed];
+        let mut outs = vec![];
+        for i in 0..W {
+            outs.push(Demux::new(alloc, word[i], sel.into()).get_out(alloc));
+        }
+        words.push_back((Bus::from(from_fn(|i| outs[i].out1)), demuxed + 1));
+        words.push_back((Bus::from(from_fn(|i| outs[i].out2)), demuxed + 1));
+    }
+    let words = words
+        .into_iter()
+        .map(|(word, _)| word.outputs())
+        .collect::<Vec<_>>();
+    ArrayLenN {
+        out: from_fn(|i| words[i / W][i % W]),
+    }
+}
+
+#[chip]
+fn andmult4<'a>(
+    alloc: &'a Bump,
+    in_: [&'a ChipInput<'a>; 4],
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let mut out = And::new(alloc, in_[0].into(), in_[1].into())
+        .get_out(alloc)
+        .out;
+    // clippy would have the inputs iterated over, which GATE_COUNT can't count
+    #[allow(clippy::needless_range_loop)]
+    for i in 2..4 {
+        out = And::new(alloc, in_[i].into(), out.into())
+            .get_out(alloc)
+            .out;
+    }
+    UnaryChipOutput { out: out.into() }
+}
+
+#[chip]
+fn ormultn<'a>(alloc: &'a Bump, in_: &[&'a ChipInput<'a>]) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let initial_nor = Or::new(alloc, in_[0].into(), in_[1].into());
+    let out = in_.iter().skip(2).fold(initial_nor, |acc, in_| {
+        Or::new(alloc, (*in_).into(), acc.get_out(alloc).out.into())
+    });
+    UnaryChipOutput {
+        out: out.get_out(alloc).out.into(),
+    }
+}
+
+#[chip]
+fn ormult16<'a>(alloc: &'a Bump, in_: Bus<'a, 16>) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let mut out = Or::new(alloc, in_[0], in_[1]).get_out(alloc).out;
+    for i in 2..16 {
+        out = Or::new(alloc, in_[i], out.into()).get_out(alloc).out;
+    }
+    UnaryChipOutput { out: out.into() }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct AdderOut<T> {
+    sum: T,
+    carry: T,
+}
+
+#[chip]
+fn halfadder<'a>(
+    alloc: &'a Bump,
+    num1: &'a ChipInput<'a>,
+    num2: &'a ChipInput<'a>,
+) -> AdderOut<ChipOutputType<'a>> {
+    let sum_bit = Xor::new(alloc, num1.into(), num2.into());
+    let carry_bit = And::new(alloc, num1.into(), num2.into());
+    AdderOut {
+        carry: carry_bit.get_out(alloc).out.into(),
+        sum: sum_bit.get_out(alloc).out.into(),
+    }
+}
+
+#[chip]
+fn fulladder<'a>(
+    alloc: &'a Bump,
+    num1: &'a ChipInput<'a>,
+    num2: &'a ChipInput<'a>,
+    num3: &'a ChipInput<'a>,
+) -> AdderOut<ChipOutputType<'a>> {
+    let first_hadder = Halfadder::new(alloc, num1.into(), num2.into());
+    let second_hadder = Halfadder::new(alloc, num3.into(), first_hadder.get_out(alloc).sum.into());
+    let carry_or = Or::new(
+        alloc,
+        first_hadder.get_out(alloc).carry.into(),
+        second_hadder.get_out(alloc).carry.into(),
+    );
+    AdderOut {
+        carry: carry_or.get_out(alloc).out.into(),
+        sum: second_hadder.get_out(alloc).sum.into(),
+    }
+}
+
+/// adds two numbers, dropping the final carry. The LSB is added by a half adder and
+/// each bit above it by a full adder taking the carry from the bit below
+#[chip(instance(Adder4(4), Adder8(8), Adder16(16), Adder32(32)))]
+fn addern<'a, const N: usize>(
+    alloc: &'a Bump,
+    num1: Bus<'a, N>,
+    num2: Bus<'a, N>,
+) -> ArrayLenN<ChipOutputType<'a>, N> {
+    let lsb = Halfadder::new(alloc, num1[N - 1], num2[N - 1]).get_out(alloc);
+    let mut carry = lsb.carry;
+    let mut sums = vec![lsb.sum];
+    // a loop over a range rather than an iterator, so GATE_COUNT can count the adders
+    for i in (0..N - 1).rev() {
+        let adder = Fulladder::new(alloc, carry.into(), num1[i], num2[i]).get_out(alloc);
+        carry = adder.carry;
+        sums.push(adder.sum);
+    }
+
+    ArrayLenN {
+        out: from_fn(|i| sums[N - 1 - i].into()),
+    }
+}
+
+#[chip(instance(Incrementer4(4), Incrementer8(8), Incrementer16(16), Incrementer32(32)))]
+fn incrementern<'a, const N: usize>(
+    alloc: &'a Bump,
+    num: Bus<'a, N>,
+    #[default(1)] one: Bus<'a, N>,
+) -> ArrayLenN<ChipOutputType<'a>, N> {
+    let adder = Addern::<N>::new(alloc, one, num);
+    let out = adder.get_out(alloc).out.ainto();
+    ArrayLenN { out }
+}
+
+/// the two's complement of a number, i.e. its bits flipped plus one. The most negative
+/// number has no positive counterpart, so it negates to itself
+#[chip(model = "tests::negate16_model")]
+fn negate16<'a>(alloc: &'a Bump, num: Bus<'a, 16>) -> ArrayLen16<ChipOutputType<'a>> {
+    let negated = pipeline!(alloc, num => Not16() => Incrementer16());
+    ArrayLen16 {
+        out: negated.get_out(alloc).out.ainto(),
+    }
+}
+
+/// num1 - num2, added as num1 + -num2 and wrapping like the adder
+#[chip(model = "tests::subtractor16_model")]
+fn subtractor16<'a>(
+    alloc: &'a Bump,
+    num1: Bus<'a, 16>,
+    num2: Bus<'a, 16>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let negated = Negate16::new(alloc, num2).get_out(alloc).out;
+    let difference = Adder16::new(alloc, num1, negated.into());
+    ArrayLen16 {
+        out: difference.get_out(alloc).out.ainto(),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct AluOutputs<T> {
+    out: [T; 16],
+    zr: T,
+    ng: T,
+}
+
+#[chip]
+fn zeronum<'a>(
+    alloc: &'a Bump,
+    num: Bus<'a, 16>,
+    zero: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let not_zero = Not16::new(alloc, [zero; 16].into());
+    let zero_num = And16::new(alloc, num, not_zero.get_out(alloc).out.into());
+
+    ArrayLen16 {
+        out: zero_num.get_out(alloc).out.ainto(),
+    }
+}
+
+#[chip]
+fn negatenum<'a>(
+    alloc: &'a Bump,
+    num: Bus<'a, 16>,
+    negate: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let not = Not16::new(alloc, num);
+    let mux_not_x = Mux16::new(alloc, num, not.get_out(alloc).out.into(), negate.into()); // note: it might be more power efficient in real hardware to demux first rather than
+                                                                                          // mux at the end. I'm not a real engineer though, so I don't know
+    ArrayLen16 {
+        out: mux_not_x.get_out(alloc).out.ainto(),
+    }
+}
+
+#[chip]
+fn andorplus<'a>(
+    alloc: &'a Bump,
+    num1: Bus<'a, 16>,
+    num2: Bus<'a, 16>,
+    isadd: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let add_nums = Adder16::new(alloc, num1, num2);
+    let and_nums = And16::new(alloc, num1, num2);
+    let mux = Mux16::new(
+        alloc,
+        and_nums.get_out(alloc).out.into(),
+        add_nums.get_out(alloc).out.into(),
+        isadd.into(),
+    );
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct AddFlagsOutputs<T> {
+    carry: T,
+    overflow: T,
+}
+
+/// flags the carry out of a sum, and whether it overflowed as signed numbers, from the
+/// MSBs of the numbers and of their sum. When the MSBs differ the carry in to the MSB is
+/// the inverse of its sum, and is carried out. The sum overflows when the MSBs match and
+/// its MSB doesn't. Both flags are low unless `isadd` is high
+#[chip]
+fn addflags<'a>(
+    alloc: &'a Bump,
+    msb1: &'a ChipInput<'a>,
+    msb2: &'a ChipInput<'a>,
+    sum_msb: &'a ChipInput<'a>,
+    isadd: &'a ChipInput<'a>,
+) -> AddFlagsOutputs<ChipOutputType<'a>> {
+    let msbs_differ = Xor::new(alloc, msb1.into(), msb2.into()).get_out(alloc).out;
+    let carry_in = pipeline!(alloc, sum_msb => Not() => And(msbs_differ.into()));
+    let carry = pipeline!(
+        alloc,
+        msb1
+            => And(msb2.into())
+            => Or(carry_in.get_out(alloc).out.into())
+            => And(isadd.into())
+    );
+    let sign_changed = Xor::new(alloc, sum_msb.into(), msb1.into())
+        .get_out(alloc)
+        .out;
+    let overflow = pipeline!(
+        alloc,
+        msbs_differ
+            => Not()
+            => And(sign_changed.into())
+            => And(isadd.into())
+    );
+    AddFlagsOutputs {
+        carry: carry.get_out(alloc).out.into(),
+        overflow: overflow.get_out(alloc).out.into(),
+    }
+}
+
+#[chip]
+fn alu<'a>(
+    alloc: &'a Bump,
+    x: Bus<'a, 16>,
+    y: Bus<'a, 16>,
+    zx: &'a ChipInput<'a>,
+    zy: &'a ChipInput<'a>,
+    nx: &'a ChipInput<'a>,
+    ny: &'a ChipInput<'a>,
+    f: &'a ChipInput<'a>,
+    no: &'a ChipInput<'a>,
+) -> AluOutputs<ChipOutputType<'a>> {
+    let not_x = pipeline!(alloc, x => Zeronum(zx.into()) => Negatenum(nx.into()));
+    let not_y = pipeline!(alloc, y => Zeronum(zy.into()) => Negatenum(ny.into()));
+    let negate_result = pipeline!(
+        alloc,
+        not_x.get_out(alloc).out
+            => Andorplus(not_y.get_out(alloc).out.into(), f.into())
+            => Negatenum(no.into())
+    );
+    let is_zero = pipeline!(alloc, negate_result.get_out(alloc).out => Ormult16() => Not());
+    AluOutputs {
+        out: negate_result.get_out(alloc).out.ainto(),
+        zr: is_zero.get_out(alloc).out.into(),
+        ng: negate_result.get_out(alloc).out[0].into(),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct AluExOutputs<T> {
+    out: [T; 16],
+    zr: T,
+    ng: T,
+    carry: T,
+    overflow: T,
+}
+
+/// an Alu which also flags the carry out of its addition, and whether the addition
+/// overflowed as signed numbers. The flags are of the sum before `no` negates it, so e.g.
+/// x-y, computed as !(!x+y), flags the carry and overflow of !x+y. Only the MSBs are
+/// needed for the flags, so rather than opening up the Alu, the MSBs it adds are worked
+/// out again beside it, and the MSB of its sum recovered by undoing `no`
+#[chip]
+fn aluex<'a>(
+    alloc: &'a Bump,
+    x: Bus<'a, 16>,
+    y: Bus<'a, 16>,
+    zx: &'a ChipInput<'a>,
+    zy: &'a ChipInput<'a>,
+    nx: &'a ChipInput<'a>,
+    ny: &'a ChipInput<'a>,
+    f: &'a ChipInput<'a>,
+    no: &'a ChipInput<'a>,
+) -> AluExOutputs<ChipOutputType<'a>> {
+    let alu = Alu::new(
+        alloc,
+        x,
+        y,
+        zx.into(),
+        zy.into(),
+        nx.into(),
+        ny.into(),
+        f.into(),
+        no.into(),
+    )
+    .get_out(alloc);
+    let sum_msb = Xor::new(alloc, alu.out[0].into(), no.into())
+        .get_out(alloc)
+        .out;
+    let x_msb = pipeline!(alloc, zx => Not() => And(x[0]) => Xor(nx.into()));
+    let y_msb = pipeline!(alloc, zy => Not() => And(y[0]) => Xor(ny.into()));
+    let flags = Addflags::new(
+        alloc,
+        x_msb.get_out(alloc).out.into(),
+        y_msb.get_out(alloc).out.into(),
+        sum_msb.into(),
+        f.into(),
+    )
+    .get_out(alloc);
+    AluExOutputs {
+        out: alu.out.ainto(),
+        zr: alu.zr.into(),
+        ng: alu.ng.into(),
+        carry: flags.carry.into(),
+        overflow: flags.overflow.into(),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct LatchOutput<T> {
+    q: T,
+    nq: T,
+}
+
+#[chip]
+fn srlatch<'a>(
+    alloc: &'a Bump,
+    s: &'a ChipInput<'a>,
+    r: &'a ChipInput<'a>,
+) -> LatchOutput<ChipOutputType<'a>> {
+    let (cross_nand_1, cross_nand_2): (&Nand, &Nand) = create_subchip(
+        alloc,
+        &|(nandchip,)| NandInputs {
+            in1: s.into(),
+            in2: nandchip.into(),
+        },
+        &|(nandchip,)| NandInputs {
+            in1: r.into(),
+            in2: nandchip.into(),
+        },
+    );
+
+    LatchOutput {
+        q: cross_nand_1.into(),
+        nq: cross_nand_2.into(),
+    }
+}
+
+#[chip]
+fn dlatch<'a>(
+    alloc: &'a Bump,
+    data: &'a ChipInput<'a>,
+    enable: &'a ChipInput<'a>,
+) -> LatchOutput<ChipOutputType<'a>> {
+    let notd = Not::new(alloc, data.into());
+    let nand1 = Nand::new(alloc, data.into(), enable.into());
+    let nand2 = Nand::new(alloc, notd.get_out(alloc).out.into(), enable.into());
+    let srlatch = Srlatch::new(alloc, nand1.into(), nand2.into());
+
+    let srout = srlatch.get_out(alloc);
+    LatchOutput {
+        q: srout.q.into(),
+        nq: srout.nq.into(),
+    }
+}
+
+#[chip(clocked)]
+fn dflipflop<'a>(
+    alloc: &'a Clocked<'a>,
+    data: &'a ChipInput<'a>,
+) -> LatchOutput<ChipOutputType<'a>> {
+    let clock = alloc.clock();
+    let invclock = Not::new(alloc, clock.into());
+    let latch1 = Dlatch::new(alloc, data.into(), clock.into());
+    let latch2 = Dlatch::new(
+        alloc,
+        latch1.get_out(alloc).q.into(),
+        invclock.get_out(alloc).out.into(),
+    );
+
+    let latch2out = latch2.get_out(alloc);
+    LatchOutput {
+        q: latch2out.q.into(),
+        nq: latch2out.nq.into(),
+    }
+}
+
+#[chip(clocked)]
+fn bit<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: &'a ChipInput<'a>,
+    load: &'a ChipInput<'a>,
+) -> UnaryChipOutput<ChipOutputType<'a>> {
+    let (dff, _): (&Dflipflop, &Mux) = create_subchip(
+        alloc,
+        &|(mux,)| DflipflopInputs {
+            data: mux.get_out(alloc).out.into(),
+            clock: alloc.clock().into(),
+        },
+        &|(dff,)| MuxInputs {
+            in1: dff.get_out(alloc).q.into(),
+            in2: in_.into(),
+            sel: load.into(),
+        },
+    );
+    UnaryChipOutput {
+        out: dff.get_out(alloc).q.into(),
+    }
+}
+
+#[chip(clocked)]
+fn register16<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let mut out = vec![];
+    #[allow(clippy::needless_range_loop)] // as in andmult4
+    for i in 0..16 {
+        out.push(
+            Bit::new(alloc, in_[i].into(), load.into())
+                .get_out(alloc)
+                .out,
+        );
+    }
+    ArrayLen16 {
+        out: from_fn(|i| out[i].into()),
+    }
+}
+
+// the registers of a Ram8, for its address lines
+#[derive(Clone, Copy, Debug, PartialEq, Selector)]
+enum Ram8Addr {
+    R0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+}
+
+#[chip(clocked)]
+fn ram8<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 3],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let demux = Demux1x8::new(alloc, [load.into()], address.ainto());
+    let dmxo = demux.get_out(alloc);
+
+    let reg1 = Register16::new_named(alloc, "R0", in_.ainto(), dmxo.out[0].into());
+    let reg2 = Register16::new_named(alloc, "R1", in_.ainto(), dmxo.out[1].into());
+    let reg3 = Register16::new_named(alloc, "R2", in_.ainto(), dmxo.out[2].into());
+    let reg4 = Register16::new_named(alloc, "R3", in_.ainto(), dmxo.out[3].into());
+    let reg5 = Register16::new_named(alloc, "R4", in_.ainto(), dmxo.out[4].into());
+    let reg6 = Register16::new_named(alloc, "R5", in_.ainto(), dmxo.out[5].into());
+    let reg7 = Register16::new_named(alloc, "R6", in_.ainto(), dmxo.out[6].into());
+    let reg8 = Register16::new_named(alloc, "R7", in_.ainto(), dmxo.out[7].into());
+
+    let mux = Mux16x8::new(
+        alloc,
+        [
+            reg1.get_out(alloc).out.ainto(),
+            reg2.get_out(alloc).out.ainto(),
+            reg3.get_out(alloc).out.ainto(),
+            reg4.get_out(alloc).out.ainto(),
+            reg5.get_out(alloc).out.ainto(),
+            reg6.get_out(alloc).out.ainto(),
+            reg7.get_out(alloc).out.ainto(),
+            reg8.get_out(alloc).out.ainto(),
+        ],
+        address.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+#[chip(clocked)]
+fn ram64<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 6],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let (this_addr, remaining_addr): ([_; 3], [_; 3]) = split_2(&address);
+    let demux = Demux1x8::new(alloc, [load.into()], this_addr.ainto());
+    let dmxo = demux.get_out(alloc);
+
+    // the larger RAMs name their banks for the address bits selecting them, like a Ram8
+    // names its registers, so a word can be found by its address
+    let reg1 = Ram8::new_named(
+        alloc,
+        "B0",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[0].into(),
+    );
+    let reg2 = Ram8::new_named(
+        alloc,
+        "B1",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[1].into(),
+    );
+    let reg3 = Ram8::new_named(
+        alloc,
+        "B2",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[2].into(),
+    );
+    let reg4 = Ram8::new_named(
+        alloc,
+        "B3",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[3].into(),
+    );
+    let reg5 = Ram8::new_named(
+        alloc,
+        "B4",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[4].into(),
+    );
+    let reg6 = Ram8::new_named(
+        alloc,
+        "B5",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[5].into(),
+    );
+    let reg7 = Ram8::new_named(
+        alloc,
+        "B6",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[6].into(),
+    );
+    let reg8 = Ram8::new_named(
+        alloc,
+        "B7",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[7].into(),
+    );
+
+    let mux = Mux16x8::new(
+        alloc,
+        [
+            reg1.get_out(alloc).out.ainto(),
+            reg2.get_out(alloc).out.ainto(),
+            reg3.get_out(alloc).out.ainto(),
+            reg4.get_out(alloc).out.ainto(),
+            reg5.get_out(alloc).out.ainto(),
+            reg6.get_out(alloc).out.ainto(),
+            reg7.get_out(alloc).out.ainto(),
+            reg8.get_out(alloc).out.ainto(),
+        ],
+        this_addr.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+#[chip(clocked)]
+fn ram512<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 9],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let (this_addr, remaining_addr): ([_; 3], [_; 6]) = split_2(&address);
+    let demux = Demux1x8::new(alloc, [load.into()], this_addr.ainto());
+    let dmxo = demux.get_out(alloc);
+
+    let reg1 = Ram64::new_named(
+        alloc,
+        "B0",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[0].into(),
+    );
+    let reg2 = Ram64::new_named(
+        alloc,
+        "B1",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[1].into(),
+    );
+    let reg3 = Ram64::new_named(
+        alloc,
+        "B2",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[2].into(),
+    );
+    let reg4 = Ram64::new_named(
+        alloc,
+        "B3",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[3].into(),
+    );
+    let reg5 = Ram64::new_named(
+        alloc,
+        "B4",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[4].into(),
+    );
+    let reg6 = Ram64::new_named(
+        alloc,
+        "B5",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[5].into(),
+    );
+    let reg7 = Ram64::new_named(
+        alloc,
+        "B6",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[6].into(),
+    );
+    let reg8 = Ram64::new_named(
+        alloc,
+        "B7",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[7].into(),
+    );
+
+    let mux = Mux16x8::new(
+        alloc,
+        [
+            reg1.get_out(alloc).out.ainto(),
+            reg2.get_out(alloc).out.ainto(),
+            reg3.get_out(alloc).out.ainto(),
+            reg4.get_out(alloc).out.ainto(),
+            reg5.get_out(alloc).out.ainto(),
+            reg6.get_out(alloc).out.ainto(),
+            reg7.get_out(alloc).out.ainto(),
+            reg8.get_out(alloc).out.ainto(),
+        ],
+        this_addr.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+fn split_2<T: Copy, const NARR: usize, const N1: usize, const N2: usize>(
+    arr: &[T; NARR],
+) -> ([T; N1], [T; N2]) {
+    const {
+        assert!(
+            NARR == N1 + N2,
+            "Split sections of the array must sum to total array length"
+        );
+    };
+    (from_fn(|i| arr[i]), from_fn(|i| arr[i + N1]))
+}
+
+#[chip(clocked)]
+fn ram16k<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 14],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let (this_addr, remaining_addr): ([_; 2], [_; 12]) = split_2(&address);
+    let demux = Demux1x4::new(alloc, [load.into()], this_addr.ainto());
+    let dmxo = demux.get_out(alloc);
+
+    let reg1 = Ram4k::new_named(
+        alloc,
+        "B0",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[0].into(),
+    );
+    let reg2 = Ram4k::new_named(
+        alloc,
+        "B1",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[1].into(),
+    );
+    let reg3 = Ram4k::new_named(
+        alloc,
+        "B2",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[2].into(),
+    );
+    let reg4 = Ram4k::new_named(
+        alloc,
+        "B3",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[3].into(),
+    );
+
+    let mux = Mux16x4::new(
+        alloc,
+        [
+            reg1.get_out(alloc).out.ainto(),
+            reg2.get_out(alloc).out.ainto(),
+            reg3.get_out(alloc).out.ainto(),
+            reg4.get_out(alloc).out.ainto(),
+        ],
+        this_addr.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+#[chip(clocked)]
+fn ram4k<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 12],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let this_addr: [_; 3] = from_fn(|i| address[i]);
+    let remaining_addr: [_; 9] = from_fn(|i| address[i + 3]);
+    let demux = Demux1x8::new(alloc, [load.into()], this_addr.ainto());
+    let dmxo = demux.get_out(alloc);
+
+    let reg1 = Ram512::new_named(
+        alloc,
+        "B0",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[0].into(),
+    );
+    let reg2 = Ram512::new_named(
+        alloc,
+        "B1",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[1].into(),
+    );
+    let reg3 = Ram512::new_named(
+        alloc,
+        "B2",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[2].into(),
+    );
+    let reg4 = Ram512::new_named(
+        alloc,
+        "B3",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[3].into(),
+    );
+    let reg5 = Ram512::new_named(
+        alloc,
+        "B4",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[4].into(),
+    );
+    let reg6 = Ram512::new_named(
+        alloc,
+        "B5",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[5].into(),
+    );
+    let reg7 = Ram512::new_named(
+        alloc,
+        "B6",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[6].into(),
+    );
+    let reg8 = Ram512::new_named(
+        alloc,
+        "B7",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        dmxo.out[7].into(),
+    );
+
+    let mux = Mux16x8::new(
+        alloc,
+        [
+            reg1.get_out(alloc).out.ainto(),
+            reg2.get_out(alloc).out.ainto(),
+            reg3.get_out(alloc).out.ainto(),
+            reg4.get_out(alloc).out.ainto(),
+            reg5.get_out(alloc).out.ainto(),
+            reg6.get_out(alloc).out.ainto(),
+            reg7.get_out(alloc).out.ainto(),
+            reg8.get_out(alloc).out.ainto(),
+        ],
+        this_addr.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+/// the 8K words of screen memory, each row of the 512x256 pixel display taking 32 words
+#[chip(clocked)]
+fn screen<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 13],
+    load: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let remaining_addr: [_; 12] = from_fn(|i| address[i + 1]);
+    let demux = Demux::new(alloc, load.into(), address[0].into()).get_out(alloc);
+    let reg1 = Ram4k::new_named(
+        alloc,
+        "B0",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        demux.out1.into(),
+    );
+    let reg2 = Ram4k::new_named(
+        alloc,
+        "B1",
+        in_.ainto(),
+        remaining_addr.ainto(),
+        demux.out2.into(),
+    );
+    let mux = Mux16::new(
+        alloc,
+        reg1.get_out(alloc).out.into(),
+        reg2.get_out(alloc).out.into(),
+        address[0].into(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+/// the Hack data memory: addresses below 0x4000 are RAM, 0x4000 up to 0x6000 the
+/// screen, and 0x6000 reads the keyboard, which is an input so whatever drives the
+/// machine can press keys. Writes above the screen are ignored
+#[chip(clocked)]
+fn memory<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    address: [&'a ChipInput<'a>; 15],
+    load: &'a ChipInput<'a>,
+    keyboard: Bus<'a, 16>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let region: [_; 2] = from_fn(|i| address[i]);
+    let ram_addr: [_; 14] = from_fn(|i| address[i + 1]);
+    let screen_addr: [_; 13] = from_fn(|i| address[i + 2]);
+    let demux = Demux1x4::new(alloc, [load.into()], region.ainto()).get_out(alloc);
+    let load_ram = Or::new(alloc, demux.out[0].into(), demux.out[1].into())
+        .get_out(alloc)
+        .out;
+    let ram = Ram16k::new(alloc, in_.ainto(), ram_addr.ainto(), load_ram.into());
+    let screen = Screen::new(alloc, in_.ainto(), screen_addr.ainto(), demux.out[2].into());
+
+    let mux = Mux16x4::new(
+        alloc,
+        [
+            ram.get_out(alloc).out.ainto(),
+            ram.get_out(alloc).out.ainto(),
+            screen.get_out(alloc).out.ainto(),
+            keyboard.inputs(),
+        ],
+        region.ainto(),
+    );
+
+    ArrayLen16 {
+        out: mux.get_out(alloc).out.ainto(),
+    }
+}
+
+/// the Hack instruction memory, holding the program it's built with. Storing 32K words in
+/// gates would take millions of NANDs, so the words are kept in a lookup table, and
+/// `Machine::load_program()` swaps in another program without rebuilding
+#[chip]
+fn rom32k<'a>(
+    alloc: &'a Bump,
+    address: [&'a ChipInput<'a>; 15],
+    #[param] program: &'a [u16],
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let table = LookupTable::new(alloc, program);
+    let word = Lookup::word(alloc, &address.map(Input::from), table);
+    ArrayLen16 {
+        out: word.map(ChipOutputType::from),
+    }
+}
+
+/// The Hack computer's program counter, nand2tetris's PC: on each clock cycle it's
+/// cleared by reset, or else takes in_ on load, or else counts up on inc, and otherwise
+/// holds its value
+#[chip(clocked)]
+fn counter16<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: [&'a ChipInput<'a>; 16],
+    inc: &'a ChipInput<'a>,
+    load: &'a ChipInput<'a>,
+    reset: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let load_or_reset = Or::new(alloc, load.into(), reset.into()).get_out(alloc).out;
+    let load_or_reset_or_inc = Or::new(alloc, load_or_reset.into(), inc.into())
+        .get_out(alloc)
+        .out;
+    let (reg, _): (&Register16, &Incrementer16) = create_subchip(
+        alloc,
+        &|(inc,)| {
+            let loaded_value = Mux16::new(
+                alloc,
+                inc.get_out(alloc).out.into(),
+                in_.into(),
+                load.into(),
+            )
+            .get_out(alloc)
+            .out;
+            let loaded_value = Mux16::new(
+                alloc,
+                loaded_value.into(),
+                from_fn::<_, 16, _>(|_| UserInput::new(alloc)).into(),
+                reset.into(),
+            )
+            .get_out(alloc)
+            .out;
+            Register16Inputs {
+                in_: loaded_value.ainto(),
+                load: load_or_reset_or_inc.into(),
+                clock: alloc.clock().into(),
+            }
+        },
+        &|(reg,)| Incrementer16Inputs {
+            num: reg.get_out(alloc).out.ainto(),
+        },
+    );
+
+    ArrayLen16 {
+        out: reg.get_out(alloc).out.ainto(),
+    }
+}
+
+/// a counter which can also count down. The control lines take priority in the order
+/// reset, load, inc, dec, and with none of them high the count is held
+#[chip(clocked)]
+fn updowncounter16<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: Bus<'a, 16>,
+    load: &'a ChipInput<'a>,
+    inc: &'a ChipInput<'a>,
+    dec: &'a ChipInput<'a>,
+    reset: &'a ChipInput<'a>,
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let reg: &Register16 = DefaultChip::new(alloc);
+    let count = reg.get_out(alloc).out;
+    let incremented = Incrementer16::new(alloc, count.into()).get_out(alloc).out;
+    let minus_one = from_fn::<_, 16, _>(|_| UserInput::from(alloc, true));
+    let decremented = Adder16::new(alloc, count.into(), minus_one.into())
+        .get_out(alloc)
+        .out;
+
+    // the lowest priority line is muxed in first, so each line overrides those below it
+    let next = pipeline!(
+        alloc,
+        count
+            => Mux16(decremented.into(), dec.into())
+            => Mux16(incremented.into(), inc.into())
+            => Mux16(in_, load.into())
+            => Mux16(from_fn::<_, 16, _>(|_| UserInput::new(alloc)).into(), reset.into())
+    );
+    reg.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: next.get_out(alloc).out.ainto(),
+            load: UserInput::from(alloc, true).into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    ArrayLen16 { out: count.ainto() }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct ShiftRegisterOutputs<T> {
+    out: [T; 16],
+    serial_out: T,
+}
+
+/// a register which shifts its bits one place towards the LSB on each cycle with `shift`
+/// high, taking `serial_in` in to the MSB. The LSB is the bit shifted out next, on
+/// `serial_out`. `load` takes priority over `shift`
+#[chip(clocked)]
+fn shiftregister16<'a>(
+    alloc: &'a Clocked<'a>,
+    in_: Bus<'a, 16>,
+    serial_in: &'a ChipInput<'a>,
+    load: &'a ChipInput<'a>,
+    shift: &'a ChipInput<'a>,
+) -> ShiftRegisterOutputs<ChipOutputType<'a>> {
+    let reg: &Register16 = DefaultChip::new(alloc);
+    let bits = reg.get_out(alloc).out;
+    let shifted: [Input; 16] = from_fn(|i| {
+        if i == 0 {
+            serial_in.into()
+        } else {
+            bits[i - 1].into()
+        }
+    });
+    let next = Mux16::new(alloc, shifted.into(), in_, load.into());
+    let load_or_shift = Or::new(alloc, load.into(), shift.into()).get_out(alloc).out;
+    reg.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: next.get_out(alloc).out.ainto(),
+            load: load_or_shift.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    ShiftRegisterOutputs {
+        out: bits.ainto(),
+        serial_out: bits[15].into(),
+    }
+}
+
+/// counts in Gray code, so exactly one output changes each cycle. The code is held in
+/// flip-flops which drive the outputs directly, so unlike the outputs of a binary counter
+/// decoded to Gray code, they don't glitch as the count is worked out. The next code is
+/// the code decoded to binary, incremented and encoded again
+#[chip(clocked, instance(Graycounter4(4), Graycounter8(8)))]
+fn graycountern<'a, const N: usize>(
+    alloc: &'a Clocked<'a>,
+    reset: &'a ChipInput<'a>,
+) -> ArrayLenN<ChipOutputType<'a>, N> {
+    // the chips are built in loops over ranges rather than in closures, so GATE_COUNT can
+    // count them
+    let mut bits = vec![];
+    for _ in 0..N {
+        let bit: &Bit = DefaultChip::new(alloc);
+        bits.push(bit);
+    }
+    let code: [_; N] = from_fn(|i| bits[i].get_out(alloc).out);
+
+    // each binary bit is the parity of the code's bits down to it
+    let mut binary: [Input; N] = code.map(Input::from);
+    for i in 1..N {
+        binary[i] = Xor::new(alloc, binary[i - 1], binary[i])
+            .get_out(alloc)
+            .out
+            .into();
+    }
+    let next = Incrementern::<N>::new(alloc, binary.into())
+        .get_out(alloc)
+        .out;
+    // and each code bit is the XOR of the binary bit and the one above it
+    let mut next_code = vec![next[0]];
+    for i in 1..N {
+        next_code.push(
+            Xor::new(alloc, next[i - 1].into(), next[i].into())
+                .get_out(alloc)
+                .out,
+        );
+    }
+    for i in 0..N {
+        let next_code = pipeline!(alloc, reset => Not() => And(next_code[i].into()));
+        bits[i].set_inputs(
+            alloc,
+            BitInputs {
+                in_: next_code.get_out(alloc).out.into(),
+                load: UserInput::from(alloc, true).into(),
+                clock: alloc.clock().into(),
+            },
+        );
+    }
+
+    ArrayLenN {
+        out: code.map(|bit| bit.into()),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct MultiplierOutputs<T> {
+    high: [T; 16],
+    low: [T; 16],
+    busy: T,
+    done: T,
+}
+
+/// multiplies two unsigned numbers by shift and add, a bit of `b` a cycle. A cycle with
+/// `start` high loads the numbers and raises `busy`, and 16 cycles later `busy` falls and
+/// `done` rises with the product in `high` and `low`. Each cycle adds `a` to the high word
+/// if the low word's LSB is high, then shifts the carry and both words right, so `b` is
+/// shifted out of the low word as the product is shifted in to it
+#[chip(clocked)]
+fn multiplier16<'a>(
+    alloc: &'a Clocked<'a>,
+    a: Bus<'a, 16>,
+    b: Bus<'a, 16>,
+    start: &'a ChipInput<'a>,
+) -> MultiplierOutputs<ChipOutputType<'a>> {
+    let zero = || UserInput::new(alloc);
+    // the words and the busy flag feed back in to themselves, so they're connected once
+    // the step is
+    let high: &Register16 = DefaultChip::new(alloc);
+    let low: &Register16 = DefaultChip::new(alloc);
+    let busy: &Bit = DefaultChip::new(alloc);
+    let high_out = high.get_out(alloc).out;
+    let low_out = low.get_out(alloc).out;
+    let busy_out = busy.get_out(alloc).out;
+
+    let multiplicand = Register16::new(alloc, a.inputs(), start.into())
+        .get_out(alloc)
+        .out;
+    // a loop over a range rather than a map, so GATE_COUNT can count the gates
+    let mut addend = vec![];
+    #[allow(clippy::needless_range_loop)]
+    for i in 0..16 {
+        addend.push(
+            And::new(alloc, multiplicand[i].into(), low_out[15].into())
+                .get_out(alloc)
+                .out,
+        );
+    }
+    let addend: [_; 16] = from_fn(|i| addend[i]);
+    let sum = Addern::<17>::new(
+        alloc,
+        Bus::from([zero()]).concat(Bus::from(high_out)),
+        Bus::from([zero()]).concat(Bus::from(addend)),
+    )
+    .get_out(alloc)
+    .out;
+    let shifted_high: [_; 16] = from_fn(|i| sum[i]);
+    let shifted_low: [_; 16] = from_fn(|i| if i == 0 { sum[16] } else { low_out[i - 1] });
+
+    let step = Or::new(alloc, start.into(), busy_out.into())
+        .get_out(alloc)
+        .out;
+    let high_in = Mux16::new(
+        alloc,
+        shifted_high.into(),
+        from_fn::<_, 16, _>(|_| zero()).into(),
+        start.into(),
+    );
+    high.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: high_in.get_out(alloc).out.ainto(),
+            load: step.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+    let low_in = Mux16::new(alloc, shifted_low.into(), b, start.into());
+    low.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: low_in.get_out(alloc).out.ainto(),
+            load: step.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    // the steps are counted while busy, and busy falls as the 16th is taken. The count
+    // then stays at 16 until the next start
+    let count = Counter16::new(
+        alloc,
+        from_fn::<_, 16, _>(|_| zero()).ainto(),
+        busy_out.into(),
+        zero().into(),
+        start.into(),
+    )
+    .get_out(alloc)
+    .out;
+    let last_step = Andmult4::new(alloc, from_fn::<_, 4, _>(|i| count[i + 12]).ainto())
+        .get_out(alloc)
+        .out;
+    let finishing = And::new(alloc, busy_out.into(), last_step.into())
+        .get_out(alloc)
+        .out;
+    let busy_load = Or::new(alloc, start.into(), finishing.into())
+        .get_out(alloc)
+        .out;
+    busy.set_inputs(
+        alloc,
+        BitInputs {
+            in_: start.into(),
+            load: busy_load.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    MultiplierOutputs {
+        high: high_out.ainto(),
+        low: low_out.ainto(),
+        busy: busy_out.into(),
+        done: count[11].into(),
+    }
+}
+
+#[derive(StructuredData, PartialEq, Debug)]
+struct CpuOutputs<T> {
+    out_m: [T; 16],
+    write_m: T,
+    address_m: [T; 15],
+    pc: [T; 15],
+}
+
+/// the Hack CPU. Instructions with the top bit low load themselves in to A, and the
+/// rest are computations of the form 111a cccc ccdd djjj: `a` picks A or M as the
+/// ALU's y, the c bits are the ALU's zx nx zy ny f no, the d bits store the result in
+/// A, D and M, and the j bits jump to A when the result is <0, =0 or >0
+#[chip(clocked)]
+fn cpu<'a>(
+    alloc: &'a Clocked<'a>,
+    instruction: Bus<'a, 16>,
+    in_m: Bus<'a, 16>,
+    reset: &'a ChipInput<'a>,
+) -> CpuOutputs<ChipOutputType<'a>> {
+    let is_c = instruction[0];
+    // the bits of a C instruction the CPU reads, each low for an A instruction. The
+    // destination and jump bits are gated in a loop over a range rather than in a closure,
+    // so GATE_COUNT can count the gates
+    let a_bit = And::new(alloc, is_c, instruction[3]).get_out(alloc).out;
+    let mut dest_and_jump = vec![];
+    for i in 10..16 {
+        dest_and_jump.push(And::new(alloc, is_c, instruction[i]).get_out(alloc).out);
+    }
+    let bit = |index: usize| dest_and_jump[index - 10];
+
+    // A, D and the ALU feed each other, so the registers are connected once the ALU is
+    let a_reg: &Register16 = DefaultChip::new(alloc);
+    let d_reg: &Register16 = DefaultChip::new(alloc);
+    let y = Mux16::new(alloc, a_reg.get_out(alloc).out.into(), in_m, a_bit.into());
+    let alu = Alu::new(
+        alloc,
+        d_reg.get_out(alloc).out.into(),
+        y.get_out(alloc).out.into(),
+        instruction[4],
+        instruction[6],
+        instruction[5],
+        instruction[7],
+        instruction[8],
+        instruction[9],
+    )
+    .get_out(alloc);
+
+    let a_in = Mux16::new(alloc, instruction, alu.out.into(), is_c);
+    let is_a = Not::new(alloc, is_c).get_out(alloc).out;
+    let load_a = Or::new(alloc, is_a.into(), bit(10).into())
+        .get_out(alloc)
+        .out;
+    a_reg.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: a_in.get_out(alloc).out.ainto(),
+            load: load_a.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+    d_reg.set_inputs(
+        alloc,
+        Register16Inputs {
+            in_: alu.out.ainto(),
+            load: bit(11).into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    let positive = pipeline!(alloc, alu.zr => Or(alu.ng.into()) => Not());
+    let jlt = And::new(alloc, bit(13).into(), alu.ng.into())
+        .get_out(alloc)
+        .out;
+    let jeq = And::new(alloc, bit(14).into(), alu.zr.into())
+        .get_out(alloc)
+        .out;
+    let jgt = And::new(alloc, bit(15).into(), positive.get_out(alloc).out.into())
+        .get_out(alloc)
+        .out;
+    let jump = pipeline!(alloc, jlt => Or(jeq.into()) => Or(jgt.into()));
+    let pc = Counter16::new(
+        alloc,
+        a_reg.get_out(alloc).out.ainto(),
+        UserInput::from(alloc, true).into(),
+        jump.get_out(alloc).out.into(),
+        reset.into(),
+    )
+    .get_out(alloc)
+    .out;
+
+    let address = a_reg.get_out(alloc).out;
+    CpuOutputs {
+        out_m: alu.out.ainto(),
+        write_m: bit(12).into(),
+        address_m: from_fn(|i| address[i + 1].into()),
+        pc: from_fn(|i| pc[i + 1].into()),
+    }
+}
+
+/// the Hack computer, running the program in its ROM from the first instruction after a
+/// reset. Its outputs are the CPU's, for watching it work, and `keyboard` is the code of
+/// the key held down, or 0 for none
+#[chip(clocked)]
+fn computer<'a>(
+    alloc: &'a Clocked<'a>,
+    reset: &'a ChipInput<'a>,
+    keyboard: Bus<'a, 16>,
+    #[param] program: &'a [u16],
+) -> CpuOutputs<ChipOutputType<'a>> {
+    // the CPU reads memory at the address it puts out, so it's connected once the
+    // memory is
+    let cpu: &Cpu = DefaultChip::new(alloc);
+    let cpu_out = cpu.get_out(alloc);
+    let rom = Rom32k::new(alloc, cpu_out.pc.ainto(), program);
+    let memory = Memory::new(
+        alloc,
+        cpu_out.out_m.ainto(),
+        cpu_out.address_m.ainto(),
+        cpu_out.write_m.into(),
+        keyboard,
+    );
+    cpu.set_inputs(
+        alloc,
+        CpuInputs {
+            instruction: rom.get_out(alloc).out.ainto(),
+            in_m: memory.get_out(alloc).out.ainto(),
+            reset: reset.into(),
+            clock: alloc.clock().into(),
+        },
+    );
+
+    CpuOutputs {
+        out_m: cpu_out.out_m.ainto(),
+        write_m: cpu_out.write_m.into(),
+        address_m: cpu_out.address_m.ainto(),
+        pc: cpu_out.pc.ainto(),
+    }
+}
+
+/// the ROM without a program, for the chip library, which can load one in to it with
+/// `Machine::load_program()`
+#[chip]
+fn blankrom32k<'a>(
+    alloc: &'a Bump,
+    address: [&'a ChipInput<'a>; 15],
+) -> ArrayLen16<ChipOutputType<'a>> {
+    let rom = Rom32k::new(alloc, address.ainto(), &[]);
+    ArrayLen16 {
+        out: rom.get_out(alloc).out.ainto(),
+    }
+}
+
+/// the computer without a program, which the chip library can load one in to like the
+/// ROM's
+#[chip(clocked)]
+fn blankcomputer<'a>(
+    alloc: &'a Clocked<'a>,
+    reset: &'a ChipInput<'a>,
+    keyboard: Bus<'a, 16>,
+) -> CpuOutputs<ChipOutputType<'a>> {
+    let computer = Computer::new(alloc, reset.into(), keyboard, &[]);
+    let out = computer.get_out(alloc);
+    CpuOutputs {
+        out_m: out.out_m.ainto(),
+        write_m: out.write_m.into(),
+        address_m: out.address_m.ainto(),
+        pc: out.pc.ainto(),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)] // truth tables read better with explicit bools
+mod tests {
+    use crate::*;
+    use bumpalo::Bump;
+    use hdl::{
+        bits::{self, BitOrder},
+        state::MachineState,
+        timing::Timing,
+        trace::Trace,
+        vcd::VcdSignal,
+        Logic, Machine,
+    };
+    use std::{cell::RefCell, sync::Mutex};
+
+    // Machines with 16K words of RAM take gigabytes to build, so tests building them
+    // take turns rather than running out of memory together
+    static LARGE_MACHINE: Mutex<()> = Mutex::new(());
+
+    fn large_machine() -> std::sync::MutexGuard<'static, ()> {
+        // a failed test poisons the lock, but doesn't leave anything behind
+        LARGE_MACHINE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // behavioural models the chips are checked against, see #[chip(model = ...)]
+    pub(super) fn xor_model(inputs: XorInputs<bool>) -> UnaryChipOutput<bool> {
+        UnaryChipOutput {
+            out: inputs.in1 != inputs.in2,
+        }
+    }
+
+    pub(super) fn mux_model(inputs: MuxInputs<bool>) -> UnaryChipOutput<bool> {
+        UnaryChipOutput {
+            out: if inputs.sel { inputs.in2 } else { inputs.in1 },
+        }
+    }
+
+    pub(super) fn demux_model(inputs: DemuxInputs<bool>) -> BinaryChipOutput<bool> {
+        BinaryChipOutput {
+            out1: inputs.in_ && !inputs.sel,
+            out2: inputs.in_ && inputs.sel,
+        }
+    }
+
+    pub(super) fn negate16_model(inputs: Negate16Inputs<bool>) -> ArrayLen16<bool> {
+        ArrayLen16::from_u16(inputs.num_to_u16().wrapping_neg())
+    }
+
+    pub(super) fn subtractor16_model(inputs: Subtractor16Inputs<bool>) -> ArrayLen16<bool> {
+        ArrayLen16::from_u16(inputs.num1_to_u16().wrapping_sub(inputs.num2_to_u16()))
+    }
+
+    fn adder_model<const N: usize>(num1: [bool; N], num2: [bool; N]) -> ArrayLenN<bool, N> {
+        ArrayLenN {
+            out: bits::from_u64(
+                bits::to_u64(num1, BitOrder::MsbFirst) + bits::to_u64(num2, BitOrder::MsbFirst),
+                BitOrder::MsbFirst,
+            ),
+        }
+    }
+
+    // the truth table every width of adder is checked against: both zero, the half
+    // adder's carry, a full adder's, the MSB, and overflow past it
+    fn check_adder<const N: usize>(mut add: impl FnMut([bool; N], [bool; N]) -> [bool; N]) {
+        let max = u64::MAX >> (64 - N);
+        for (num1, num2) in [(0, 0), (1, 1), (3, 3), (1, max >> 1), (max, 1), (max, max)] {
+            assert_eq!(
+                add(
+                    bits::from_u64(num1, BitOrder::MsbFirst),
+                    bits::from_u64(num2, BitOrder::MsbFirst)
+                ),
+                bits::from_u64::<N>(num1 + num2, BitOrder::MsbFirst),
+                "{num1} + {num2} at {N} bits"
+            );
+        }
+    }
+
+    fn check_incrementer<const N: usize>(mut increment: impl FnMut([bool; N]) -> [bool; N]) {
+        let max = u64::MAX >> (64 - N);
+        for num in [0, 1, 2, max - 1, max] {
+            assert_eq!(
+                increment(bits::from_u64(num, BitOrder::MsbFirst)),
+                bits::from_u64::<N>(num + 1, BitOrder::MsbFirst),
+                "{num} + 1 at {N} bits"
+            );
+        }
+    }
+
+    #[test]
+    fn registry_paths_are_unique_and_namespaced() {
+        let library = registry::library();
+        let namespaces = library.by_namespace();
+        assert_eq!(
+            namespaces.keys().copied().collect::<Vec<_>>(),
+            [
+                registry::ARITH,
+                registry::COMPUTER,
+                registry::GATES,
+                registry::MEMORY,
+                registry::SEQ
+            ]
+        );
+
+        let paths = namespaces
+            .values()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        let unique_paths = paths.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(paths.len(), unique_paths.len());
+        assert!(library.find("arith::Alu").is_some());
+    }
+
+    #[test]
+    fn library_truth_tables_cover_small_chips_and_turn_down_large_ones() {
+        let library = registry::library();
+        let table = (library.find("gates::Xor").unwrap().truth_table)().unwrap();
+        assert_eq!(table.inputs, ["in1", "in2"]);
+        assert_eq!(
+            table
+                .rows
+                .iter()
+                .map(|row| (row.inputs.clone(), row.outputs.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (vec![false, false], vec![Logic::Low]),
+                (vec![false, true], vec![Logic::High]),
+                (vec![true, false], vec![Logic::High]),
+                (vec![true, true], vec![Logic::Low]),
+            ]
+        );
+
+        assert!((library.find("memory::Ram8").unwrap().truth_table)().is_err());
+    }
+
+    #[test]
+    fn library_graphs_can_be_exported_for_scripts() {
+        let library = registry::library();
+        let file = std::env::temp_dir().join(format!("export-{}.dot", std::process::id()));
+        let file_name = file.display().to_string();
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let export = ["gates::And", "--format", "dot", "--expand", "all"];
+        registry::export_graph(
+            &library,
+            &args(&[&export[..], &["--output", &file_name]].concat()),
+        )
+        .unwrap();
+        let dot = std::fs::read_to_string(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert!(
+            dot.contains("label=\"Not\""),
+            "the And's Not should be expanded"
+        );
+
+        for bad in [
+            &["gates::Nope"][..],
+            &["gates::And", "--format", "svg"],
+            &["gates::And", "--expand-depth"],
+            &["gates::And", "--colour", "red"],
+        ] {
+            assert!(registry::export_graph(&library, &args(bad)).is_err());
+        }
+    }
+
+    #[test]
+    fn the_library_chips_gates_can_be_counted_from_their_bodies() {
+        let counts = [
+            ("Not16", Not16::GATE_COUNT),
+            ("And16", And16::GATE_COUNT),
+            ("Or2", Or2::GATE_COUNT),
+            ("Mux16", Mux16::GATE_COUNT),
+            ("Demux16", Demux16::GATE_COUNT),
+            ("Mux16x4", Mux16x4::GATE_COUNT),
+            ("Mux16x8", Mux16x8::GATE_COUNT),
+            ("Demux1x4", Demux1x4::GATE_COUNT),
+            ("Demux1x8", Demux1x8::GATE_COUNT),
+            ("Demux16x8", Demux16x8::GATE_COUNT),
+            ("Andmult4", Andmult4::GATE_COUNT),
+            ("Ormult16", Ormult16::GATE_COUNT),
+            ("Incrementer16", Incrementer16::GATE_COUNT),
+            ("Negate16", Negate16::GATE_COUNT),
+            ("Subtractor16", Subtractor16::GATE_COUNT),
+            ("Alu", Alu::GATE_COUNT),
+            ("Aluex", Aluex::GATE_COUNT),
+            ("Bit", Bit::GATE_COUNT),
+            ("Register16", Register16::GATE_COUNT),
+            ("Counter16", Counter16::GATE_COUNT),
+            ("Updowncounter16", Updowncounter16::GATE_COUNT),
+            ("Shiftregister16", Shiftregister16::GATE_COUNT),
+            ("Graycounter8", Graycounter8::GATE_COUNT),
+            ("Multiplier16", Multiplier16::GATE_COUNT),
+            ("Ram8", Ram8::GATE_COUNT),
+            ("Ram16k", Ram16k::GATE_COUNT),
+            ("Screen", Screen::GATE_COUNT),
+            ("Memory", Memory::GATE_COUNT),
+            ("Cpu", Cpu::GATE_COUNT),
+            ("Computer", Computer::GATE_COUNT),
+            ("Blankcomputer", Blankcomputer::GATE_COUNT),
+        ];
+        let uncounted = counts
+            .iter()
+            .filter(|(_, count)| count.is_none())
+            .map(|(chip, _)| *chip)
+            .collect::<Vec<_>>();
+        assert_eq!(uncounted, Vec::<&str>::new());
+
+        // a 16 bit gate is 16 of the gate, and a mux tree a mux for each word but one
+        assert_eq!(Not16::GATE_COUNT, Some(16));
+        assert_eq!(Mux16x8::GATE_COUNT, Some(7 * Mux16::GATE_COUNT.unwrap()));
+        assert_eq!(
+            Demux16x8::GATE_COUNT,
+            Some(7 * Demux16::GATE_COUNT.unwrap())
+        );
+    }
+
+    #[test]
+    fn when_split_2_is_passed_consistent_const_vars_the_array_is_divided_with_no_remainder() {
+        let (sub1, sub2): ([u32; 3], [u32; 2]) = split_2(&[1, 2, 3, 4, 5]);
+        assert_eq!(sub1, [1, 2, 3]);
+        assert_eq!(sub2, [4, 5]);
+    }
+
+    #[test]
+    fn adder16_is_built_from_a_half_adder_and_a_chain_of_full_adders() {
+        assert_eq!(Halfadder::GATE_COUNT, Some(10));
+        assert_eq!(Fulladder::GATE_COUNT, Some(23));
+        assert_eq!(Adder16::GATE_COUNT, Some(10 + 15 * 23));
+
+        // the final carry is dropped, so the last full adder's carry logic isn't reached
+        // from the outputs of a machine
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Adder16::from).unwrap();
+        assert_eq!(machine.gate_count(), 10 + 15 * 23 - 7);
+    }
+
+    #[test]
+    fn counter16_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+
+        // load bit
+        let mut inputs = Counter16Inputs {
+            in_: bits::from_i16(123, BitOrder::MsbFirst),
+            inc: false,
+            load: true,
+            reset: false,
+            clock: true,
+        };
+
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(123, BitOrder::MsbFirst));
+
+        // reset bit
+        inputs.in_ = bits::from_i16(321, BitOrder::MsbFirst);
+        inputs.reset = true;
+        inputs.clock = true;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(123, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+
+        // increment bit
+        let mut inputs = Counter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            inc: true,
+            load: false,
+            reset: false,
+            clock: true,
+        };
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(1, BitOrder::MsbFirst));
+
+        inputs.clock = true;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(1, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(2, BitOrder::MsbFirst));
+
+        // maintenance
+        let mut inputs = Counter16Inputs {
+            in_: bits::from_i16(456, BitOrder::MsbFirst),
+            inc: false,
+            load: false,
+            reset: false,
+            clock: true,
+        };
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(2, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(2, BitOrder::MsbFirst));
+
+        inputs.clock = true;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(2, BitOrder::MsbFirst));
+
+        inputs.clock = false;
+        let res = machine.process(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(2, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn when_counter16_controls_are_combined_reset_beats_load_and_load_beats_inc() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+        let mut cycle = |in_, inc, load, reset| {
+            machine
+                .cycle(Counter16Inputs {
+                    in_: bits::from_i16(in_, BitOrder::MsbFirst),
+                    inc,
+                    load,
+                    reset,
+                    clock: false,
+                })
+                .unwrap()
+                .out
+        };
+
+        assert_eq!(
+            cycle(7, false, true, false),
+            bits::from_i16(7, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            cycle(9, true, true, true),
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            cycle(9, true, true, false),
+            bits::from_i16(9, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            cycle(9, true, false, true),
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            cycle(9, true, false, false),
+            bits::from_i16(1, BitOrder::MsbFirst)
+        );
+
+        // counting wraps around past the largest word
+        assert_eq!(
+            cycle(-1, false, true, false),
+            bits::from_i16(-1, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            cycle(0, true, false, false),
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+    }
+
+    // a Counter16 machine cycled with the word and control lines in_, inc, load and reset,
+    // giving the count after each cycle
+    fn counter16_cycles(alloc: &Bump) -> impl FnMut(u16, bool, bool, bool) -> u16 + '_ {
+        let mut machine = Machine::new(alloc, Counter16::from).unwrap();
+        move |in_, inc, load, reset| {
+            let res = machine
+                .cycle(Counter16Inputs {
+                    in_: bits::from_u16(in_, BitOrder::MsbFirst),
+                    inc,
+                    load,
+                    reset,
+                    clock: false,
+                })
+                .unwrap();
+            bits::to_u16(res.out, BitOrder::MsbFirst)
+        }
+    }
+
+    #[test]
+    fn when_counter16_has_no_control_high_it_holds_its_count_whatever_its_input() {
+        let alloc = Bump::new();
+        let mut cycle = counter16_cycles(&alloc);
+
+        assert_eq!(cycle(0x1234, false, true, false), 0x1234);
+        for in_ in [0, 0xffff, 0x4321, 0x1235] {
+            assert_eq!(cycle(in_, false, false, false), 0x1234);
+        }
+        // and counts on from the count it held
+        assert_eq!(cycle(0, true, false, false), 0x1235);
+        assert_eq!(cycle(0, false, false, false), 0x1235);
+    }
+
+    #[test]
+    fn when_counter16_counts_past_0xffff_it_wraps_to_zero_and_counts_on() {
+        let alloc = Bump::new();
+        let mut cycle = counter16_cycles(&alloc);
+
+        assert_eq!(cycle(0xfffe, false, true, false), 0xfffe);
+        assert_eq!(cycle(0, true, false, false), 0xffff);
+        assert_eq!(cycle(0, true, false, false), 0);
+        assert_eq!(cycle(0, true, false, false), 1);
+        // a count loaded at the top wraps the same way
+        assert_eq!(cycle(0xffff, true, true, false), 0xffff);
+        assert_eq!(cycle(0, true, false, false), 0);
+    }
+
+    #[test]
+    fn when_counter16_is_loaded_or_reset_while_counting_it_counts_on_from_the_new_count() {
+        let alloc = Bump::new();
+        let mut cycle = counter16_cycles(&alloc);
+
+        assert_eq!(cycle(0, false, false, true), 0);
+        assert_eq!(cycle(100, true, false, false), 1);
+        assert_eq!(cycle(100, true, false, false), 2);
+        // load beats inc, so the word is taken rather than the count going up
+        assert_eq!(cycle(100, true, true, false), 100);
+        assert_eq!(cycle(200, true, false, false), 101);
+        // reset beats load and inc
+        assert_eq!(cycle(200, true, true, true), 0);
+        assert_eq!(cycle(200, true, false, false), 1);
+        // reset beats inc on its own too, and holds the count at zero while it's high
+        assert_eq!(cycle(0, true, false, true), 0);
+        assert_eq!(cycle(0, true, false, true), 0);
+        assert_eq!(cycle(0, true, false, false), 1);
+    }
+
+    #[test]
+    fn when_the_cpu_runs_instructions_it_computes_stores_and_jumps() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Cpu::from).unwrap();
+        let mut run = |instruction: u16, in_m: i16, reset: bool| {
+            let inputs = CpuInputs {
+                instruction: bits::from_i16(instruction as i16, BitOrder::MsbFirst),
+                in_m: bits::from_i16(in_m, BitOrder::MsbFirst),
+                reset,
+                clock: false,
+            };
+            machine.cycle_settled(inputs, 8).unwrap()
+        };
+
+        // @5 loads A and moves on to the next instruction
+        let res = run(5, 0, true);
+        assert_eq!(res.pc, bits::from_i16(0, BitOrder::MsbFirst));
+        let res = run(5, 0, false);
+        assert_eq!(
+            (res.address_m, res.pc, res.write_m),
+            (
+                bits::from_i16(5, BitOrder::MsbFirst),
+                bits::from_i16(1, BitOrder::MsbFirst),
+                false
+            )
+        );
+
+        // D=A, then D=D-A with A=7
+        run(0xEC10, 0, false);
+        run(7, 0, false);
+        let res = run(0xE4D0, 0, false);
+        assert_eq!(res.out_m, bits::from_i16(-9, BitOrder::MsbFirst));
+        assert_eq!(res.pc, bits::from_i16(4, BitOrder::MsbFirst));
+
+        // M=D writes D to the address in A
+        let res = run(0xE308, 0, false);
+        assert_eq!(
+            (res.out_m, res.write_m),
+            (bits::from_i16(-2, BitOrder::MsbFirst), true)
+        );
+        assert_eq!(res.address_m, bits::from_i16(7, BitOrder::MsbFirst));
+
+        // D=M reads memory, and A=-1 stores a computation in A
+        let res = run(0xFC10, 42, false);
+        assert_eq!(res.out_m, bits::from_i16(42, BitOrder::MsbFirst));
+        let res = run(0xEEA0, 0, false);
+        assert_eq!(res.address_m, bits::from_i16(0x7fff, BitOrder::MsbFirst));
+        assert_eq!(res.pc, bits::from_i16(7, BitOrder::MsbFirst));
+
+        // D;JLT and D;JEQ aren't taken with D=42, but D;JGT jumps to A
+        run(20, 0, false);
+        assert_eq!(
+            run(0xE304, 0, false).pc,
+            bits::from_i16(9, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            run(0xE302, 0, false).pc,
+            bits::from_i16(10, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            run(0xE301, 0, false).pc,
+            bits::from_i16(20, BitOrder::MsbFirst)
+        );
+
+        // 0;JMP always jumps, and reset returns to the first instruction
+        run(3, 0, false);
+        assert_eq!(
+            run(0xEA87, 0, false).pc,
+            bits::from_i16(3, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            run(0xEA87, 0, true).pc,
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+    }
+
+    #[test]
+    fn when_a_multiplier16_is_started_it_is_busy_until_the_product_is_done() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Multiplier16::from).unwrap();
+        let mut cycle = |a: u16, b: u16, start: bool| {
+            let inputs = Multiplier16Inputs {
+                a: bits::from_u16(a, BitOrder::MsbFirst),
+                b: bits::from_u16(b, BitOrder::MsbFirst),
+                start,
+                clock: false,
+            };
+            machine.cycle_settled(inputs, 8).unwrap()
+        };
+
+        for (a, b) in [(3, 5), (1234, 5678), (0xffff, 0xffff), (0, 0xbeef)] {
+            let res = cycle(a, b, true);
+            assert!(res.busy && !res.done);
+            // the inputs are only read on starting
+            let mut cycles = 0;
+            let res = loop {
+                let res = cycle(0, 0, false);
+                cycles += 1;
+                if res.done {
+                    break res;
+                }
+                assert!(res.busy && cycles < 16);
+            };
+            assert_eq!(cycles, 16);
+            assert!(!res.busy);
+            let product = u32::from(bits::to_u16(res.high, BitOrder::MsbFirst)) << 16
+                | u32::from(bits::to_u16(res.low, BitOrder::MsbFirst));
+            assert_eq!(product, u32::from(a) * u32::from(b), "{a} * {b}");
+
+            // the product is held until the next start
+            let res = cycle(0, 0, false);
+            assert!(res.done && !res.busy);
+            assert_eq!(
+                bits::to_u16(res.low, BitOrder::MsbFirst),
+                (a.wrapping_mul(b))
+            );
+        }
+    }
+
+    #[test]
+    fn when_a_counter16_is_cycled_by_the_machine_clock_it_counts_up() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+        assert_eq!(machine.get_clock(), Some("clock".to_owned()));
+
+        let mut inputs = Counter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            inc: false,
+            load: false,
+            reset: true,
+            clock: false,
+        };
+        let res = machine.cycle(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+
+        inputs.reset = false;
+        inputs.inc = true;
+        for i in 1..=5 {
+            let res = machine.cycle(inputs.clone()).unwrap();
+            assert_eq!(res.out, bits::from_i16(i, BitOrder::MsbFirst));
+        }
+    }
+
+    #[test]
+    fn updowncounter16_control_lines_take_priority_in_order() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Updowncounter16::from).unwrap();
+        let mut inputs = Updowncounter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            load: false,
+            inc: false,
+            dec: false,
+            reset: true,
+            clock: false,
+        };
+        assert_eq!(
+            machine.cycle(inputs.clone()).unwrap().out,
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+
+        // every combination of the lines, each from a count of 100
+        for lines in 0..16 {
+            let [reset, load, inc, dec] = from_fn(|i| lines >> (3 - i) & 1 == 1);
+            machine
+                .cycle(Updowncounter16Inputs {
+                    in_: bits::from_i16(100, BitOrder::MsbFirst),
+                    load: true,
+                    inc: false,
+                    dec: false,
+                    reset: false,
+                    clock: false,
+                })
+                .unwrap();
+
+            inputs = Updowncounter16Inputs {
+                in_: bits::from_i16(-7, BitOrder::MsbFirst),
+                load,
+                inc,
+                dec,
+                reset,
+                clock: false,
+            };
+            let expected = if reset {
+                0
+            } else if load {
+                -7
+            } else if inc {
+                101
+            } else if dec {
+                99
+            } else {
+                100
+            };
+            let res = machine.cycle(inputs.clone()).unwrap();
+            assert_eq!(
+                res.out,
+                bits::from_i16(expected, BitOrder::MsbFirst),
+                "reset {reset} load {load} inc {inc} dec {dec}"
+            );
+        }
+
+        // counting wraps around in both directions
+        inputs = Updowncounter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            load: false,
+            inc: false,
+            dec: true,
+            reset: false,
+            clock: false,
+        };
+        machine
+            .cycle(Updowncounter16Inputs {
+                reset: true,
+                ..inputs.clone()
+            })
+            .unwrap();
+        assert_eq!(
+            machine.cycle(inputs.clone()).unwrap().out,
+            bits::from_i16(-1, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.cycle(inputs.clone()).unwrap().out,
+            bits::from_i16(-2, BitOrder::MsbFirst)
+        );
+        inputs.dec = false;
+        inputs.inc = true;
+        assert_eq!(
+            machine.cycle(inputs.clone()).unwrap().out,
+            bits::from_i16(-1, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.cycle(inputs.clone()).unwrap().out,
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+    }
+
+    #[test]
+    fn shiftregister16_shifts_a_word_out_as_another_is_shifted_in() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Shiftregister16::from).unwrap();
+        let mut inputs = Shiftregister16Inputs {
+            in_: bits::from_u16(0xb00f, BitOrder::MsbFirst),
+            serial_in: false,
+            load: true,
+            shift: false,
+            clock: false,
+        };
+        let res = machine.cycle(inputs.clone()).unwrap();
+        assert_eq!(bits::to_u16(res.out, BitOrder::MsbFirst), 0xb00f);
+        assert!(res.serial_out);
+
+        // the word held is read out LSB first, while 0x1234 is shifted in MSB last
+        inputs.load = false;
+        inputs.shift = true;
+        for bit in 0..16 {
+            let expected_out = 0xb00fu16 >> bit & 1 == 1;
+            let res = machine.process(inputs.clone()).unwrap();
+            assert_eq!(res.serial_out, expected_out, "bit {bit}");
+
+            inputs.serial_in = 0x1234 >> bit & 1 == 1;
+            let res = machine.cycle(inputs.clone()).unwrap();
+            let shifted_in = (0x1234u32 << 16 | 0xb00f) >> (bit + 1);
+            assert_eq!(bits::to_u16(res.out, BitOrder::MsbFirst), shifted_in as u16);
+        }
+
+        // without shift or load the word is held, and load takes priority over shift
+        inputs.shift = false;
+        let res = machine.cycle(inputs.clone()).unwrap();
+        assert_eq!(bits::to_u16(res.out, BitOrder::MsbFirst), 0x1234);
+        inputs.shift = true;
+        inputs.load = true;
+        inputs.in_ = bits::from_u16(0x8001, BitOrder::MsbFirst);
+        let res = machine.cycle(inputs.clone()).unwrap();
+        assert_eq!(bits::to_u16(res.out, BitOrder::MsbFirst), 0x8001);
+    }
+
+    #[test]
+    fn graycounters_change_exactly_one_bit_per_cycle() {
+        // every code is counted through once before the count wraps
+        fn check<const N: usize>(mut cycle: impl FnMut(bool) -> [bool; N]) {
+            let first = cycle(true);
+            assert_eq!(first, [false; N]);
+            let mut seen = vec![first];
+            let mut code = first;
+            for _ in 0..1 << N {
+                let next = cycle(false);
+                let changed = (0..N).filter(|&i| next[i] != code[i]).count();
+                assert_eq!(changed, 1, "{code:?} to {next:?}");
+                code = next;
+                seen.push(code);
+            }
+            assert_eq!(code, first);
+            seen.pop();
+            seen.sort();
+            seen.dedup();
+            assert_eq!(seen.len(), 1 << N);
+        }
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Graycounter4::from).unwrap();
+        check(|reset| {
+            let inputs = Graycounter4Inputs {
+                reset,
+                clock: false,
+            };
+            machine.cycle_settled(inputs, 8).unwrap().out
+        });
+        let mut machine = Machine::new(&alloc, Graycounter8::from).unwrap();
+        check(|reset| {
+            let inputs = Graycounter8Inputs {
+                reset,
+                clock: false,
+            };
+            machine.cycle_settled(inputs, 8).unwrap().out
+        });
+    }
+
+    #[test]
+    fn when_a_counter16_is_reset_it_counts_from_zero() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+        let inputs = Counter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            inc: true,
+            load: false,
+            reset: false,
+            clock: false,
+        };
+        for _ in 0..3 {
+            machine.cycle(inputs.clone()).unwrap();
+        }
+
+        machine.reset().unwrap();
+        let res = machine.cycle(inputs.clone()).unwrap();
+        assert_eq!(res.out, bits::from_i16(1, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn when_a_register16_without_a_reset_input_is_reset_its_state_is_cleared() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Register16::from).unwrap();
+        assert_eq!(machine.get_reset(), None);
+        let res = machine
+            .cycle(Register16Inputs {
+                in_: bits::from_i16(123, BitOrder::MsbFirst),
+                load: true,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(res.out, bits::from_i16(123, BitOrder::MsbFirst));
+
+        machine.reset().unwrap();
+        let res = machine
+            .process(Register16Inputs {
+                in_: bits::from_i16(123, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn ram16k_when_a_value_is_stored_it_can_be_retrieved_again() {
+        let _turn = large_machine();
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram16k::from).unwrap();
+        let number = bits::from_i16(1092, BitOrder::MsbFirst);
+        let out = machine
+            .process(Ram16kInputs {
+                in_: number,
+                address: bits::from_i16(13987, BitOrder::MsbFirst),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, [false; 16]);
+        let out = machine
+            .process(Ram16kInputs {
+                in_: [false; 16],
+                address: bits::from_i16(13987, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, number);
+    }
+
+    // a ROM and a computer built with a program, as a chip with a parameter is only built
+    // in to a machine by a chip giving it a value
+    #[chip]
+    fn programrom<'a>(
+        alloc: &'a Bump,
+        address: [&'a ChipInput<'a>; 15],
+    ) -> ArrayLen16<ChipOutputType<'a>> {
+        let rom = Rom32k::new(alloc, address.ainto(), &[0x1234, 0xec10, 7]);
+        ArrayLen16 {
+            out: rom.get_out(alloc).out.ainto(),
+        }
+    }
+
+    // RAM[0] = 2 + 3, then read RAM[0] back
+    #[chip(clocked)]
+    fn addingcomputer<'a>(
+        alloc: &'a Clocked<'a>,
+        reset: &'a ChipInput<'a>,
+    ) -> CpuOutputs<ChipOutputType<'a>> {
+        let program = &[
+            2, 0xec10, // @2, D=A
+            3, 0xe090, // @3, D=D+A
+            0, 0xe308, // @0, M=D
+            0xfc10, // D=M
+        ];
+        let keyboard = from_fn::<_, 16, _>(|_| UserInput::new(alloc)).into();
+        let computer = Computer::new(alloc, reset.into(), keyboard, program);
+        let out = computer.get_out(alloc);
+        CpuOutputs {
+            out_m: out.out_m.ainto(),
+            write_m: out.write_m.into(),
+            address_m: out.address_m.ainto(),
+            pc: out.pc.ainto(),
+        }
+    }
+
+    #[test]
+    fn rom32k_reads_the_words_of_its_program() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Programrom::from).unwrap();
+        let mut read = |address: i16| {
+            machine
+                .process(ProgramromInputs {
+                    address: bits::from_i16(address, BitOrder::MsbFirst),
+                })
+                .unwrap()
+                .out
+        };
+        assert_eq!(read(0), bits::from_i16(0x1234, BitOrder::MsbFirst));
+        assert_eq!(
+            read(1),
+            bits::from_i16(0xec10u16 as i16, BitOrder::MsbFirst)
+        );
+        assert_eq!(read(2), bits::from_i16(7, BitOrder::MsbFirst));
+        // addresses past the end of the program read as zero
+        assert_eq!(read(3), bits::from_i16(0, BitOrder::MsbFirst));
+        assert_eq!(read(0x7fff), bits::from_i16(0, BitOrder::MsbFirst));
+
+        // a loaded program replaces the one it was built with, and the ROM costs no gates
+        machine.load_program(&[5]).unwrap();
+        let res = machine.process(ProgramromInputs {
+            address: bits::from_i16(0, BitOrder::MsbFirst),
+        });
+        assert_eq!(res.unwrap().out, bits::from_i16(5, BitOrder::MsbFirst));
+        assert_eq!(machine.gate_count(), 0);
+    }
+
+    #[test]
+    fn when_a_machine_has_no_lookup_table_a_program_cant_be_loaded() {
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Not::from).unwrap();
+        assert_eq!(
+            machine.load_program(&[1]),
+            Err(hdl::Error::ProgramMemory { tables: 0 })
+        );
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        ignore = "building 16K words of RAM takes minutes without optimisations"
+    )]
+    fn when_the_computer_runs_a_program_it_stores_the_result_in_ram() {
+        let _turn = large_machine();
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Addingcomputer::from).unwrap();
+        // RAM[0] is the first register of the first bank all the way down, read from out-15,
+        // its MSB. The computer's netlist is too large to find it in
+        let ram0 = (0..16)
+            .rev()
+            .map(|bit| {
+                format!("Addingcomputer0/Computer0/Memory0/Ram16k0/B0/B0/B0/B0/R0/out-{bit}")
+            })
+            .collect::<Vec<_>>();
+        let ram0 = machine
+            .find_named_outputs(&ram0.iter().map(String::as_str).collect::<Vec<_>>())
+            .unwrap();
+        let read_ram0 = || -> [bool; 16] { from_fn(|bit| ram0[bit].value() == Logic::High) };
+        let mut cycle = |reset| {
+            let inputs = AddingcomputerInputs {
+                reset,
+                clock: false,
+            };
+            machine.cycle_settled(inputs, 8).unwrap()
+        };
+
+        // the outputs are the CPU's as it runs the instruction at pc
+        assert_eq!(cycle(true).pc, bits::from_i16(0, BitOrder::MsbFirst));
+        for _ in 0..4 {
+            cycle(false);
+        }
+        assert_ne!(read_ram0(), bits::from_i16(5, BitOrder::MsbFirst));
+        let res = cycle(false);
+        assert_eq!(res.pc, bits::from_i16(5, BitOrder::MsbFirst));
+        assert_eq!(
+            (res.out_m, res.write_m),
+            (bits::from_i16(5, BitOrder::MsbFirst), true)
+        );
+        assert_eq!(res.address_m, bits::from_i16(0, BitOrder::MsbFirst));
+
+        // D=M computes the word now stored at address 0
+        let res = cycle(false);
+        assert_eq!(res.pc, bits::from_i16(6, BitOrder::MsbFirst));
+        assert_eq!(
+            (res.out_m, res.write_m),
+            (bits::from_i16(5, BitOrder::MsbFirst), false)
+        );
+        assert_eq!(read_ram0(), bits::from_i16(5, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn memory_addresses_map_to_ram_then_the_screen_then_the_keyboard() {
+        let _turn = large_machine();
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Memory::from).unwrap();
+        let inputs = |in_: i16, address: i16, load| MemoryInputs {
+            in_: bits::from_i16(in_, BitOrder::MsbFirst),
+            address: bits::from_i16(address, BitOrder::MsbFirst),
+            load,
+            keyboard: bits::from_i16(75, BitOrder::MsbFirst),
+            clock: false,
+        };
+
+        assert_eq!(
+            machine.cycle(inputs(1092, 0x3fff, true)).unwrap().out,
+            bits::from_i16(1092, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.cycle(inputs(-5, 0x4000, true)).unwrap().out,
+            bits::from_i16(-5, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.process(inputs(0, 0x3fff, false)).unwrap().out,
+            bits::from_i16(1092, BitOrder::MsbFirst)
+        );
+
+        // the keyboard can be read but not written, and doesn't alias the RAM below
+        assert_eq!(
+            machine.process(inputs(0, 0x6000, false)).unwrap().out,
+            bits::from_i16(75, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.cycle(inputs(9, 0x6000, true)).unwrap().out,
+            bits::from_i16(75, BitOrder::MsbFirst)
+        );
+        assert_eq!(
+            machine.process(inputs(0, 0x2000, false)).unwrap().out,
+            bits::from_i16(0, BitOrder::MsbFirst)
+        );
+    }
+
+    #[test]
+    fn ram16k_keeps_a_separate_word_in_each_of_its_banks() {
+        let _turn = large_machine();
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram16k::from).unwrap();
+        // the top two address bits pick one of the four Ram4k banks
+        let addresses = [0x0123, 0x1123, 0x2123, 0x3123];
+        for (i, address) in addresses.into_iter().enumerate() {
+            let res = machine.cycle(Ram16kInputs {
+                in_: bits::from_i16(i as i16 + 1, BitOrder::MsbFirst),
+                address: bits::from_i16(address, BitOrder::MsbFirst),
+                load: true,
+                clock: false,
+            });
+            assert_eq!(
+                res.unwrap().out,
+                bits::from_i16(i as i16 + 1, BitOrder::MsbFirst)
+            );
+        }
+        for (i, address) in addresses.into_iter().enumerate() {
+            let res = machine.process(Ram16kInputs {
+                in_: [false; 16],
+                address: bits::from_i16(address, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            });
+            assert_eq!(
+                res.unwrap().out,
+                bits::from_i16(i as i16 + 1, BitOrder::MsbFirst)
+            );
+        }
+        // the highest address is in the last bank
+        let res = machine.process(Ram16kInputs {
+            in_: [false; 16],
+            address: bits::from_i16(0x3fff, BitOrder::MsbFirst),
+            load: false,
+            clock: false,
+        });
+        assert_eq!(res.unwrap().out, bits::from_i16(0, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn ram4k_when_a_value_is_stored_it_can_be_retrieved_again() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram512::from).unwrap();
+        let number = bits::from_i16(1092, BitOrder::MsbFirst);
+        let out = machine
+            .process(Ram512Inputs {
+                in_: number,
+                address: bits::from_i16(2941, BitOrder::MsbFirst),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, [false; 16]);
+        let out = machine
+            .process(Ram512Inputs {
+                in_: [false; 16],
+                address: bits::from_i16(2941, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, number);
+    }
+
+    #[test]
+    fn ram512_when_a_value_is_stored_it_can_be_retrieved_again() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram512::from).unwrap();
+        let number = bits::from_i16(1092, BitOrder::MsbFirst);
+        let out = machine
+            .process(Ram512Inputs {
+                in_: number,
+                address: bits::from_i16(132, BitOrder::MsbFirst),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, [false; 16]);
+        let out = machine
+            .process(Ram512Inputs {
+                in_: [false; 16],
+                address: bits::from_i16(132, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, number);
+    }
+
+    #[test]
+    fn ram64_when_a_value_is_stored_it_is_stored_in_only_one_place() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram64::from).unwrap();
+        let number = bits::from_i16(1092, BitOrder::MsbFirst);
+        let out = machine
+            .process(Ram64Inputs {
+                in_: number,
+                address: bits::from_i16(18, BitOrder::MsbFirst),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, [false; 16]);
+        let out = machine
+            .process(Ram64Inputs {
+                in_: [false; 16],
+                address: bits::from_i16(18, BitOrder::MsbFirst),
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, number);
+
+        for i in 0..64 {
+            if i == 18 {
+                continue;
+            }
+            let out = machine
+                .process(Ram64Inputs {
+                    in_: [false; 16],
+                    address: bits::from_i16(i, BitOrder::MsbFirst),
+                    load: false,
+                    clock: false,
+                })
+                .unwrap(); // tock
+            assert_eq!(out.out, [false; 16]);
+        }
+    }
+
+    #[test]
+    fn ram8_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram8::from).unwrap();
+        let num1 = bits::from_i16(4321, BitOrder::MsbFirst);
+        let num2 = bits::from_i16(1234, BitOrder::MsbFirst);
+        let out = machine
+            .process(Ram8Inputs {
+                in_: num1,
+                address: Ram8Addr::R0.into(),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, bits::from_i16(0, BitOrder::MsbFirst));
+        let out = machine
+            .process(Ram8Inputs {
+                in_: num1,
+                address: Ram8Addr::R0.into(),
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, num1);
+        let out = machine
+            .process(Ram8Inputs {
+                in_: num2,
+                address: Ram8Addr::R2.into(),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, bits::from_i16(0, BitOrder::MsbFirst));
+        let out = machine
+            .process(Ram8Inputs {
+                in_: bits::from_i16(0, BitOrder::MsbFirst),
+                address: Ram8Addr::R2.into(),
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, num2);
+        let out = machine
+            .process(Ram8Inputs {
+                in_: num1,
+                address: Ram8Addr::R0.into(),
+                load: false,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(out.out, num1);
+        let out = machine
+            .process(Ram8Inputs {
+                in_: num1,
+                address: Ram8Addr::R2.into(),
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(out.out, num2);
+    }
+
+    #[test]
+    fn ram8_write_strobes_can_be_watched() {
+        let strobes = RefCell::new(vec![]);
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram8::from).unwrap();
+        machine
+            .watch("Ram8_0/Demux1x8_0/out-5", |old, new, cycle| {
+                strobes.borrow_mut().push((old, new, cycle))
+            })
+            .unwrap();
+
+        let mut inputs = Ram8Inputs {
+            in_: bits::from_i16(1234, BitOrder::MsbFirst),
+            address: Ram8Addr::R2.into(),
+            load: true,
+            clock: false,
+        };
+        machine.cycle(inputs.clone()).unwrap();
+        inputs.address = Ram8Addr::R0.into();
+        machine.cycle(inputs.clone()).unwrap();
+        inputs.load = false;
+        inputs.address = Ram8Addr::R2.into();
+        machine.cycle(inputs).unwrap();
+        drop(machine);
+
+        assert_eq!(
+            strobes.into_inner(),
+            [(Logic::Low, Logic::High, 0), (Logic::High, Logic::Low, 2)]
+        );
+    }
+
+    #[test]
+    fn bit_can_be_loaded_by_posting_to_a_server() {
+        use std::{
+            io::{Read, Write},
+            net::TcpStream,
+            thread,
+        };
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Bit::from).unwrap();
+        let server = ui::Server::bind(("127.0.0.1", 0)).unwrap();
+        let handle = server.handle();
+        let client = thread::spawn(move || {
+            let send = |method: &str, path: &str, body: &str| {
+                let mut stream = TcpStream::connect(("127.0.0.1", handle.port())).unwrap();
+                write!(
+                    stream,
+                    "{method} {path} HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                let (status, body) = response.split_once("\r\n\r\n").unwrap();
+                (status.lines().next().unwrap().to_owned(), body.to_owned())
+            };
+            let responses = [
+                send("POST", "/step", r#"{"in_": 1, "load": 1}"#),
+                send("POST", "/inputs", r#"{"in_": 0, "load": 0}"#),
+                send("POST", "/step", r#"{"load": 1}"#),
+                send("GET", "/step", ""),
+                send("POST", "/inputs", r#"{"nope": 1}"#),
+            ];
+            handle.shutdown();
+            responses
+        });
+
+        server.serve_machine(&mut machine).unwrap();
+        let ok = |body: &str| ("HTTP/1.1 200 OK".to_owned(), body.to_owned());
+        let responses = client.join().unwrap();
+        assert_eq!(responses[0], ok(r#"{"outputs":{"out":"1"}}"#));
+        assert_eq!(responses[1], ok(r#"{"outputs":{"out":"1"}}"#));
+        assert_eq!(responses[2], ok(r#"{"outputs":{"out":"0"}}"#));
+        assert_eq!(responses[3].0, "HTTP/1.1 405 Method Not Allowed");
+        assert_eq!(responses[4].0, "HTTP/1.1 400 Bad Request");
+    }
+
+    #[test]
+    fn bit_changes_can_be_followed_as_server_sent_events() {
+        use std::{
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpStream,
+            thread,
+        };
+
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Bit::from).unwrap();
+        let server = ui::Server::bind(("127.0.0.1", 0)).unwrap();
+        let handle = server.handle();
+        let client = thread::spawn(move || {
+            let connect = |request: &str| {
+                let mut stream = TcpStream::connect(("127.0.0.1", handle.port())).unwrap();
+                stream.write_all(request.as_bytes()).unwrap();
+                BufReader::new(stream)
+            };
+            let mut unknown = String::new();
+            connect("GET /events?watch=nope HTTP/1.1\r\n\r\n")
+                .read_to_string(&mut unknown)
+                .unwrap();
+
+            let mut events = connect("GET /events?watch=out HTTP/1.1\r\n\r\n");
+            // the events' data, skipping the response head and comments
+            let mut next_data = || loop {
+                let mut line = String::new();
+                events.read_line(&mut line).unwrap();
+                if let Some(data) = line.strip_prefix("data: ") {
+                    return data.trim_end().to_owned();
+                }
+            };
+            let first = next_data();
+            let step = |body: &str| {
+                connect(&format!(
+                    "POST /step HTTP/1.1\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                ))
+                .read_to_string(&mut String::new())
+                .unwrap();
+            };
+            step(r#"{"in_": 1, "load": 1}"#);
+            let loaded = next_data();
+            step(r#"{"in_": 0, "load": 0}"#);
+            step(r#"{"load": 1}"#);
+            let cleared = next_data();
+            handle.shutdown();
+            (unknown, [first, loaded, cleared])
+        });
+
+        server.serve_machine(&mut machine).unwrap();
+        let (unknown, events) = client.join().unwrap();
+        assert!(unknown.starts_with("HTTP/1.1 400 Bad Request"));
+        assert_eq!(
+            events,
+            [
+                r#"{"step":0,"changes":{"out":"0"}}"#,
+                r#"{"step":2,"changes":{"out":"1"}}"#,
+                r#"{"step":6,"changes":{"out":"0"}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn ram8_write_strobes_can_be_dumped_for_a_waveform_viewer() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Ram8::from).unwrap();
+        machine.start_vcd(&["Ram8_0/Demux1x8_0/out-5"]).unwrap();
+
+        let mut inputs = Ram8Inputs {
+            in_: bits::from_i16(1234, BitOrder::MsbFirst),
+            address: Ram8Addr::R2.into(),
+            load: true,
+            clock: false,
+        };
+        machine.cycle(inputs.clone()).unwrap();
+        inputs.address = Ram8Addr::R0.into();
+        machine.cycle(inputs.clone()).unwrap();
+        inputs.load = false;
+        inputs.address = Ram8Addr::R2.into();
+        machine.cycle(inputs).unwrap();
+        let vcd = machine.stop_vcd().unwrap();
+
+        // the inputs and outputs come first
+        let strobe = vcd.signals.len() - 1;
+        assert_eq!(strobe, 21 + 16);
+        assert_eq!(
+            vcd.signals[strobe],
+            VcdSignal {
+                scope: vec!["top".into(), "Ram8_0".into(), "Demux1x8_0".into()],
+                name: "out-5".into(),
+            }
+        );
+        assert_eq!(
+            vcd.steps
+                .iter()
+                .map(|step| step[strobe])
+                .collect::<Vec<_>>(),
+            [
+                Logic::High,
+                Logic::High,
+                Logic::Low,
+                Logic::Low,
+                Logic::Low,
+                Logic::Low
+            ]
+        );
+        let vcd = vcd.to_string();
+        assert!(vcd.contains(
+            "$scope module Ram8_0 $end\n$scope module Demux1x8_0 $end\n$var wire 1 F out-5 $end\n$upscope $end\n$upscope $end\n$upscope $end\n$enddefinitions $end"
+        ));
+        // the strobe falls on the third tick
+        let changes_at = |time: usize| {
+            let (_, changes) = vcd.split_once(&format!("#{time}\n")).unwrap();
+            changes.split('#').next().unwrap().to_owned()
+        };
+        assert!(changes_at(2).lines().any(|change| change == "0F"));
+        assert!(!changes_at(3).lines().any(|change| change.ends_with('F')));
+    }
+
+    #[test]
+    fn register16_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Register16::from).unwrap();
+        let res = machine
+            .process(Register16Inputs {
+                in_: bits::from_i16(4321, BitOrder::MsbFirst),
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // initial state
+        assert_eq!(res.out, bits::from_i16(0, BitOrder::MsbFirst));
+        let res = machine
+            .process(Register16Inputs {
+                in_: bits::from_i16(0, BitOrder::MsbFirst),
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(res.out, bits::from_i16(4321, BitOrder::MsbFirst));
+    }
+
+    #[test]
+    fn bit_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Bit::from).unwrap();
+        let res = machine
+            .process(BitInputs {
+                in_: true,
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // initial state
+        assert_eq!(res.out, false);
+        let res = machine
+            .process(BitInputs {
+                in_: true,
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(res.out, true);
+        let res = machine
+            .process(BitInputs {
+                in_: true,
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // same tock
+        assert_eq!(res.out, true);
+        let res = machine
+            .process(BitInputs {
+                in_: false,
+                load: false,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(res.out, true);
+        let res = machine
+            .process(BitInputs {
+                in_: false,
+                load: false,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(res.out, true);
+        let res = machine
+            .process(BitInputs {
+                in_: false,
+                load: true,
+                clock: true,
+            })
+            .unwrap(); // tick
+        assert_eq!(res.out, true);
+        let res = machine
+            .process(BitInputs {
+                in_: false,
+                load: true,
+                clock: false,
+            })
+            .unwrap(); // tock
+        assert_eq!(res.out, false);
+    }
+
+    #[test]
+    fn dflipflop_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Dflipflop::from).unwrap();
+        let res = machine
+            .process(DflipflopInputs {
+                data: true,
+                clock: true,
+            })
+            .unwrap();
+        assert_eq!(res.q, false, "q should not transition until a clock tick ");
+        let res = machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(res.q, true, "data should transition on a clock tick");
+        let res = machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(res.q, true, "data should not transition until a clock tick");
+        let res = machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res.q, true,
+            "data should not transition until a clock tick after it was changed"
+        );
+        let res = machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(res.q, false, "data should transition on a clock tick");
+        let res = machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res.q, false,
+            "data should not transition until a clock tick"
+        );
+    }
+
+    #[test]
+    fn when_a_dflipflop_starts_unknown_q_is_unknown_until_data_is_clocked_in() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Dflipflop::from).unwrap();
+        machine.set_unknown();
+        let res = machine
+            .process_logic(DflipflopInputs {
+                data: Logic::High,
+                clock: Logic::High,
+            })
+            .unwrap();
+        assert_eq!(res.q, Logic::Unknown, "q should hold its unknown state");
+        let res = machine
+            .process_logic(DflipflopInputs {
+                data: Logic::Low,
+                clock: Logic::Low,
+            })
+            .unwrap();
+        assert_eq!(res.q, Logic::High, "data should transition on a clock tick");
+    }
+
+    #[test]
+    fn when_a_dflipflop_uses_unit_delays_it_settles_to_the_same_states() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Dflipflop::from).unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 100 });
+        assert!(
+            machine
+                .process_timed(DflipflopInputs {
+                    data: Logic::High,
+                    clock: Logic::High,
+                })
+                .is_err(),
+            "both latch NANDs power up low, which is metastable"
+        );
+
+        let mut machine = Machine::new(&alloc, Dflipflop::from).unwrap();
+        machine
+            .process(DflipflopInputs {
+                data: false,
+                clock: false,
+            })
+            .unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 100 });
+        for (data, clock, q) in [
+            (true, true, false),
+            (false, false, true),
+            (false, true, true),
+            (false, false, false),
+        ] {
+            let res = machine.process(DflipflopInputs { data, clock }).unwrap();
+            assert_eq!(res.q, q);
+            assert_eq!(res.nq, !q);
+        }
+    }
+
+    #[test]
+    fn dlatch_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Dlatch::from).unwrap();
+        let res = machine
+            .process(DlatchInputs {
+                data: true,
+                enable: true,
+            })
+            .unwrap();
+        assert_eq!(res.q, true);
+        let res = machine
+            .process(DlatchInputs {
+                data: false,
+                enable: false,
+            })
+            .unwrap();
+        assert_eq!(res.q, true);
+        let res = machine
+            .process(DlatchInputs {
+                data: false,
+                enable: true,
+            })
+            .unwrap();
+        assert_eq!(res.q, false);
+    }
+
+    #[test]
+    fn srlatch_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Srlatch::from).unwrap();
+        let res1 = machine
+            .process(SrlatchInputs { s: false, r: true })
+            .unwrap();
+        assert_eq!(res1.q, true);
+        let res2 = machine.process(SrlatchInputs { s: true, r: true }).unwrap();
+        assert_eq!(res2.q, true);
+        let res3 = machine
+            .process(SrlatchInputs { s: true, r: false })
+            .unwrap();
+        assert_eq!(res3.q, false);
+        let res4 = machine.process(SrlatchInputs { s: true, r: true }).unwrap();
+        assert_eq!(res4.q, false);
+    }
+
+    #[test]
+    fn srlatch_has_stable_output_if_input_is_valid() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Srlatch::from).unwrap();
+        let res1 = machine
+            .process(SrlatchInputs { s: false, r: true })
+            .unwrap();
+        assert_eq!(res1.q, true);
+        let res2 = machine.process(SrlatchInputs { s: true, r: true }).unwrap();
+        assert_eq!(res2.q, true);
+        let res4 = machine.process(SrlatchInputs { s: true, r: true }).unwrap();
+        assert_eq!(res4.q, true);
+    }
+
+    #[test]
+    fn aluex_matches_a_model_of_the_alu_with_signed_and_unsigned_addition_flags() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Aluex::from).unwrap();
+        machine
+            .check_model(|inputs: AluexInputs<bool>| {
+                let operand = |num: u16, zero: bool, negate: bool| {
+                    let num = if zero { 0 } else { num };
+                    if negate {
+                        !num
+                    } else {
+                        num
+                    }
+                };
+                let x = operand(inputs.x_to_u16(), inputs.zx, inputs.nx);
+                let y = operand(inputs.y_to_u16(), inputs.zy, inputs.ny);
+                let (sum, carry) = x.overflowing_add(y);
+                let result = if inputs.f { sum } else { x & y };
+                let out = if inputs.no { !result } else { result };
+                AluExOutputs {
+                    out: bits::from_u16(out, BitOrder::MsbFirst),
+                    zr: out == 0,
+                    ng: (out as i16) < 0,
+                    carry: inputs.f && carry,
+                    overflow: inputs.f && (x as i16).checked_add(y as i16).is_none(),
+                }
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn aluex_flags_carries_and_overflows_at_the_ends_of_the_range() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Aluex::from).unwrap();
+        let mut add = |x: i16, y: i16| {
+            machine
+                .process(AluexInputs {
+                    x: bits::from_i16(x, BitOrder::MsbFirst),
+                    y: bits::from_i16(y, BitOrder::MsbFirst),
+                    zx: false,
+                    zy: false,
+                    nx: false,
+                    ny: false,
+                    f: true,
+                    no: false,
+                })
+                .unwrap()
+        };
+
+        assert_eq!(
+            add(i16::MAX, 1),
+            AluExOutputs {
+                out: bits::from_i16(i16::MIN, BitOrder::MsbFirst),
+                zr: false,
+                ng: true,
+                carry: false,
+                overflow: true
+            }
+        );
+        assert_eq!(
+            add(i16::MIN, i16::MIN),
+            AluExOutputs {
+                out: bits::from_i16(0, BitOrder::MsbFirst),
+                zr: true,
+                ng: false,
+                carry: true,
+                overflow: true
+            }
+        );
+        assert_eq!(
+            add(i16::MIN, -1),
+            AluExOutputs {
+                out: bits::from_i16(i16::MAX, BitOrder::MsbFirst),
+                zr: false,
+                ng: false,
+                carry: true,
+                overflow: true
+            }
+        );
+        assert_eq!(
+            add(-1, 1),
+            AluExOutputs {
+                out: bits::from_i16(0, BitOrder::MsbFirst),
+                zr: true,
+                ng: false,
+                carry: true,
+                overflow: false
+            }
+        );
+        assert_eq!(
+            add(i16::MIN, i16::MAX),
+            AluExOutputs {
+                out: bits::from_i16(-1, BitOrder::MsbFirst),
+                zr: false,
+                ng: true,
+                carry: false,
+                overflow: false
+            }
+        );
+
+        // anding never carries or overflows
+        let res = machine
+            .process(AluexInputs {
+                x: bits::from_i16(i16::MIN, BitOrder::MsbFirst),
+                y: bits::from_i16(i16::MIN, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                nx: false,
+                ny: false,
+                f: false,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            (res.out, res.carry, res.overflow),
+            (bits::from_i16(i16::MIN, BitOrder::MsbFirst), false, false)
+        );
+    }
+
+    #[test]
+    fn negate16_and_subtractor16_wrap_at_the_ends_of_the_range() {
+        let alloc = Bump::new();
+        let mut negate = Machine::new(&alloc, Negate16::from).unwrap();
+        for (num, negated) in [
+            (0, 0),
+            (1, -1),
+            (i16::MAX, -i16::MAX),
+            (-i16::MAX, i16::MAX),
+            (i16::MIN, i16::MIN),
+        ] {
+            let res = negate
+                .process(Negate16Inputs {
+                    num: bits::from_i16(num, BitOrder::MsbFirst),
+                })
+                .unwrap();
+            assert_eq!(res.to_i16(), negated, "-{num}");
+        }
+
+        let mut subtract = Machine::new(&alloc, Subtractor16::from).unwrap();
+        for (num1, num2, difference) in [
+            (5, 3, 2),
+            (3, 5, -2),
+            (0, 0, 0),
+            (i16::MIN, 1, i16::MAX),
+            (i16::MAX, -1, i16::MIN),
+            (0, i16::MIN, i16::MIN),
+            (-1, i16::MAX, i16::MIN),
+            (i16::MIN, i16::MIN, 0),
+        ] {
+            let res = subtract
+                .process(Subtractor16Inputs {
+                    num1: bits::from_i16(num1, BitOrder::MsbFirst),
+                    num2: bits::from_i16(num2, BitOrder::MsbFirst),
+                })
+                .unwrap();
+            assert_eq!(res.to_i16(), difference, "{num1} - {num2}");
+        }
+    }
+
+    #[test]
+    fn alu_chip_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Alu::from).unwrap();
+
+        // addition works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(1, BitOrder::MsbFirst),
+                y: bits::from_i16(1, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(2, BitOrder::MsbFirst),
+                zr: false,
+                ng: false
+            }
+        );
+
+        // zx works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(1, BitOrder::MsbFirst),
+                y: bits::from_i16(2, BitOrder::MsbFirst),
+                zx: true,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(2, BitOrder::MsbFirst),
+                zr: false,
+                ng: false
+            }
+        );
+
+        // zy works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(1, BitOrder::MsbFirst),
+                y: bits::from_i16(2, BitOrder::MsbFirst),
+                zx: false,
+                zy: true,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(1, BitOrder::MsbFirst),
+                zr: false,
+                ng: false
+            }
+        );
+
+        // nx works
+        let res = machine
+            .process(AluInputs {
+                x: [false; 16],
+                y: [true; 16],
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: true,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(-2, BitOrder::MsbFirst),
+                zr: false,
+                ng: true
+            }
+        );
+
+        // ny works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(-1, BitOrder::MsbFirst),
+                y: bits::from_i16(0, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: true,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(-2, BitOrder::MsbFirst),
+                zr: false,
+                ng: true
+            }
+        );
+
+        // no works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(-1, BitOrder::MsbFirst),
+                y: bits::from_i16(-1, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // and works
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(-1, BitOrder::MsbFirst),
+                y: bits::from_i16(-1, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: false,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(-1, BitOrder::MsbFirst),
+                ng: true,
+                zr: false
+            }
+        );
+
+        // now I'll just put in the rest of the truth table as per the book
+        // 0
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(-1, BitOrder::MsbFirst),
+                y: bits::from_i16(-1, BitOrder::MsbFirst),
+                zx: true,
+                zy: true,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(0, BitOrder::MsbFirst),
+                ng: false,
+                zr: true
+            }
+        );
+
+        // 1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(-1, BitOrder::MsbFirst),
+                y: bits::from_i16(-1, BitOrder::MsbFirst),
+                zx: true,
+                zy: true,
+                ny: true,
+                nx: true,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // -1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(132, BitOrder::MsbFirst),
+                y: bits::from_i16(876, BitOrder::MsbFirst),
+                zx: true,
+                zy: true,
+                ny: false,
+                nx: true,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(-1, BitOrder::MsbFirst),
+                ng: true,
+                zr: false
+            }
+        );
+
+        // x
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: true,
+                ny: true,
+                nx: false,
+                f: false,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // y
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: true,
+                zy: false,
+                ny: false,
+                nx: true,
+                f: false,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(671, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // !x
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: true,
+                ny: true,
+                nx: false,
+                f: false,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(!452, BitOrder::MsbFirst),
+                ng: true,
+                zr: false
+            }
+        );
+
+        // !y
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: true,
+                zy: false,
+                ny: false,
+                nx: true,
+                f: false,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(!671, BitOrder::MsbFirst),
+                ng: true,
+                zr: false
+            }
+        );
+
+        // x+1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: true,
+                ny: true,
+                nx: true,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452 + 1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // y+1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: true,
+                zy: false,
+                ny: true,
+                nx: true,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(671 + 1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // x-1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: true,
+                ny: true,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452 - 1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // y-1
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: true,
+                zy: false,
+                ny: false,
+                nx: true,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(671 - 1, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // x+y
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452 + 671, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // x-y
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: true,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452 - 671, BitOrder::MsbFirst),
+                ng: true,
+                zr: false
+            }
+        );
+
+        // y-x
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: true,
+                nx: false,
+                f: true,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(671 - 452, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+
+        // x|y
+        let res = machine
+            .process(AluInputs {
+                x: bits::from_i16(452, BitOrder::MsbFirst),
+                y: bits::from_i16(671, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: true,
+                nx: true,
+                f: false,
+                no: true,
+            })
+            .unwrap();
+        assert_eq!(
+            res,
+            AluOutputs {
+                out: bits::from_i16(452 | 671, BitOrder::MsbFirst),
+                ng: false,
+                zr: false
+            }
+        );
+    }
+
+    #[test]
+    fn alu_internal_outputs_can_be_probed_by_path() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Alu::from).unwrap();
+        machine
+            .process(AluInputs {
+                x: bits::from_i16(1, BitOrder::MsbFirst),
+                y: bits::from_i16(1, BitOrder::MsbFirst),
+                zx: false,
+                zy: false,
+                ny: false,
+                nx: false,
+                f: true,
+                no: false,
+            })
+            .unwrap();
+
+        assert!(machine.probe("Alu0/Andorplus0/Adder16_0/out-1").unwrap());
+        assert!(!machine.probe("Alu0/Andorplus0/Adder16_0/out-0").unwrap());
+        // the AND of the inputs is computed even though the mux discards it
+        assert!(machine.probe("Alu0/Andorplus0/And16_0/out-0").unwrap());
+        assert_eq!(
+            machine.probe("Alu0/Andorplus1/Adder16_0/out-0"),
+            Err(hdl::Error::UnknownNode(
+                "Alu0/Andorplus1/Adder16_0/out-0".to_owned()
+            ))
+        );
+
+        let netlist = machine.netlist();
+        let node = netlist
+            .find_output("Alu0/Andorplus0/Adder16_0/out-1")
+            .unwrap();
+        assert_eq!(netlist.node_path(node), "Alu0/Andorplus0/Adder16_0/out-1");
+    }
+
+    #[test]
+    fn library_chips_describe_themselves_with_their_doc_comments() {
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Subtractor16::from).unwrap();
+        let netlist = machine.netlist();
+        let describe = |label| {
+            let chip = netlist.chips.iter().find(|chip| chip.label == label);
+            chip.unwrap().description
+        };
+        assert_eq!(
+            describe("Subtractor16"),
+            "num1 - num2, added as num1 + -num2 and wrapping like the adder"
+        );
+        assert!(describe("Negate16").starts_with("the two's complement of a number"));
+    }
+
+    #[test]
+    fn not_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Not::from).unwrap();
+        assert_eq!(
+            machine.process(NotInputs { in_: true }).unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine.process(NotInputs { in_: false }).unwrap(),
+            UnaryChipOutput { out: true }
+        );
+    }
+
+    #[test]
+    fn and_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, And::from).unwrap();
+        assert_eq!(
+            machine
+                .process(AndInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(AndInputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(AndInputs {
+                    in1: false,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(AndInputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+    }
+
+    #[test]
+    fn or_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Or::from).unwrap();
+        assert_eq!(
+            machine
+                .process(OrInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(OrInputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(OrInputs {
+                    in1: false,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(OrInputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+    }
+
+    #[test]
+    fn xor_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Xor::from).unwrap();
+        assert_eq!(
+            machine
+                .process(XorInputs {
+                    in1: true,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(XorInputs {
+                    in1: true,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(XorInputs {
+                    in1: false,
+                    in2: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(XorInputs {
+                    in1: false,
+                    in2: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+    }
+
+    #[test]
+    fn mux_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Mux::from).unwrap();
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: true,
+                    in2: true,
+                    sel: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: true,
+                    in2: false,
+                    sel: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: true,
+                    in2: true,
+                    sel: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: false,
+                    in2: true,
+                    sel: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: true,
+                    in2: false,
+                    sel: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: false,
+                    in2: true,
+                    sel: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: false,
+                    in2: false,
+                    sel: false
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(MuxInputs {
+                    in1: false,
+                    in2: false,
+                    sel: true
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+    }
+
+    #[test]
+    fn when_a_mux_selector_falls_with_both_inputs_high_the_output_glitches() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Mux::from).unwrap();
+        machine.set_timing(Timing::UnitDelay { max_steps: 100 });
+        let hazards = machine.find_single_input_hazards().unwrap();
+
+        // the inverted selector lags behind, so both AND gates are briefly low
+        assert_eq!(
+            hazards
+                .iter()
+                .map(|hazard| (hazard.from.clone(), hazard.to.clone()))
+                .collect::<Vec<_>>(),
+            [(vec![true, true, true], vec![true, true, false])]
+        );
+    }
+
+    #[test]
+    fn demux_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Demux::from).unwrap();
+        assert_eq!(
+            machine
+                .process(DemuxInputs {
+                    in_: true,
+                    sel: true
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: true
+            }
+        );
+        assert_eq!(
+            machine
+                .process(DemuxInputs {
+                    in_: true,
+                    sel: false
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: true,
+                out2: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(DemuxInputs {
+                    in_: false,
+                    sel: true
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(DemuxInputs {
+                    in_: false,
+                    sel: false
+                })
+                .unwrap(),
+            BinaryChipOutput {
+                out1: false,
+                out2: false
+            }
+        );
+    }
+
+    #[test]
+    fn not16_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Not16::from).unwrap();
+        assert_eq!(
+            machine.process(Not16Inputs { input: [true; 16] }).unwrap(),
+            ArrayLenN { out: [false; 16] }
+        );
+        assert_eq!(
+            machine.process(Not16Inputs { input: [false; 16] }).unwrap(),
+            ArrayLenN { out: [true; 16] }
+        );
+    }
+
+    #[test]
+    fn and16_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, And16::from).unwrap();
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: [true; 16],
+                    in2: [true; 16]
+                })
+                .unwrap(),
+            ArrayLen16 { out: [true; 16] }
+        );
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: bits::from_i16(i16::MAX, BitOrder::MsbFirst),
+                    in2: bits::from_i16(-1, BitOrder::MsbFirst)
+                })
+                .unwrap(),
+            ArrayLen16 {
+                out: bits::from_i16(i16::MAX, BitOrder::MsbFirst)
+            }
+        );
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: bits::from_i16(-5, BitOrder::MsbFirst),
+                    in2: bits::from_i16(-1, BitOrder::MsbFirst)
+                })
+                .unwrap(),
+            ArrayLen16 {
+                out: bits::from_i16(-5, BitOrder::MsbFirst)
+            }
+        );
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: bits::from_i16(-1, BitOrder::MsbFirst),
+                    in2: bits::from_i16(i16::MAX, BitOrder::MsbFirst)
+                })
+                .unwrap(),
+            ArrayLen16 {
+                out: bits::from_i16(i16::MAX, BitOrder::MsbFirst)
+            }
+        );
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: bits::from_i16(-1, BitOrder::MsbFirst),
+                    in2: bits::from_i16(-765, BitOrder::MsbFirst)
+                })
+                .unwrap(),
+            ArrayLen16 {
+                out: bits::from_i16(-765, BitOrder::MsbFirst)
+            }
+        );
+        // ...
+        assert_eq!(
+            machine
+                .process(And16Inputs {
+                    in1: bits::from_i16(0, BitOrder::MsbFirst),
+                    in2: bits::from_i16(0, BitOrder::MsbFirst)
+                })
+                .unwrap(),
+            ArrayLen16 {
+                out: bits::from_i16(0, BitOrder::MsbFirst)
+            }
+        );
+    }
+
+    #[test]
+    fn or2_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Or2::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [true, true],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [true, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [false, true],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [true, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [true, false],
+                    in2: [true, true]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [true, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [true, true],
+                    in2: [false, true]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [true, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [true, true],
+                    in2: [true, false]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [true, true] }
+        );
+        // ...
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [false, true],
+                    in2: [false, false]
+                })
+                .unwrap(),
+            ArrayLen2 { out: [false, true] }
+        );
+        assert_eq!(
+            machine
+                .process(Or2Inputs {
+                    in1: [false, false],
+                    in2: [false, false]
+                })
+                .unwrap(),
+            ArrayLen2 {
+                out: [false, false]
+            }
+        );
+    }
+
+    #[test]
+    fn mux16_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Mux16::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Mux16Inputs {
+                    in1: [true; 16],
+                    in2: [false; 16],
+                    sel: true
+                })
+                .unwrap(),
+            ArrayLen16 { out: [false; 16] }
+        );
+        assert_eq!(
+            machine
+                .process(Mux16Inputs {
+                    in1: [true; 16],
+                    in2: [false; 16],
+                    sel: false
+                })
+                .unwrap(),
+            ArrayLen16 { out: [true; 16] }
+        );
+        // ...
+    }
+
+    #[test]
+    fn demux16_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Demux16::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Demux16Inputs {
+                    in_: [true; 16],
+                    sel: true
+                })
+                .unwrap(),
+            BinaryArrayLen16 {
+                out1: [false; 16],
+                out2: [true; 16]
+            }
+        );
+        assert_eq!(
+            machine
+                .process(Demux16Inputs {
+                    in_: [true; 16],
+                    sel: false
+                })
+                .unwrap(),
+            BinaryArrayLen16 {
+                out1: [true; 16],
+                out2: [false; 16]
+            }
+        );
+        // ...
+    }
+
+    #[test]
+    fn mux16x8_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Mux16x8::from).unwrap();
+        let out = machine
+            .process(Mux16x8Inputs {
+                in_: [
+                    [true; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                ],
+                sel: [false, false, false],
+            })
+            .unwrap();
+        assert_eq!(out.out, [true; 16]);
+
+        let out = machine
+            .process(Mux16x8Inputs {
+                in_: [
+                    [true; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                ],
+                sel: [true, true, true],
+            })
+            .unwrap();
+        assert_eq!(out.out, [false; 16]);
+
+        let out = machine
+            .process(Mux16x8Inputs {
+                in_: [
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [true; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                ],
+                sel: [true, false, false],
+            })
+            .unwrap();
+        assert_eq!(out.out, [true; 16]);
+
+        let out = machine
+            .process(Mux16x8Inputs {
+                in_: [
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                    [true; 16],
+                    [false; 16],
+                    [false; 16],
+                    [false; 16],
+                ],
+                sel: [true, true, false],
+            })
+            .unwrap();
+        assert_eq!(out.out, [false; 16]);
+
+        // ...
+    }
+
+    #[test]
+    fn demux16x8_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Demux16x8::from).unwrap();
+        let out = machine
+            .process(Demux16x8Inputs {
+                in_: [true; 16],
+                sel: [true, true, true],
+            })
+            .unwrap();
+        assert_eq!(out.out[7 * 16..8 * 16], [true; 16]);
+        assert_eq!(out.out[6 * 16..7 * 16], [false; 16]);
+        assert_eq!(out.out[..16], [false; 16]);
+
+        let out = machine
+            .process(Demux16x8Inputs {
+                in_: [true; 16],
+                sel: [false, true, true],
+            })
+            .unwrap();
+
+        assert_eq!(out.out[3 * 16..4 * 16], [true; 16]);
+        assert_eq!(out.out[7 * 16..8 * 16], [false; 16]);
+        assert_eq!(out.out[2 * 16..3 * 16], [false; 16]);
+
+        let out = machine
+            .process(Demux16x8Inputs {
+                in_: [true; 16],
+                sel: [false, false, false],
+            })
+            .unwrap();
+
+        assert_eq!(out.out[..16], [true; 16]);
+        assert_eq!(out.out[7 * 16..8 * 16], [false; 16]);
+        // ...
+    }
+
+    #[test]
+    fn andmult4_gate_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Andmult4::from).unwrap();
+        assert_eq!(
+            machine
+                .process(Andmult4Inputs {
+                    in_: [true, true, true, true]
+                })
+                .unwrap(),
+            UnaryChipOutput { out: true }
+        );
+        assert_eq!(
+            machine
+                .process(Andmult4Inputs {
+                    in_: [false, true, true, true]
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(Andmult4Inputs {
+                    in_: [true, false, true, true]
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(Andmult4Inputs {
+                    in_: [true, true, false, true]
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        assert_eq!(
+            machine
+                .process(Andmult4Inputs {
+                    in_: [true, true, true, false]
+                })
+                .unwrap(),
+            UnaryChipOutput { out: false }
+        );
+        // ...
+    }
+
+    #[test]
+    fn halfadder_chip_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Halfadder::from).unwrap();
+        assert_eq!(
+            machine
+                .process(HalfadderInputs {
+                    num1: false,
+                    num2: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(HalfadderInputs {
+                    num1: false,
+                    num2: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(HalfadderInputs {
+                    num1: true,
+                    num2: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(HalfadderInputs {
+                    num1: true,
+                    num2: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: true
+            }
+        );
+    }
+
+    #[test]
+    fn fulladder_chip_has_correct_truth_table() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Fulladder::from).unwrap();
+
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: false,
+                    num2: false,
+                    num3: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: false,
+                    num2: false,
+                    num3: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: false,
+                    num2: true,
+                    num3: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: true,
+                    num2: false,
+                    num3: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: false
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: false,
+                    num2: true,
+                    num3: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: true
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: true,
+                    num2: false,
+                    num3: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: true
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: true,
+                    num2: true,
+                    num3: false
+                })
+                .unwrap(),
+            AdderOut {
+                sum: false,
+                carry: true
+            }
+        );
+        assert_eq!(
+            machine
+                .process(FulladderInputs {
+                    num1: true,
+                    num2: true,
+                    num3: true
+                })
+                .unwrap(),
+            AdderOut {
+                sum: true,
+                carry: true
+            }
+        );
+    }
+
+    #[test]
+    fn adders_add_at_every_width() {
+        let alloc = Bump::new();
+
+        let mut adder4 = Machine::new(&alloc, Adder4::from).unwrap();
+        check_adder(|num1, num2| adder4.process(Adder4Inputs { num1, num2 }).unwrap().out);
+        adder4
+            .check_model(|inputs| adder_model(inputs.num1, inputs.num2))
+            .unwrap();
+
+        let mut adder8 = Machine::new(&alloc, Adder8::from).unwrap();
+        check_adder(|num1, num2| adder8.process(Adder8Inputs { num1, num2 }).unwrap().out);
+        adder8
+            .check_model(|inputs| adder_model(inputs.num1, inputs.num2))
+            .unwrap();
+
+        let mut adder16 = Machine::new(&alloc, Adder16::from).unwrap();
+        check_adder(|num1, num2| adder16.process(Adder16Inputs { num1, num2 }).unwrap().out);
+        adder16
+            .check_model(|inputs| adder_model(inputs.num1, inputs.num2))
+            .unwrap();
+
+        let mut adder32 = Machine::new(&alloc, Adder32::from).unwrap();
+        check_adder(|num1, num2| adder32.process(Adder32Inputs { num1, num2 }).unwrap().out);
+        adder32
+            .check_model(|inputs| adder_model(inputs.num1, inputs.num2))
+            .unwrap();
+    }
+
+    #[test]
+    fn adder16_adds_numbers_given_as_words() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Adder16::from).unwrap();
+
+        let sum = machine
+            .process(Adder16InputsWords {
+                num1: 452,
+                num2: 671,
+            })
+            .unwrap();
+        assert_eq!(bits::to_u64(sum.out, BitOrder::MsbFirst), 1123);
+    }
+
+    #[test]
+    fn incrementers_add_just_one_at_every_width() {
+        let alloc = Bump::new();
+
+        let mut incrementer4 = Machine::new(&alloc, Incrementer4::from).unwrap();
+        check_incrementer(|num| {
+            incrementer4
+                .process(Incrementer4Inputs { num })
+                .unwrap()
+                .out
+        });
+
+        let mut incrementer8 = Machine::new(&alloc, Incrementer8::from).unwrap();
+        check_incrementer(|num| {
+            incrementer8
+                .process(Incrementer8Inputs { num })
+                .unwrap()
+                .out
+        });
+
+        let mut incrementer16 = Machine::new(&alloc, Incrementer16::from).unwrap();
+        check_incrementer(|num| {
+            incrementer16
+                .process(Incrementer16Inputs { num })
+                .unwrap()
+                .out
+        });
+
+        let mut incrementer32 = Machine::new(&alloc, Incrementer32::from).unwrap();
+        check_incrementer(|num| {
+            incrementer32
+                .process(Incrementer32Inputs { num })
+                .unwrap()
+                .out
+        });
+    }
+
+    #[test]
+    fn mux16_sel_fans_out_to_every_bit() {
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Mux16::from).unwrap();
+        let netlist = machine.netlist();
+        let fan_out = netlist.fan_out();
+        let gate_fan_out = netlist.gate_fan_out();
+
+        // the chip's sel input is wired to 16 Mux chips, each of which feeds it to an
+        // And and a Not
+        let sel = netlist.consumers(netlist.inputs[32]).next().unwrap();
+        assert_eq!(netlist.nodes[sel].label, "sel");
+        assert_eq!(fan_out[sel], 16);
+        assert_eq!(gate_fan_out[sel], 48);
+        assert!(matches!(
+            machine.check_fan_out(16),
+            Err(hdl::Error::FanOut { exceeded, .. }) if exceeded.len() == 1
+        ));
+        assert_eq!(machine.check_fan_out(48), Ok(()));
+    }
+
+    #[test]
+    fn register16_loads_correctly_whatever_state_it_powers_up_in() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Register16::from).unwrap();
+        for seed in 0..4 {
+            machine.randomize_state(seed);
+            let res = machine
+                .cycle(Register16Inputs {
+                    in_: bits::from_i16(4321, BitOrder::MsbFirst),
+                    load: true,
+                    clock: false,
+                })
+                .unwrap();
+            assert_eq!(res.out, bits::from_i16(4321, BitOrder::MsbFirst));
+        }
+    }
+
+    #[test]
+    fn counter16_resumes_counting_from_a_restored_state() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+        let inc = Counter16Inputs {
+            in_: bits::from_i16(0, BitOrder::MsbFirst),
+            inc: true,
+            load: false,
+            reset: false,
+            clock: false,
+        };
+        for _ in 0..5 {
+            machine.cycle(inc.clone()).unwrap();
+        }
+        let saved = machine.save_state().to_bytes();
+        for _ in 0..3 {
+            machine.cycle(inc.clone()).unwrap();
+        }
+
+        let state = MachineState::from_bytes(&saved).unwrap();
+        machine.load_state(&state).unwrap();
+        assert_eq!(
+            machine.cycle(inc.clone()).unwrap().out,
+            bits::from_i16(6, BitOrder::MsbFirst)
+        );
+
+        // a fresh machine built from the same chip can pick up where the first left off
+        let alloc = Bump::new();
+        let mut resumed = Machine::new(&alloc, Counter16::from).unwrap();
+        resumed.load_state(&state).unwrap();
+        assert_eq!(
+            resumed.cycle(inc).unwrap().out,
+            bits::from_i16(6, BitOrder::MsbFirst)
+        );
+
+        let alloc = Bump::new();
+        let mut register = Machine::new(&alloc, Register16::from).unwrap();
+        assert!(matches!(
+            register.load_state(&state),
+            Err(hdl::Error::StateMismatch { .. })
+        ));
+        assert_eq!(
+            MachineState::from_bytes(&saved[..saved.len() - 1]),
+            Err(hdl::Error::InvalidState)
+        );
+    }
+
+    #[test]
+    fn counter16_trace_replays_against_a_fresh_machine() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Counter16::from).unwrap();
+        let mut inputs = Counter16Inputs {
+            in_: bits::from_i16(40, BitOrder::MsbFirst),
+            inc: false,
+            load: true,
+            reset: false,
+            clock: false,
+        };
+        machine.start_trace();
+        machine.cycle(inputs.clone()).unwrap();
+        inputs.load = false;
+        inputs.inc = true;
+        for _ in 0..3 {
+            machine.cycle(inputs.clone()).unwrap();
+        }
+        let trace = machine.stop_trace().unwrap();
+        assert_eq!(trace.steps.len(), 8);
+
+        let trace: Trace = trace.to_string().parse().unwrap();
+        let alloc = Bump::new();
+        let mut replayed = Machine::new(&alloc, Counter16::from).unwrap();
+        replayed.replay(&trace).unwrap();
+
+        let mut tampered = trace.clone();
+        // 40 + 3 is odd, so clear the least significant bit
+        tampered.steps[7].outputs[15] = Logic::Low;
+        let alloc = Bump::new();
+        let mut replayed = Machine::new(&alloc, Counter16::from).unwrap();
+        assert!(matches!(
+            replayed.replay(&tampered),
+            Err(hdl::Error::TraceMismatch { step: 7, .. })
+        ));
+
+        let alloc = Bump::new();
+        let mut register = Machine::new(&alloc, Register16::from).unwrap();
+        assert!(matches!(
+            register.replay(&trace),
+            Err(hdl::Error::InvalidTrace(_))
+        ));
+    }
+
+    #[test]
+    fn cpu_trace_of_settled_cycles_replays_against_a_fresh_machine() {
+        let inputs = |instruction: u16, reset: bool| CpuInputs {
+            instruction: bits::from_i16(instruction as i16, BitOrder::MsbFirst),
+            in_m: bits::from_i16(0, BitOrder::MsbFirst),
+            reset,
+            clock: false,
+        };
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Cpu::from).unwrap();
+        machine.cycle_settled(inputs(0, true), 8).unwrap();
+        machine.start_trace();
+        // @5, D=A, then M=D writing 5 to address 5
+        machine.cycle_settled(inputs(5, false), 8).unwrap();
+        machine.cycle_settled(inputs(0xEC10, false), 8).unwrap();
+        let res = machine.cycle_settled(inputs(0xE308, false), 8).unwrap();
+        assert_eq!(
+            (res.out_m, res.address_m, res.pc),
+            (
+                bits::from_i16(5, BitOrder::MsbFirst),
+                bits::from_i16(5, BitOrder::MsbFirst),
+                bits::from_i16(3, BitOrder::MsbFirst)
+            )
+        );
+        let trace = machine.stop_trace().unwrap();
+        // each cycle is a tick, a tock and the final process, with the settling passes
+        // after each edge traced between them
+        assert!(trace.steps.len() > 3 * 3);
+
+        let alloc = Bump::new();
+        let mut replayed = Machine::new(&alloc, Cpu::from).unwrap();
+        replayed.cycle_settled(inputs(0, true), 8).unwrap();
+        replayed.replay(&trace).unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+use std::{collections::HashSet, error::Error, fs};
+
+use bumpalo::Bump;
+use hdl::Machine;
+use ui::{ChipLibrary, GraphFormat, GraphOptions, LibraryEntry, Peripherals};
+
+use crate::*;
+
+pub const GATES: &str = "gates";
+pub const ARITH: &str = "arith";
+pub const SEQ: &str = "seq";
+pub const MEMORY: &str = "memory";
+pub const COMPUTER: &str = "computer";
+
+macro_rules! entry {
+    ($namespace:expr, $chip:ident) => {
+        entry!($namespace, $chip, Peripherals::default())
+    };
+    ($namespace:expr, $chip:ident, $peripherals:expr) => {
+        LibraryEntry {
+            namespace: $namespace,
+            name: stringify!($chip),
+            graph: |options| {
+                let alloc = Bump::new();
+                let machine = Machine::new(&alloc, $chip::from)
+                    .expect("library chips should be fully connected");
+                ui::graph_machine(&machine, options)
+            },
+            serve: |address| {
+                let alloc = Bump::new();
+                let mut machine = Machine::new(&alloc, $chip::from)?;
+                ui::start_interactive_server_with(&mut machine, address, &$peripherals)?;
+                Ok(())
+            },
+            truth_table: || ui::truth_table(|alloc| Machine::new(alloc, $chip::from)),
+        }
+    };
+}
+
+pub fn library() -> ChipLibrary {
+    ChipLibrary::new(vec![
+        entry!(GATES, Not),
+        entry!(GATES, And),
+        entry!(GATES, Or),
+        entry!(GATES, Xor),
+        entry!(GATES, Mux),
+        entry!(GATES, Demux),
+        entry!(GATES, Not16),
+        entry!(GATES, And16),
+        entry!(GATES, Or2),
+        entry!(GATES, Mux16),
+        entry!(GATES, Demux16),
+        entry!(GATES, Demux1x4),
+        entry!(GATES, Demux1x8),
+        entry!(GATES, Demux16x8),
+        entry!(GATES, Mux16x4),
+        entry!(GATES, Mux16x8),
+        entry!(GATES, Andmult4),
+        entry!(GATES, Ormult16),
+        entry!(ARITH, Halfadder),
+        entry!(ARITH, Fulladder),
+        entry!(ARITH, Adder4),
+        entry!(ARITH, Adder8),
+        entry!(ARITH, Adder16),
+        entry!(ARITH, Adder32),
+        entry!(ARITH, Incrementer4),
+        entry!(ARITH, Incrementer8),
+        entry!(ARITH, Incrementer16),
+        entry!(ARITH, Incrementer32),
+        entry!(ARITH, Negate16),
+        entry!(ARITH, Subtractor16),
+        entry!(ARITH, Zeronum),
+        entry!(ARITH, Negatenum),
+        entry!(ARITH, Andorplus),
+        entry!(ARITH, Addflags),
+        entry!(ARITH, Alu),
+        entry!(ARITH, Aluex),
+        entry!(SEQ, Srlatch),
+        entry!(SEQ, Dlatch),
+        entry!(SEQ, Dflipflop),
+        entry!(SEQ, Bit),
+        entry!(SEQ, Register16),
+        entry!(SEQ, Counter16),
+        entry!(SEQ, Updowncounter16),
+        entry!(SEQ, Shiftregister16),
+        entry!(SEQ, Graycounter4),
+        entry!(SEQ, Graycounter8),
+        entry!(SEQ, Multiplier16),
+        entry!(MEMORY, Ram8),
+        entry!(MEMORY, Ram64),
+        entry!(MEMORY, Ram512),
+        entry!(MEMORY, Ram4k),
+        entry!(MEMORY, Ram16k),
+        entry!(MEMORY, Screen),
+        entry!(MEMORY, Memory),
+        entry!(MEMORY, Blankrom32k),
+        entry!(COMPUTER, Cpu),
+        entry!(COMPUTER, Blankcomputer, hack_peripherals()),
+    ])
+}
+
+// the computer's screen and keyboard, which its page draws and types in to
+fn hack_peripherals() -> Peripherals {
+    Peripherals {
+        screen: screen_words("Blankcomputer0/Computer0/Memory0/Screen0"),
+        keyboard: Some("keyboard"),
+    }
+}
+
+// the registers of a Screen at the path, in address order. The top address bit picks its
+// Ram4k bank, and each three bits after it the bank or register in the RAM below
+fn screen_words(screen: &str) -> Vec<String> {
+    (0..8192)
+        .map(|address| {
+            format!(
+                "{screen}/B{}/B{}/B{}/B{}/R{}",
+                address >> 12,
+                address >> 9 & 7,
+                address >> 6 & 7,
+                address >> 3 & 7,
+                address & 7
+            )
+        })
+        .collect()
+}
+
+pub fn print_library(library: &ChipLibrary) {
+    for (namespace, entries) in library.by_namespace() {
+        println!("{namespace}::");
+        for entry in entries {
+            println!("  {}", entry.path());
+        }
+    }
+}
+
+// Writes a chip's graph for scripts, given the arguments after `export`:
+//   <path> [--format mermaid|dot|json] [--expand <ids>|all] [--expand-depth <n>]
+//   [--output <file>]
+// where ids are separated by commas, and the graph goes to stdout without an output
+pub fn export_graph(library: &ChipLibrary, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (path, flags) = args
+        .split_first()
+        .ok_or("`export` needs a chip, see `project list`")?;
+    let entry = library
+        .find(path)
+        .ok_or_else(|| format!("unknown chip `{path}`, see `project list`"))?;
+    let mut format = GraphFormat::Mermaid;
+    let mut options = GraphOptions::default();
+    let mut output = None;
+    for flag in flags.chunks(2) {
+        let [flag, value] = flag else {
+            return Err(format!("`{}` needs a value", flag[0]).into());
+        };
+        match flag.as_str() {
+            "--format" => {
+                format = GraphFormat::from_name(value)
+                    .ok_or_else(|| format!("unknown format `{value}`"))?
+            }
+            "--expand" if value == "all" => options.expand_depth = usize::MAX,
+            "--expand" => {
+                options.show_chips = value
+                    .split(',')
+                    .filter(|id| !id.is_empty())
+                    .map(String::from)
+                    .collect::<HashSet<_>>()
+            }
+            "--expand-depth" => options.expand_depth = value.parse()?,
+            "--output" => output = Some(value),
+            _ => return Err(format!("unknown option `{flag}`").into()),
+        }
+    }
+    let graph = (entry.graph)(options).export(format);
+    match output {
+        Some(file) => fs::write(file, graph)?,
+        None => println!("{graph}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::array::from_fn;
+
+    use hdl::{
+        bits::{self, BitOrder},
+        Logic,
+    };
+
+    use super::*;
+
+    #[test]
+    fn when_a_word_is_stored_on_the_screen_its_register_holds_it() {
+        let alloc = Bump::new();
+        let mut machine = Machine::new(&alloc, Screen::from).unwrap();
+        let address = 0x1a5b;
+        machine
+            .cycle(ScreenInputs {
+                in_: bits::from_u16(0xbeef, BitOrder::MsbFirst),
+                address: bits::from_u16(address as u16, BitOrder::MsbFirst),
+                load: true,
+                clock: false,
+            })
+            .unwrap();
+
+        let register = &screen_words("Screen0")[address];
+        let paths = (0..16)
+            .rev()
+            .map(|bit| format!("{register}/out-{bit}"))
+            .collect::<Vec<_>>();
+        let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        let word = machine.find_named_outputs(&paths).unwrap();
+        let word: [_; 16] = from_fn(|bit| word[bit].value() == Logic::High);
+        assert_eq!(bits::to_u16(word, BitOrder::MsbFirst), 0xbeef);
+    }
+
+    #[test]
+    fn the_computer_has_the_screen_and_keyboard_its_page_uses() {
+        let alloc = Bump::new();
+        let machine = Machine::new(&alloc, Blankcomputer::from).unwrap();
+        let peripherals = hack_peripherals();
+        let paths = peripherals
+            .screen
+            .iter()
+            .map(|register| format!("{register}/out-0"))
+            .collect::<Vec<_>>();
+        let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        assert!(machine.find_named_outputs(&paths).is_ok());
+        let keyboard = format!("{}-15", peripherals.keyboard.unwrap());
+        assert!(machine.inputs().iter().any(|(input, _)| *input == keyboard));
+    }
+}
@@ -0,0 +1,75 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // the directory rather than the file, which is usually missing
+    println!("cargo:rerun-if-changed=http/vendor");
+    #[cfg(feature = "bundled-mermaid")]
+    mermaid::bundle();
+}
+
+// The `bundled-mermaid` feature builds Mermaid in to the ui. It's taken from
+// http/vendor/mermaid.min.js when that's been fetched, and otherwise fetched in to the
+// build's output directory, which needs curl and a connection. Either way it must have
+// the SHA-256 pinned in http/vendor/mermaid.min.js.sha256, so a changed file isn't built in
+#[cfg(feature = "bundled-mermaid")]
+mod mermaid {
+    use sha2::{Digest, Sha256};
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process::{self, Command};
+
+    const MERMAID_URL: &str = "https://cdn.jsdelivr.net/npm/mermaid@10.9.1/dist/mermaid.min.js";
+    const VENDORED_MERMAID: &str = "http/vendor/mermaid.min.js";
+    const MERMAID_SHA256: &str = "http/vendor/mermaid.min.js.sha256";
+
+    pub(crate) fn bundle() {
+        let Some(pinned) = pinned_sha256() else {
+            fail(&format!(
+                "ui/{MERMAID_SHA256} doesn't pin Mermaid's SHA-256. Run \
+                 ui/http/vendor/fetch-mermaid.sh, check the file it fetched, and commit \
+                 the digest it writes"
+            ));
+        };
+        let out = Path::new(&env::var_os("OUT_DIR").unwrap()).join("mermaid.min.js");
+        if Path::new(VENDORED_MERMAID).exists() {
+            fs::copy(VENDORED_MERMAID, &out).unwrap();
+        } else {
+            let fetched = Command::new("curl")
+                .args(["-fsSL", "-o"])
+                .arg(&out)
+                .arg(MERMAID_URL)
+                .status()
+                .is_ok_and(|status| status.success());
+            if !fetched {
+                fail(&format!(
+                    "couldn't fetch Mermaid from {MERMAID_URL}. Run \
+                     ui/http/vendor/fetch-mermaid.sh on a machine with a connection and \
+                     copy ui/{VENDORED_MERMAID} here, or build without the feature"
+                ));
+            }
+        }
+        let sha256: String = Sha256::digest(fs::read(&out).unwrap())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        if sha256 != pinned {
+            fs::remove_file(&out).unwrap();
+            fail(&format!(
+                "Mermaid's SHA-256 is {sha256} rather than the {pinned} pinned in \
+                 ui/{MERMAID_SHA256}"
+            ));
+        }
+    }
+
+    // the first word of the file, as `sha256sum` writes it
+    fn pinned_sha256() -> Option<String> {
+        let pinned = fs::read_to_string(MERMAID_SHA256).ok()?;
+        let digest = pinned.split_whitespace().next()?.to_ascii_lowercase();
+        (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then_some(digest)
+    }
+
+    fn fail(message: &str) -> ! {
+        eprintln!("error: bundled-mermaid: {message}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,51 @@
+// Mermaid is loaded from a CDN, unless the `bundled-mermaid` feature builds it in so the
+// pages work offline. build.rs fetches its file, or takes the one fetched by
+// `http/vendor/fetch-mermaid.sh`
+const MERMAID_CDN: &str = "https://cdn.jsdelivr.net/npm/mermaid@10.9.1/dist/mermaid.min.js";
+const MERMAID_PATH: &str = "/mermaid.min.js";
+
+#[cfg(feature = "bundled-mermaid")]
+const MERMAID_JS: Option<&str> = Some(include_str!(concat!(env!("OUT_DIR"), "/mermaid.min.js")));
+#[cfg(not(feature = "bundled-mermaid"))]
+const MERMAID_JS: Option<&str> = None;
+
+// the files the server answers with itself, rather than asking the machine
+pub(crate) fn asset(path: &str) -> Option<&'static str> {
+    MERMAID_JS.filter(|_| path == MERMAID_PATH)
+}
+
+// A served page asks the server for a bundled Mermaid, while an exported page has no
+// server to ask and carries it inline
+pub(crate) fn mermaid_script(served: bool) -> String {
+    match MERMAID_JS {
+        Some(_) if served => format!("<script src=\"{MERMAID_PATH}\"></script>"),
+        // an inline script ends at the first closing tag it contains
+        Some(js) => format!("<script>{}</script>", js.replace("</script", "<\\/script")),
+        None => format!("<script src=\"{MERMAID_CDN}\"></script>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_mermaid_is_bundled_it_is_served_and_inlined_otherwise_fetched() {
+        match MERMAID_JS {
+            Some(js) => {
+                assert_eq!(asset(MERMAID_PATH), Some(js));
+                assert_eq!(
+                    mermaid_script(true),
+                    "<script src=\"/mermaid.min.js\"></script>"
+                );
+                assert!(mermaid_script(false).starts_with("<script>"));
+            }
+            None => {
+                assert_eq!(asset(MERMAID_PATH), None);
+                assert_eq!(mermaid_script(true), mermaid_script(false));
+                assert!(mermaid_script(true).contains(MERMAID_CDN));
+            }
+        }
+        assert_eq!(asset("/"), None);
+    }
+}
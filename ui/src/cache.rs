@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use crate::{GraphOptions, MermaidGraph};
+
+// the number of graphs kept, enough for clicking back and forth between expansions
+const GRAPH_CACHE_LEN: usize = 8;
+
+// The graphs most recently drawn, so asking for the same expansion again doesn't walk
+// the whole machine. As the graphs show the machine's values, each is kept for the
+// version of the machine's state it was drawn from, and a newer version drops them all
+#[derive(Debug, Default)]
+pub(crate) struct GraphCache {
+    version: u64,
+    // least recently used first
+    graphs: VecDeque<(GraphOptions, MermaidGraph)>,
+}
+
+impl GraphCache {
+    pub(crate) fn graph(
+        &mut self,
+        version: u64,
+        options: GraphOptions,
+        build: impl FnOnce(GraphOptions) -> MermaidGraph,
+    ) -> MermaidGraph {
+        if version != self.version {
+            self.version = version;
+            self.graphs.clear();
+        }
+        if let Some(index) = self
+            .graphs
+            .iter()
+            .position(|(cached, _)| *cached == options)
+        {
+            let entry = self.graphs.remove(index).unwrap();
+            let graph = entry.1.clone();
+            self.graphs.push_back(entry);
+            return graph;
+        }
+        let graph = build(options.clone());
+        if self.graphs.len() == GRAPH_CACHE_LEN {
+            self.graphs.pop_front();
+        }
+        self.graphs.push_back((options, graph.clone()));
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::HashSet};
+
+    use super::*;
+
+    #[test]
+    fn when_a_graph_is_asked_for_again_it_is_built_only_once_per_state_version() {
+        let builds = Cell::new(0);
+        let build = |options: GraphOptions| {
+            builds.set(builds.get() + 1);
+            MermaidGraph::new("", options.expand_depth.to_string())
+        };
+        let depth = |expand_depth| GraphOptions {
+            expand_depth,
+            ..Default::default()
+        };
+        let mut cache = GraphCache::default();
+
+        assert_eq!(cache.graph(0, depth(1), build).id, "1");
+        assert_eq!(cache.graph(0, depth(1), build).id, "1");
+        assert_eq!(builds.get(), 1);
+        cache.graph(0, depth(2), build);
+        assert_eq!(builds.get(), 2);
+
+        // the values drawn are out of date once the machine changes
+        cache.graph(1, depth(1), build);
+        assert_eq!(builds.get(), 3);
+
+        // the least recently used graph makes way for a new one
+        for expand_depth in 2..=GRAPH_CACHE_LEN {
+            cache.graph(1, depth(expand_depth), build);
+        }
+        cache.graph(1, depth(1), build);
+        cache.graph(1, depth(GRAPH_CACHE_LEN + 1), build);
+        let builds_so_far = builds.get();
+        cache.graph(1, depth(1), build);
+        assert_eq!(builds.get(), builds_so_far);
+        cache.graph(1, depth(2), build);
+        assert_eq!(builds.get(), builds_so_far + 1);
+
+        let expanded = GraphOptions {
+            show_chips: HashSet::from(["chip1".into()]),
+            ..Default::default()
+        };
+        cache.graph(1, expanded.clone(), build);
+        cache.graph(1, expanded, build);
+        assert_eq!(builds.get(), builds_so_far + 2);
+    }
+}
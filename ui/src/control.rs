@@ -0,0 +1,188 @@
+use hdl::{Logic, Machine, StructuredData, StructuredDataFamily};
+
+use crate::{
+    http::{HttpError, HttpRequest},
+    json::{json_string, parse_flat_object},
+    live::{port_value, Waveforms},
+};
+
+// The machine can be driven without a websocket, by posting JSON keyed by input name:
+//
+//   POST /inputs  {"a": 1, "b": false}  sets the inputs and processes them
+//   POST /step    {"load": 1}           sets the inputs, if any, then drives a clock
+//                                       cycle, high then low
+//
+// where inputs are flattened names as `Machine::set_input()` takes them, and values are
+// 0, 1, true or false. Each is answered with the machine's outputs keyed by name, as
+// their logical values, eg `{"outputs":{"out":"1"}}`
+//
+// Chip outputs are forced and released the same way, by the ids the graph gives them:
+//
+//   POST /force    {"id": 12, "value": 1}  holds the output at the value until released
+//   POST /release  {"id": 12}              lets the output follow its chip again
+//
+// each answered with the value the output is forced to, if any, eg `{"forced":"1"}`
+pub(crate) fn control<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    waveforms: &mut Waveforms<'a>,
+    request: &HttpRequest,
+) -> Result<String, HttpError> {
+    if request.method != "POST" {
+        return Err(HttpError::MethodNotAllowed);
+    }
+    let inputs = parse_inputs(&request.body)?;
+    let bad_request = |_| HttpError::BadRequest;
+    for (name, value) in inputs {
+        machine.set_input(&name, value).map_err(bad_request)?;
+    }
+    match request.path.as_str() {
+        "/inputs" => {
+            machine.update().map_err(bad_request)?;
+            waveforms.sample(machine);
+        }
+        "/step" => {
+            let clock = machine.get_clock().ok_or(HttpError::BadRequest)?;
+            for level in [true, false] {
+                machine.set_input(&clock, level).map_err(bad_request)?;
+                machine.update().map_err(bad_request)?;
+                waveforms.sample(machine);
+            }
+        }
+        _ => return Err(HttpError::NotFound),
+    }
+    Ok(outputs_json(
+        machine.outputs(),
+        &TFam::StructuredOutput::<bool>::active_low(),
+    ))
+}
+
+pub(crate) fn override_output<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &Machine<'a, TFam, NINPUT, NOUT>,
+    request: &HttpRequest,
+) -> Result<String, HttpError> {
+    if request.method != "POST" {
+        return Err(HttpError::MethodNotAllowed);
+    }
+    let (id, value) = parse_override(&request.body)?;
+    let out = machine.find_chip_output(id).ok_or(HttpError::NotFound)?;
+    match (request.path.as_str(), value) {
+        ("/force", Some(value)) => out.force(value),
+        ("/release", None) => out.release(),
+        ("/force" | "/release", _) => return Err(HttpError::BadRequest),
+        _ => return Err(HttpError::NotFound),
+    }
+    Ok(match out.get_forced() {
+        Some(value) => format!("{{\"forced\":\"{}\"}}", u8::from(value)),
+        None => "{\"forced\":null}".into(),
+    })
+}
+
+// the output's id, and the value it's forced to when there is one
+fn parse_override(body: &str) -> Result<(u32, Option<bool>), HttpError> {
+    let mut id = None;
+    let mut value = None;
+    for (name, field) in parse_flat_object(body).ok_or(HttpError::BadRequest)? {
+        match (name.as_str(), field.as_str()) {
+            ("id", field) => id = Some(field.parse().map_err(|_| HttpError::BadRequest)?),
+            ("value", "0" | "false") => value = Some(false),
+            ("value", "1" | "true") => value = Some(true),
+            _ => return Err(HttpError::BadRequest),
+        }
+    }
+    Ok((id.ok_or(HttpError::BadRequest)?, value))
+}
+
+// an empty body sets no inputs
+fn parse_inputs(body: &str) -> Result<Vec<(String, bool)>, HttpError> {
+    if body.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    parse_flat_object(body)
+        .ok_or(HttpError::BadRequest)?
+        .into_iter()
+        .map(|(name, value)| match value.as_str() {
+            "0" | "false" => Ok((name, false)),
+            "1" | "true" => Ok((name, true)),
+            _ => Err(HttpError::BadRequest),
+        })
+        .collect()
+}
+
+fn outputs_json(levels: Vec<(String, Logic)>, active_low: &[bool]) -> String {
+    let outputs = levels
+        .into_iter()
+        .zip(active_low)
+        .map(|((name, level), &active_low)| {
+            format!(
+                "{}:\"{}\"",
+                json_string(&name),
+                port_value(level, active_low).to_char()
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{\"outputs\":{{{}}}}}", outputs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_inputs_are_posted_they_are_read_as_bits_and_outputs_written_by_name() {
+        assert_eq!(
+            parse_inputs(r#"{"a": 1, "b": false, "c": "0"}"#),
+            Ok(vec![
+                ("a".into(), true),
+                ("b".into(), false),
+                ("c".into(), false)
+            ])
+        );
+        assert_eq!(parse_inputs(" "), Ok(vec![]));
+        assert_eq!(parse_inputs(r#"{"a": 2}"#), Err(HttpError::BadRequest));
+        assert_eq!(parse_inputs("a=1"), Err(HttpError::BadRequest));
+
+        assert_eq!(
+            outputs_json(
+                vec![("out".into(), Logic::High), ("nreset".into(), Logic::High)],
+                &[false, true]
+            ),
+            r#"{"outputs":{"out":"1","nreset":"0"}}"#
+        );
+    }
+
+    #[test]
+    fn when_an_override_is_posted_it_is_read_as_an_output_id_and_a_bit() {
+        assert_eq!(
+            parse_override(r#"{"id": 12, "value": 1}"#),
+            Ok((12, Some(true)))
+        );
+        assert_eq!(
+            parse_override(r#"{"value": "false", "id": 3}"#),
+            Ok((3, Some(false)))
+        );
+        assert_eq!(parse_override(r#"{"id": 12}"#), Ok((12, None)));
+        for body in [
+            r#"{"id": 12, "value": 2}"#,
+            r#"{"id": "abc"}"#,
+            r#"{"value": 1}"#,
+            r#"{"id": 12, "other": 1}"#,
+            "id=12",
+        ] {
+            assert_eq!(
+                parse_override(body),
+                Err(HttpError::BadRequest),
+                "{body} should be rejected"
+            );
+        }
+    }
+}
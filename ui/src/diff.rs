@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use crate::{
+    get_subgraph_ids, MermaidBus, MermaidClass, MermaidGraph, MermaidLine, MermaidStatement,
+};
+
+const ADDED_STYLE: (&str, &str) = ("fill:#d4f7d4,stroke:#2a2", "stroke:#2a2,stroke-width:3px");
+const REMOVED_STYLE: (&str, &str) = (
+    "fill:#f9d0d0,stroke:#c22,stroke-dasharray:4",
+    "stroke:#c22,stroke-dasharray:4",
+);
+const CHANGED_STYLE: (&str, &str) = ("fill:#fdebc8,stroke:#d80", "stroke:#d80,stroke-width:3px");
+
+// Draws `after` with what changed since `before` picked out. Nodes, chips and lines
+// only in `after` are marked as added, and those only in `before` are drawn where they
+// were and marked as removed. Nodes whose names changed, e.g. by being forced, and lines
+// whose values changed are marked as changed. Nodes are matched by their labels, so
+// two machines should be built in fresh id scopes, see `hdl::ids::IdScope`, for their
+// ids to line up
+pub fn diff_graphs(before: &MermaidGraph, after: &MermaidGraph) -> MermaidGraph {
+    let node_names = |graph: &MermaidGraph| {
+        graph
+            .nodes()
+            .into_iter()
+            .map(|node| (node.get_label(), node.name.clone()))
+            .collect::<HashMap<_, _>>()
+    };
+    let (before_nodes, after_nodes) = (node_names(before), node_names(after));
+    let (before_lines, after_lines) = (lines(before), lines(after));
+    let (before_chips, after_chips) = (get_subgraph_ids(before), get_subgraph_ids(after));
+
+    let mut diff = after.clone();
+    add_removed(&mut diff, before, &after_nodes, &after_lines);
+
+    let mut added = vec![];
+    let mut changed = vec![];
+    for (label, name) in &after_nodes {
+        match before_nodes.get(label) {
+            None => added.push(label.clone()),
+            Some(before_name) if before_name != name => changed.push(label.clone()),
+            Some(_) => {}
+        }
+    }
+    added.extend(
+        after_chips
+            .difference(&before_chips)
+            .map(|id| id.to_string()),
+    );
+    let mut removed = before_nodes
+        .keys()
+        .filter(|label| !after_nodes.contains_key(*label))
+        .cloned()
+        .chain(
+            before_chips
+                .difference(&after_chips)
+                .map(|id| id.to_string()),
+        )
+        .collect::<Vec<_>>();
+
+    let mut added_lines = vec![];
+    let mut changed_lines = vec![];
+    for (key, line) in &after_lines {
+        match before_lines.get(key) {
+            None => added_lines.push(line.clone()),
+            Some(before_line) if before_line.value != line.value => {
+                changed_lines.push(line.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    let removed_lines = before_lines
+        .iter()
+        .filter(|(key, _)| !after_lines.contains_key(*key))
+        .map(|(_, line)| line.clone())
+        .collect();
+
+    for members in [&mut added, &mut removed, &mut changed] {
+        members.sort();
+    }
+    let class = |name: &str, (style, line_style): (&str, &str), members, lines| MermaidClass {
+        name: name.into(),
+        style: style.into(),
+        members,
+        lines,
+        line_style: line_style.into(),
+    };
+    diff.classes = vec![
+        class("added", ADDED_STYLE, added, added_lines),
+        class("removed", REMOVED_STYLE, removed, removed_lines),
+        class("changed", CHANGED_STYLE, changed, changed_lines),
+    ];
+    diff
+}
+
+// every line in the graph, keyed by the labels of the nodes it connects
+fn lines(graph: &MermaidGraph) -> HashMap<(String, String), MermaidLine> {
+    let mut lines = graph
+        .subgraphs
+        .values()
+        .flat_map(lines)
+        .collect::<HashMap<_, _>>();
+    for statement in &graph.statements {
+        if let MermaidStatement::Line(line) | MermaidStatement::Bus(MermaidBus { line, .. }) =
+            statement
+        {
+            lines.insert((line.from.get_label(), line.to.get_label()), line.clone());
+        }
+    }
+    lines
+}
+
+// copies what's only in `before` in to the same subgraphs of the diff
+fn add_removed(
+    diff: &mut MermaidGraph,
+    before: &MermaidGraph,
+    after_nodes: &HashMap<String, String>,
+    after_lines: &HashMap<(String, String), MermaidLine>,
+) {
+    for statement in &before.statements {
+        let is_removed = match statement {
+            MermaidStatement::Line(line) | MermaidStatement::Bus(MermaidBus { line, .. }) => {
+                !after_lines.contains_key(&(line.from.get_label(), line.to.get_label()))
+            }
+            MermaidStatement::Node(node) => !after_nodes.contains_key(&node.get_label()),
+        };
+        if is_removed {
+            diff.statements.push(statement.clone());
+        }
+    }
+    for (id, subgraph) in &before.subgraphs {
+        let diff_subgraph = diff
+            .subgraphs
+            .entry(id.clone())
+            .or_insert_with(|| MermaidGraph::new(subgraph.name, subgraph.id.clone()));
+        add_removed(diff_subgraph, subgraph, after_nodes, after_lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hdl::Logic;
+
+    use super::*;
+    use crate::MermaidNode;
+
+    #[test]
+    fn when_graphs_are_diffed_added_removed_and_changed_parts_are_classed() {
+        let node = |identifier, type_, name: &str| MermaidNode {
+            identifier,
+            type_,
+            name: name.into(),
+        };
+        let line = |from: &MermaidNode, to: &MermaidNode, value| MermaidLine {
+            from: from.clone(),
+            to: to.clone(),
+            value,
+        };
+        let input = node(0, "INPUT", "INPUT");
+        let in_ = node(0, "IN", "in");
+        let old_nand = node(0, "NAND", "NAND");
+        let new_nand = node(1, "NAND", "NAND");
+        let out = node(0, "OUT", "out");
+        let forced_out = node(0, "OUT", "out, forced 1");
+
+        let graph = |lines: Vec<MermaidLine>| {
+            let mut graph = MermaidGraph::new("", "".into());
+            graph.statements = lines.into_iter().map(MermaidStatement::Line).collect();
+            graph
+        };
+        let before = graph(vec![
+            line(&input, &in_, Logic::Low),
+            line(&in_, &old_nand, Logic::Low),
+            line(&old_nand, &out, Logic::High),
+        ]);
+        let after = graph(vec![
+            line(&input, &in_, Logic::High),
+            line(&in_, &new_nand, Logic::High),
+            line(&new_nand, &forced_out, Logic::High),
+        ]);
+        let diff = diff_graphs(&before, &after);
+
+        let classes = diff
+            .classes
+            .iter()
+            .map(|class| {
+                (
+                    class.name.as_str(),
+                    class.members.clone(),
+                    class.lines.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            classes,
+            [
+                ("added", vec!["1NAND".to_owned()], 2),
+                ("removed", vec!["0NAND".to_owned()], 2),
+                ("changed", vec!["0OUT".to_owned()], 1),
+            ]
+        );
+        // removed lines are drawn after the graph's own
+        assert_eq!(diff.statements.len(), 5);
+        assert!(diff
+            .compile()
+            .contains("\nlinkStyle 3,4 stroke:#c22,stroke-dasharray:4"));
+    }
+}
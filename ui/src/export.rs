@@ -0,0 +1,225 @@
+use crate::{
+    bus_value, json::json_string, GraphDirection, MermaidGraph, MermaidLine, MermaidNode,
+    MermaidStatement,
+};
+
+// The formats a graph can be written out in, for scripts drawing diagrams without a
+// server: Mermaid as the pages draw it, Graphviz's DOT, or JSON for other tools
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    Mermaid,
+    Dot,
+    Json,
+}
+
+impl GraphFormat {
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        match name {
+            "mermaid" => Some(GraphFormat::Mermaid),
+            "dot" => Some(GraphFormat::Dot),
+            "json" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl MermaidGraph {
+    pub fn export(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Mermaid => self.compile(),
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    // Chips are drawn as clusters holding the nodes drawn inside them, and every line
+    // is declared after them, as Graphviz puts a node in the first cluster naming it
+    fn to_dot(&self) -> String {
+        let rankdir = match self.layout.direction {
+            GraphDirection::TopDown => "TB",
+            GraphDirection::LeftRight => "LR",
+            GraphDirection::RightLeft => "RL",
+            GraphDirection::BottomUp => "BT",
+        };
+        let mut res = format!("digraph {{\n  rankdir={rankdir};");
+        res += &self.dot_clusters(1);
+        for node in self.nodes() {
+            res += &format!(
+                "\n  {} [label={}];",
+                json_string(&node.get_label()),
+                json_string(&node.name)
+            );
+        }
+        let mut edges = vec![];
+        self.dot_edges(&mut edges);
+        for edge in edges {
+            res += &format!("\n  {edge}");
+        }
+        res + "\n}\n"
+    }
+
+    fn dot_clusters(&self, depth: usize) -> String {
+        let indent = "  ".repeat(depth);
+        let mut res = String::new();
+        let mut subgraphs = self.subgraphs.values().collect::<Vec<_>>();
+        subgraphs.sort_by(|a, b| a.id.cmp(&b.id));
+        for subgraph in subgraphs {
+            res += &format!(
+                "\n{indent}subgraph {} {{\n{indent}  label={};",
+                json_string(&format!("cluster_{}", subgraph.id)),
+                json_string(&subgraph.title())
+            );
+            res += &subgraph.dot_clusters(depth + 1);
+            for node in subgraph.drawn_nodes() {
+                res += &format!("\n{indent}  {};", json_string(&node.get_label()));
+            }
+            res += &format!("\n{indent}}}");
+        }
+        res
+    }
+
+    fn dot_edges(&self, edges: &mut Vec<String>) {
+        for subgraph in self.subgraphs.values() {
+            subgraph.dot_edges(edges);
+        }
+        for statement in &self.statements {
+            let (line, label, width) = match statement {
+                MermaidStatement::Line(line) => (line, line.value.to_string(), 1),
+                MermaidStatement::Bus(bus) => (
+                    &bus.line,
+                    format!("{} bits {}", bus.values.len(), bus_value(&bus.values)),
+                    3,
+                ),
+                MermaidStatement::Node(_) => continue,
+            };
+            edges.push(format!(
+                "{} -> {} [label={}, penwidth={width}];",
+                json_string(&line.from.get_label()),
+                json_string(&line.to.get_label()),
+                json_string(&label)
+            ));
+        }
+    }
+
+    // Each chip with the nodes drawn inside it and the lines declared in it, and the
+    // chips inside it in turn. Nodes are named by their Mermaid labels, which lines
+    // refer to them by
+    fn to_json(&self) -> String {
+        let nodes = self
+            .drawn_nodes()
+            .into_iter()
+            .map(|node| {
+                format!(
+                    "{{\"label\":{},\"id\":{},\"type\":{},\"name\":{}}}",
+                    json_string(&node.get_label()),
+                    node.identifier,
+                    json_string(node.type_),
+                    json_string(&node.name)
+                )
+            })
+            .collect::<Vec<_>>();
+        let lines = self
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                MermaidStatement::Line(line) => Some(json_line(line, "")),
+                // a bus's value is its bits', written as its label is
+                MermaidStatement::Bus(bus) => Some(json_line(
+                    &bus.line,
+                    &format!(",\"bits\":{}", json_string(&bus_value(&bus.values))),
+                )),
+                MermaidStatement::Node(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let mut subgraphs = self.subgraphs.values().collect::<Vec<_>>();
+        subgraphs.sort_by(|a, b| a.id.cmp(&b.id));
+        let chips = subgraphs
+            .into_iter()
+            .map(MermaidGraph::to_json)
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"id\":{},\"name\":{},\"instances\":{},\"nodes\":[{}],\"lines\":[{}],\"chips\":[{}]}}",
+            json_string(&self.id),
+            json_string(self.name),
+            self.instances,
+            nodes.join(","),
+            lines.join(","),
+            chips.join(",")
+        )
+    }
+
+    // the nodes belonging to this level of the graph, as `node_owners()` counts them
+    fn drawn_nodes(&self) -> Vec<&MermaidNode> {
+        self.statements
+            .iter()
+            .filter_map(|statement| match statement {
+                MermaidStatement::Node(node) => Some(node),
+                MermaidStatement::Line(line) if line.to.type_ == "OUT" => Some(&line.to),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn json_line(line: &MermaidLine, members: &str) -> String {
+    format!(
+        "{{\"from\":{},\"to\":{},\"value\":\"{}\"{members}}}",
+        json_string(&line.from.get_label()),
+        json_string(&line.to.get_label()),
+        line.value.to_char()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use hdl::Logic;
+
+    use super::*;
+
+    #[test]
+    fn when_a_graph_is_exported_its_chips_nodes_and_lines_are_written_in_the_format() {
+        let node = |identifier, type_, name: &str| MermaidNode {
+            identifier,
+            type_,
+            name: name.into(),
+        };
+        let in_ = node(1, "IN", "Not in");
+        let mut graph = MermaidGraph::new("", "".into());
+        let mut not = MermaidGraph::new("Not", "not".into());
+        not.statements.push(MermaidStatement::Node(in_.clone()));
+        graph.subgraphs.insert("not".into(), not);
+        graph.statements.push(MermaidStatement::Line(MermaidLine {
+            from: node(0, "INPUT", "INPUT"),
+            to: in_,
+            value: Logic::High,
+        }));
+
+        assert_eq!(graph.export(GraphFormat::Mermaid), graph.compile());
+        assert_eq!(
+            graph.export(GraphFormat::Dot),
+            r#"digraph {
+  rankdir=TB;
+  subgraph "cluster_not" {
+    label="Not";
+    "1IN";
+  }
+  "0INPUT" [label="INPUT"];
+  "1IN" [label="Not in"];
+  "0INPUT" -> "1IN" [label="1", penwidth=1];
+}
+"#
+        );
+        assert_eq!(
+            graph.export(GraphFormat::Json),
+            concat!(
+                r#"{"id":"","name":"","instances":1,"nodes":[],"#,
+                r#""lines":[{"from":"0INPUT","to":"1IN","value":"1"}],"#,
+                r#""chips":[{"id":"not","name":"Not","instances":1,"#,
+                r#""nodes":[{"label":"1IN","id":1,"type":"IN","name":"Not in"}],"#,
+                r#""lines":[],"chips":[]}]}"#
+            )
+        );
+        assert_eq!(GraphFormat::from_name("dot"), Some(GraphFormat::Dot));
+        assert_eq!(GraphFormat::from_name("svg"), None);
+    }
+}
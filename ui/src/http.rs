@@ -0,0 +1,362 @@
+use std::io::{self, BufRead, ErrorKind, Read, Write};
+
+// The parts of HTTP/1.1 the server needs: requests with their headers and an optional
+// body of known length, and responses which keep the connection open when the client
+// asks for it
+
+// requests are small, so anything longer is a misbehaving client
+const MAX_BODY_LEN: usize = 1 << 16;
+const MAX_LINE_LEN: usize = 1 << 13;
+const MAX_HEADERS: usize = 100;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct HttpRequest {
+    pub(crate) method: String,
+    // percent decoded, without the query
+    pub(crate) path: String,
+    // percent decoded names and values, in the order they were given
+    pub(crate) query: Vec<(String, String)>,
+    // names are lowercase
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) version: String,
+    pub(crate) body: String,
+}
+
+// An error answered with its status code. Anything the client got wrong is a bad
+// request, unless its headers were too long, and a server failing to answer is an
+// internal error
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum HttpError {
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    HeadersTooLarge,
+    Internal,
+}
+
+impl HttpError {
+    fn status(&self) -> &'static str {
+        match self {
+            HttpError::BadRequest => "400 Bad Request",
+            HttpError::NotFound => "404 Not Found",
+            HttpError::MethodNotAllowed => "405 Method Not Allowed",
+            HttpError::HeadersTooLarge => "431 Request Header Fields Too Large",
+            HttpError::Internal => "500 Internal Server Error",
+        }
+    }
+}
+
+// A response's body with the type of its content, so browsers needn't guess it
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct HttpResponse {
+    pub(crate) content_type: &'static str,
+    pub(crate) body: String,
+}
+
+impl HttpResponse {
+    pub(crate) fn html(body: impl Into<String>) -> HttpResponse {
+        HttpResponse::new("text/html; charset=utf-8", body)
+    }
+
+    pub(crate) fn json(body: impl Into<String>) -> HttpResponse {
+        HttpResponse::new("application/json", body)
+    }
+
+    pub(crate) fn javascript(body: impl Into<String>) -> HttpResponse {
+        HttpResponse::new("text/javascript; charset=utf-8", body)
+    }
+
+    pub(crate) fn text(body: impl Into<String>) -> HttpResponse {
+        HttpResponse::new("text/plain; charset=utf-8", body)
+    }
+
+    fn new(content_type: &'static str, body: impl Into<String>) -> HttpResponse {
+        HttpResponse {
+            content_type,
+            body: body.into(),
+        }
+    }
+}
+
+impl HttpRequest {
+    // parses the request line and headers, without the blank line ending them
+    pub(crate) fn parse(lines: &[String]) -> Result<HttpRequest, HttpError> {
+        let (request_line, header_lines) = lines.split_first().ok_or(HttpError::BadRequest)?;
+        let [method, target, version] = request_line.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(HttpError::BadRequest);
+        };
+        if !version.starts_with("HTTP/1.") {
+            return Err(HttpError::BadRequest);
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        if !path.starts_with('/') {
+            return Err(HttpError::BadRequest);
+        }
+        let query = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+                Ok((decode_query(name)?, decode_query(value)?))
+            })
+            .collect::<Result<_, HttpError>>()?;
+        let headers = header_lines
+            .iter()
+            .map(|line| {
+                let (name, value) = line.split_once(':').ok_or(HttpError::BadRequest)?;
+                Ok((name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+            })
+            .collect::<Result<_, HttpError>>()?;
+        Ok(HttpRequest {
+            method: method.into(),
+            path: percent_decode(path)?,
+            query,
+            headers,
+            version: version.into(),
+            body: String::new(),
+        })
+    }
+
+    // the first value given for the parameter
+    pub(crate) fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // HTTP/1.1 connections stay open unless the client says otherwise, and HTTP/1.0
+    // ones only when it asks
+    pub(crate) fn keep_alive(&self) -> bool {
+        let connection = self.header("connection").map(str::to_ascii_lowercase);
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => self.version == "HTTP/1.1",
+        }
+    }
+
+    fn content_length(&self) -> Result<usize, HttpError> {
+        match self.header("content-length") {
+            Some(length) => length.parse().map_err(|_| HttpError::BadRequest),
+            None => Ok(0),
+        }
+    }
+}
+
+// The next request on the connection, or `None` if the client closed it, or left it idle
+// past the read timeout, before starting one. Like the body, a line and the number of
+// headers are capped, and no more of a request past them is read
+pub(crate) fn read_request(
+    reader: &mut impl BufRead,
+) -> io::Result<Option<Result<HttpRequest, HttpError>>> {
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        // a line is read up to one byte past the cap, so a line filling it is ended by
+        // its newline
+        match reader
+            .by_ref()
+            .take(MAX_LINE_LEN as u64 + 1)
+            .read_line(&mut line)
+        {
+            Ok(0) if lines.is_empty() => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(len) if len > MAX_LINE_LEN && !line.ends_with('\n') => {
+                return Ok(Some(Err(HttpError::HeadersTooLarge)))
+            }
+            Ok(_) => {}
+            Err(err)
+                if lines.is_empty()
+                    && line.is_empty()
+                    && matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                return Ok(None)
+            }
+            Err(err) => return Err(err),
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() {
+            break;
+        }
+        // the request line and its headers
+        if lines.len() > MAX_HEADERS {
+            return Ok(Some(Err(HttpError::HeadersTooLarge)));
+        }
+        lines.push(line.to_owned());
+    }
+    let mut request = match HttpRequest::parse(&lines) {
+        Ok(request) => request,
+        Err(err) => return Ok(Some(Err(err))),
+    };
+    let length = match request.content_length() {
+        Ok(length) if length <= MAX_BODY_LEN => length,
+        _ => return Ok(Some(Err(HttpError::BadRequest))),
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    request.body = match String::from_utf8(body) {
+        Ok(body) => body,
+        Err(_) => return Ok(Some(Err(HttpError::BadRequest))),
+    };
+    Ok(Some(Ok(request)))
+}
+
+pub(crate) fn write_response(
+    stream: &mut impl Write,
+    response: Result<HttpResponse, HttpError>,
+    keep_alive: bool,
+) -> io::Result<()> {
+    let (status, response) = match response {
+        Ok(response) => ("200 OK", response),
+        Err(err) => (err.status(), HttpResponse::text(err.status())),
+    };
+    let HttpResponse { content_type, body } = response;
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: {connection}\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+// the start of a response whose body is server-sent events, written as they happen
+// until the connection is closed
+pub(crate) fn write_event_stream_head(stream: &mut impl Write) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )
+}
+
+// a query's spaces may be sent as '+'
+fn decode_query(value: &str) -> Result<String, HttpError> {
+    percent_decode(&value.replace('+', " "))
+}
+
+fn percent_decode(value: &str) -> Result<String, HttpError> {
+    let mut bytes = vec![];
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).ok_or(HttpError::BadRequest)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| HttpError::BadRequest)?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| HttpError::BadRequest)?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| HttpError::BadRequest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::*;
+
+    fn read(request: &str) -> Option<Result<HttpRequest, HttpError>> {
+        read_request(&mut BufReader::new(request.as_bytes())).expect("request not read")
+    }
+
+    #[test]
+    fn when_a_request_is_parsed_its_path_and_query_are_decoded() {
+        let request = read(
+            "GET /gates%3A%3AAnd?expanded=a%2Cb,&q=ram+8&flag HTTP/1.1\r\nHost: localhost\r\nX-Extra:  spaced  \r\n\r\n",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/gates::And");
+        assert_eq!(request.query_param("expanded"), Some("a,b,"));
+        assert_eq!(request.query_param("q"), Some("ram 8"));
+        assert_eq!(request.query_param("flag"), Some(""));
+        assert_eq!(request.query_param("missing"), None);
+        assert_eq!(request.header("x-extra"), Some("spaced"));
+        assert_eq!(request.header("HOST"), Some("localhost"));
+    }
+
+    #[test]
+    fn when_a_request_is_malformed_it_is_a_bad_request() {
+        for request in [
+            "GET\r\n\r\n",
+            "GET * HTTP/1.1\r\n\r\n",
+            "GET / SPDY/3\r\n\r\n",
+            "GET /?expanded=%zz HTTP/1.1\r\n\r\n",
+            "GET /%ff HTTP/1.1\r\n\r\n",
+            "GET / HTTP/1.1\r\nno colon\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: lots\r\n\r\n",
+        ] {
+            assert_eq!(
+                read(request),
+                Some(Err(HttpError::BadRequest)),
+                "{request:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn when_a_header_line_or_the_headers_are_too_long_the_rest_is_not_read() {
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-Extra: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        for request in [long_line, many_headers] {
+            assert_eq!(read(&request), Some(Err(HttpError::HeadersTooLarge)));
+        }
+
+        // a line up to the cap, with its line ending, and as many headers are read
+        let longest_line = format!("GET /{} HTTP/1.1\r\n", "a".repeat(MAX_LINE_LEN - 16));
+        let request = format!("{longest_line}{}\r\n", "X-Extra: 1\r\n".repeat(MAX_HEADERS));
+        assert!(matches!(read(&request), Some(Ok(_))));
+    }
+
+    #[test]
+    fn when_requests_share_a_connection_each_is_read_with_its_body_in_turn() {
+        let mut reader = BufReader::new(
+            concat!(
+                "POST /step HTTP/1.1\r\nContent-Length: 4\r\n\r\n{}{}",
+                "GET / HTTP/1.0\r\n\r\n",
+            )
+            .as_bytes(),
+        );
+        let mut next = || read_request(&mut reader).unwrap().map(Result::unwrap);
+
+        let post = next().unwrap();
+        assert_eq!((post.method.as_str(), post.body.as_str()), ("POST", "{}{}"));
+        assert!(post.keep_alive());
+        let get = next().unwrap();
+        assert_eq!(get.path, "/");
+        assert!(!get.keep_alive(), "HTTP/1.0 closes by default");
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn when_a_response_is_written_its_status_content_type_and_connection_are_given() {
+        let mut response = vec![];
+        write_response(&mut response, Ok(HttpResponse::json("{}")), true).unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\r\n{}"
+        );
+
+        let mut response = vec![];
+        write_response(&mut response, Err(HttpError::NotFound), false).unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 13\r\nConnection: close\r\n\r\n404 Not Found"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use hdl::{
+    netlist::{Netlist, NodeKind},
+    Machine, StructuredData, StructuredDataFamily,
+};
+
+// The machine's netlist for tools that would rather not parse Mermaid: its nodes with
+// their values at the last process, the edges between them as [from, to] node indices,
+// the chip hierarchy and the machine's ports by name
+pub(crate) fn machine_netlist<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &Machine<'a, TFam, NINPUT, NOUT>,
+) -> String {
+    netlist_json(
+        &machine.netlist(),
+        &Machine::<TFam, NINPUT, NOUT>::input_names(),
+        &TFam::StructuredInput::<bool>::active_low(),
+        &Machine::<TFam, NINPUT, NOUT>::output_names(),
+        &TFam::StructuredOutput::<bool>::active_low(),
+    )
+}
+
+fn netlist_json(
+    netlist: &Netlist,
+    input_names: &[String],
+    input_active_low: &[bool],
+    output_names: &[String],
+    output_active_low: &[bool],
+) -> String {
+    let ports = |names: &[String], active_low: &[bool], nodes: &[usize]| {
+        let ports = names
+            .iter()
+            .zip(active_low)
+            .zip(nodes)
+            .map(|((name, active_low), node)| {
+                format!(
+                    "{{\"name\":{},\"node\":{node},\"active_low\":{active_low}}}",
+                    json_string(name)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", ports.join(","))
+    };
+    let nodes = netlist
+        .nodes
+        .iter()
+        .map(|node| {
+            format!(
+                "{{\"kind\":\"{}\",\"label\":{},\"source_id\":{},\"chip\":{},\"value\":\"{}\"}}",
+                kind_name(node.kind),
+                json_string(&node.label),
+                node.source_id,
+                json_option(node.chip),
+                node.value.to_char()
+            )
+        })
+        .collect::<Vec<_>>();
+    let edges = netlist
+        .edges
+        .iter()
+        .map(|edge| format!("[{},{}]", edge.from, edge.to))
+        .collect::<Vec<_>>();
+    let chips = netlist
+        .chips
+        .iter()
+        .map(|chip| {
+            format!(
+                "{{\"id\":{},\"label\":{},\"name\":{},\"description\":{},\"parent\":{}}}",
+                json_string(&chip.id),
+                json_string(chip.label),
+                chip.name.map_or_else(|| "null".into(), json_string),
+                json_string(chip.description),
+                json_option(chip.parent)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"inputs\":{},\"outputs\":{},\"nodes\":[{}],\"edges\":[{}],\"chips\":[{}]}}",
+        ports(input_names, input_active_low, &netlist.inputs),
+        ports(output_names, output_active_low, &netlist.outputs),
+        nodes.join(","),
+        edges.join(","),
+        chips.join(",")
+    )
+}
+
+// The chips whose label or instance name contains the query, ignoring case, with their
+// paths as `Netlist::node_path()` names them. Each comes with the ids of the chips
+// containing it, outermost first, which have to be expanded for it to be drawn
+pub(crate) fn chip_search(netlist: &Netlist, query: &str) -> String {
+    let query = query.to_lowercase();
+    let names = netlist.chip_names();
+    let matches = netlist
+        .chips
+        .iter()
+        .enumerate()
+        .filter(|(_, chip)| {
+            chip.label.to_lowercase().contains(&query)
+                || chip
+                    .name
+                    .is_some_and(|name| name.to_lowercase().contains(&query))
+        })
+        .map(|(index, chip)| {
+            let mut path = vec![];
+            let mut ancestors = vec![];
+            let mut parent = Some(index);
+            while let Some(ancestor) = parent {
+                path.push(names[ancestor].as_str());
+                if ancestor != index {
+                    ancestors.push(json_string(&netlist.chips[ancestor].id));
+                }
+                parent = netlist.chips[ancestor].parent;
+            }
+            path.reverse();
+            ancestors.reverse();
+            format!(
+                "{{\"id\":{},\"path\":{},\"ancestors\":[{}]}}",
+                json_string(&chip.id),
+                json_string(&path.join("/")),
+                ancestors.join(",")
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", matches.join(","))
+}
+
+// The members of a JSON object whose values are strings, numbers or literals, in the
+// order they're given. Strings are unescaped, and the rest are given as written. Nested
+// objects and arrays aren't needed by anything the server is sent
+pub(crate) fn parse_flat_object(json: &str) -> Option<Vec<(String, String)>> {
+    let mut chars = json.trim().chars().peekable();
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+    let mut members = vec![];
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip_whitespace(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.next()? != '"' {
+                return None;
+            }
+            let name = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            if chars.next()? != ':' {
+                return None;
+            }
+            skip_whitespace(&mut chars);
+            let value = if chars.next_if_eq(&'"').is_some() {
+                parse_string(&mut chars)?
+            } else {
+                let mut value = String::new();
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || "+-.".contains(*c))
+                {
+                    value.push(c);
+                }
+                if value.is_empty() {
+                    return None;
+                }
+                value
+            };
+            members.push((name, value));
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                '}' => break,
+                _ => return None,
+            }
+        }
+    }
+    chars.next().is_none().then_some(members)
+}
+
+// the rest of a string whose opening quote has been read
+fn parse_string(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(string),
+            '\\' => string.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let hex = chars.take(4).collect::<String>();
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                }
+                c @ ('"' | '\\' | '/') => c,
+                _ => return None,
+            }),
+            c => string.push(c),
+        }
+    }
+}
+
+fn kind_name(kind: NodeKind) -> &'static str {
+    match kind {
+        NodeKind::UserInput => "user_input",
+        NodeKind::ChipInput => "chip_input",
+        NodeKind::ChipOutput => "chip_output",
+        NodeKind::Nand => "nand",
+        NodeKind::SharedBus => "shared_bus",
+        NodeKind::Lookup => "lookup",
+        NodeKind::MachineOutput => "machine_output",
+    }
+}
+
+fn json_option(index: Option<usize>) -> String {
+    index.map_or_else(|| "null".into(), |index| index.to_string())
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use hdl::{
+        netlist::{NetlistChip, NetlistEdge, NetlistNode},
+        Logic,
+    };
+
+    use super::*;
+
+    #[test]
+    fn when_a_netlist_is_written_as_json_ports_are_named_and_chips_nested() {
+        let node = |kind, label: &str, chip| NetlistNode {
+            kind,
+            label: label.into(),
+            source_id: 7,
+            chip,
+            value: Logic::High,
+        };
+        let netlist = Netlist {
+            nodes: vec![
+                node(NodeKind::UserInput, "a", None),
+                node(NodeKind::ChipOutput, "say \"hi\"", Some(0)),
+            ],
+            edges: vec![NetlistEdge { from: 0, to: 1 }],
+            chips: vec![NetlistChip {
+                id: "Not0".into(),
+                label: "Not",
+                description: "",
+                name: None,
+                parent: None,
+            }],
+            inputs: vec![0],
+            outputs: vec![1],
+        };
+
+        assert_eq!(
+            netlist_json(&netlist, &["in".into()], &[false], &["out".into()], &[true]),
+            concat!(
+                r#"{"inputs":[{"name":"in","node":0,"active_low":false}],"#,
+                r#""outputs":[{"name":"out","node":1,"active_low":true}],"#,
+                r#""nodes":[{"kind":"user_input","label":"a","source_id":7,"chip":null,"value":"1"},"#,
+                r#"{"kind":"chip_output","label":"say \"hi\"","source_id":7,"chip":0,"value":"1"}],"#,
+                r#""edges":[[0,1]],"#,
+                r#""chips":[{"id":"Not0","label":"Not","name":null,"description":"","parent":null}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn when_a_flat_object_is_parsed_its_members_come_in_order() {
+        assert_eq!(
+            parse_flat_object(r#" { "load" : 1, "in\"1\"":true,"name":"a\u0062\n" } "#),
+            Some(vec![
+                ("load".into(), "1".into()),
+                ("in\"1\"".into(), "true".into()),
+                ("name".into(), "ab\n".into()),
+            ])
+        );
+        assert_eq!(parse_flat_object("{}"), Some(vec![]));
+        for json in [
+            "",
+            "[]",
+            "{\"a\":}",
+            "{\"a\":1,}",
+            "{\"a\":{}}",
+            "{\"a\":1} x",
+            "{a:1}",
+        ] {
+            assert_eq!(parse_flat_object(json), None, "{json} should be rejected");
+        }
+    }
+
+    #[test]
+    fn when_chips_are_searched_matches_come_with_their_paths_and_ancestors() {
+        let chip = |id: &str, label, name, parent| NetlistChip {
+            id: id.into(),
+            label,
+            description: "",
+            name,
+            parent,
+        };
+        let netlist = Netlist {
+            chips: vec![
+                chip("10", "Ram64", None, None),
+                chip("11", "Ram8", None, Some(0)),
+                chip("12", "Register16", Some("R0"), Some(1)),
+                chip("13", "Ram8", None, Some(0)),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            chip_search(&netlist, "ram8"),
+            concat!(
+                r#"[{"id":"11","path":"Ram64_0/Ram8_0","ancestors":["10"]},"#,
+                r#"{"id":"13","path":"Ram64_0/Ram8_1","ancestors":["10"]}]"#
+            )
+        );
+        assert_eq!(
+            chip_search(&netlist, "r0"),
+            r#"[{"id":"12","path":"Ram64_0/Ram8_0/R0","ancestors":["10","11"]}]"#
+        );
+        assert_eq!(chip_search(&netlist, "alu"), "[]");
+    }
+}
@@ -1,12 +1,44 @@
+mod assets;
+mod cache;
+mod control;
+mod diff;
+mod export;
+mod http;
+mod json;
+mod library;
+mod live;
+mod peripherals;
+mod truth_table;
+mod websocket;
+
+pub use diff::diff_graphs;
+pub use export::GraphFormat;
+pub use library::{start_library_server, ChipLibrary, LibraryEntry};
+pub use peripherals::Peripherals;
+pub use truth_table::{truth_table, TruthTable, TruthTableRow, MAX_TRUTH_TABLE_INPUTS};
+
 use std::{
+    cell::Cell,
     collections::{HashMap, HashSet},
-    io::{BufRead, BufReader, Write},
-    net::{TcpListener, TcpStream},
+    fs,
+    io::{self, BufReader, ErrorKind, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
 };
 
+use http::{HttpError, HttpRequest, HttpResponse};
+use json::json_string;
+
 use hdl::{
-    ChipInput, ChipOutputType, ChipOutputWrapper, Input, Machine, Nand, Output,
-    StructuredDataFamily, UserInput,
+    Chip, ChipInput, ChipOutputType, ChipOutputWrapper, Input, Logic, Lookup, Machine, Nand,
+    Output, SharedBus, StructuredDataFamily, UserInput,
 };
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -22,16 +54,41 @@ impl MermaidNode {
     }
 }
 
+// a connection, carrying the value its driver had at the last process
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
 struct MermaidLine {
     from: MermaidNode,
     to: MermaidNode,
+    value: Logic,
+}
+
+// the lines between the bits of two array ports drawn as one, with the bits' values in
+// port order. Its line's value is theirs if they agree, and unknown otherwise
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+struct MermaidBus {
+    line: MermaidLine,
+    values: Vec<Logic>,
 }
 
 #[derive(Debug, Eq, Hash, PartialEq, Clone)]
 enum MermaidStatement {
     Line(MermaidLine),
     Node(MermaidNode),
+    Bus(MermaidBus),
+}
+
+// the node types a bus's ends are drawn with, standing in for a port's IN or OUT bits
+const BUS_TYPES: [(&str, &str); 2] = [("IN", "IN_BUS"), ("OUT", "OUT_BUS")];
+
+// a class of nodes and chips, by their ids, and the Mermaid style they're drawn with,
+// along with the lines drawn in `line_style`
+#[derive(Debug, Clone, PartialEq)]
+struct MermaidClass {
+    name: String,
+    style: String,
+    members: Vec<String>,
+    lines: Vec<MermaidLine>,
+    line_style: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,7 +96,16 @@ pub struct MermaidGraph {
     statements: Vec<MermaidStatement>,
     name: &'static str,
     id: String,
+    // how many identical chips the subgraph stands for
+    instances: usize,
     subgraphs: HashMap<String, MermaidGraph>,
+    // only the top of the graph has classes, which cover the nodes and chips inside it
+    classes: Vec<MermaidClass>,
+    // for the inside of a root chip, the chips opened to reach it from the top of the
+    // machine, itself last, as ids and names
+    path: Vec<(String, &'static str)>,
+    // like its classes, only the top of the graph is laid out
+    layout: GraphLayout,
 }
 
 impl MermaidGraph {
@@ -48,27 +114,415 @@ impl MermaidGraph {
             statements: Vec::new(),
             subgraphs: HashMap::new(),
             id,
+            instances: 1,
             name,
+            classes: vec![],
+            path: vec![],
+            layout: Default::default(),
         }
     }
 
-    fn get_subgraph(&mut self, path: &Vec<String>) -> &mut MermaidGraph {
+    // the nodes drawn anywhere in the graph, ordered by their labels
+    fn nodes(&self) -> Vec<&MermaidNode> {
+        let mut nodes = self
+            .statements
+            .iter()
+            .flat_map(|statement| match statement {
+                MermaidStatement::Line(line) => vec![&line.from, &line.to],
+                MermaidStatement::Node(node) => vec![node],
+                MermaidStatement::Bus(bus) => vec![&bus.line.from, &bus.line.to],
+            })
+            .chain(self.subgraphs.values().flat_map(MermaidGraph::nodes))
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| node.get_label());
+        nodes.dedup_by_key(|node| node.get_label());
+        nodes
+    }
+
+    fn get_subgraph(&mut self, path: &[String]) -> &mut MermaidGraph {
         path.iter()
             .fold(self, |subgraph, id| subgraph.subgraphs.get_mut(id).unwrap())
     }
 
+    // the title of the subgraph at the path, if it's been drawn
+    fn subgraph_title(&self, path: &[String]) -> Option<&'static str> {
+        let subgraph = path
+            .iter()
+            .try_fold(self, |subgraph, id| subgraph.subgraphs.get(id))?;
+        (!path.is_empty()).then_some(subgraph.name)
+    }
+
+    // A tooltip for each node by its label: the chips it's drawn in, its name and id, and
+    // the value it drives, or if it drives nothing drawn, the value it's driven with
+    fn tooltips(&self) -> Vec<(String, String)> {
+        let mut owners = HashMap::new();
+        self.node_owners(&[], &mut owners);
+        let mut values = HashMap::new();
+        self.line_values(&mut values);
+        self.nodes()
+            .into_iter()
+            .map(|node| {
+                let label = node.get_label();
+                let mut path = owners
+                    .get(&label)
+                    .map(|path| {
+                        (1..=path.len())
+                            .filter_map(|depth| self.subgraph_title(&path[..depth]))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                path.push(&node.name);
+                let value = values.get(&label).map_or("-", |(_, value)| value);
+                let tooltip = format!(
+                    "{}\nid {}, value {value}",
+                    path.join(" › "),
+                    node.identifier
+                );
+                (label, tooltip)
+            })
+            .collect()
+    }
+
+    // the value on the lines at each node, by its label, and whether it's driving them
+    fn line_values(&self, values: &mut HashMap<String, (bool, String)>) {
+        for statement in &self.statements {
+            let (line, value) = match statement {
+                MermaidStatement::Line(line) => (line, line.value.to_string()),
+                MermaidStatement::Bus(bus) => (&bus.line, bus_value(&bus.values)),
+                MermaidStatement::Node(_) => continue,
+            };
+            values.insert(line.from.get_label(), (true, value.clone()));
+            let to = values
+                .entry(line.to.get_label())
+                .or_insert((false, value.clone()));
+            if !to.0 {
+                to.1 = value;
+            }
+        }
+        for subgraph in self.subgraphs.values() {
+            subgraph.line_values(values);
+        }
+    }
+
+    // Inputs that aren't the machine's are constants, so they're dropped along with their
+    // lines, or drawn as terminals with their value
+    fn draw_constants(&mut self, inputs: &[u32], constant_inputs: ConstantInputs) {
+        let is_constant =
+            |node: &MermaidNode| node.type_ == "INPUT" && !inputs.contains(&node.identifier);
+        self.statements.retain_mut(|statement| match statement {
+            MermaidStatement::Line(line) if is_constant(&line.from) => match constant_inputs {
+                ConstantInputs::Shown => true,
+                ConstantInputs::Hidden => false,
+                ConstantInputs::Terminals => {
+                    line.from = MermaidNode {
+                        identifier: line.from.identifier,
+                        type_: "CONST",
+                        name: line.value.to_char().into(),
+                    };
+                    true
+                }
+            },
+            _ => true,
+        });
+        for subgraph in self.subgraphs.values_mut() {
+            subgraph.draw_constants(inputs, constant_inputs);
+        }
+    }
+
+    // Collapsed sibling chips with the same title and ports are drawn as the first of
+    // them, badged with how many there are, and the lines to and from the others are
+    // moved onto it
+    fn group_repeated(&mut self, show_chips: &HashSet<String>) {
+        let mut replacements = HashMap::new();
+        self.group_subgraphs(show_chips, &mut replacements);
+        self.replace_nodes(&replacements);
+    }
+
+    fn group_subgraphs(
+        &mut self,
+        show_chips: &HashSet<String>,
+        replacements: &mut HashMap<MermaidNode, MermaidNode>,
+    ) {
+        let mut groups = HashMap::<_, Vec<&String>>::new();
+        for (id, subgraph) in &self.subgraphs {
+            let is_collapsed = !show_chips.contains(id)
+                && subgraph.subgraphs.is_empty()
+                && subgraph
+                    .statements
+                    .iter()
+                    .all(|statement| matches!(statement, MermaidStatement::Node(_)));
+            if is_collapsed {
+                let ports = subgraph
+                    .boundary_nodes()
+                    .into_iter()
+                    .map(|node| node.name)
+                    .collect::<Vec<_>>();
+                groups.entry((subgraph.name, ports)).or_default().push(id);
+            }
+        }
+        let groups = groups
+            .into_values()
+            .filter(|ids| ids.len() > 1)
+            .map(|mut ids| {
+                ids.sort();
+                ids.into_iter().cloned().collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for ids in groups {
+            let kept = &ids[0];
+            let kept_nodes = self.subgraphs[kept].boundary_nodes();
+            for id in &ids[1..] {
+                let removed = self.subgraphs.remove(id).unwrap();
+                replacements.extend(
+                    removed
+                        .boundary_nodes()
+                        .into_iter()
+                        .zip(kept_nodes.iter().cloned()),
+                );
+            }
+            self.subgraphs.get_mut(kept).unwrap().instances = ids.len();
+        }
+        for subgraph in self.subgraphs.values_mut() {
+            subgraph.group_subgraphs(show_chips, replacements);
+        }
+    }
+
+    // a collapsed chip's inputs and outputs, in the order of their names
+    fn boundary_nodes(&self) -> Vec<MermaidNode> {
+        let mut nodes = self
+            .statements
+            .iter()
+            .filter_map(|statement| match statement {
+                MermaidStatement::Node(node) => Some(node.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        nodes
+    }
+
+    // lines that end up the same once their nodes are replaced are only drawn once
+    fn replace_nodes(&mut self, replacements: &HashMap<MermaidNode, MermaidNode>) {
+        let replace = |node: &mut MermaidNode| {
+            if let Some(replacement) = replacements.get(node) {
+                *node = replacement.clone();
+            }
+        };
+        let mut drawn = HashSet::new();
+        self.statements.retain_mut(|statement| {
+            if let MermaidStatement::Line(line) = statement {
+                replace(&mut line.from);
+                replace(&mut line.to);
+            }
+            drawn.insert(statement.clone())
+        });
+        for subgraph in self.subgraphs.values_mut() {
+            subgraph.replace_nodes(replacements);
+        }
+    }
+
+    // Lines between the bits of two chips' array ports, like a Mux16's `out-0` to
+    // `out-15` feeding another chip's `in-0` to `in-15`, are drawn as one bus between
+    // the ports. Bits with no lines left are drawn only as part of their port's bus
+    fn bundle_buses(&mut self) {
+        let mut owners = HashMap::new();
+        self.node_owners(&[], &mut owners);
+        let mut groups = BusLines::new();
+        self.bus_lines(&[], &owners, &mut groups);
+        groups.retain(|_, (_, lines)| lines.len() > 1);
+
+        // a port's bus node stands in for all its bits drawn in buses
+        let mut port_bits = HashMap::<_, Vec<(usize, MermaidNode)>>::new();
+        for ((from_port, to_port), (_, lines)) in &groups {
+            for line in lines {
+                for (port, node) in [(from_port, &line.from), (to_port, &line.to)] {
+                    let (_, bit) = port_bit(node).unwrap();
+                    port_bits
+                        .entry(port.clone())
+                        .or_default()
+                        .push((bit, node.clone()));
+                }
+            }
+        }
+        let mut bundled_lines = HashMap::<String, usize>::new();
+        let mut bus_nodes = HashMap::new();
+        for ((owner, base, type_), bits) in &mut port_bits {
+            bits.sort_by_key(|(bit, _)| *bit);
+            bits.dedup_by_key(|(bit, _)| *bit);
+            let (first, first_node) = &bits[0];
+            let last = bits[bits.len() - 1].0;
+            let bus_type = BUS_TYPES.iter().find(|(bit_type, _)| bit_type == type_);
+            let node = MermaidNode {
+                identifier: first_node.identifier,
+                type_: bus_type.unwrap().1,
+                name: format!("{base}[{first}..{last}]"),
+            };
+            self.get_subgraph(owner)
+                .statements
+                .push(MermaidStatement::Node(node.clone()));
+            bus_nodes.insert((owner.clone(), base.clone(), *type_), node);
+        }
+
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        for ((from_port, to_port), (path, mut lines)) in groups {
+            lines.sort_by_key(|line| port_bit(&line.from).unwrap().1);
+            for line in &lines {
+                for node in [&line.from, &line.to] {
+                    *bundled_lines.entry(node.get_label()).or_default() += 1;
+                }
+            }
+            let values = lines.iter().map(|line| line.value).collect::<Vec<_>>();
+            let value = match values.iter().all(|&value| value == values[0]) {
+                true => values[0],
+                false => Logic::Unknown,
+            };
+            let graph = self.get_subgraph(&path);
+            graph.statements.retain(|statement| match statement {
+                MermaidStatement::Line(line) => !lines.contains(line),
+                _ => true,
+            });
+            graph.statements.push(MermaidStatement::Bus(MermaidBus {
+                line: MermaidLine {
+                    from: bus_nodes[&from_port].clone(),
+                    to: bus_nodes[&to_port].clone(),
+                    value,
+                },
+                values,
+            }));
+        }
+
+        let mut line_counts = HashMap::new();
+        self.count_lines(&mut line_counts);
+        self.remove_bundled_bits(&|node| {
+            bundled_lines.contains_key(&node.get_label())
+                && !line_counts.contains_key(&node.get_label())
+        });
+    }
+
+    // the path of the chip each boundary node is drawn in
+    fn node_owners(&self, path: &[String], owners: &mut HashMap<String, Vec<String>>) {
+        for statement in &self.statements {
+            let node = match statement {
+                MermaidStatement::Node(node) => node,
+                // an expanded chip's outputs are drawn by the lines to them
+                MermaidStatement::Line(line) if line.to.type_ == "OUT" => &line.to,
+                _ => continue,
+            };
+            owners.insert(node.get_label(), path.to_vec());
+        }
+        for (id, subgraph) in &self.subgraphs {
+            subgraph.node_owners(&[path, std::slice::from_ref(id)].concat(), owners);
+        }
+    }
+
+    // lines between port bits, by the ports at their ends, with the path of the graph
+    // they're drawn in
+    fn bus_lines(
+        &self,
+        path: &[String],
+        owners: &HashMap<String, Vec<String>>,
+        groups: &mut BusLines,
+    ) {
+        let port = |node: &MermaidNode| {
+            let (base, _) = port_bit(node)?;
+            let owner = owners.get(&node.get_label())?;
+            Some((owner.clone(), base.to_owned(), node.type_))
+        };
+        for statement in &self.statements {
+            if let MermaidStatement::Line(line) = statement {
+                if let (Some(from), Some(to)) = (port(&line.from), port(&line.to)) {
+                    groups
+                        .entry((from, to))
+                        .or_insert_with(|| (path.to_vec(), vec![]))
+                        .1
+                        .push(line.clone());
+                }
+            }
+        }
+        for (id, subgraph) in &self.subgraphs {
+            subgraph.bus_lines(&[path, std::slice::from_ref(id)].concat(), owners, groups);
+        }
+    }
+
+    // how many lines are drawn to or from each node
+    fn count_lines(&self, counts: &mut HashMap<String, usize>) {
+        for statement in &self.statements {
+            if let MermaidStatement::Line(line) = statement {
+                for node in [&line.from, &line.to] {
+                    *counts.entry(node.get_label()).or_default() += 1;
+                }
+            }
+        }
+        for subgraph in self.subgraphs.values() {
+            subgraph.count_lines(counts);
+        }
+    }
+
+    fn remove_bundled_bits(&mut self, is_bundled: &dyn Fn(&MermaidNode) -> bool) {
+        self.statements.retain(|statement| match statement {
+            MermaidStatement::Node(node) => !is_bundled(node),
+            _ => true,
+        });
+        for subgraph in self.subgraphs.values_mut() {
+            subgraph.remove_bundled_bits(is_bundled);
+        }
+    }
+
+    // lines are annotated with their values, and the high ones coloured in. Mermaid
+    // styles lines by the order they're declared in
     pub fn compile(&self) -> String {
-        let mut res = "graph TD".to_owned();
-        res += &self.compile_subgraph();
+        let mut res = self.layout.header();
+        let mut lines = vec![];
+        res += &self.compile_subgraph(self.layout.chip_order, &mut lines);
+        let line_numbers = |is_styled: &dyn Fn(&MermaidLine) -> bool| {
+            lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| is_styled(line))
+                .map(|(i, _)| i.to_string())
+                .collect::<Vec<_>>()
+        };
+        let high_lines = line_numbers(&|line| line.value == Logic::High);
+        if !high_lines.is_empty() {
+            res += &format!("\nlinkStyle {} stroke:{HIGH_COLOUR}", high_lines.join(","));
+        }
+        for class in &self.classes {
+            if !class.members.is_empty() {
+                res += &format!("\nclassDef {} {}", class.name, class.style);
+                res += &format!("\nclass {} {}", class.members.join(","), class.name);
+            }
+            let class_lines = line_numbers(&|line| class.lines.contains(line));
+            if !class_lines.is_empty() {
+                res += &format!("\nlinkStyle {} {}", class_lines.join(","), class.line_style);
+            }
+        }
         res
     }
 
-    fn compile_subgraph(&self) -> String {
+    // repeated chips drawn as one are counted
+    fn title(&self) -> String {
+        match self.instances {
+            1 => self.name.to_owned(),
+            instances => format!("{} ×{instances}", self.name),
+        }
+    }
+
+    fn compile_subgraph<'g>(
+        &'g self,
+        chip_order: ChipOrder,
+        lines: &mut Vec<&'g MermaidLine>,
+    ) -> String {
         let mut res = String::new();
-        for (_, subgraph) in &self.subgraphs {
-            let label = subgraph.name;
-            res += &format!("\nsubgraph {} [{}]", subgraph.id, label);
-            res += &subgraph.compile_subgraph();
+        let mut subgraphs = self.subgraphs.values().collect::<Vec<_>>();
+        match chip_order {
+            ChipOrder::Id => subgraphs.sort_by(|a, b| a.id.cmp(&b.id)),
+            ChipOrder::Name => subgraphs.sort_by(|a, b| (a.name, &a.id).cmp(&(b.name, &b.id))),
+        }
+        for subgraph in subgraphs {
+            res += &format!("\nsubgraph {} [{}]", subgraph.id, subgraph.title());
+            res += &subgraph.compile_subgraph(chip_order, lines);
             res += "\nend";
         }
         for statement in &self.statements {
@@ -78,17 +532,305 @@ impl MermaidGraph {
                     let right_label = line.to.get_label();
                     let left_name = &line.from.name;
                     let right_name = &line.to.name;
-                    res += &format!("\n{left_label}({left_name})-->{right_label}({right_name})");
+                    let value = line.value.to_char();
+                    res += &format!(
+                        "\n{left_label}({left_name})-->|{value}|{right_label}({right_name})"
+                    );
+                    lines.push(line);
                 }
                 MermaidStatement::Node(node) => {
                     res += &format!("\n{}({})", node.get_label(), node.name);
                 }
+                // buses are drawn thick, labelled with their width and value
+                MermaidStatement::Bus(bus) => {
+                    let left_label = bus.line.from.get_label();
+                    let right_label = bus.line.to.get_label();
+                    let left_name = &bus.line.from.name;
+                    let right_name = &bus.line.to.name;
+                    let value = bus_value(&bus.values);
+                    res += &format!(
+                        "\n{left_label}({left_name})==>|{} bits {value}|{right_label}({right_name})",
+                        bus.values.len()
+                    );
+                    lines.push(&bus.line);
+                }
             }
         }
         res
     }
 }
 
+const HIGH_COLOUR: &str = "#2a2";
+
+// a chip, by its path, with one of its array ports, by its name and the type of its bits
+type PortKey = (Vec<String>, String, &'static str);
+// the lines from one port to another, with the path of the graph they're drawn in
+type BusLines = HashMap<(PortKey, PortKey), (Vec<String>, Vec<MermaidLine>)>;
+
+// the port an IN or OUT node is a bit of, with the bit's index. A forced bit's name
+// doesn't end in its index, so it's drawn on its own
+fn port_bit(node: &MermaidNode) -> Option<(&str, usize)> {
+    if !BUS_TYPES
+        .iter()
+        .any(|(bit_type, _)| *bit_type == node.type_)
+    {
+        return None;
+    }
+    let (base, bit) = node.name.rsplit_once('-')?;
+    Some((base, bit.parse().ok()?))
+}
+
+// a bus's value in hex, taking its first bit as the most significant as the chips
+// do, or bit by bit when any are unknown or floating
+fn bus_value(values: &[Logic]) -> String {
+    let bits = values
+        .iter()
+        .map(|&value| match value {
+            Logic::Low => Some(0),
+            Logic::High => Some(1),
+            _ => None,
+        })
+        .collect::<Option<Vec<u128>>>();
+    match bits {
+        Some(bits) if bits.len() <= 128 => {
+            let value = bits.iter().fold(0, |value, bit| value << 1 | bit);
+            format!("0x{value:0width$X}", width = bits.len().div_ceil(4))
+        }
+        _ => values.iter().copied().map(Logic::to_char).collect(),
+    }
+}
+
+// Which chips are drawn expanded: those listed by id, every chip within
+// `expand_depth` levels of the top of the hierarchy, so a machine can be explored
+// without knowing its chip ids up front, and every chip in the subtrees under the
+// chips in `expand_subtrees`, along with the chips containing them. With
+// `group_repeated`, identical collapsed chips side by side, like a Ram8's registers,
+// are drawn as one, and with `bundle_buses` the bits of array ports connected to each
+// other, like a Mux16's outputs, are drawn as one line. Nodes and chips are coloured
+// by their kind as `theme` says, and inputs tied to a constant drawn as
+// `constant_inputs` says, and laid out as `layout` says. With a
+// `root` chip, only the inside of that chip is drawn, as if it were the machine, so a
+// machine too large to draw at once can be opened a level at a time
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GraphOptions {
+    pub show_chips: HashSet<String>,
+    pub expand_depth: usize,
+    pub expand_subtrees: HashSet<String>,
+    pub group_repeated: bool,
+    pub bundle_buses: bool,
+    pub constant_inputs: ConstantInputs,
+    pub layout: GraphLayout,
+    pub theme: GraphTheme,
+    pub root: Option<String>,
+}
+
+// How the inputs chips are built with tied to a constant, like an Incrementer16's
+// one, are drawn: as inputs like the machine's own, not at all, or as small terminals
+// labelled with their value
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ConstantInputs {
+    #[default]
+    Shown,
+    Hidden,
+    Terminals,
+}
+
+// How the graph is laid out: the direction its lines flow in, the gaps in pixels
+// between nodes side by side and between the ranks they're placed in, which Mermaid
+// picks if they aren't given, and the order chips are declared in, which Mermaid keeps
+// where it can
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GraphLayout {
+    pub direction: GraphDirection,
+    pub node_spacing: Option<u32>,
+    pub rank_spacing: Option<u32>,
+    pub chip_order: ChipOrder,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GraphDirection {
+    #[default]
+    TopDown,
+    LeftRight,
+    RightLeft,
+    BottomUp,
+}
+
+// chips in the same chip are ordered by their ids, or by their titles
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChipOrder {
+    #[default]
+    Id,
+    Name,
+}
+
+impl GraphDirection {
+    fn code(self) -> &'static str {
+        match self {
+            GraphDirection::TopDown => "TD",
+            GraphDirection::LeftRight => "LR",
+            GraphDirection::RightLeft => "RL",
+            GraphDirection::BottomUp => "BT",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<GraphDirection> {
+        [
+            GraphDirection::TopDown,
+            GraphDirection::LeftRight,
+            GraphDirection::RightLeft,
+            GraphDirection::BottomUp,
+        ]
+        .into_iter()
+        .find(|direction| direction.code() == code)
+    }
+}
+
+impl GraphLayout {
+    // the spacing is set by an init directive ahead of the graph
+    fn header(&self) -> String {
+        let spacing = [
+            ("nodeSpacing", self.node_spacing),
+            ("rankSpacing", self.rank_spacing),
+        ]
+        .into_iter()
+        .filter_map(|(name, spacing)| Some(format!("\"{name}\":{}", spacing?)))
+        .collect::<Vec<_>>();
+        let init = if spacing.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "%%{{init: {{\"flowchart\":{{{}}}}}}}%%\n",
+                spacing.join(",")
+            )
+        };
+        format!("{init}graph {}", self.direction.code())
+    }
+}
+
+// The Mermaid styles, e.g. "fill:#fdd,stroke:#a00", that each kind of node is drawn
+// with. Chips are drawn in the style of their label's category in `categories` if it
+// has one, and otherwise in the sequential style if they take a clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphTheme {
+    pub nand: String,
+    // chip inputs and outputs
+    pub boundary: String,
+    // the machine's inputs and outputs
+    pub port: String,
+    pub bus: String,
+    pub sequential: String,
+    // chip labels to the names of their categories
+    pub categories: HashMap<&'static str, String>,
+    // category names to their styles
+    pub category_styles: HashMap<String, String>,
+}
+
+impl Default for GraphTheme {
+    fn default() -> Self {
+        GraphTheme {
+            nand: "fill:#fde2b8,stroke:#c77d0a".into(),
+            boundary: "fill:#dbe9fb,stroke:#3b73b9".into(),
+            port: "fill:#e6d8f5,stroke:#7446a8".into(),
+            bus: "fill:#fbd5d5,stroke:#b33".into(),
+            sequential: "fill:#fff7d6,stroke:#b89b00".into(),
+            categories: HashMap::new(),
+            category_styles: HashMap::new(),
+        }
+    }
+}
+
+const SEQUENTIAL_CLASS: &str = "sequential";
+
+impl GraphTheme {
+    // a clocked chip is given a clock port by #[chip(clocked)], and latches are wired
+    // one by hand
+    fn chip_class(&self, chip: &dyn Chip) -> Option<String> {
+        let label = chip.get_label();
+        if let Some(category) = self.categories.get(label) {
+            return Some(category.clone());
+        }
+        chip.get_ports()
+            .inputs
+            .iter()
+            .any(|port| port.name == "clock")
+            .then(|| SEQUENTIAL_CLASS.into())
+    }
+
+    fn classes(&self, graph: &MermaidGraph, outs: &[Output]) -> Vec<MermaidClass> {
+        let nodes = graph.nodes();
+        let node_class = |name: &str, style: &String, types: &[&str]| MermaidClass {
+            name: name.into(),
+            style: style.clone(),
+            members: nodes
+                .iter()
+                .filter(|node| types.contains(&node.type_))
+                .map(|node| node.get_label())
+                .collect(),
+            lines: vec![],
+            line_style: String::new(),
+        };
+        let mut classes = vec![
+            node_class("nand", &self.nand, &["NAND", "LOOKUP"]),
+            node_class(
+                "boundary",
+                &self.boundary,
+                &["IN", "OUT", "IN_BUS", "OUT_BUS"],
+            ),
+            node_class("port", &self.port, &["INPUT", "OUTPUT", "CONST"]),
+            node_class("bus", &self.bus, &["BUS"]),
+        ];
+
+        // only the chips that are drawn are styled
+        let drawn = get_subgraph_ids(graph);
+        let mut chip_classes = HashMap::<String, Vec<String>>::new();
+        let mut visited = HashSet::new();
+        let mut stack = outs.iter().map(|out| out.output.parent).collect::<Vec<_>>();
+        while let Some(chip) = stack.pop() {
+            let id = chip.get_id();
+            if !visited.insert(id.clone()) {
+                continue;
+            }
+            if drawn.contains(id.as_str()) {
+                if let Some(class) = self.chip_class(chip) {
+                    chip_classes.entry(class).or_default().push(id);
+                }
+            }
+            stack.extend(chip.get_children());
+        }
+        let mut chip_classes = chip_classes.into_iter().collect::<Vec<_>>();
+        chip_classes.sort();
+        for (name, mut members) in chip_classes {
+            let style = match self.category_styles.get(&name) {
+                Some(style) => style.clone(),
+                None if name == SEQUENTIAL_CLASS => self.sequential.clone(),
+                // a category without a style is left to Mermaid's
+                None => continue,
+            };
+            members.sort();
+            classes.push(MermaidClass {
+                name,
+                style,
+                members,
+                lines: vec![],
+                line_style: String::new(),
+            });
+        }
+
+        classes.retain(|class| !class.members.is_empty());
+        classes
+    }
+}
+
+impl From<HashSet<String>> for GraphOptions {
+    fn from(show_chips: HashSet<String>) -> Self {
+        GraphOptions {
+            show_chips,
+            ..Default::default()
+        }
+    }
+}
+
 pub fn graph_machine<
     'a,
     TFam: StructuredDataFamily<NINPUT, NOUT>,
@@ -96,12 +838,101 @@ pub fn graph_machine<
     const NOUT: usize,
 >(
     machine: &Machine<'a, TFam, NINPUT, NOUT>,
-    show_chips: HashSet<String>,
+    options: impl Into<GraphOptions>,
 ) -> MermaidGraph {
-    graph_outputs(&machine.outputs, show_chips)
+    graph_outputs(&machine.outputs, &machine.input_ids(), options)
+}
+
+// the ids of the chips down to `depth` levels below the machine, where the chips
+// the machine's outputs come from are the first level
+fn chips_to_depth(outs: &[Output], depth: usize) -> HashSet<String> {
+    let mut chips = HashSet::new();
+    let mut level = outs.iter().map(|out| out.output.parent).collect::<Vec<_>>();
+    for _ in 0..depth {
+        // the whole hierarchy is expanded by asking for an unbounded depth
+        if level.is_empty() {
+            break;
+        }
+        level.retain(|chip| chips.insert(chip.get_id()));
+        level = level.iter().flat_map(|chip| chip.get_children()).collect();
+    }
+    chips
+}
+
+fn chips_in_subtrees(outs: &[Output], roots: &HashSet<String>) -> HashSet<String> {
+    let mut chips = HashSet::new();
+    let mut visited = HashSet::new();
+    // each chip with the ids of the chips containing it, and whether one of them
+    // is a root
+    let mut stack = outs
+        .iter()
+        .map(|out| (out.output.parent, vec![], false))
+        .collect::<Vec<_>>();
+    while let Some((chip, mut ancestors, in_subtree)) = stack.pop() {
+        let id = chip.get_id();
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        let in_subtree = in_subtree || roots.contains(&id);
+        if in_subtree {
+            chips.extend(ancestors.iter().cloned());
+            chips.insert(id.clone());
+        }
+        ancestors.push(id);
+        stack.extend(
+            chip.get_children()
+                .into_iter()
+                .map(|child| (child, ancestors.clone(), in_subtree)),
+        );
+    }
+    chips
+}
+
+// the ids of the chips from the top of the hierarchy down to the chip
+fn chip_path(outs: &[Output], chip_id: &str) -> Option<Vec<String>> {
+    let mut visited = HashSet::new();
+    let mut stack = outs
+        .iter()
+        .map(|out| (out.output.parent, vec![]))
+        .collect::<Vec<_>>();
+    while let Some((chip, mut path)) = stack.pop() {
+        let id = chip.get_id();
+        if !visited.insert(id.clone()) {
+            continue;
+        }
+        path.push(id);
+        if path.last().is_some_and(|id| id == chip_id) {
+            return Some(path);
+        }
+        stack.extend(
+            chip.get_children()
+                .into_iter()
+                .map(|child| (child, path.clone())),
+        );
+    }
+    None
 }
 
-fn graph_outputs(outs: &[Output], show_chips: HashSet<String>) -> MermaidGraph {
+// graphs the outputs of a machine with the inputs by id, so any other inputs are known
+// to be constants
+fn graph_outputs(
+    outs: &[Output],
+    inputs: &[u32],
+    options: impl Into<GraphOptions>,
+) -> MermaidGraph {
+    let options = options.into();
+    // the chips containing the root are expanded to draw it, and taken off afterwards
+    let root_path = match &options.root {
+        Some(root) => match chip_path(outs, root) {
+            Some(path) => path,
+            None => return MermaidGraph::new("", "".into()),
+        },
+        None => vec![],
+    };
+    let mut show_chips = chips_to_depth(outs, options.expand_depth);
+    show_chips.extend(options.show_chips);
+    show_chips.extend(chips_in_subtrees(outs, &options.expand_subtrees));
+    show_chips.extend(root_path.iter().cloned());
     let mut graph_map = MermaidGraph::new("", "".into());
     let mut node_set = HashSet::new();
     for out in outs.iter().rev() {
@@ -115,11 +946,32 @@ fn graph_outputs(outs: &[Output], show_chips: HashSet<String>) -> MermaidGraph {
             },
         );
     }
+    if options.constant_inputs != ConstantInputs::Shown {
+        graph_map.draw_constants(inputs, options.constant_inputs);
+    }
+    if options.group_repeated {
+        graph_map.group_repeated(&show_chips);
+    }
+    let mut path = vec![];
+    for id in &root_path {
+        graph_map = match graph_map.subgraphs.remove(id) {
+            Some(subgraph) => subgraph,
+            None => return MermaidGraph::new("", "".into()),
+        };
+        path.push((graph_map.id.clone(), graph_map.name));
+    }
+    graph_map.path = path;
+    if options.bundle_buses {
+        graph_map.bundle_buses();
+    }
+    graph_map.classes = options.theme.classes(&graph_map, outs);
+    graph_map.layout = options.layout;
     graph_map
 }
 
 fn graph_output(out: &Output<'_>, graph_inputs: &mut GraphInputs<'_>) {
     let node = graph_output_wrapper(out.output, graph_inputs);
+    let value = out.output.inner.value();
 
     graph_inputs
         .graph_map
@@ -129,8 +981,9 @@ fn graph_output(out: &Output<'_>, graph_inputs: &mut GraphInputs<'_>) {
             to: MermaidNode {
                 identifier: out.identifier,
                 name: "OUTPUT".into(),
-                type_: "OUTPUT".into(),
+                type_: "OUTPUT",
             },
+            value,
         }));
 }
 
@@ -145,7 +998,7 @@ fn graph_user_input(in_: &UserInput, node_set: &mut HashSet<String>) -> MermaidN
     let node = MermaidNode {
         identifier: in_.id,
         name: "INPUT".into(),
-        type_: "INPUT".into(),
+        type_: "INPUT",
     };
 
     // make sure we haven't already expanded this node
@@ -162,14 +1015,20 @@ fn graph_input(in_: Input<'_>, graph_inputs: &mut GraphInputs<'_>) -> MermaidNod
         Input::ChipOutput(x) => graph_output_wrapper(x, graph_inputs),
         Input::ChipInput(x) => graph_chip_input(x, graph_inputs),
         Input::NandInput(x) => graph_nand(x, graph_inputs),
+        Input::SharedBus(x) => graph_shared_bus(x, graph_inputs),
+        Input::Lookup(x) => graph_lookup(x, graph_inputs),
     }
 }
 
 fn graph_chip_input(in_: &ChipInput<'_>, graph_inputs: &mut GraphInputs<'_>) -> MermaidNode {
     let node = MermaidNode {
         identifier: in_.id,
-        name: format!("IN {}", in_.label),
-        type_: "IN".into(),
+        name: boundary_name(
+            in_.label,
+            graph_inputs.graph_map.subgraph_title(&graph_inputs.path),
+            is_node_expanded(&graph_inputs.path, graph_inputs.show_chips),
+        ),
+        type_: "IN",
     };
 
     // make sure we haven't already expanded this node
@@ -203,6 +1062,7 @@ fn graph_chip_input(in_: &ChipInput<'_>, graph_inputs: &mut GraphInputs<'_>) ->
             .push(MermaidStatement::Line(MermaidLine {
                 from: prev_node,
                 to: node.clone(),
+                value: in_.in_.value(),
             }));
     }
     node
@@ -221,10 +1081,15 @@ fn graph_output_wrapper(
     let is_node_shown = is_node_shown(&new_path, graph_inputs.show_chips);
 
     // graph the current component
+    let title = out.parent.get_name().unwrap_or(out.parent.get_label());
+    let name = boundary_name(out.inner.label, Some(title), is_node_expanded);
     let node = MermaidNode {
         identifier: out.inner.id,
-        name: format!("OUT {}", out.inner.label),
-        type_: "OUT".into(),
+        name: match out.inner.get_forced() {
+            Some(value) => format!("{name}, forced {}", u8::from(value)),
+            None => name,
+        },
+        type_: "OUT",
     };
 
     // make sure we haven't already expanded this node
@@ -238,7 +1103,7 @@ fn graph_output_wrapper(
         let current_graph = graph_inputs.graph_map.get_subgraph(&graph_inputs.path); // TODO: this is a bit crap
         let new_graph_name = chip_id.clone();
         if !current_graph.subgraphs.contains_key(&new_graph_name) {
-            let subgraph = MermaidGraph::new(out.parent.get_label(), chip_id.clone());
+            let subgraph = MermaidGraph::new(title, chip_id.clone());
             current_graph.subgraphs.insert(chip_id.clone(), subgraph);
         }
     }
@@ -272,6 +1137,24 @@ fn graph_output_wrapper(
                 show_chips: graph_inputs.show_chips,
             },
         ),
+        ChipOutputType::SharedBus(bus) => graph_shared_bus(
+            bus,
+            &mut GraphInputs {
+                graph_map: graph_inputs.graph_map,
+                path: new_path.clone(),
+                node_set: graph_inputs.node_set,
+                show_chips: graph_inputs.show_chips,
+            },
+        ),
+        ChipOutputType::Lookup(lookup) => graph_lookup(
+            lookup,
+            &mut GraphInputs {
+                graph_map: graph_inputs.graph_map,
+                path: new_path.clone(),
+                node_set: graph_inputs.node_set,
+                show_chips: graph_inputs.show_chips,
+            },
+        ),
     };
 
     if is_node_shown {
@@ -282,6 +1165,7 @@ fn graph_output_wrapper(
                 .push(MermaidStatement::Line(MermaidLine {
                     from: prev_node,
                     to: node.clone(),
+                    value: out.inner.get_out().value(),
                 }));
         } else {
             subgraph
@@ -293,12 +1177,22 @@ fn graph_output_wrapper(
     node
 }
 
-fn is_node_expanded(path: &Vec<String>, show_chips: &HashSet<String>) -> bool {
+// chip inputs and outputs go by their port labels. A chip that isn't expanded is
+// drawn as just these nodes, which are prefixed with the chip's title to tell them
+// apart from its neighbours'
+fn boundary_name(label: &str, chip_title: Option<&str>, is_expanded: bool) -> String {
+    match chip_title {
+        Some(title) if !is_expanded => format!("{title} {label}"),
+        _ => label.to_owned(),
+    }
+}
+
+fn is_node_expanded(path: &[String], show_chips: &HashSet<String>) -> bool {
     path.iter().all(|chip_id| show_chips.contains(chip_id))
 }
 
-fn is_node_shown(path: &Vec<String>, show_chips: &HashSet<String>) -> bool {
-    path.len() == 0
+fn is_node_shown(path: &[String], show_chips: &HashSet<String>) -> bool {
+    path.is_empty()
         || path
             .iter()
             .take(path.len() - 1)
@@ -344,112 +1238,709 @@ fn graph_nand(nand: &Nand<'_>, graph_inputs: &mut GraphInputs<'_>) -> MermaidNod
             .push(MermaidStatement::Line(MermaidLine {
                 from: from_node_1,
                 to: node.clone(),
+                value: in1.value(),
             }));
         current_graph
             .statements
             .push(MermaidStatement::Line(MermaidLine {
                 from: from_node_2,
                 to: node.clone(),
+                value: in2.value(),
             }));
     }
 
     node
 }
 
+fn graph_shared_bus(bus: &SharedBus<'_>, graph_inputs: &mut GraphInputs<'_>) -> MermaidNode {
+    let node = MermaidNode {
+        identifier: bus.identifier,
+        name: "BUS".into(),
+        type_: "BUS",
+    };
+    if graph_inputs.node_set.contains(&node.get_label()) {
+        return node;
+    }
+    graph_inputs.node_set.insert(node.get_label());
+
+    for driver in bus.get_drivers() {
+        for in_ in [driver.data, driver.enable] {
+            let from_node = graph_input(
+                in_,
+                &mut GraphInputs {
+                    graph_map: graph_inputs.graph_map,
+                    path: graph_inputs.path.clone(),
+                    node_set: graph_inputs.node_set,
+                    show_chips: graph_inputs.show_chips,
+                },
+            );
+            if is_node_expanded(&graph_inputs.path, graph_inputs.show_chips) {
+                let current_graph = graph_inputs.graph_map.get_subgraph(&graph_inputs.path);
+                current_graph
+                    .statements
+                    .push(MermaidStatement::Line(MermaidLine {
+                        from: from_node,
+                        to: node.clone(),
+                        value: in_.value(),
+                    }));
+            }
+        }
+    }
+
+    node
+}
+
+// a lookup is drawn like a gate, with a line from each of its address bits
+fn graph_lookup(lookup: &Lookup<'_>, graph_inputs: &mut GraphInputs<'_>) -> MermaidNode {
+    let node = MermaidNode {
+        identifier: lookup.identifier,
+        name: "LOOKUP".into(),
+        type_: "LOOKUP",
+    };
+    if graph_inputs.node_set.contains(&node.get_label()) {
+        return node;
+    }
+    graph_inputs.node_set.insert(node.get_label());
+
+    for &in_ in lookup.get_address() {
+        let from_node = graph_input(
+            in_,
+            &mut GraphInputs {
+                graph_map: graph_inputs.graph_map,
+                path: graph_inputs.path.clone(),
+                node_set: graph_inputs.node_set,
+                show_chips: graph_inputs.show_chips,
+            },
+        );
+        if is_node_expanded(&graph_inputs.path, graph_inputs.show_chips) {
+            let current_graph = graph_inputs.graph_map.get_subgraph(&graph_inputs.path);
+            current_graph
+                .statements
+                .push(MermaidStatement::Line(MermaidLine {
+                    from: from_node,
+                    to: node.clone(),
+                    value: in_.value(),
+                }));
+        }
+    }
+
+    node
+}
+
+// serves the machine at the address until the process is stopped. See `Server` for a
+// server which can be shut down
 pub fn start_interactive_server<
     'a,
     TFam: StructuredDataFamily<NINPUT, NOUT>,
     const NINPUT: usize,
     const NOUT: usize,
 >(
-    machine: &Machine<'a, TFam, NINPUT, NOUT>,
-    port: u16,
-) {
-    let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
-
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-
-        handle_connection(stream, machine);
-    }
+    machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    address: impl ToSocketAddrs,
+) -> io::Result<()> {
+    start_interactive_server_with(machine, address, &Peripherals::default())
 }
 
-fn handle_connection<
+// serves the machine like `start_interactive_server()`, with the page drawing its screen
+// and typing in to its keyboard
+pub fn start_interactive_server_with<
     'a,
     TFam: StructuredDataFamily<NINPUT, NOUT>,
     const NINPUT: usize,
     const NOUT: usize,
 >(
+    machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    address: impl ToSocketAddrs,
+    peripherals: &Peripherals,
+) -> io::Result<()> {
+    let server = Server::bind(address)?;
+    println!("serving on http://{}", server.address());
+    server.serve_machine_with(machine, peripherals)
+}
+
+// A server bound to its address but not yet serving, so the port can be read (binding to
+// port 0 picks a free one) and a handle taken to shut it down from another thread.
+// Binding to 0.0.0.0 or :: serves every interface, eg from inside a container.
+// Serving blocks the calling thread, which answers the requests
+pub struct Server {
+    listener: TcpListener,
+    handle: ServerHandle,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerHandle {
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Server> {
+        let listener = TcpListener::bind(address)?;
+        let handle = ServerHandle {
+            address: listener.local_addr()?,
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        Ok(Server { listener, handle })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.handle.port()
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.handle.address
+    }
+
+    pub fn handle(&self) -> ServerHandle {
+        self.handle.clone()
+    }
+
+    pub fn serve_machine<
+        'a,
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        self,
+        machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    ) -> io::Result<()> {
+        self.serve_machine_with(machine, &Peripherals::default())
+    }
+
+    // fails before serving if the peripherals aren't found in the machine
+    pub fn serve_machine_with<
+        'a,
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        self,
+        machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+        peripherals: &Peripherals,
+    ) -> io::Result<()> {
+        let devices = peripherals::Devices::attach(machine, peripherals)?;
+        let mut waveforms = live::Waveforms::default();
+        let mut graphs = cache::GraphCache::default();
+        // counts the changes to the machine, so graphs of an older state aren't reused
+        let state_version = Cell::new(0);
+        let changed = || state_version.set(state_version.get() + 1);
+        self.serve(|request| match request {
+            Request::Page(request) if request.path == "/netlist.json" => {
+                Ok(HttpResponse::json(json::machine_netlist(machine)))
+            }
+            Request::Page(request) if request.path == "/search" => {
+                let query = request.query_param("q").ok_or(HttpError::BadRequest)?;
+                Ok(HttpResponse::json(json::chip_search(
+                    &machine.netlist(),
+                    query,
+                )))
+            }
+            Request::Page(request) if ["/inputs", "/step"].contains(&request.path.as_str()) => {
+                changed();
+                control::control(machine, &mut waveforms, &request).map(HttpResponse::json)
+            }
+            Request::Page(request) if ["/force", "/release"].contains(&request.path.as_str()) => {
+                changed();
+                control::override_output(machine, &request).map(HttpResponse::json)
+            }
+            Request::Page(request) if request.path != "/" => Err(HttpError::NotFound),
+            Request::Page(request) => {
+                let machine = &*machine;
+                let graph_function = |options| {
+                    graphs.graph(state_version.get(), options, |options| {
+                        graph_machine(machine, options)
+                    })
+                };
+                get_response(&request, graph_function, true).map(HttpResponse::html)
+            }
+            Request::Message(message) => {
+                // any command but `state` may change the machine
+                changed();
+                Ok(HttpResponse::json(live::simulate(
+                    machine,
+                    &mut waveforms,
+                    &devices,
+                    &message,
+                )))
+            }
+            Request::Events { signals, stream } => match stream {
+                EventStream::Open => waveforms
+                    .follow(machine, &signals)
+                    .map(|()| HttpResponse::text(""))
+                    .map_err(|_| HttpError::BadRequest),
+                EventStream::Since(since) => Ok(HttpResponse::text(live::events(
+                    &waveforms, &signals, since,
+                ))),
+                EventStream::Closed => {
+                    waveforms.unfollow(&signals);
+                    Ok(HttpResponse::text(""))
+                }
+            },
+        })
+    }
+
+    // Connections are read and written by a pool of workers, so a slow client doesn't
+    // hold up the others. Websockets and event streams stay open, so they're handed off
+    // to a thread of their own, leaving the workers free for pages. Requests are
+    // answered one at a time on the calling thread, as machines can't be shared between
+    // threads. A request which panics is answered as an internal error, leaving the
+    // machine as the panic found it
+    fn serve(
+        self,
+        mut respond: impl FnMut(Request) -> Result<HttpResponse, HttpError>,
+    ) -> io::Result<()> {
+        let Server { listener, handle } = self;
+        let (connection_sender, connections) = mpsc::channel::<TcpStream>();
+        let connections = Mutex::new(connections);
+        let (request_sender, requests) = mpsc::channel::<(Request, Responder)>();
+        let stopped = &*handle.stopped;
+
+        thread::scope(|scope| {
+            for _ in 0..WORKER_COUNT {
+                let connections = &connections;
+                let request_sender = request_sender.clone();
+                scope.spawn(move || loop {
+                    let Ok(stream) = connections.lock().unwrap().recv() else {
+                        return;
+                    };
+                    // a failed connection shouldn't take the server down with it
+                    match handle_connection(stream, &request_sender, stopped) {
+                        Ok(Some(open)) => {
+                            let request_sender = request_sender.clone();
+                            scope.spawn(move || {
+                                if let Err(err) = open.handle(&request_sender, stopped) {
+                                    eprintln!("connection failed: {err}");
+                                }
+                            });
+                        }
+                        Ok(None) => {}
+                        Err(err) => eprintln!("connection failed: {err}"),
+                    }
+                });
+            }
+            drop(request_sender);
+
+            // once stopped, the workers finish their connections and then the requests
+            // run out
+            scope.spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    match stream {
+                        Ok(stream) => {
+                            if connection_sender.send(stream).is_err() {
+                                return;
+                            }
+                        }
+                        Err(err) => eprintln!("connection failed: {err}"),
+                    }
+                }
+            });
+
+            for (request, response_sender) in requests {
+                let response = panic::catch_unwind(AssertUnwindSafe(|| respond(request)))
+                    .unwrap_or(Err(HttpError::Internal));
+                // the worker has given up on a client that's gone
+                let _ = response_sender.send(response);
+            }
+        });
+        Ok(())
+    }
+}
+
+impl ServerHandle {
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    // stops the server accepting connections. Serving returns once the connections in
+    // progress are answered
+    pub fn shutdown(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            // wakes the server up from waiting on its next connection, over loopback
+            // when it's listening on every interface
+            let mut address = self.address;
+            if address.ip().is_unspecified() {
+                address.set_ip(match address {
+                    SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
+            }
+            let _ = TcpStream::connect(address);
+        }
+    }
+}
+
+const WORKER_COUNT: usize = 4;
+// a client which stalls mid request or response is dropped after this long
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// an open connection holds a worker, so one left idle is closed sooner
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(2);
+const SOCKET_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// an event stream asks the machine for changes this often, and sends a comment every
+// so many polls without any, to notice a client that's gone
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const EVENT_HEARTBEAT_POLLS: u32 = 100;
+
+// a page asked for over HTTP, a message from a live simulation's websocket, or a
+// request from an event stream for its signals
+enum Request {
+    Page(HttpRequest),
+    Message(String),
+    Events {
+        signals: Vec<String>,
+        stream: EventStream,
+    },
+}
+
+// An event stream follows its signals once it's opened, polls for their changes after a
+// step, see `live::events()`, and stops following them once it's closed
+enum EventStream {
+    Open,
+    Since(Option<u64>),
+    Closed,
+}
+
+type Responder = mpsc::Sender<Result<HttpResponse, HttpError>>;
+
+// a connection which stays open once its first request is answered
+enum OpenConnection {
+    WebSocket(TcpStream),
+    Events(TcpStream, HttpRequest),
+}
+
+impl OpenConnection {
+    fn handle(
+        self,
+        requests: &mpsc::Sender<(Request, Responder)>,
+        stopped: &AtomicBool,
+    ) -> io::Result<()> {
+        match self {
+            OpenConnection::WebSocket(stream) => handle_websocket(stream, requests, stopped),
+            OpenConnection::Events(stream, request) => {
+                handle_events(stream, requests, stopped, &request)
+            }
+        }
+    }
+}
+
+// Requests are answered in turn until the client closes the connection, asks for it to
+// be closed, or leaves it idle. The server being shut down closes it after the request
+// in progress. A websocket or event stream is returned once it's opened, to be handled
+// off the worker
+fn handle_connection(
     mut stream: TcpStream,
-    machine: &Machine<'a, TFam, NINPUT, NOUT>,
-) {
-    let buf_reader = BufReader::new(&mut stream);
-    let lines = buf_reader
-        .lines()
-        .map(|elem| elem.unwrap())
-        .take_while(|line| !line.is_empty())
-        .collect();
-    let graph_function = |show_chips| graph_machine(machine, show_chips);
-    let response = match get_response(lines, graph_function) {
-        Ok(s) => format!(
-            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
-            s.len(),
-            s
-        ),
-        Err(_) => "HTTP/1.1 404 NOK\r\n\r\n".into(),
+    requests: &mpsc::Sender<(Request, Responder)>,
+    stopped: &AtomicBool,
+) -> io::Result<Option<OpenConnection>> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    loop {
+        let request = match http::read_request(&mut reader)? {
+            Some(Ok(request)) => request,
+            Some(Err(err)) => {
+                http::write_response(&mut stream, Err(err), false)?;
+                return Ok(None);
+            }
+            None => return Ok(None),
+        };
+        match websocket::request_key(&request) {
+            Some(Ok(key)) => {
+                websocket::accept(&mut stream, key)?;
+                return Ok(Some(OpenConnection::WebSocket(stream)));
+            }
+            Some(Err(err)) => {
+                http::write_response(&mut stream, Err(err), false)?;
+                return Ok(None);
+            }
+            None => {}
+        }
+        if request.path == "/events" && request.method == "GET" {
+            return Ok(Some(OpenConnection::Events(stream, request)));
+        }
+        let keep_alive = request.keep_alive() && !stopped.load(Ordering::SeqCst);
+        // assets don't need the machine, so they're answered here
+        let response = match assets::asset(&request.path) {
+            Some(asset) => Ok(HttpResponse::javascript(asset)),
+            None => respond(requests, Request::Page(request))?,
+        };
+        http::write_response(&mut stream, response, keep_alive)?;
+        if !keep_alive {
+            return Ok(None);
+        }
+        stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT))?;
+    }
+}
+
+// A websocket stays open between messages, so it's polled to notice the server being
+// shut down while it's idle. Each message is answered with a message back, and a
+// message the server can't answer closes the socket
+fn handle_websocket(
+    mut stream: TcpStream,
+    requests: &mpsc::Sender<(Request, Responder)>,
+    stopped: &AtomicBool,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(SOCKET_POLL_INTERVAL))?;
+    let mut reader = websocket::MessageReader::default();
+    loop {
+        let message = match reader.read_message(&mut stream) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(()),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if stopped.load(Ordering::SeqCst) {
+                    return websocket::close(&mut stream);
+                }
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        match respond(requests, Request::Message(message))? {
+            Ok(response) => websocket::write_message(&mut stream, &response.body)?,
+            Err(_) => return websocket::close(&mut stream),
+        }
+    }
+}
+
+// An event stream stays open, sending the changes to the watched signals, given as
+// `/events?watch=a,b`, as the machine is stepped. A client reconnecting with the last
+// event's id carries on after it. The stream is closed once the server is shut down
+fn handle_events(
+    mut stream: TcpStream,
+    requests: &mpsc::Sender<(Request, Responder)>,
+    stopped: &AtomicBool,
+    request: &HttpRequest,
+) -> io::Result<()> {
+    let signals = request
+        .query_param("watch")
+        .iter()
+        .flat_map(|signals| signals.split(','))
+        .filter(|signal| !signal.is_empty())
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let since = request
+        .header("last-event-id")
+        .and_then(|id| id.parse().ok());
+    let event_stream = |stream| {
+        respond(
+            requests,
+            Request::Events {
+                signals: signals.clone(),
+                stream,
+            },
+        )
+    };
+    // an unknown signal is answered with an error, leaving none followed
+    if let Err(err) = event_stream(EventStream::Open)? {
+        return http::write_response(&mut stream, Err(err), false);
+    }
+    let streamed = stream_events(&mut stream, stopped, since, |since| {
+        event_stream(EventStream::Since(since))
+    });
+    // the signals stop being followed however the stream ends, e.g. by the client going
+    // away, unless the server's stopped already
+    let _ = event_stream(EventStream::Closed);
+    streamed
+}
+
+fn stream_events(
+    stream: &mut TcpStream,
+    stopped: &AtomicBool,
+    mut since: Option<u64>,
+    poll: impl Fn(Option<u64>) -> io::Result<Result<HttpResponse, HttpError>>,
+) -> io::Result<()> {
+    let mut events = match poll(since)? {
+        Ok(events) => events.body,
+        Err(err) => return http::write_response(stream, Err(err), false),
     };
-    stream.write_all(response.as_bytes()).unwrap();
+    http::write_event_stream_head(stream)?;
+    let mut idle_polls = 0;
+    loop {
+        let (steps, text) = events.split_once('\n').unwrap_or((&events, ""));
+        since = steps.parse().ok();
+        if !text.is_empty() {
+            stream.write_all(text.as_bytes())?;
+            idle_polls = 0;
+        } else if idle_polls % EVENT_HEARTBEAT_POLLS == 0 {
+            stream.write_all(b":\n\n")?;
+        }
+        stream.flush()?;
+        if stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        thread::sleep(EVENT_POLL_INTERVAL);
+        idle_polls += 1;
+        events = match poll(since)? {
+            Ok(events) => events.body,
+            Err(_) => return Ok(()),
+        };
+    }
+}
+
+// waits for the serving thread to answer the request
+fn respond(
+    requests: &mpsc::Sender<(Request, Responder)>,
+    request: Request,
+) -> io::Result<Result<HttpResponse, HttpError>> {
+    let (response_sender, response) = mpsc::channel();
+    let server_stopped = || io::Error::other("server stopped");
+    requests
+        .send((request, response_sender))
+        .map_err(|_| server_stopped())?;
+    response.recv().map_err(|_| server_stopped())
 }
 
 const HTTP_RESPONSE_TEMPLATE: &str = include_str!("../http/index.html");
-fn get_response<'a, F: FnOnce(HashSet<String>) -> MermaidGraph>(
-    lines: Vec<String>,
+fn get_response<F: FnOnce(GraphOptions) -> MermaidGraph>(
+    request: &HttpRequest,
     graph_function: F,
-) -> Result<String, ()> {
-    let http_line = match lines.iter().find(|line| line.starts_with("GET")) {
-        Some(s) => Ok(s),
-        None => Err(()),
-    }?;
-
-    let is_get = http_line.starts_with("GET");
-    if !is_get {
-        return Err(());
-    }
-
-    let expanded = http_line
-        .split_once("?")
-        .and_then(|(_, post_params)| post_params.split_once(" "))
-        .and_then(|(params, _)| Some(params.split("&")))
-        .and_then(|mut params_list| params_list.find(|param| param.starts_with("expanded")))
-        .and_then(|expanded_param| Some(expanded_param.replace("expanded=", "")))
-        .and_then(|expanded| {
-            Some(
-                expanded
-                    .split(",")
-                    .filter(|e| !e.is_empty())
-                    .map(String::from)
-                    .collect::<Vec<_>>(),
-            )
-        });
-    let show_chips = match expanded {
-        Some(e) => HashSet::from_iter(e.into_iter()),
-        None => HashSet::new(),
+    // false when the graph isn't backed by a long-lived machine, so there's nothing for
+    // the page to force at /force and /release
+    overrides_enabled: bool,
+) -> Result<String, HttpError> {
+    if request.method != "GET" {
+        return Err(HttpError::MethodNotAllowed);
+    }
+
+    let chip_ids = |param| {
+        request
+            .query_param(param)
+            .iter()
+            .flat_map(|ids| ids.split(","))
+            .filter(|id| !id.is_empty())
+            .map(String::from)
+            .collect::<HashSet<_>>()
+    };
+    let spacing = |param| {
+        request
+            .query_param(param)
+            .map(|spacing| spacing.parse().map_err(|_| HttpError::BadRequest))
+            .transpose()
+    };
+    let expand_depth = match request.query_param("expand_depth") {
+        Some(depth) => depth.parse().map_err(|_| HttpError::BadRequest)?,
+        None => 0,
+    };
+    // `expanded=all` and `expanded=none` stand in for listing every chip or none of them
+    let options = match request.query_param("expanded") {
+        Some("all") => GraphOptions {
+            expand_depth: usize::MAX,
+            ..Default::default()
+        },
+        Some("none") => GraphOptions::default(),
+        _ => GraphOptions {
+            show_chips: chip_ids("expanded"),
+            expand_depth,
+            expand_subtrees: chip_ids("expand_subtree"),
+            ..Default::default()
+        },
+    };
+    // the root is given as the path of chips opened to reach it, so the page can go
+    // back up a level
+    let options = GraphOptions {
+        group_repeated: request.query_param("group_repeated") == Some("1"),
+        bundle_buses: request.query_param("bundle_buses") == Some("1"),
+        layout: GraphLayout {
+            direction: match request.query_param("direction") {
+                Some(code) => GraphDirection::from_code(code).ok_or(HttpError::BadRequest)?,
+                None => GraphDirection::TopDown,
+            },
+            node_spacing: spacing("node_spacing")?,
+            rank_spacing: spacing("rank_spacing")?,
+            chip_order: match request.query_param("chip_order") {
+                None | Some("id") => ChipOrder::Id,
+                Some("name") => ChipOrder::Name,
+                Some(_) => return Err(HttpError::BadRequest),
+            },
+        },
+        constant_inputs: match request.query_param("constants") {
+            None | Some("shown") => ConstantInputs::Shown,
+            Some("hidden") => ConstantInputs::Hidden,
+            Some("terminals") => ConstantInputs::Terminals,
+            Some(_) => return Err(HttpError::BadRequest),
+        },
+        root: request
+            .query_param("root")
+            .and_then(|path| path.rsplit(",").find(|id| !id.is_empty()).map(String::from)),
+        ..options
     };
 
-    let graph = graph_function(show_chips);
-    let chip_ids = get_subgraph_ids(&graph);
+    let graph = graph_function(options);
+    Ok(render_page(&graph, true, overrides_enabled))
+}
+
+// Chips can be clicked to expand them when the page is served, as that's done by
+// asking the server for the page again
+fn render_page(graph: &MermaidGraph, expandable: bool, overrides_enabled: bool) -> String {
+    let chip_ids = if expandable {
+        get_subgraph_ids(graph)
+    } else {
+        HashSet::new()
+    };
 
-    Ok(HTTP_RESPONSE_TEMPLATE
+    HTTP_RESPONSE_TEMPLATE
+        .replace(
+            "{REPLACE_MERMAID_SCRIPT}",
+            &assets::mermaid_script(expandable),
+        )
         .replace("{REPLACE_GRAPH}", &graph.compile())
+        .replace(
+            "{REPLACE_TOOLTIPS}",
+            &format!(
+                "{{{}}}",
+                graph
+                    .tooltips()
+                    .iter()
+                    .map(|(label, tooltip)| format!(
+                        "{}:{}",
+                        json_string(label),
+                        json_string(tooltip)
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        )
+        .replace(
+            "{REPLACE_BREADCRUMBS}",
+            &graph
+                .path
+                .iter()
+                .map(|(id, name)| format!("[{},{}],", json_string(id), json_string(name)))
+                .collect::<String>(),
+        )
         .replace(
             "{REPLACE_CHIP_IDS}",
             &chip_ids
                 .iter()
                 .fold(String::new(), |acc, elem| format!("{}\"{}\",", acc, elem)),
-        ))
+        )
+        .replace(
+            "{REPLACE_OVERRIDES_ENABLED}",
+            &overrides_enabled.to_string(),
+        )
 }
 
-fn get_subgraph_ids<'a>(graph: &'a MermaidGraph) -> HashSet<&'a str> {
+// writes the graph to a standalone page, for sharing a diagram without a server. The
+// chips in `show_chips` are drawn expanded as there's no server to expand them later
+pub fn export_html<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &Machine<'a, TFam, NINPUT, NOUT>,
+    path: impl AsRef<Path>,
+    show_chips: HashSet<String>,
+) -> io::Result<()> {
+    export_graph(&graph_machine(machine, show_chips), path)
+}
+
+fn export_graph(graph: &MermaidGraph, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, render_page(graph, false, false))
+}
+
+fn get_subgraph_ids(graph: &MermaidGraph) -> HashSet<&str> {
     graph
         .subgraphs
         .iter()
@@ -463,41 +1954,46 @@ fn get_subgraph_ids<'a>(graph: &'a MermaidGraph) -> HashSet<&'a str> {
 
 #[cfg(test)]
 mod tests {
-    use std::{cmp::Ordering, collections::HashMap, vec};
+    use std::{
+        cell::Cell,
+        cmp::Ordering,
+        collections::HashMap,
+        io::{BufRead, Write},
+        vec,
+    };
 
     use bumpalo::Bump;
-    use hdl::{Chip, ChipInput, ChipOutput, Input, Output};
+    use hdl::{ids::IdScope, Chip, ChipInput, ChipOutput, Input, Output};
 
     use crate::*;
 
     impl Ord for MermaidStatement {
         fn cmp(&self, other: &Self) -> Ordering {
-            self.partial_cmp(other).unwrap()
+            // lines come before buses, which come before nodes
+            let key = |statement: &MermaidStatement| match statement {
+                MermaidStatement::Line(line) => (0, line.from.get_label() + &line.to.get_label()),
+                MermaidStatement::Bus(bus) => {
+                    (1, bus.line.from.get_label() + &bus.line.to.get_label())
+                }
+                MermaidStatement::Node(node) => (2, node.get_label()),
+            };
+            key(self).cmp(&key(other))
         }
     }
 
     impl PartialOrd for MermaidStatement {
         fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            match self {
-                MermaidStatement::Line(self_line) => match other {
-                    MermaidStatement::Line(other_line) => (self_line.from.get_label()
-                        + &self_line.to.get_label())
-                        .partial_cmp(&(other_line.from.get_label() + &other_line.to.get_label())),
-                    MermaidStatement::Node(_) => Option::Some(Ordering::Less),
-                },
-                MermaidStatement::Node(self_node) => match other {
-                    MermaidStatement::Line(_) => Option::Some(Ordering::Greater),
-                    MermaidStatement::Node(other_node) => {
-                        self_node.get_label().partial_cmp(&other_node.get_label())
-                    }
-                },
-            }
+            Some(self.cmp(other))
         }
     }
 
+    fn request(line: &str) -> HttpRequest {
+        HttpRequest::parse(&[line.into()]).expect("request not valid")
+    }
+
     fn sort_mermaid_graph(graph: &mut MermaidGraph) {
         graph.statements.sort();
-        for (_, child) in &mut graph.subgraphs {
+        for child in graph.subgraphs.values_mut() {
             sort_mermaid_graph(child);
         }
     }
@@ -505,24 +2001,35 @@ mod tests {
     #[test]
     fn when_a_request_with_no_query_params_is_passed_in_get_response_returns_success_response_with_internal_implementation_hidden(
     ) {
-        let lines = vec!["GET /".into()];
-        let resp = get_response(lines, |show_chips| {
-            assert_eq!(show_chips, HashSet::new());
-            MermaidGraph {
-                statements: vec![],
-                name: "",
-                id: "".into(),
-                subgraphs: HashMap::from([(
-                    "chip1".into(),
-                    MermaidGraph {
-                        statements: vec![],
-                        name: "",
-                        id: "".into(),
-                        subgraphs: HashMap::new(),
-                    },
-                )]),
-            }
-        })
+        let resp = get_response(
+            &request("GET / HTTP/1.1"),
+            |options: GraphOptions| {
+                assert_eq!(options, GraphOptions::default());
+                MermaidGraph {
+                    statements: vec![],
+                    name: "",
+                    id: "".into(),
+                    instances: 1,
+                    classes: vec![],
+                    path: vec![],
+                    layout: Default::default(),
+                    subgraphs: HashMap::from([(
+                        "chip1".into(),
+                        MermaidGraph {
+                            statements: vec![],
+                            name: "",
+                            id: "".into(),
+                            instances: 1,
+                            classes: vec![],
+                            path: vec![],
+                            layout: Default::default(),
+                            subgraphs: HashMap::new(),
+                        },
+                    )]),
+                }
+            },
+            true,
+        )
         .expect("response not valid");
         assert!(
             resp.contains("[\"chip1\",]"),
@@ -533,25 +2040,377 @@ mod tests {
     #[test]
     fn when_a_request_with_some_query_params_is_passed_in_get_response_returns_success_response_with_internal_implementation_shown(
     ) {
-        let lines = vec!["GET /?expanded=chip1, HTTP/1.1".into()];
-        get_response(lines, |show_chips| {
-            assert_eq!(show_chips, HashSet::from(["chip1".into()]));
-            MermaidGraph {
-                statements: vec![],
-                name: "",
-                id: "".into(),
-                subgraphs: HashMap::from([(
-                    "chip1".into(),
-                    MermaidGraph {
-                        statements: vec![],
-                        name: "",
-                        id: "".into(),
-                        subgraphs: HashMap::new(),
-                    },
-                )]),
+        get_response(
+            &request("GET /?expanded=chip1,&expand_depth=2 HTTP/1.1"),
+            |options: GraphOptions| {
+                assert_eq!(options.show_chips, HashSet::from(["chip1".into()]));
+                assert_eq!(options.expand_depth, 2);
+                MermaidGraph {
+                    statements: vec![],
+                    name: "",
+                    id: "".into(),
+                    instances: 1,
+                    classes: vec![],
+                    path: vec![],
+                    layout: Default::default(),
+                    subgraphs: HashMap::from([(
+                        "chip1".into(),
+                        MermaidGraph {
+                            statements: vec![],
+                            name: "",
+                            id: "".into(),
+                            instances: 1,
+                            classes: vec![],
+                            path: vec![],
+                            layout: Default::default(),
+                            subgraphs: HashMap::new(),
+                        },
+                    )]),
+                }
+            },
+            true,
+        )
+        .expect("response not valid");
+    }
+
+    #[test]
+    fn when_all_or_no_chips_are_asked_to_be_expanded_get_response_passes_on_the_shortcut() {
+        let options = |query: &str| {
+            let mut options = None;
+            get_response(
+                &request(&format!("GET /?{query} HTTP/1.1")),
+                |graph_options| {
+                    options = Some(graph_options);
+                    MermaidGraph::new("", "".into())
+                },
+                false,
+            )
+            .expect("response not valid");
+            options.unwrap()
+        };
+
+        assert_eq!(options("expanded=all").expand_depth, usize::MAX);
+        assert_eq!(
+            options("expand_depth=2&expand_subtree=chip2&expanded=none"),
+            GraphOptions::default()
+        );
+        assert_eq!(
+            options("expand_subtree=chip2,chip3,&group_repeated=1&expanded=chip1,"),
+            GraphOptions {
+                show_chips: HashSet::from(["chip1".into()]),
+                expand_depth: 0,
+                expand_subtrees: HashSet::from(["chip2".into(), "chip3".into()]),
+                group_repeated: true,
+                ..Default::default()
             }
-        })
+        );
+        assert_eq!(
+            options("root=outer,inner&by_level=1").root,
+            Some("inner".into())
+        );
+        assert_eq!(
+            options("direction=LR&node_spacing=20&chip_order=name").layout,
+            GraphLayout {
+                direction: GraphDirection::LeftRight,
+                node_spacing: Some(20),
+                rank_spacing: None,
+                chip_order: ChipOrder::Name,
+            }
+        );
+        assert_eq!(
+            options("constants=terminals").constant_inputs,
+            ConstantInputs::Terminals
+        );
+    }
+
+    #[test]
+    fn when_a_request_with_a_force_param_is_passed_in_get_response_it_only_graphs() {
+        let graphed = Cell::new(false);
+        get_response(
+            &request("GET /?force=12:1&expanded=chip1, HTTP/1.1"),
+            |options: GraphOptions| {
+                assert_eq!(options.show_chips, HashSet::from(["chip1".into()]));
+                graphed.set(true);
+                MermaidGraph::new("", "".into())
+            },
+            true,
+        )
         .expect("response not valid");
+        assert!(graphed.get());
+        let resp = get_response(
+            &request("POST / HTTP/1.1"),
+            |_| MermaidGraph::new("", "".into()),
+            true,
+        );
+        assert_eq!(resp, Err(HttpError::MethodNotAllowed));
+    }
+
+    #[test]
+    fn when_a_client_stalls_the_server_still_answers_other_clients() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        let address = ("127.0.0.1", server.port());
+        thread::spawn(move || {
+            server.serve(|request| match request {
+                Request::Page(request) => Ok(HttpResponse::text(request.path)),
+                Request::Message(_) | Request::Events { .. } => Err(HttpError::NotFound),
+            })
+        });
+
+        // connects without ever finishing its request
+        let _stalled = TcpStream::connect(address).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /page HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut client, &mut response).unwrap();
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\nConnection: close\r\n\r\n/page"
+        );
+    }
+
+    #[test]
+    fn when_an_event_stream_is_closed_its_signals_stop_being_followed() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        let address = ("127.0.0.1", server.port());
+        let (sender, stages) = mpsc::channel();
+        thread::spawn(move || {
+            server.serve(|request| match request {
+                Request::Events { signals, stream } => {
+                    let stage = match stream {
+                        EventStream::Open => "open",
+                        EventStream::Since(_) => "since",
+                        EventStream::Closed => "closed",
+                    };
+                    sender.send((stage, signals)).unwrap();
+                    Ok(HttpResponse::text("1\nid: 1\ndata: {}\n\n"))
+                }
+                Request::Page(_) | Request::Message(_) => Err(HttpError::NotFound),
+            })
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /events?watch=a,b HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let mut stream = BufReader::new(stream);
+        let mut status = String::new();
+        stream.read_line(&mut status).unwrap();
+        assert!(status.starts_with("HTTP/1.1 200 OK"));
+        drop(stream);
+
+        let signals = vec!["a".to_owned(), "b".to_owned()];
+        assert_eq!(stages.recv().unwrap(), ("open", signals.clone()));
+        let closed = stages.iter().find(|(stage, _)| *stage != "since").unwrap();
+        assert_eq!(closed, ("closed", signals));
+    }
+
+    #[test]
+    fn when_more_event_streams_are_open_than_workers_the_server_still_answers_pages() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        let address = ("127.0.0.1", server.port());
+        thread::spawn(move || {
+            server.serve(|request| match request {
+                Request::Page(request) => Ok(HttpResponse::text(request.path)),
+                Request::Events { .. } => Ok(HttpResponse::text("0\n")),
+                Request::Message(_) => Err(HttpError::NotFound),
+            })
+        });
+
+        let _streams = (0..=WORKER_COUNT)
+            .map(|_| {
+                let mut stream = TcpStream::connect(address).unwrap();
+                stream
+                    .write_all(b"GET /events?watch=out HTTP/1.1\r\n\r\n")
+                    .unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut status = String::new();
+                stream.read_line(&mut status).unwrap();
+                assert!(status.starts_with("HTTP/1.1 200 OK"));
+                stream
+            })
+            .collect::<Vec<_>>();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /page HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut client, &mut response).unwrap();
+        assert!(response.ends_with("/page"));
+    }
+
+    #[test]
+    fn when_a_connection_is_kept_alive_each_request_is_answered_in_turn() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        let address = ("127.0.0.1", server.port());
+        thread::spawn(move || {
+            server.serve(|request| match request {
+                Request::Page(request) if request.path == "/panic" => panic!("graphing failed"),
+                Request::Page(request) => Ok(HttpResponse::text(request.path)),
+                Request::Message(_) | Request::Events { .. } => Err(HttpError::NotFound),
+            })
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .write_all(b"GET /first HTTP/1.1\r\n\r\nGET /panic HTTP/1.1\r\n\r\nGET /last HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        io::Read::read_to_string(&mut client, &mut response).unwrap();
+        assert_eq!(
+            response,
+            concat!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 6\r\nConnection: keep-alive\r\n\r\n/first",
+                "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 25\r\nConnection: keep-alive\r\n\r\n500 Internal Server Error",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\nConnection: close\r\n\r\n/last",
+            )
+        );
+    }
+
+    #[test]
+    fn when_a_websocket_is_opened_each_message_is_answered_until_the_client_closes_it() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        let handle = server.handle();
+        let server = thread::spawn(move || {
+            server.serve(|request| match request {
+                Request::Page(_) | Request::Events { .. } => Err(HttpError::NotFound),
+                Request::Message(message) => Ok(HttpResponse::text(message.to_uppercase())),
+            })
+        });
+
+        let mut client = TcpStream::connect(("127.0.0.1", handle.port())).unwrap();
+        client
+            .write_all(b"GET /live HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+            .unwrap();
+        let mut client = BufReader::new(client);
+        let mut handshake = String::new();
+        while !handshake.ends_with("\r\n\r\n") {
+            client.read_line(&mut handshake).unwrap();
+        }
+        assert!(handshake.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+
+        // a masked text frame holding "tick", then a close frame
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![0x81, 0x84];
+        frame.extend(mask);
+        frame.extend(b"tick".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        frame.extend([0x88, 0x80, 0, 0, 0, 0]);
+        client.get_mut().write_all(&frame).unwrap();
+        let mut frames = vec![];
+        io::Read::read_to_end(&mut client, &mut frames).unwrap();
+        assert_eq!(frames, [0x81, 4, b'T', b'I', b'C', b'K', 0x88, 0]);
+
+        handle.shutdown();
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn when_a_server_is_bound_to_port_0_it_picks_a_port_and_can_be_shut_down() {
+        let server = Server::bind(("127.0.0.1", 0)).unwrap();
+        assert_ne!(server.port(), 0);
+        let handle = server.handle();
+        let client = thread::spawn(move || {
+            let mut client = TcpStream::connect(("127.0.0.1", handle.port())).unwrap();
+            client
+                .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            io::Read::read_to_string(&mut client, &mut response).unwrap();
+            handle.shutdown();
+            response
+        });
+
+        server.serve_library(&ChipLibrary::new(vec![])).unwrap();
+        assert!(client.join().unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn when_a_server_is_bound_to_every_interface_or_ipv6_it_answers_and_shuts_down() {
+        for (address, loopback) in [("0.0.0.0:0", "127.0.0.1"), ("[::1]:0", "::1")] {
+            let server = Server::bind(address).unwrap();
+            let handle = server.handle();
+            let client = thread::spawn(move || {
+                let mut client = TcpStream::connect((loopback, handle.port())).unwrap();
+                client
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+                let mut response = String::new();
+                io::Read::read_to_string(&mut client, &mut response).unwrap();
+                handle.shutdown();
+                response
+            });
+
+            server.serve_library(&ChipLibrary::new(vec![])).unwrap();
+            assert!(
+                client.join().unwrap().starts_with("HTTP/1.1 200 OK"),
+                "{address} should be served"
+            );
+        }
+    }
+
+    #[test]
+    fn when_an_output_is_forced_it_is_annotated_in_the_graph() {
+        struct TestChip {}
+        impl<'a> Chip<'a> for TestChip {
+            fn get_id(&self) -> String {
+                "1".into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                "TestChip"
+            }
+        }
+
+        let alloc = Bump::new();
+        let in1 = UserInput::new(&alloc);
+        let cin1 = ChipInput::new(&alloc, Input::UserInput(in1), "in".into());
+        let cout1 = ChipOutput::new(&alloc, "out".into(), ChipOutputType::ChipInput(cin1));
+        cout1.force(true);
+        let outs = [Output::new(ChipOutputWrapper::new(
+            &alloc,
+            cout1,
+            &TestChip {},
+        ))];
+        let mermaid_out = graph_outputs(&outs, &[], HashSet::new());
+
+        assert!(mermaid_out
+            .compile()
+            .contains(&format!("{}OUT(TestChip out, forced 1)", cout1.id)));
+    }
+
+    #[test]
+    fn when_a_graph_is_exported_the_page_embeds_it_without_expandable_chips() {
+        struct TestChip {}
+        impl<'a> Chip<'a> for TestChip {
+            fn get_id(&self) -> String {
+                "1".into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                "TestChip"
+            }
+        }
+
+        let alloc = Bump::new();
+        let in1 = UserInput::new(&alloc);
+        let cin1 = ChipInput::new(&alloc, Input::UserInput(in1), "in".into());
+        let cout1 = ChipOutput::new(&alloc, "out".into(), ChipOutputType::ChipInput(cin1));
+        let outs = [Output::new(ChipOutputWrapper::new(
+            &alloc,
+            cout1,
+            &TestChip {},
+        ))];
+        let graph = graph_outputs(&outs, &[], HashSet::from(["1".into()]));
+
+        let path = std::env::temp_dir().join(format!("export-{}.html", std::process::id()));
+        export_graph(&graph, &path).unwrap();
+        let page = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(page.contains(&graph.compile()));
+        assert!(page.contains("const chipIds = [];"));
+        assert!(page.contains("const overridesEnabled = false;"));
     }
 
     #[test]
@@ -569,59 +2428,320 @@ mod tests {
         }
 
         let alloc = Bump::new();
-        let in1 = UserInput::new(&alloc);
-        let win1 = Input::UserInput(in1);
-        let in2 = UserInput::new(&alloc);
-        let win2 = Input::UserInput(in2);
-        let cin1 = ChipInput::new(&alloc, win1, "an input".into());
-        let cin2 = ChipInput::new(&alloc, win2, "another input".into());
-        let nand = Nand::new(&alloc, Input::ChipInput(&cin1), Input::ChipInput(&cin2));
-        let cout1 = ChipOutput::new(&alloc, "out2".into(), ChipOutputType::NandOutput(nand));
-        let cout2 = ChipOutput::new(&alloc, "out1".into(), ChipOutputType::ChipInput(cin1));
-        let outs = [
-            Output::new(&ChipOutputWrapper::new(&alloc, &cout1, &TestChip {})),
-            Output::new(&ChipOutputWrapper::new(&alloc, &cout2, &TestChip {})),
-        ];
-        let mermaid_out = graph_outputs(&outs, HashSet::from([CHIP_ID.into()]));
+        // a fresh scope numbers each kind of node from 0, whatever other tests have built
+        let outs = IdScope::new().enter(|| {
+            let win1 = Input::UserInput(UserInput::from(&alloc, true));
+            let win2 = Input::UserInput(UserInput::new(&alloc));
+            let cin1 = ChipInput::new(&alloc, win1, "an input".into());
+            let cin2 = ChipInput::new(&alloc, win2, "another input".into());
+            let nand = Nand::new(&alloc, Input::ChipInput(cin1), Input::ChipInput(cin2));
+            let cout1 = ChipOutput::new(&alloc, "out2".into(), ChipOutputType::NandOutput(nand));
+            let cout2 = ChipOutput::new(&alloc, "out1".into(), ChipOutputType::ChipInput(cin1));
+            [
+                Output::new(ChipOutputWrapper::new(&alloc, cout1, &TestChip {})),
+                Output::new(ChipOutputWrapper::new(&alloc, cout2, &TestChip {})),
+            ]
+        });
+        let mermaid_out = graph_outputs(&outs, &[], HashSet::from([CHIP_ID.into()]));
 
-        let expected = format!(
-            "graph TD
+        let expected = "graph TD
 subgraph 1 [TestChip]
-{}IN(IN an input)
-{}IN(IN an input)-->{}OUT(OUT out1)
-{}IN(IN another input)
-{}IN(IN an input)-->{}NAND(NAND)
-{}IN(IN another input)-->{}NAND(NAND)
-{}NAND(NAND)-->{}OUT(OUT out2)
+0IN(an input)
+0IN(an input)-->|1|1OUT(out1)
+1IN(another input)
+0IN(an input)-->|1|0NAND(NAND)
+1IN(another input)-->|0|0NAND(NAND)
+0NAND(NAND)-->|0|0OUT(out2)
 end
-{}INPUT(INPUT)-->{}IN(IN an input)
-{}OUT(OUT out1)-->{}OUTPUT(OUTPUT)
-{}INPUT(INPUT)-->{}IN(IN another input)
-{}OUT(OUT out2)-->{}OUTPUT(OUTPUT)",
-            cin1.id,
-            cin1.id,
-            cout2.id,
-            cin2.id,
-            cin1.id,
-            nand.identifier,
-            cin2.id,
-            nand.identifier,
-            nand.identifier,
-            cout1.id,
-            in1.id,
-            cin1.id,
-            cout2.id,
-            outs[1].identifier,
-            in2.id,
-            cin2.id,
-            cout1.id,
-            outs[0].identifier
-        );
+0INPUT(INPUT)-->|1|0IN(an input)
+1OUT(out1)-->|0|1OUTPUT(OUTPUT)
+1INPUT(INPUT)-->|0|1IN(another input)
+0OUT(out2)-->|0|0OUTPUT(OUTPUT)
+linkStyle 0,1,4 stroke:#2a2
+classDef nand fill:#fde2b8,stroke:#c77d0a
+class 0NAND nand
+classDef boundary fill:#dbe9fb,stroke:#3b73b9
+class 0IN,0OUT,1IN,1OUT boundary
+classDef port fill:#e6d8f5,stroke:#7446a8
+class 0INPUT,0OUTPUT,1INPUT,1OUTPUT port";
         let actual = mermaid_out.compile();
 
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn when_a_graph_is_themed_chips_are_styled_by_category_before_their_clock() {
+        struct TestChip {
+            id: &'static str,
+            label: &'static str,
+        }
+        impl<'a> Chip<'a> for TestChip {
+            fn get_id(&self) -> String {
+                self.id.into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                self.label
+            }
+
+            fn get_ports(&self) -> hdl::ports::Description {
+                hdl::ports::Description {
+                    inputs: hdl::ports::ports(vec![("clock".into(), 0)], &[false]),
+                    outputs: vec![],
+                }
+            }
+        }
+
+        let alloc = Bump::new();
+        let outs = [("latch", "Dlatch"), ("counter", "Counter")].map(|(id, label)| {
+            let in_ = ChipInput::new(
+                &alloc,
+                Input::UserInput(UserInput::new(&alloc)),
+                "clock".into(),
+            );
+            let out = ChipOutput::new(&alloc, "out".into(), ChipOutputType::ChipInput(in_));
+            Output::new(ChipOutputWrapper::new(
+                &alloc,
+                out,
+                alloc.alloc(TestChip { id, label }),
+            ))
+        });
+        let theme = GraphTheme {
+            categories: HashMap::from([("Counter", "arith".into())]),
+            category_styles: HashMap::from([("arith".into(), "fill:#0f0".into())]),
+            ..Default::default()
+        };
+        let graph = graph_outputs(
+            &outs,
+            &[],
+            GraphOptions {
+                theme,
+                ..Default::default()
+            },
+        );
+
+        let chip_classes = graph
+            .classes
+            .iter()
+            .filter(|class| ["arith", SEQUENTIAL_CLASS].contains(&class.name.as_str()))
+            .map(|class| (class.name.as_str(), class.members.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chip_classes,
+            [
+                ("arith", vec!["counter".to_owned()]),
+                (SEQUENTIAL_CLASS, vec!["latch".to_owned()])
+            ]
+        );
+    }
+
+    #[test]
+    fn when_repeated_chips_are_grouped_they_are_drawn_once_with_their_count() {
+        let node = |identifier, type_, name: &str| MermaidNode {
+            identifier,
+            type_,
+            name: name.into(),
+        };
+        let line = |from: &MermaidNode, to: &MermaidNode| {
+            MermaidStatement::Line(MermaidLine {
+                from: from.clone(),
+                to: to.clone(),
+                value: Logic::Low,
+            })
+        };
+        let input = node(0, "INPUT", "INPUT");
+        let chip = |id: &str, title, in_: &MermaidNode, out: &MermaidNode| {
+            let mut chip = MermaidGraph::new(title, id.into());
+            chip.statements = vec![
+                MermaidStatement::Node(in_.clone()),
+                MermaidStatement::Node(out.clone()),
+            ];
+            chip
+        };
+        let mut graph = MermaidGraph::new("", "".into());
+        let mut outputs = vec![];
+        for (id, title) in [
+            (1, "Register"),
+            (2, "Register"),
+            (3, "Register"),
+            (4, "Not"),
+        ] {
+            let in_ = node(id, "IN", &format!("{title} in"));
+            let out = node(id, "OUT", &format!("{title} out"));
+            let output = node(id, "OUTPUT", "OUTPUT");
+            graph.statements.push(line(&input, &in_));
+            graph.statements.push(line(&out, &output));
+            graph
+                .subgraphs
+                .insert(id.to_string(), chip(&id.to_string(), title, &in_, &out));
+            outputs.push(output);
+        }
+        // an expanded chip is left as it is
+        let mut expected = graph.clone();
+        graph.group_repeated(&HashSet::from(["3".into()]));
+
+        expected.subgraphs.remove("2");
+        expected.subgraphs.get_mut("1").unwrap().instances = 2;
+        let [in1, out1] = [node(1, "IN", "Register in"), node(1, "OUT", "Register out")];
+        expected.statements = vec![
+            line(&input, &in1),
+            line(&out1, &outputs[0]),
+            line(&out1, &outputs[1]),
+            line(&input, &node(3, "IN", "Register in")),
+            line(&node(3, "OUT", "Register out"), &outputs[2]),
+            line(&input, &node(4, "IN", "Not in")),
+            line(&node(4, "OUT", "Not out"), &outputs[3]),
+        ];
+        assert_eq!(graph, expected);
+        assert!(graph.compile().contains("subgraph 1 [Register ×2]"));
+    }
+
+    #[test]
+    fn when_a_graph_is_drawn_its_nodes_have_tooltips_with_their_chips_ids_and_values() {
+        let node = |identifier, type_, name: &str| MermaidNode {
+            identifier,
+            type_,
+            name: name.into(),
+        };
+        let input = node(0, "INPUT", "INPUT");
+        let in_ = node(1, "IN", "B in-0");
+        let out = node(2, "OUT", "B out");
+        let mut graph = MermaidGraph::new("", "".into());
+        let mut a = MermaidGraph::new("A", "a".into());
+        let mut b = MermaidGraph::new("B", "b".into());
+        b.statements.push(MermaidStatement::Node(in_.clone()));
+        b.statements.push(MermaidStatement::Line(MermaidLine {
+            from: node(3, "NAND", "NAND"),
+            to: out.clone(),
+            value: Logic::High,
+        }));
+        a.subgraphs.insert("b".into(), b);
+        graph.subgraphs.insert("a".into(), a);
+        graph.statements.push(MermaidStatement::Line(MermaidLine {
+            from: input,
+            to: in_,
+            value: Logic::Low,
+        }));
+        graph.statements.push(MermaidStatement::Line(MermaidLine {
+            from: out,
+            to: node(4, "OUTPUT", "OUTPUT"),
+            value: Logic::High,
+        }));
+
+        assert_eq!(
+            graph.tooltips(),
+            [
+                ("0INPUT".into(), "INPUT\nid 0, value 0".into()),
+                ("1IN".into(), "A › B › B in-0\nid 1, value 0".into()),
+                ("2OUT".into(), "A › B › B out\nid 2, value 1".into()),
+                ("3NAND".into(), "NAND\nid 3, value 1".into()),
+                ("4OUTPUT".into(), "OUTPUT\nid 4, value 1".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn when_a_graph_is_laid_out_its_direction_spacing_and_chip_order_are_given_to_mermaid() {
+        let mut graph = MermaidGraph::new("", "".into());
+        graph
+            .subgraphs
+            .insert("a".into(), MermaidGraph::new("Register", "a".into()));
+        graph
+            .subgraphs
+            .insert("b".into(), MermaidGraph::new("Bit", "b".into()));
+        assert_eq!(
+            graph.compile(),
+            "graph TD\nsubgraph a [Register]\nend\nsubgraph b [Bit]\nend"
+        );
+
+        graph.layout = GraphLayout {
+            direction: GraphDirection::LeftRight,
+            node_spacing: Some(20),
+            rank_spacing: Some(80),
+            chip_order: ChipOrder::Name,
+        };
+        assert_eq!(
+            graph.compile(),
+            "%%{init: {\"flowchart\":{\"nodeSpacing\":20,\"rankSpacing\":80}}}%%\ngraph LR\nsubgraph b [Bit]\nend\nsubgraph a [Register]\nend"
+        );
+    }
+
+    #[test]
+    fn when_buses_are_bundled_parallel_port_bits_are_drawn_as_one_line() {
+        let node = |identifier, type_, name: &str| MermaidNode {
+            identifier,
+            type_,
+            name: name.into(),
+        };
+        let mut graph = MermaidGraph::new("", "".into());
+        let mut a = MermaidGraph::new("A", "a".into());
+        let mut b = MermaidGraph::new("B", "b".into());
+        let values = [
+            Logic::High,
+            Logic::Low,
+            Logic::High,
+            Logic::High,
+            Logic::Low,
+        ];
+        for (bit, value) in values.into_iter().enumerate() {
+            let out = node(bit as u32, "OUT", &format!("A out-{bit}"));
+            let in_ = node(10 + bit as u32, "IN", &format!("B in-{bit}"));
+            a.statements.push(MermaidStatement::Node(out.clone()));
+            b.statements.push(MermaidStatement::Node(in_.clone()));
+            graph.statements.push(MermaidStatement::Line(MermaidLine {
+                from: out,
+                to: in_,
+                value,
+            }));
+        }
+        // a bit with a line of its own is still drawn
+        let output = node(20, "OUTPUT", "OUTPUT");
+        graph.statements.push(MermaidStatement::Line(MermaidLine {
+            from: node(4, "OUT", "A out-4"),
+            to: output.clone(),
+            value: Logic::Low,
+        }));
+        graph.subgraphs.insert("a".into(), a);
+        graph.subgraphs.insert("b".into(), b);
+        graph.bundle_buses();
+
+        let out_bus = node(0, "OUT_BUS", "A out[0..4]");
+        let in_bus = node(10, "IN_BUS", "B in[0..4]");
+        assert_eq!(
+            graph.statements,
+            [
+                MermaidStatement::Line(MermaidLine {
+                    from: node(4, "OUT", "A out-4"),
+                    to: output,
+                    value: Logic::Low,
+                }),
+                MermaidStatement::Bus(MermaidBus {
+                    line: MermaidLine {
+                        from: out_bus.clone(),
+                        to: in_bus.clone(),
+                        value: Logic::Unknown,
+                    },
+                    values: values.to_vec(),
+                }),
+            ]
+        );
+        assert_eq!(
+            graph.subgraphs["a"].statements,
+            [
+                MermaidStatement::Node(node(4, "OUT", "A out-4")),
+                MermaidStatement::Node(out_bus),
+            ]
+        );
+        assert_eq!(
+            graph.subgraphs["b"].statements,
+            [MermaidStatement::Node(in_bus)]
+        );
+        assert!(graph
+            .compile()
+            .contains("\n0OUT_BUS(A out[0..4])==>|5 bits 0x16|10IN_BUS(B in[0..4])"));
+        assert_eq!(bus_value(&[Logic::High, Logic::HighZ]), "1Z");
+    }
+
     #[test]
     fn multiple_outputs_are_represented_properly_in_mermaid_structure() {
         struct TestChip {}
@@ -643,13 +2763,13 @@ end
         let in2 = Input::UserInput(uin2);
         let cin1 = ChipInput::new(&alloc, in1, "an input".into());
         let cin2 = ChipInput::new(&alloc, in2, "another input".into());
-        let nand = Nand::new(&alloc, Input::ChipInput(&cin1), Input::ChipInput(&cin2));
+        let nand = Nand::new(&alloc, Input::ChipInput(cin1), Input::ChipInput(cin2));
         let out1 = ChipOutput::new(&alloc, "out1".into(), ChipOutputType::NandOutput(nand));
         let out2 = ChipOutput::new(&alloc, "out2".into(), ChipOutputType::ChipInput(cin1));
-        let mout1 = Output::new(&ChipOutputWrapper::new(&alloc, &out1, &TestChip {}));
-        let mout2 = Output::new(&ChipOutputWrapper::new(&alloc, &out2, &TestChip {}));
+        let mout1 = Output::new(ChipOutputWrapper::new(&alloc, out1, &TestChip {}));
+        let mout2 = Output::new(ChipOutputWrapper::new(&alloc, out2, &TestChip {}));
         let mouts = [mout1, mout2];
-        let mut mermaid_out = graph_outputs(&mouts, HashSet::from([CHIP_ID.into()]));
+        let mut mermaid_out = graph_outputs(&mouts, &[], HashSet::from([CHIP_ID.into()]));
 
         let mut expected = MermaidGraph {
             statements: Vec::from([
@@ -661,14 +2781,15 @@ end
                     },
                     to: MermaidNode {
                         identifier: cin1.id,
-                        name: "IN an input".into(),
+                        name: "an input".into(),
                         type_: "IN",
                     },
+                    value: Logic::Low,
                 }),
                 MermaidStatement::Line(MermaidLine {
                     from: MermaidNode {
                         identifier: out1.id,
-                        name: "OUT out1".into(),
+                        name: "out1".into(),
                         type_: "OUT",
                     },
                     to: MermaidNode {
@@ -676,6 +2797,7 @@ end
                         name: "OUTPUT".into(),
                         type_: "OUTPUT",
                     },
+                    value: Logic::Low,
                 }),
                 MermaidStatement::Line(MermaidLine {
                     from: MermaidNode {
@@ -685,14 +2807,15 @@ end
                     },
                     to: MermaidNode {
                         identifier: cin2.id,
-                        name: "IN another input".into(),
+                        name: "another input".into(),
                         type_: "IN",
                     },
+                    value: Logic::Low,
                 }),
                 MermaidStatement::Line(MermaidLine {
                     from: MermaidNode {
                         identifier: out2.id,
-                        name: "OUT out2".into(),
+                        name: "out2".into(),
                         type_: "OUT",
                     },
                     to: MermaidNode {
@@ -700,28 +2823,33 @@ end
                         name: "OUTPUT".into(),
                         type_: "OUTPUT",
                     },
+                    value: Logic::Low,
                 }),
             ]),
             name: "",
             id: "".into(),
+            instances: 1,
+            classes: vec![],
+            path: vec![],
+            layout: Default::default(),
             subgraphs: HashMap::from([(
                 String::from("1"),
                 MermaidGraph {
                     statements: Vec::from([
                         MermaidStatement::Node(MermaidNode {
                             identifier: cin1.id,
-                            name: "IN an input".into(),
+                            name: "an input".into(),
                             type_: "IN",
                         }),
                         MermaidStatement::Node(MermaidNode {
                             identifier: cin2.id,
-                            name: "IN another input".into(),
+                            name: "another input".into(),
                             type_: "IN",
                         }),
                         MermaidStatement::Line(MermaidLine {
                             from: MermaidNode {
                                 identifier: cin1.id,
-                                name: "IN an input".into(),
+                                name: "an input".into(),
                                 type_: "IN",
                             },
                             to: MermaidNode {
@@ -729,11 +2857,12 @@ end
                                 name: "NAND".into(),
                                 type_: "NAND",
                             },
+                            value: Logic::Low,
                         }),
                         MermaidStatement::Line(MermaidLine {
                             from: MermaidNode {
                                 identifier: cin2.id,
-                                name: "IN another input".into(),
+                                name: "another input".into(),
                                 type_: "IN",
                             },
                             to: MermaidNode {
@@ -741,6 +2870,7 @@ end
                                 name: "NAND".into(),
                                 type_: "NAND",
                             },
+                            value: Logic::Low,
                         }),
                         MermaidStatement::Line(MermaidLine {
                             from: MermaidNode {
@@ -750,35 +2880,160 @@ end
                             },
                             to: MermaidNode {
                                 identifier: out1.id,
-                                name: "OUT out1".into(),
+                                name: "out1".into(),
                                 type_: "OUT",
                             },
+                            value: Logic::Low,
                         }),
                         MermaidStatement::Line(MermaidLine {
                             from: MermaidNode {
                                 identifier: cin1.id,
-                                name: "IN an input".into(),
+                                name: "an input".into(),
                                 type_: "IN",
                             },
                             to: MermaidNode {
                                 identifier: out2.id,
-                                name: "OUT out2".into(),
+                                name: "out2".into(),
                                 type_: "OUT",
                             },
+                            value: Logic::Low,
                         }),
                     ]),
                     name: "TestChip",
                     subgraphs: HashMap::new(),
                     id: "1".into(),
+                    instances: 1,
+                    classes: vec![],
+                    path: vec![],
+                    layout: Default::default(),
                 },
             )]),
         };
         sort_mermaid_graph(&mut expected);
         sort_mermaid_graph(&mut mermaid_out);
+        // the classes are checked by mermaid_compiles_properly_to_text
+        mermaid_out.classes.clear();
 
         assert_eq!(expected, mermaid_out);
     }
 
+    #[test]
+    fn when_an_expand_depth_is_given_chips_down_to_it_are_expanded() {
+        struct Outer<'a> {
+            inner: &'a Inner,
+        }
+        impl<'a> Chip<'a> for Outer<'a> {
+            fn get_id(&self) -> String {
+                "outer".into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                "Outer"
+            }
+
+            fn get_children(&self) -> Vec<&'a dyn Chip<'a>> {
+                vec![self.inner]
+            }
+        }
+
+        struct Inner {}
+        impl<'a> Chip<'a> for Inner {
+            fn get_id(&self) -> String {
+                "inner".into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                "Inner"
+            }
+        }
+
+        let alloc = Bump::new();
+        let in1 = Input::UserInput(UserInput::new(&alloc));
+        let outer_in = ChipInput::new(&alloc, in1, "in".into());
+        let inner_in = ChipInput::new(&alloc, Input::ChipInput(outer_in), "in".into());
+        let inner = alloc.alloc(Inner {});
+        let inner_out = ChipOutput::new(&alloc, "out".into(), ChipOutputType::ChipInput(inner_in));
+        let outer_out = ChipOutput::new(
+            &alloc,
+            "out".into(),
+            ChipOutputType::ChipOutput(ChipOutputWrapper::new(&alloc, inner_out, inner)),
+        );
+        let outer = alloc.alloc(Outer { inner });
+        let outs = [Output::new(ChipOutputWrapper::new(
+            &alloc, outer_out, outer,
+        ))];
+
+        assert_eq!(chips_to_depth(&outs, 0), HashSet::new());
+        assert_eq!(chips_to_depth(&outs, 1), HashSet::from(["outer".into()]));
+        assert_eq!(
+            chips_to_depth(&outs, 5),
+            HashSet::from(["outer".into(), "inner".into()])
+        );
+        assert_eq!(
+            chips_to_depth(&outs, usize::MAX),
+            HashSet::from(["outer".into(), "inner".into()])
+        );
+        assert_eq!(
+            chips_in_subtrees(&outs, &HashSet::from(["inner".into()])),
+            HashSet::from(["outer".into(), "inner".into()])
+        );
+        assert_eq!(
+            chips_in_subtrees(&outs, &HashSet::from(["elsewhere".into()])),
+            HashSet::new()
+        );
+
+        let graph = graph_outputs(
+            &outs,
+            &[],
+            GraphOptions {
+                expand_depth: 1,
+                ..Default::default()
+            },
+        );
+        assert!(graph.subgraphs["outer"].subgraphs.contains_key("inner"));
+        assert!(graph.subgraphs["outer"].subgraphs["inner"]
+            .statements
+            .iter()
+            .all(|statement| matches!(statement, MermaidStatement::Node(_))));
+
+        // a root chip's inside is drawn on its own, with its children collapsed
+        assert_eq!(
+            chip_path(&outs, "inner"),
+            Some(vec!["outer".into(), "inner".into()])
+        );
+        let root = |id: &str| {
+            graph_outputs(
+                &outs,
+                &[],
+                GraphOptions {
+                    root: Some(id.into()),
+                    ..Default::default()
+                },
+            )
+        };
+        let outer_level = root("outer");
+        assert_eq!(outer_level.id, "outer");
+        assert!(outer_level.subgraphs["inner"]
+            .statements
+            .iter()
+            .all(|statement| matches!(statement, MermaidStatement::Node(_))));
+        let inner_level = root("inner");
+        assert_eq!(inner_level.id, "inner");
+        assert_eq!(
+            inner_level.path,
+            [
+                ("outer".into(), outer_level.name),
+                ("inner".into(), inner_level.name)
+            ]
+        );
+        assert!(inner_level.subgraphs.is_empty());
+        assert!(inner_level
+            .statements
+            .iter()
+            .any(|statement| matches!(statement, MermaidStatement::Line(_))));
+        assert_eq!(root("elsewhere"), MermaidGraph::new("", "".into()));
+    }
+
     #[test]
     fn when_a_chip_is_not_in_the_show_nodes_set_but_its_parent_is_only_the_inputs_and_outputs_are_rendered(
     ) {
@@ -815,16 +3070,16 @@ end
         let c1in2 = ChipInput::new(&alloc, in2, "another input".into());
         let c2in1 = ChipInput::new(&alloc, Input::ChipInput(c1in1), "yet another input".into());
         let c2in2 = ChipInput::new(&alloc, Input::ChipInput(c1in2), "last input".into());
-        let nand = Nand::new(&alloc, Input::ChipInput(&c2in1), Input::ChipInput(&c2in2));
+        let nand = Nand::new(&alloc, Input::ChipInput(c2in1), Input::ChipInput(c2in2));
         let c2out = ChipOutput::new(&alloc, "out1".into(), ChipOutputType::NandOutput(nand));
         let c1out = ChipOutput::new(
             &alloc,
             "out2".into(),
             ChipOutputType::ChipOutput(ChipOutputWrapper::new(&alloc, c2out, &TestChip2 {})),
         );
-        let mout1 = Output::new(&ChipOutputWrapper::new(&alloc, &c1out, &TestChip1 {}));
+        let mout1 = Output::new(ChipOutputWrapper::new(&alloc, c1out, &TestChip1 {}));
         let mouts = [mout1];
-        let mermaid_out = graph_outputs(&mouts, HashSet::from([]));
+        let mermaid_out = graph_outputs(&mouts, &[], HashSet::from([]));
 
         assert!(
             mermaid_out.subgraphs.contains_key(CHIP_ID_1),
@@ -835,7 +3090,7 @@ end
             .iter()
             .all(|s| match s {
                 MermaidStatement::Node(x) => x.type_ == "IN" || x.type_ == "OUT",
-                MermaidStatement::Line(_) => true,
+                MermaidStatement::Line(_) | MermaidStatement::Bus(_) => true,
             });
         assert!(
             testchip1_has_only_input_and_output_nodes,
@@ -849,4 +3104,63 @@ end
         );
         assert_eq!(mermaid_out.subgraphs[CHIP_ID_1].subgraphs.len(), 0);
     }
+
+    #[test]
+    fn when_constant_inputs_are_hidden_or_drawn_as_terminals_only_the_machines_inputs_remain() {
+        struct TestChip {}
+        impl<'a> Chip<'a> for TestChip {
+            fn get_id(&self) -> String {
+                "chip".into()
+            }
+
+            fn get_label(&self) -> &'static str {
+                "TestChip"
+            }
+        }
+
+        let alloc = Bump::new();
+        let input = UserInput::new(&alloc);
+        let one = UserInput::from(&alloc, true);
+        let in1 = ChipInput::new(&alloc, Input::UserInput(input), "in".into());
+        let in2 = ChipInput::new(&alloc, Input::UserInput(one), "one".into());
+        let nand = Nand::new(&alloc, Input::ChipInput(in1), Input::ChipInput(in2));
+        let out = ChipOutput::new(&alloc, "out".into(), ChipOutputType::NandOutput(nand));
+        let outs = [Output::new(ChipOutputWrapper::new(
+            &alloc,
+            out,
+            &TestChip {},
+        ))];
+        let sources = |constant_inputs| {
+            let graph = graph_outputs(
+                &outs,
+                &[input.id],
+                GraphOptions {
+                    constant_inputs,
+                    ..Default::default()
+                },
+            );
+            let mut sources = graph
+                .statements
+                .into_iter()
+                .filter_map(|statement| match statement {
+                    MermaidStatement::Line(line) if line.to.type_ == "IN" => {
+                        Some((line.from.type_, line.from.name))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            sources.sort();
+            sources
+        };
+
+        assert_eq!(
+            sources(ConstantInputs::Shown),
+            [("INPUT", "INPUT".into()), ("INPUT", "INPUT".into())]
+        );
+        assert_eq!(sources(ConstantInputs::Hidden), [("INPUT", "INPUT".into())]);
+        assert_eq!(
+            sources(ConstantInputs::Terminals),
+            [("CONST", "1".into()), ("INPUT", "INPUT".into())]
+        );
+    }
 }
@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+};
+
+use crate::{
+    cache::GraphCache,
+    get_response,
+    http::{HttpError, HttpRequest, HttpResponse},
+    GraphOptions, MermaidGraph, Request, Server, TruthTable,
+};
+
+// A library entry is type-erased so that chips with different input and output
+// sizes can be listed side by side. Graphing builds a fresh machine each time as
+// a `Machine` borrows the arena it was built in.
+pub struct LibraryEntry {
+    pub namespace: &'static str,
+    pub name: &'static str,
+    pub graph: fn(GraphOptions) -> MermaidGraph,
+    // starts an interactive server backed by a long-lived machine
+    pub serve: fn(SocketAddr) -> Result<(), Box<dyn Error>>,
+    // simulates the chip on every combination of its inputs, see `truth_table()`
+    pub truth_table: fn() -> Result<TruthTable, Box<dyn Error>>,
+}
+
+impl LibraryEntry {
+    pub fn path(&self) -> String {
+        format!("{}::{}", self.namespace, self.name)
+    }
+}
+
+pub struct ChipLibrary {
+    entries: Vec<LibraryEntry>,
+}
+
+impl ChipLibrary {
+    pub fn new(entries: Vec<LibraryEntry>) -> ChipLibrary {
+        ChipLibrary { entries }
+    }
+
+    pub fn find(&self, path: &str) -> Option<&LibraryEntry> {
+        self.entries.iter().find(|entry| entry.path() == path)
+    }
+
+    // namespaces are sorted, entries within a namespace keep their registration order
+    pub fn by_namespace(&self) -> BTreeMap<&'static str, Vec<&LibraryEntry>> {
+        self.entries
+            .iter()
+            .fold(BTreeMap::new(), |mut namespaces, entry| {
+                namespaces
+                    .entry(entry.namespace)
+                    .or_insert_with(Vec::new)
+                    .push(entry);
+                namespaces
+            })
+    }
+}
+
+pub fn start_library_server(library: &ChipLibrary, address: impl ToSocketAddrs) -> io::Result<()> {
+    let server = Server::bind(address)?;
+    println!("serving on http://{}", server.address());
+    server.serve_library(library)
+}
+
+impl Server {
+    pub fn serve_library(self, library: &ChipLibrary) -> io::Result<()> {
+        let mut graphs = HashMap::new();
+        self.serve(|request| match request {
+            Request::Page(request) => get_library_response(&request, library, &mut graphs),
+            // library pages aren't backed by a machine to simulate
+            Request::Message(_) | Request::Events { .. } => Err(HttpError::NotFound),
+        })
+    }
+}
+
+const LIBRARY_TEMPLATE: &str = include_str!("../http/library.html");
+// each chip's graphs are cached by its path. They're drawn from fresh machines, so their
+// state never changes
+fn get_library_response(
+    request: &HttpRequest,
+    library: &ChipLibrary,
+    graphs: &mut HashMap<String, GraphCache>,
+) -> Result<HttpResponse, HttpError> {
+    let path = request.path.as_str();
+
+    if path == "/" {
+        return Ok(HttpResponse::html(
+            LIBRARY_TEMPLATE.replace("{REPLACE_LIBRARY}", &render_library(library)),
+        ));
+    }
+
+    // a chip's truth table is at /<chip path>/truth_table, or truth_table.json
+    if let Some((chip, page)) = path[1..].split_once('/') {
+        let table = (library.find(chip).ok_or(HttpError::NotFound)?.truth_table)();
+        return match (page, table) {
+            ("truth_table", Ok(table)) => Ok(HttpResponse::html(table.to_html())),
+            ("truth_table", Err(err)) => Ok(HttpResponse::text(err.to_string())),
+            ("truth_table.json", Ok(table)) => Ok(HttpResponse::json(table.to_json())),
+            _ => Err(HttpError::NotFound),
+        };
+    }
+
+    let entry = library.find(&path[1..]).ok_or(HttpError::NotFound)?;
+    let graphs = graphs.entry(entry.path()).or_default();
+    get_response(
+        request,
+        |options| graphs.graph(0, options, entry.graph),
+        false,
+    )
+    .map(HttpResponse::html)
+}
+
+fn render_library(library: &ChipLibrary) -> String {
+    library
+        .by_namespace()
+        .iter()
+        .fold(String::new(), |acc, (namespace, entries)| {
+            let items = entries.iter().fold(String::new(), |acc, entry| {
+                format!(
+                    "{acc}\n      <li><a href=\"/{0}\">{1}</a> (<a href=\"/{0}/truth_table\">truth table</a>)</li>",
+                    entry.path(),
+                    entry.name
+                )
+            });
+            format!("{acc}\n    <h2>{namespace}::</h2>\n    <ul>{items}\n    </ul>")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use hdl::Logic;
+
+    use crate::{
+        http::{HttpError, HttpRequest, HttpResponse},
+        ChipLibrary, LibraryEntry, MermaidGraph, TruthTable, TruthTableRow,
+    };
+
+    use super::get_library_response;
+
+    fn request(line: &str) -> HttpRequest {
+        HttpRequest::parse(&[line.into()]).expect("request not valid")
+    }
+
+    fn test_library() -> ChipLibrary {
+        ChipLibrary::new(vec![
+            LibraryEntry {
+                namespace: "seq",
+                name: "Bit",
+                graph: |_| MermaidGraph::new("", "".into()),
+                serve: |_| Ok(()),
+                truth_table: || Err("no truth table".into()),
+            },
+            LibraryEntry {
+                namespace: "gates",
+                name: "Not",
+                graph: |_| MermaidGraph::new("", "".into()),
+                serve: |_| Ok(()),
+                truth_table: || Err("no truth table".into()),
+            },
+            LibraryEntry {
+                namespace: "gates",
+                name: "And",
+                graph: |options| MermaidGraph {
+                    statements: vec![],
+                    name: "",
+                    id: "".into(),
+                    instances: 1,
+                    classes: vec![],
+                    path: vec![],
+                    layout: Default::default(),
+                    subgraphs: options
+                        .show_chips
+                        .into_iter()
+                        .map(|id| (id.clone(), MermaidGraph::new("And", id)))
+                        .collect::<HashMap<_, _>>(),
+                },
+                serve: |_| Ok(()),
+                truth_table: || Err("no truth table".into()),
+            },
+        ])
+    }
+
+    #[test]
+    fn library_entries_are_grouped_by_namespace() {
+        let library = test_library();
+        let namespaces = library.by_namespace();
+
+        assert_eq!(
+            namespaces.keys().copied().collect::<Vec<_>>(),
+            ["gates", "seq"]
+        );
+        assert_eq!(
+            namespaces["gates"]
+                .iter()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            ["gates::Not", "gates::And"]
+        );
+        assert!(library.find("seq::Bit").is_some());
+        assert!(library.find("Bit").is_none());
+    }
+
+    #[test]
+    fn when_the_index_is_requested_the_library_is_listed_by_namespace() {
+        let resp = get_library_response(
+            &request("GET / HTTP/1.1"),
+            &test_library(),
+            &mut HashMap::new(),
+        )
+        .expect("response not valid")
+        .body;
+
+        let gates = resp
+            .find("<h2>gates::</h2>")
+            .expect("gates should be listed");
+        let seq = resp.find("<h2>seq::</h2>").expect("seq should be listed");
+        assert!(gates < seq, "namespaces should be sorted");
+        assert!(resp.contains("<a href=\"/gates::Not\">Not</a>"));
+        assert!(resp.contains("<a href=\"/seq::Bit\">Bit</a>"));
+    }
+
+    #[test]
+    fn when_a_chip_path_is_requested_its_graph_is_rendered() {
+        let resp = get_library_response(
+            &request("GET /gates::And?expanded=chip1, HTTP/1.1"),
+            &test_library(),
+            &mut HashMap::new(),
+        )
+        .expect("response not valid")
+        .body;
+
+        assert!(resp.contains("[\"chip1\",]"));
+        assert!(resp.contains("const overridesEnabled = false;"));
+    }
+
+    #[test]
+    fn when_a_truth_table_is_requested_it_is_rendered_as_html_or_json() {
+        let mut library = test_library();
+        library.entries[1].truth_table = || {
+            Ok(TruthTable {
+                inputs: vec!["in".into()],
+                outputs: vec!["out".into()],
+                rows: vec![
+                    TruthTableRow {
+                        inputs: vec![false],
+                        outputs: vec![Logic::High],
+                    },
+                    TruthTableRow {
+                        inputs: vec![true],
+                        outputs: vec![Logic::Low],
+                    },
+                ],
+            })
+        };
+        let get = |path: &str| {
+            get_library_response(
+                &request(&format!("GET {path} HTTP/1.1")),
+                &library,
+                &mut HashMap::new(),
+            )
+        };
+
+        assert_eq!(
+            get("/gates::Not/truth_table.json"),
+            Ok(HttpResponse::json(
+                r#"{"inputs":["in"],"outputs":["out"],"rows":[["0","1"],["1","0"]]}"#
+            ))
+        );
+        let html = get("/gates::Not/truth_table")
+            .expect("response not valid")
+            .body;
+        assert!(html.contains("<tr><th>in</th><th>out</th></tr>"));
+        assert!(html.contains("<tr><td>1</td><td>0</td></tr>"));
+        assert_eq!(
+            get("/gates::And/truth_table"),
+            Ok(HttpResponse::text("no truth table"))
+        );
+        assert_eq!(get("/gates::Not/nope"), Err(HttpError::NotFound));
+    }
+
+    #[test]
+    fn when_an_unknown_chip_is_requested_an_error_is_returned_and_chips_are_not_overridable() {
+        let library = test_library();
+        assert_eq!(
+            get_library_response(
+                &request("GET /gates::Nope HTTP/1.1"),
+                &library,
+                &mut HashMap::new()
+            ),
+            Err(HttpError::NotFound)
+        );
+        let page = get_library_response(
+            &request("GET /gates::And?force=1:1 HTTP/1.1"),
+            &library,
+            &mut HashMap::new(),
+        )
+        .unwrap();
+        assert!(
+            page.body.contains("const overridesEnabled = false;"),
+            "overrides need a long-lived machine"
+        );
+    }
+}
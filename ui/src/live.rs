@@ -0,0 +1,402 @@
+use std::{
+    cell::OnceCell,
+    collections::{BTreeMap, VecDeque},
+};
+
+use hdl::{ChipOutput, Error, Logic, Machine, StructuredData, StructuredDataFamily};
+
+use crate::{json::json_string, peripherals::Devices};
+
+// A live simulation is driven by one command per websocket message:
+//
+//   state              reports the machine without changing it
+//   set <input> <0|1>  sets an input, by its flattened name, and processes the inputs
+//   tick, tock, cycle  drives the clock high, low, or both in turn
+//   watch <signal>     records a signal's value after each step from now on, where a
+//                      signal is an input or output by name, or a chip output by id
+//   unwatch <signal>   stops recording it
+//   key <code>         holds down the key with the code on the keyboard, or none with 0,
+//                      and processes the inputs
+//
+// and each is answered with the machine's state as JSON: the input and output values
+// the way `Machine::set_input()` and `get_output()` see them, the level on every chip
+// output keyed by the id the graph shows it with, the recent values of the watched
+// signals, the screen, as 4 hex digits a word, and whether there's a keyboard, and the
+// error if the command failed
+pub(crate) fn simulate<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    waveforms: &mut Waveforms<'a>,
+    devices: &Devices<'a>,
+    command: &str,
+) -> String {
+    let error = run_command(machine, waveforms, devices, command)
+        .err()
+        .map(|err| json_string(&err));
+    state_json(machine, waveforms, devices, error)
+}
+
+// the number of steps of history kept for each watched signal
+const WAVEFORM_LEN: usize = 64;
+
+// The values of the watched signals after each step of the simulation, oldest first.
+// A step is a `set`, or one clock edge, and the last value of each signal is for the
+// latest step
+#[derive(Default)]
+pub(crate) struct Waveforms<'a> {
+    signals: Vec<Watched>,
+    // the number of steps so far
+    steps: u64,
+    // the machine's chip outputs by id, found the first time a signal or the state needs
+    // them, as the machine's chips don't change while it's served
+    chip_outputs: OnceCell<BTreeMap<u32, &'a ChipOutput<'a>>>,
+}
+
+// a signal is kept while the `watch` command has asked for it or an event stream
+// follows it
+struct Watched {
+    signal: String,
+    history: VecDeque<Logic>,
+    commanded: bool,
+    streams: usize,
+}
+
+impl<'a> Waveforms<'a> {
+    fn watch<TFam: StructuredDataFamily<NINPUT, NOUT>, const NINPUT: usize, const NOUT: usize>(
+        &mut self,
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+        signal: &str,
+    ) -> Result<&mut Watched, String> {
+        let value = self
+            .signal_value(machine, signal)
+            .ok_or_else(|| format!("unknown signal `{signal}`"))?;
+        let index = match self
+            .signals
+            .iter()
+            .position(|watched| watched.signal == signal)
+        {
+            Some(index) => index,
+            None => {
+                self.signals.push(Watched {
+                    signal: signal.into(),
+                    history: VecDeque::from([value]),
+                    commanded: false,
+                    streams: 0,
+                });
+                self.signals.len() - 1
+            }
+        };
+        Ok(&mut self.signals[index])
+    }
+
+    // watches the signals for an event stream until it's closed and `unfollow()` is called
+    // with them. None are followed unless all are known
+    pub(crate) fn follow<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &mut self,
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+        signals: &[String],
+    ) -> Result<(), String> {
+        if let Some(signal) = signals
+            .iter()
+            .find(|signal| self.signal_value(machine, signal).is_none())
+        {
+            return Err(format!("unknown signal `{signal}`"));
+        }
+        for signal in signals {
+            self.watch(machine, signal)?.streams += 1;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn unfollow(&mut self, signals: &[String]) {
+        for signal in signals {
+            if let Some(watched) = self
+                .signals
+                .iter_mut()
+                .find(|watched| watched.signal == *signal)
+            {
+                watched.streams = watched.streams.saturating_sub(1);
+            }
+        }
+        self.signals
+            .retain(|watched| watched.commanded || watched.streams > 0);
+    }
+
+    // the value of a watched signal at a step, if it's still kept
+    fn value_at(history: &VecDeque<Logic>, steps: u64, step: u64) -> Option<Logic> {
+        let back = usize::try_from(steps.checked_sub(step)?).ok()?;
+        history.iter().rev().nth(back).copied()
+    }
+
+    fn unwatch(&mut self, signal: &str) {
+        for watched in &mut self.signals {
+            if watched.signal == signal {
+                watched.commanded = false;
+            }
+        }
+        self.signals
+            .retain(|watched| watched.commanded || watched.streams > 0);
+    }
+
+    pub(crate) fn sample<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &mut self,
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+    ) {
+        self.steps += 1;
+        let values = self
+            .signals
+            .iter()
+            .map(|watched| self.signal_value(machine, &watched.signal))
+            .collect::<Vec<_>>();
+        for (Watched { history, .. }, value) in self.signals.iter_mut().zip(values) {
+            if history.len() == WAVEFORM_LEN {
+                history.pop_front();
+            }
+            history.push_back(value.unwrap_or(Logic::Unknown));
+        }
+    }
+
+    fn chip_outputs<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &self,
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+    ) -> &BTreeMap<u32, &'a ChipOutput<'a>> {
+        self.chip_outputs.get_or_init(|| {
+            machine
+                .chip_outputs()
+                .into_iter()
+                .map(|out| (out.id, out))
+                .collect()
+        })
+    }
+
+    // a port's logical value, or the level on a chip output
+    fn signal_value<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &self,
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+        signal: &str,
+    ) -> Option<Logic> {
+        let port = |levels: Vec<(String, Logic)>, active_low: &[bool]| {
+            levels
+                .into_iter()
+                .zip(active_low)
+                .find(|((name, _), _)| name == signal)
+                .map(|((_, level), &active_low)| port_value(level, active_low))
+        };
+        port(
+            machine.inputs(),
+            &TFam::StructuredInput::<bool>::active_low(),
+        )
+        .or_else(|| {
+            port(
+                machine.outputs(),
+                &TFam::StructuredOutput::<bool>::active_low(),
+            )
+        })
+        .or_else(|| {
+            let id = signal.parse().ok()?;
+            Some(self.chip_outputs(machine).get(&id)?.value())
+        })
+    }
+}
+
+// Server-sent events following the signals, by name, which an event stream has followed
+// with `Waveforms::follow()`: an event for each step after `since` where any of them
+// changed, with the step as its id and the new values as JSON, eg
+// `{"step":3,"changes":{"out":"1"}}`. With no steps seen yet, the signals' current values
+// are sent as one event. The events follow the number of the latest step and a newline,
+// for the next call's `since`. With no signals, every watched signal is followed
+pub(crate) fn events(waveforms: &Waveforms<'_>, signals: &[String], since: Option<u64>) -> String {
+    let steps = waveforms.steps;
+    let followed = waveforms
+        .signals
+        .iter()
+        .filter(|watched| signals.is_empty() || signals.contains(&watched.signal))
+        .collect::<Vec<_>>();
+    // there's no step after the last a u64 can count
+    let first = match since {
+        Some(since) => since.checked_add(1),
+        None => Some(steps),
+    };
+    let mut events = format!("{steps}\n");
+    for step in first.into_iter().flat_map(|first| first..=steps) {
+        let changes = followed
+            .iter()
+            .filter_map(
+                |Watched {
+                     signal, history, ..
+                 }| {
+                    let value = Waveforms::value_at(history, steps, step)?;
+                    let before = step
+                        .checked_sub(1)
+                        .and_then(|before| Waveforms::value_at(history, steps, before));
+                    (since.is_none() || before != Some(value))
+                        .then(|| format!("{}:\"{value}\"", json_string(signal)))
+                },
+            )
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            events += &format!(
+                "id: {step}\ndata: {{\"step\":{step},\"changes\":{{{}}}}}\n\n",
+                changes.join(",")
+            );
+        }
+    }
+    events
+}
+
+fn run_command<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+    waveforms: &mut Waveforms<'a>,
+    devices: &Devices<'a>,
+    command: &str,
+) -> Result<(), String> {
+    let clock_levels: &[bool] = match command.split_whitespace().collect::<Vec<_>>()[..] {
+        ["state"] => return Ok(()),
+        ["watch", signal] => {
+            waveforms.watch(machine, signal)?.commanded = true;
+            return Ok(());
+        }
+        ["unwatch", signal] => {
+            waveforms.unwatch(signal);
+            return Ok(());
+        }
+        ["set", input, value] => {
+            let value = match value {
+                "0" => false,
+                "1" => true,
+                _ => return Err(format!("`{value}` isn't 0 or 1")),
+            };
+            machine.set_input(input, value).map_err(display)?;
+            machine.update().map_err(display)?;
+            waveforms.sample(machine);
+            return Ok(());
+        }
+        ["key", code] => {
+            let code = code
+                .parse()
+                .map_err(|_| format!("`{code}` isn't a key code"))?;
+            devices.press(machine, code)?;
+            machine.update().map_err(display)?;
+            waveforms.sample(machine);
+            return Ok(());
+        }
+        ["tick"] => &[true],
+        ["tock"] => &[false],
+        ["cycle"] => &[true, false],
+        _ => return Err(format!("unknown command `{command}`")),
+    };
+    let clock = machine.get_clock().ok_or_else(|| display(Error::NoClock))?;
+    for &level in clock_levels {
+        machine.set_input(&clock, level).map_err(display)?;
+        machine.update().map_err(display)?;
+        waveforms.sample(machine);
+    }
+    Ok(())
+}
+
+fn display(err: Error) -> String {
+    err.to_string()
+}
+
+fn state_json<
+    'a,
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    machine: &Machine<'a, TFam, NINPUT, NOUT>,
+    waveforms: &Waveforms<'a>,
+    devices: &Devices<'a>,
+    error: Option<String>,
+) -> String {
+    let ports = |levels: Vec<(String, Logic)>, active_low: &[bool]| {
+        let ports = levels
+            .into_iter()
+            .zip(active_low)
+            .map(|((name, level), &active_low)| {
+                format!(
+                    "[{},\"{}\"]",
+                    json_string(&name),
+                    port_value(level, active_low).to_char()
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", ports.join(","))
+    };
+    let internal = waveforms
+        .chip_outputs(machine)
+        .iter()
+        .map(|(id, out)| format!("[{id},\"{}\"]", out.value()))
+        .collect::<Vec<_>>();
+    let waveforms = waveforms
+        .signals
+        .iter()
+        .map(
+            |Watched {
+                 signal, history, ..
+             }| {
+                let values = history
+                    .iter()
+                    .copied()
+                    .map(Logic::to_char)
+                    .collect::<String>();
+                format!("[{},\"{values}\"]", json_string(signal))
+            },
+        )
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"error\":{},\"clock\":{},\"inputs\":{},\"outputs\":{},\"internal\":[{}],\"waveforms\":[{}],\"screen\":{},\"keyboard\":{}}}",
+        error.unwrap_or_else(|| "null".into()),
+        machine
+            .get_clock()
+            .map_or_else(|| "null".into(), |clock| json_string(&clock)),
+        ports(
+            machine.inputs(),
+            &TFam::StructuredInput::<bool>::active_low()
+        ),
+        ports(
+            machine.outputs(),
+            &TFam::StructuredOutput::<bool>::active_low()
+        ),
+        internal.join(","),
+        waveforms.join(","),
+        devices
+            .screen_hex()
+            .map_or_else(|| "null".into(), |screen| format!("\"{screen}\"")),
+        devices.has_keyboard()
+    )
+}
+
+// the level on an active low port's wire is the inverse of its value
+pub(crate) fn port_value(level: Logic, active_low: bool) -> Logic {
+    match (level, active_low) {
+        (Logic::Low, true) => Logic::High,
+        (Logic::High, true) => Logic::Low,
+        (level, _) => level,
+    }
+}
@@ -0,0 +1,112 @@
+use std::io;
+
+use hdl::{ChipOutput, Error, Logic, Machine, StructuredDataFamily};
+
+// The Hack computer's screen and keyboard, for a machine built with them, which the page
+// draws and types in to. `screen` is the path of the register holding each word of the
+// 512x256 screen, in address order, as `Machine::find_named_outputs()` takes chips, and
+// `keyboard` the name of the input bus taking the code of the key held down
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Peripherals {
+    pub screen: Vec<String>,
+    pub keyboard: Option<&'static str>,
+}
+
+// the words of screen memory, each 16 bits MSB first
+const SCREEN_WORDS: usize = 8192;
+
+// the peripherals of a machine being served, with the screen's registers found once
+#[derive(Default)]
+pub(crate) struct Devices<'a> {
+    screen: Vec<[&'a ChipOutput<'a>; 16]>,
+    keyboard: Option<&'static str>,
+}
+
+impl<'a> Devices<'a> {
+    pub(crate) fn attach<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        machine: &Machine<'a, TFam, NINPUT, NOUT>,
+        peripherals: &Peripherals,
+    ) -> io::Result<Devices<'a>> {
+        if !peripherals.screen.is_empty() && peripherals.screen.len() != SCREEN_WORDS {
+            return Err(io::Error::other(format!(
+                "a screen has {SCREEN_WORDS} words, not {}",
+                peripherals.screen.len()
+            )));
+        }
+        let paths = peripherals
+            .screen
+            .iter()
+            // outputs are labelled from their last element down, so out-15 is the MSB
+            .flat_map(|register| {
+                (0..16)
+                    .rev()
+                    .map(move |bit| format!("{register}/out-{bit}"))
+            })
+            .collect::<Vec<_>>();
+        let paths = paths.iter().map(String::as_str).collect::<Vec<_>>();
+        let bits = machine
+            .find_named_outputs(&paths)
+            .map_err(io::Error::other)?;
+        let screen = bits
+            .chunks(16)
+            .map(|word| core::array::from_fn(|bit| word[bit]))
+            .collect();
+        if let Some(keyboard) = peripherals.keyboard {
+            let name = format!("{keyboard}-0");
+            if !machine.inputs().iter().any(|(input, _)| *input == name) {
+                return Err(io::Error::other(Error::UnknownInput(name)));
+            }
+        }
+        Ok(Devices {
+            screen,
+            keyboard: peripherals.keyboard,
+        })
+    }
+
+    // the screen's words as 4 hex digits each, or None without a screen. A pixel is on
+    // when its bit is, and unknown bits are drawn off
+    pub(crate) fn screen_hex(&self) -> Option<String> {
+        if self.screen.is_empty() {
+            return None;
+        }
+        let hex = self
+            .screen
+            .iter()
+            .map(|word| {
+                let word = word.iter().fold(0u16, |word, bit| {
+                    word << 1 | u16::from(bit.value() == Logic::High)
+                });
+                format!("{word:04x}")
+            })
+            .collect();
+        Some(hex)
+    }
+
+    pub(crate) fn has_keyboard(&self) -> bool {
+        self.keyboard.is_some()
+    }
+
+    // holds down the key with the code, or releases the keys with 0, without processing
+    pub(crate) fn press<
+        TFam: StructuredDataFamily<NINPUT, NOUT>,
+        const NINPUT: usize,
+        const NOUT: usize,
+    >(
+        &self,
+        machine: &mut Machine<'a, TFam, NINPUT, NOUT>,
+        code: u16,
+    ) -> Result<(), String> {
+        let keyboard = self.keyboard.ok_or("the machine has no keyboard")?;
+        for bit in 0..16 {
+            let value = code >> (15 - bit) & 1 == 1;
+            machine
+                .set_input(&format!("{keyboard}-{bit}"), value)
+                .map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+}
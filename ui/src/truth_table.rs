@@ -0,0 +1,124 @@
+use std::error::Error;
+
+use hdl::{bumpalo::Bump, Logic, Machine, StructuredData, StructuredDataFamily};
+
+use crate::json::json_string;
+
+// chips with more inputs than this have too many rows to read
+pub const MAX_TRUTH_TABLE_INPUTS: usize = 6;
+
+// The outputs a chip gives for every combination of its inputs, the first input being
+// the most significant bit of the row number
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruthTable {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub rows: Vec<TruthTableRow>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruthTableRow {
+    pub inputs: Vec<bool>,
+    pub outputs: Vec<Logic>,
+}
+
+// Simulates the machine `build` returns on each row from the state it was built in, so
+// a sequential chip's rows don't depend on the rows before them. Inputs are counted
+// before the machine is built, so large chips are turned down quickly
+pub fn truth_table<
+    TFam: StructuredDataFamily<NINPUT, NOUT>,
+    const NINPUT: usize,
+    const NOUT: usize,
+>(
+    build: impl for<'a> FnOnce(&'a Bump) -> Result<Machine<'a, TFam, NINPUT, NOUT>, hdl::Error>,
+) -> Result<TruthTable, Box<dyn Error>> {
+    if NINPUT > MAX_TRUTH_TABLE_INPUTS {
+        return Err(format!(
+            "the chip has {NINPUT} inputs, but truth tables are limited to {MAX_TRUTH_TABLE_INPUTS}"
+        )
+        .into());
+    }
+    let alloc = Bump::new();
+    let mut machine = build(&alloc)?;
+    let initial = machine.save_state();
+    let rows = (0..1 << NINPUT)
+        .map(|row| {
+            let inputs: [bool; NINPUT] = core::array::from_fn(|i| row >> (NINPUT - 1 - i) & 1 == 1);
+            machine.load_state(&initial)?;
+            let outputs = machine
+                .process_logic(TFam::StructuredInput::from_flat(inputs.map(Logic::from)))?
+                .to_flat();
+            Ok(TruthTableRow {
+                inputs: inputs.to_vec(),
+                outputs: outputs.to_vec(),
+            })
+        })
+        .collect::<Result<_, hdl::Error>>()?;
+    Ok(TruthTable {
+        inputs: Machine::<TFam, NINPUT, NOUT>::input_names().to_vec(),
+        outputs: Machine::<TFam, NINPUT, NOUT>::output_names().to_vec(),
+        rows,
+    })
+}
+
+impl TruthTable {
+    // rows are written as strings of their input and output values
+    pub(crate) fn to_json(&self) -> String {
+        let names = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| json_string(name))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let (inputs, outputs) = row_values(row);
+                format!("[\"{inputs}\",\"{outputs}\"]")
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"inputs\":[{}],\"outputs\":[{}],\"rows\":[{}]}}",
+            names(&self.inputs),
+            names(&self.outputs),
+            rows.join(",")
+        )
+    }
+
+    pub(crate) fn to_html(&self) -> String {
+        let cells = |tag: &str, values: Vec<String>| {
+            values
+                .iter()
+                .map(|value| format!("<{tag}>{value}</{tag}>"))
+                .collect::<String>()
+        };
+        let header = cells("th", [&self.inputs[..], &self.outputs[..]].concat());
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                let (inputs, outputs) = row_values(row);
+                let values = inputs.chars().chain(outputs.chars()).map(String::from);
+                format!("\n      <tr>{}</tr>", cells("td", values.collect()))
+            })
+            .collect::<String>();
+        TRUTH_TABLE_TEMPLATE.replace(
+            "{REPLACE_TABLE}",
+            &format!("<table>\n      <tr>{header}</tr>{rows}\n    </table>"),
+        )
+    }
+}
+
+const TRUTH_TABLE_TEMPLATE: &str = include_str!("../http/truth_table.html");
+
+fn row_values(row: &TruthTableRow) -> (String, String) {
+    (
+        row.inputs
+            .iter()
+            .map(|&value| if value { '1' } else { '0' })
+            .collect(),
+        row.outputs.iter().copied().map(Logic::to_char).collect(),
+    )
+}
@@ -0,0 +1,361 @@
+use std::io::{self, Read, Write};
+
+use crate::http::{HttpError, HttpRequest};
+
+// The parts of RFC 6455 the live simulation needs: the opening handshake, and
+// unfragmented text messages with the control frames that can arrive between them
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// messages are single commands, so anything longer is a misbehaving client
+const MAX_MESSAGE_LEN: u64 = 1 << 16;
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+// The key a client sends when asking for its connection to be upgraded. `None` when it
+// isn't asking, and an error when it asks for anything but a version 13 websocket
+pub(crate) fn request_key(request: &HttpRequest) -> Option<Result<&str, HttpError>> {
+    let upgrade = request.header("upgrade");
+    let key = request.header("sec-websocket-key");
+    if upgrade.is_none() && key.is_none() {
+        return None;
+    }
+    let connection_upgrades = request.header("connection").is_some_and(|connection| {
+        connection
+            .split(',')
+            .any(|option| option.trim().eq_ignore_ascii_case("upgrade"))
+    });
+    let is_websocket = request.method == "GET"
+        && upgrade.is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        && connection_upgrades
+        && request.header("sec-websocket-version") == Some("13");
+    match key {
+        Some(key) if is_websocket => Some(Ok(key)),
+        _ => Some(Err(HttpError::BadRequest)),
+    }
+}
+
+pub(crate) fn accept(stream: &mut impl Write, key: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{key}{ACCEPT_GUID}").as_bytes()))
+}
+
+// Reads a websocket's messages. A stream read with a timeout can time out part way
+// through a frame, so the bytes read so far are kept for the next read to carry on from
+#[derive(Default)]
+pub(crate) struct MessageReader {
+    buffer: Vec<u8>,
+}
+
+// a frame which has been read in full
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+impl MessageReader {
+    // the next text message, answering pings on the way. `None` once the client closes
+    // the connection
+    pub(crate) fn read_message(
+        &mut self,
+        stream: &mut (impl Read + Write),
+    ) -> io::Result<Option<String>> {
+        loop {
+            let Some(frame) = self.next_frame()? else {
+                let mut chunk = [0; 4096];
+                let len = match stream.read(&mut chunk) {
+                    Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(len) => len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                };
+                self.buffer.extend_from_slice(&chunk[..len]);
+                continue;
+            };
+            match frame.opcode {
+                TEXT if frame.fin => {
+                    return String::from_utf8(frame.payload)
+                        .map(Some)
+                        .map_err(|_| invalid_data("websocket message isn't utf-8"))
+                }
+                CLOSE => {
+                    write_frame(stream, CLOSE, &[])?;
+                    return Ok(None);
+                }
+                PING => write_frame(stream, PONG, &frame.payload)?,
+                PONG => {}
+                _ => return Err(invalid_data("unsupported websocket frame")),
+            }
+        }
+    }
+
+    // takes the first frame off the buffer, once all of it has been read
+    fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let buffer = &self.buffer;
+        if buffer.len() < 2 {
+            return Ok(None);
+        }
+        let (len, mut start) = match buffer[1] & 0x7f {
+            126 => match buffer.get(2..4) {
+                Some(len) => (u16::from_be_bytes([len[0], len[1]]).into(), 4),
+                None => return Ok(None),
+            },
+            127 => match buffer.get(2..10) {
+                Some(len) => (u64::from_be_bytes(len.try_into().unwrap()), 10),
+                None => return Ok(None),
+            },
+            len => (len.into(), 2),
+        };
+        if len > MAX_MESSAGE_LEN {
+            return Err(invalid_data("websocket message is too long"));
+        }
+        // clients always mask their frames
+        let mut mask = [0; 4];
+        if buffer[1] & 0x80 != 0 {
+            match buffer.get(start..start + 4) {
+                Some(bytes) => mask.copy_from_slice(bytes),
+                None => return Ok(None),
+            }
+            start += 4;
+        }
+        let end = start + len as usize;
+        if buffer.len() < end {
+            return Ok(None);
+        }
+        let frame = Frame {
+            fin: buffer[0] & 0x80 != 0,
+            opcode: buffer[0] & 0x0f,
+            payload: buffer[start..end]
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4])
+                .collect(),
+        };
+        self.buffer.drain(..end);
+        Ok(Some(frame))
+    }
+}
+
+pub(crate) fn write_message(stream: &mut impl Write, message: &str) -> io::Result<()> {
+    write_frame(stream, TEXT, message.as_bytes())
+}
+
+pub(crate) fn close(stream: &mut impl Write) -> io::Result<()> {
+    write_frame(stream, CLOSE, &[])
+}
+
+// servers send their frames unmasked
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(payload);
+    stream.write_all(&frame)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn when_a_client_sends_a_key_the_accept_key_matches_the_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+    }
+
+    #[test]
+    fn when_a_client_asks_for_anything_but_a_version_13_websocket_the_request_is_rejected() {
+        let request = |lines: &[&str]| {
+            HttpRequest::parse(&lines.iter().map(|&line| line.into()).collect::<Vec<_>>()).unwrap()
+        };
+        let upgrade = [
+            "GET /live HTTP/1.1",
+            "Upgrade: websocket",
+            "Connection: keep-alive, Upgrade",
+            "Sec-WebSocket-Version: 13",
+            "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==",
+        ];
+        assert_eq!(
+            request_key(&request(&upgrade)),
+            Some(Ok("dGhlIHNhbXBsZSBub25jZQ=="))
+        );
+        assert_eq!(request_key(&request(&["GET / HTTP/1.1"])), None);
+
+        let mut post = upgrade;
+        post[0] = "POST /live HTTP/1.1";
+        for (i, _) in upgrade.iter().enumerate().skip(1) {
+            let mut missing = upgrade.to_vec();
+            missing.remove(i);
+            assert_eq!(
+                request_key(&request(&missing)),
+                Some(Err(HttpError::BadRequest)),
+                "{} is needed",
+                upgrade[i]
+            );
+        }
+        let mut old_version = upgrade;
+        old_version[3] = "Sec-WebSocket-Version: 8";
+        for rejected in [post, old_version] {
+            assert_eq!(
+                request_key(&request(&rejected)),
+                Some(Err(HttpError::BadRequest))
+            );
+        }
+    }
+
+    // hands out its chunks one read at a time, timing out between them
+    struct StallingStream {
+        chunks: Vec<Vec<u8>>,
+        stalled: bool,
+        written: Vec<u8>,
+    }
+
+    impl Read for StallingStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.stalled = !self.stalled;
+            if self.stalled {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let chunk = self.chunks.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl Write for StallingStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn when_a_frame_is_split_across_read_timeouts_it_is_read_once_the_rest_arrives() {
+        // a masked ping, then a masked text frame holding "tick", split part way through
+        // the header, the mask and the payload
+        let mask = [1, 2, 3, 4];
+        let mut frames = vec![0x89, 0x80];
+        frames.extend(mask);
+        frames.extend([0x81, 0x84]);
+        frames.extend(mask);
+        frames.extend(b"tick".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        let mut stream = StallingStream {
+            chunks: [&frames[..1], &frames[1..8], &frames[8..11], &frames[11..]]
+                .map(<[u8]>::to_vec)
+                .into(),
+            stalled: false,
+            written: vec![],
+        };
+
+        let mut reader = MessageReader::default();
+        let mut timeouts = 0;
+        let message = loop {
+            match reader.read_message(&mut stream) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => timeouts += 1,
+                message => break message.unwrap(),
+            }
+        };
+        assert_eq!(message.as_deref(), Some("tick"));
+        assert_eq!(timeouts, 4);
+        assert_eq!(stream.written, [0x8a, 0]);
+    }
+}